@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use spdr_isa::{generator::ProgramGenerator, program::Program};
+use std::{
+  fmt::{self, Write},
+  hint::black_box,
+};
+
+/// Discards the listing, counting its bytes.
+struct Count(usize,);
+
+impl Write for Count {
+  fn write_str(&mut self, s:&str,) -> fmt::Result {
+    self.0 += black_box(s,).len();
+    Ok((),)
+  }
+}
+
+/// Generates a program of about 1 MB of assorted instructions.
+fn program() -> Program {
+  ProgramGenerator::new(0, 200_000,).generate()
+}
+
+fn display(c:&mut Criterion,) {
+  let program = program();
+  let mut group = c.benchmark_group("display 1 MB",);
+  group.sample_size(10,);
+  group.bench_function("listing", |bench| {
+    bench.iter(|| write!(Count(0,), "{}", black_box(&program)).unwrap(),)
+  },);
+  group.bench_function("annotated listing", |bench| {
+    bench.iter(|| write!(Count(0,), "{:#}", black_box(&program)).unwrap(),)
+  },);
+  group.finish();
+}
+
+fn validate(c:&mut Criterion,) {
+  let program = program();
+  let mut group = c.benchmark_group("validate 1 MB",);
+  group.sample_size(10,);
+  group.bench_function("validate", |bench| {
+    bench.iter(|| black_box(&program,).validate(),)
+  },);
+  group.finish();
+}
+
+criterion_group!(benches, display, validate);
+criterion_main!(benches);
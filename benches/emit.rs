@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use spdr_isa::{instruction::Instruction, program::Program, registers::Register};
+use std::hint::black_box;
+
+/// Instructions emitted by each benchmark.
+const COUNT:usize = 100_000;
+
+/// Emits `COUNT` instructions averaging 4 bytes each into `program`.
+fn emit(mut program:Program,) -> Program {
+  let (a, b,) = (Register::new(16,), Register::new(17,),);
+  for i in 0..COUNT / 4 {
+    program.emit_load(a, i as f32,);
+    program.emit(Instruction::AddRR { dst:b, a, b, },);
+    program.emit(Instruction::Inc { dst:a, },);
+    program.emit(Instruction::Push { src:b, },);
+  }
+  program
+}
+
+fn emission(c:&mut Criterion,) {
+  let mut group = c.benchmark_group("emit 100k instructions",);
+  group.bench_function("new", |bench| bench.iter(|| emit(black_box(Program::new(),),),),);
+  group.bench_function("with_capacity", |bench| {
+    bench.iter(|| emit(black_box(Program::with_capacity(COUNT * 4,),),),)
+  },);
+  group.finish();
+}
+
+criterion_group!(benches, emission);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+#![no_main]
+
+//! Feeds raw bytes to the decoder. Decoding, validating, and printing any
+//! program must never panic.
+
+use libfuzzer_sys::fuzz_target;
+use spdr_isa::program::Program;
+
+fuzz_target!(|bytes:&[u8]| {
+  let program = Program::from(bytes,);
+  for item in program.instructions() {
+    if item.is_err() {
+      break;
+    }
+  }
+  let _ = program.validate();
+  let _ = program.to_string();
+  let _ = Program::from_file_bytes(bytes,);
+});
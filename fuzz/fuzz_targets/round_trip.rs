@@ -0,0 +1,28 @@
+#![no_main]
+
+//! Encodes arbitrary instructions and checks decoding them and encoding them
+//! again gives back the same instructions and bytes.
+
+use libfuzzer_sys::fuzz_target;
+use spdr_isa::{instruction::Instruction, program::Program};
+
+fuzz_target!(|insts:Vec<Instruction,>| {
+  let mut program = Program::new();
+  for inst in &insts {
+    program.emit(*inst,);
+  }
+
+  let decoded = program
+    .instructions()
+    .map(|item| item.expect("encoded instructions decode",).1,)
+    .collect::<Vec<_,>>();
+  assert_eq!(decoded, insts);
+
+  let mut encoded = Program::new();
+  for inst in &decoded {
+    encoded.emit(*inst,);
+  }
+  assert_eq!(encoded.as_slice(), program.as_slice());
+  let _ = program.validate();
+  let _ = program.to_string();
+});
@@ -0,0 +1,57 @@
+/*
+ * C interface to spdr-isa, built with the `ffi` feature:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Every function is safe to call with null pointers and never unwinds into C.
+ */
+#ifndef SPDR_ISA_H
+#define SPDR_ISA_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes. Keep in sync with src/ffi.rs. */
+#define SPDR_OK 0
+#define SPDR_NULL_POINTER (-1)
+#define SPDR_PANIC (-2)
+#define SPDR_DECODE_ERROR 1
+#define SPDR_JUMP_OUT_OF_BOUNDS 2
+#define SPDR_JUMP_INTO_OPERAND 3
+#define SPDR_INEXACT_OFFSET 5
+#define SPDR_MISSING_TERMINATOR 6
+#define SPDR_UNKNOWN_SYSCALL 7
+#define SPDR_ADDRESS_OUT_OF_BOUNDS 8
+
+/*
+ * Disassembles the `len` bytes of code at `code` and stores the listing in
+ * `*out` as a nul-terminated string. Free it with spdr_string_free. `code`
+ * may be null if `len` is 0.
+ */
+int32_t spdr_disassemble(const uint8_t *code, size_t len, char **out);
+
+/* Frees a string returned by spdr_disassemble. Does nothing if `s` is null. */
+void spdr_string_free(char *s);
+
+/*
+ * Validates the `len` bytes of code at `code`. Returns SPDR_OK if they are
+ * valid, otherwise the status of the first problem, storing the offset of
+ * the instruction it was found in in `*offset` if `offset` is not null.
+ */
+int32_t spdr_validate(const uint8_t *code, size_t len, uint32_t *offset);
+
+/*
+ * Returns the length in bytes of an instruction with the opcode `opcode`,
+ * including the opcode, or 0 if `opcode` is not an opcode.
+ */
+size_t spdr_instruction_len(uint8_t opcode);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SPDR_ISA_H */
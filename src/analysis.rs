@@ -0,0 +1,1210 @@
+use crate::{
+  errors::DecodeError,
+  instruction::Instruction,
+  memory::STACK_SIZE,
+  opcodes::OpCode,
+  program::Program,
+  registers::{CallingConvention, Register, REGISTER_NAMES, REG_COUNT},
+  symbols::SymbolTable,
+  syscalls::SyscallTable,
+};
+use alloc::{
+  collections::{BTreeMap, BTreeSet},
+  format,
+  string::{String, ToString},
+  vec,
+  vec::Vec,
+};
+use core::{cmp::Reverse, fmt::Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// A read or write of a register by the instruction at `at`.
+pub struct Access {
+  pub at:u32,
+  /// Whether the register is not an operand of the instruction, like the
+  /// [`EQ`](crate::registers::EQ) written by `Cmp_RR`.
+  pub implicit:bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Which instructions in a [`Program`] read and write each register, found by
+/// [`register_usage`].
+pub struct RegisterUsage {
+  /// Indexed by register.
+  reads:Vec<Vec<Access,>,>,
+  /// Indexed by register.
+  writes:Vec<Vec<Access,>,>,
+}
+
+impl RegisterUsage {
+  /// Returns every read of `reg` in order of offset.
+  pub fn reads(&self, reg:Register,) -> &[Access] {
+    &self.reads[reg.index() as usize]
+  }
+
+  /// Returns every write of `reg` in order of offset.
+  pub fn writes(&self, reg:Register,) -> &[Access] {
+    &self.writes[reg.index() as usize]
+  }
+
+  /// Whether any instruction reads or writes `reg`.
+  pub fn is_used(&self, reg:Register,) -> bool {
+    !self.reads(reg,).is_empty() || !self.writes(reg,).is_empty()
+  }
+
+  /// Returns the highest register read or written, or `None` if the program
+  /// uses no registers.
+  pub fn max_register_used(&self,) -> Option<Register,> {
+    (0..REG_COUNT)
+      .rev()
+      .map(|idx| Register::new(idx as u8,),)
+      .find(|reg| self.is_used(*reg,),)
+  }
+
+  /// Returns every explicit write to a named register
+  /// ([`PC`](crate::registers::PC) through [`FP`](crate::registers::FP)) and
+  /// the offset of the instruction which writes it.
+  ///
+  /// Implicit writes, like a `Push` moving [`SP`](crate::registers::SP), are
+  /// expected and are not included.
+  pub fn writes_to_reserved(&self,) -> Vec<(Register, u32,),> {
+    let mut writes = (0..REGISTER_NAMES.len())
+      .map(|idx| Register::new(idx as u8,),)
+      .flat_map(|reg| {
+        self
+          .writes(reg,)
+          .iter()
+          .filter(|access| !access.implicit,)
+          .map(move |access| (reg, access.at,),)
+      },)
+      .collect::<Vec<_,>>();
+    writes.sort_by_key(|(_, at,)| *at,);
+    writes
+  }
+
+  /// Returns the argument registers of the [`CallingConvention`] which are
+  /// never read or written.
+  pub fn unused_argument_registers(&self,) -> Vec<Register,> {
+    CallingConvention::ARG_REGISTERS
+      .map(Register::new,)
+      .filter(|reg| !self.is_used(*reg,),)
+      .collect()
+  }
+}
+
+/// Records the registers read and written by every instruction in the
+/// [`Program`], including the registers instructions use implicitly.
+///
+/// The instructions are scanned in order so registers used by code which is
+/// never run are still counted.
+///
+/// Errors if the program cannot be decoded.
+pub fn register_usage(program:&Program,) -> Result<RegisterUsage, DecodeError,> {
+  let mut usage = RegisterUsage {
+    reads:vec![Vec::new(); REG_COUNT],
+    writes:vec![Vec::new(); REG_COUNT],
+  };
+  for item in program.instructions() {
+    let (at, inst,) = item?;
+    let record = |table:&mut Vec<Vec<Access,>,>, regs:Vec<Register,>, implicit:bool| {
+      for reg in regs {
+        table[reg.index() as usize].push(Access { at, implicit, },);
+      }
+    };
+    record(&mut usage.reads, inst.reads(), false,);
+    record(&mut usage.reads, inst.implicit_reads(), true,);
+    record(&mut usage.writes, inst.writes(), false,);
+    record(&mut usage.writes, inst.implicit_writes(), true,);
+  }
+  Ok(usage,)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// A problem with the stack found by [`stack_depth`].
+pub enum StackIssue {
+  /// Paths reach the instruction at `at` with the stack holding different
+  /// numbers of items.
+  Unbalanced { at:u32, depths:(i32, i32,), },
+  /// The loop which jumps back to `at` leaves `growth` more items on the stack
+  /// each iteration so the stack grows without bound.
+  Unbounded { at:u32, growth:i32, },
+  /// The stack holds `depth` items at `at`, more than [`STACK_SIZE`].
+  Overflow { at:u32, depth:u32, },
+  /// The instruction at `at` pops more items than its function pushed.
+  Underflow { at:u32, depth:i32, },
+  /// The function's stack holds `depth` items when the `Ret` at `at` runs
+  /// instead of only the return address.
+  UnbalancedReturn { at:u32, depth:i32, },
+  /// The `Leave` at `at` has no matching `Enter`.
+  UnmatchedLeave { at:u32, },
+  /// The `Call` at `at` calls a function with no reachable `Ret` so the
+  /// number of arguments it cleans up is unknown. It is assumed to clean up
+  /// none.
+  UnknownCleanup { at:u32, target:u32, },
+  /// The `Call` at `at` is part of a recursive cycle so the stack depth of
+  /// the calls is unbounded.
+  Recursive { at:u32, target:u32, },
+  /// The `JmpR` or `CallR` at `at` goes to an address only known at runtime.
+  /// Paths are not followed past a `JmpR` and a `CallR` is assumed to leave
+  /// the stack unchanged.
+  Indirect { at:u32, },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// The stack usage of a [`Program`] found by [`stack_depth`].
+pub struct StackReport {
+  /// Most items the stack holds on any path from the entry point, or `None` if
+  /// it is unbounded.
+  pub max_depth:Option<u32,>,
+  /// Every problem found, ordered by offset.
+  pub issues:Vec<StackIssue,>,
+}
+
+impl StackReport {
+  /// Whether no problems were found.
+  pub fn is_ok(&self,) -> bool {
+    self.issues.is_empty()
+  }
+}
+
+/// Stack usage of a function called by `Call`.
+#[derive(Debug, Clone, Copy,)]
+struct FunctionStack {
+  /// Most items the function pushes above its return address, or `None` if
+  /// unbounded.
+  max:Option<u32,>,
+  /// Offset the most items are pushed at.
+  max_at:u32,
+  /// Number of arguments the function's `Ret` cleans up.
+  cleanup:Option<u8,>,
+}
+
+#[derive(Debug, Clone,)]
+struct PathState {
+  depth:i32,
+  /// Depths before each `Enter` which has not been left.
+  frames:Vec<i32,>,
+}
+
+struct StackWalker {
+  insts:Vec<(u32, Instruction,),>,
+  /// Functions which have been walked, or `None` while a function is being
+  /// walked.
+  functions:BTreeMap<u32, Option<FunctionStack,>,>,
+  issues:Vec<StackIssue,>,
+}
+
+impl StackWalker {
+  fn inst_at(&self, at:u32,) -> Option<Instruction,> {
+    self
+      .insts
+      .binary_search_by_key(&at, |(offset, _,)| *offset,)
+      .ok()
+      .map(|idx| self.insts[idx].1,)
+  }
+
+  /// Walks every path through the function starting at `start`. Depths are
+  /// counted from the function's entry.
+  fn function(&mut self, start:u32,) -> FunctionStack {
+    let mut seen = BTreeMap::<u32, i32,>::new();
+    let mut work = vec![(
+      None,
+      start,
+      PathState {
+        depth:0,
+        frames:Vec::new(),
+      },
+    )];
+    let mut stack = FunctionStack {
+      max:Some(0,),
+      max_at:start,
+      cleanup:None,
+    };
+    let raise = |stack:&mut FunctionStack, depth:Option<u32,>, at:u32| match (stack.max, depth,) {
+      (Some(max,), Some(depth,),) if depth > max => {
+        stack.max = Some(depth,);
+        stack.max_at = at;
+      }
+      (_, None,) => stack.max = None,
+      _ => {}
+    };
+
+    while let Some((from, at, mut state,),) = work.pop() {
+      if let Some(&depth,) = seen.get(&at,) {
+        if depth != state.depth {
+          // A jump backwards to a depth already seen closes a loop
+          let issue = match from {
+            Some(from,) if at <= from && state.depth > depth => {
+              raise(&mut stack, None, at,);
+              StackIssue::Unbounded {
+                at,
+                growth:state.depth - depth,
+              }
+            }
+            _ => StackIssue::Unbalanced {
+              at,
+              depths:(depth, state.depth,),
+            },
+          };
+          self.issues.push(issue,);
+        }
+        continue;
+      }
+      seen.insert(at, state.depth,);
+      let Some(inst,) = self.inst_at(at,)
+      else {
+        continue;
+      };
+      let next = at + inst.opcode().instruction_len() as u32;
+
+      match inst {
+        Instruction::Push { .. } | Instruction::PushI { .. } => state.depth += 1,
+        Instruction::Pop | Instruction::PopR { .. } => state.depth -= 1,
+        Instruction::PopN { count, } => state.depth -= count as i32,
+        Instruction::Enter { locals, } => {
+          state.frames.push(state.depth,);
+          state.depth += 1 + locals as i32;
+        }
+        Instruction::Leave => match state.frames.pop() {
+          Some(depth,) => state.depth = depth,
+          None => self.issues.push(StackIssue::UnmatchedLeave { at, },),
+        },
+        Instruction::Call { target, } => {
+          let callee = match self.functions.get(&target,) {
+            Some(Some(callee,),) => *callee,
+            Some(None,) => {
+              self.issues.push(StackIssue::Recursive { at, target, },);
+              FunctionStack {
+                max:None,
+                max_at:at,
+                cleanup:Some(0,),
+              }
+            }
+            None => {
+              self.functions.insert(target, None,);
+              let callee = self.function(target,);
+              self.functions.insert(target, Some(callee,),);
+              callee
+            }
+          };
+          // The return address is pushed above the caller's items
+          let peak = callee.max.map(|max| (state.depth.max(0,) as u32) + 1 + max,);
+          raise(&mut stack, peak, callee.max_at,);
+          let cleanup = callee.cleanup.unwrap_or_else(|| {
+            self.issues.push(StackIssue::UnknownCleanup { at, target, },);
+            0
+          },);
+          state.depth -= cleanup as i32;
+        }
+        Instruction::CallR { .. } => self.issues.push(StackIssue::Indirect { at, },),
+        Instruction::JmpR { .. } => {
+          self.issues.push(StackIssue::Indirect { at, },);
+          continue;
+        }
+        Instruction::Ret { args, } => {
+          if state.depth != 0 {
+            self.issues.push(StackIssue::UnbalancedReturn {
+              at,
+              depth:state.depth,
+            },);
+          }
+          stack.cleanup.get_or_insert(args,);
+          continue;
+        }
+        Instruction::Hlt => continue,
+        _ => {}
+      }
+
+      if state.depth < 0 {
+        self.issues.push(StackIssue::Underflow {
+          at,
+          depth:state.depth,
+        },);
+      }
+      raise(&mut stack, Some(state.depth.max(0,) as u32,), at,);
+
+      let target = match inst {
+        Instruction::Call { .. } => None,
+        _ => inst.resolve_target(at,),
+      };
+      let unconditional = matches!(inst, Instruction::Jmp { .. } | Instruction::JmpRel { .. });
+      if !unconditional {
+        work.push((Some(at,), next, state.clone(),),);
+      }
+      if let Some(target,) = target {
+        work.push((Some(at,), target, state,),);
+      }
+    }
+    stack
+  }
+}
+
+/// Walks every path from the [`Program`]'s entry point tracking how many items
+/// are on the stack.
+///
+/// - Paths which reach the same instruction with different depths are reported
+///   as [`StackIssue::Unbalanced`], or [`StackIssue::Unbounded`] if the
+///   instruction is reached by jumping backwards with a deeper stack.
+/// - Each function called by `Call` is walked once. Its `Ret` cleanup count is
+///   subtracted from the caller's depth after the call.
+/// - A maximum depth above [`STACK_SIZE`] is reported as a
+///   [`StackIssue::Overflow`].
+///
+/// Errors if the program cannot be decoded.
+pub fn stack_depth(program:&Program,) -> Result<StackReport, DecodeError,> {
+  let mut walker = StackWalker {
+    insts:program.instructions().collect::<Result<Vec<_,>, _,>>()?,
+    functions:BTreeMap::new(),
+    issues:Vec::new(),
+  };
+  let entry = walker.function(program.entry_point(),);
+  if let Some(max,) = entry.max.filter(|max| *max as usize > STACK_SIZE,) {
+    walker.issues.push(StackIssue::Overflow {
+      at:entry.max_at,
+      depth:max,
+    },);
+  }
+
+  let mut issues = walker.issues;
+  issues.sort_by_key(|issue| match *issue {
+    StackIssue::Unbalanced { at, .. }
+    | StackIssue::Unbounded { at, .. }
+    | StackIssue::Overflow { at, .. }
+    | StackIssue::Underflow { at, .. }
+    | StackIssue::UnbalancedReturn { at, .. }
+    | StackIssue::UnmatchedLeave { at, }
+    | StackIssue::UnknownCleanup { at, .. }
+    | StackIssue::Recursive { at, .. }
+    | StackIssue::Indirect { at, } => at,
+  },);
+  issues.dedup();
+  Ok(StackReport {
+    max_depth:entry.max,
+    issues,
+  },)
+}
+
+#[derive(Debug, Clone, PartialEq,)]
+/// A run of instructions which is only entered at its first instruction and
+/// only left after its last.
+pub struct BasicBlock {
+  /// Offset of the first instruction.
+  pub start:u32,
+  /// Offset just past the last instruction.
+  pub end:u32,
+  pub insts:Vec<(u32, Instruction,),>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// How control passes along an [`Edge`].
+pub enum EdgeKind {
+  /// Execution continues with the next instruction.
+  Fallthrough,
+  /// A jump is taken.
+  Taken,
+  /// A `Call` enters a function.
+  Call,
+  /// A `Ret` returns to the instruction after a `Call` of its function.
+  Return,
+  /// A `JmpR` or `CallR` goes to an address only known at runtime.
+  Unknown,
+}
+
+impl Display for EdgeKind {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      EdgeKind::Fallthrough => write!(f, "fallthrough"),
+      EdgeKind::Taken => write!(f, "taken"),
+      EdgeKind::Call => write!(f, "call"),
+      EdgeKind::Return => write!(f, "return"),
+      EdgeKind::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// A way control can pass between the [`BasicBlock`]s of a [`Cfg`].
+pub struct Edge {
+  /// Index of the block control leaves.
+  pub from:usize,
+  /// Index of the block control enters, or `None` for an
+  /// [`EdgeKind::Unknown`] edge.
+  pub to:Option<usize,>,
+  pub kind:EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq,)]
+/// The control-flow graph of a [`Program`] built by [`cfg()`].
+pub struct Cfg {
+  /// Ordered by offset.
+  blocks:Vec<BasicBlock,>,
+  edges:Vec<Edge,>,
+}
+
+impl Cfg {
+  /// Returns the graph's [`BasicBlock`]s in order of their offsets.
+  pub fn blocks(&self,) -> &[BasicBlock] {
+    &self.blocks
+  }
+
+  /// Returns every [`Edge`] in the graph.
+  pub fn edges(&self,) -> &[Edge] {
+    &self.edges
+  }
+
+  /// Returns the index of the block holding the instruction at `offset`.
+  pub fn block_at(&self, offset:u32,) -> Option<usize,> {
+    let idx = self
+      .blocks
+      .partition_point(|block| block.start <= offset,)
+      .checked_sub(1,)?;
+    (offset < self.blocks[idx].end).then_some(idx,)
+  }
+
+  /// Iterates over the edges leaving the block at index `block`.
+  pub fn successors(&self, block:usize,) -> impl Iterator<Item = &Edge,> {
+    self.edges.iter().filter(move |edge| edge.from == block,)
+  }
+
+  /// Formats the graph in Graphviz's dot language. Each block is listed with
+  /// its instructions and each edge is labeled with its [`EdgeKind`].
+  pub fn to_dot(&self,) -> String {
+    let mut dot = String::from("digraph cfg {\n  node [shape=box, fontname=monospace];\n",);
+    for (idx, block,) in self.blocks.iter().enumerate() {
+      let label = block
+        .insts
+        .iter()
+        .map(|(at, inst,)| format!("{:04}: {}\\l", at, inst),)
+        .collect::<String>();
+      dot.push_str(&format!(
+        "  b{} [label=\"{}\"];\n",
+        idx,
+        label.replace('"', "\\\""),
+      ),);
+    }
+    if self.edges.iter().any(|edge| edge.to.is_none(),) {
+      dot.push_str("  unknown [shape=point];\n",);
+    }
+    for edge in &self.edges {
+      let to = match edge.to {
+        Some(to,) => format!("b{}", to),
+        None => "unknown".to_string(),
+      };
+      dot.push_str(&format!(
+        "  b{} -> {} [label=\"{}\"];\n",
+        edge.from, to, edge.kind
+      ),);
+    }
+    dot.push_str("}\n",);
+    dot
+  }
+}
+
+/// Splits the [`Program`] into [`BasicBlock`]s and connects them with
+/// [`Edge`]s.
+///
+/// Blocks start at the program's first instruction, its entry point, every
+/// jump and call target, and after every jump, call, `Ret`, and `Hlt`.
+/// Targets which are not the start of an instruction have no edge; see
+/// [`Program::validate`] for finding them.
+///
+/// A block ending in a `Call` has a [`EdgeKind::Call`] edge to the function
+/// and a [`EdgeKind::Fallthrough`] edge to the instruction the function
+/// returns to. Blocks ending in a `Ret` have a [`EdgeKind::Return`] edge to
+/// the instruction after every `Call` of the function they are in.
+///
+/// Errors if the program cannot be decoded.
+pub fn cfg(program:&Program,) -> Result<Cfg, DecodeError,> {
+  let insts = program.instructions().collect::<Result<Vec<_,>, _,>>()?;
+  let leaders = leaders(&insts, program.entry_point(),);
+
+  let mut blocks = Vec::<BasicBlock,>::new();
+  for (at, inst,) in insts {
+    let end = at + inst.opcode().instruction_len() as u32;
+    match blocks.last_mut() {
+      Some(block,) if leaders.binary_search(&at,).is_err() => {
+        block.end = end;
+        block.insts.push((at, inst,),);
+      }
+      _ => blocks.push(BasicBlock {
+        start:at,
+        end,
+        insts:vec![(at, inst,)],
+      },),
+    }
+  }
+
+  let mut cfg = Cfg {
+    blocks,
+    edges:Vec::new(),
+  };
+  for (from, block,) in cfg.blocks.iter().enumerate() {
+    let (at, inst,) = *block.insts.last().unwrap();
+    let next = cfg.block_at(block.end,);
+    let target = inst.resolve_target(at,).and_then(|target| {
+      cfg
+        .block_at(target,)
+        .filter(|to| cfg.blocks[*to].start == target,)
+    },);
+    let mut edge = |to:Option<usize,>, kind| {
+      if to.is_some() || kind == EdgeKind::Unknown {
+        cfg.edges.push(Edge { from, to, kind, },);
+      }
+    };
+    match inst {
+      Instruction::Hlt | Instruction::Ret { .. } => {}
+      Instruction::Jmp { .. } | Instruction::JmpRel { .. } => edge(target, EdgeKind::Taken,),
+      Instruction::JmpR { .. } => edge(None, EdgeKind::Unknown,),
+      Instruction::Call { .. } => {
+        edge(target, EdgeKind::Call,);
+        edge(next, EdgeKind::Fallthrough,);
+      }
+      Instruction::CallR { .. } => {
+        edge(None, EdgeKind::Unknown,);
+        edge(next, EdgeKind::Fallthrough,);
+      }
+      _ => {
+        edge(next, EdgeKind::Fallthrough,);
+        edge(target, EdgeKind::Taken,);
+      }
+    }
+  }
+
+  // Connect the `Ret`s of each called function to the instructions its calls
+  // return to
+  let mut returns = Vec::new();
+  for call in cfg.edges.iter().filter(|edge| edge.kind == EdgeKind::Call,) {
+    let Some(site,) = cfg
+      .successors(call.from,)
+      .find(|edge| edge.kind == EdgeKind::Fallthrough,)
+    else {
+      continue;
+    };
+    let mut seen = vec![false; cfg.blocks.len()];
+    let mut work = vec![call.to.unwrap()];
+    while let Some(block,) = work.pop() {
+      if core::mem::replace(&mut seen[block], true,) {
+        continue;
+      }
+      if matches!(cfg.blocks[block].insts.last(), Some((_, Instruction::Ret { .. }))) {
+        returns.push(Edge {
+          from:block,
+          to:site.to,
+          kind:EdgeKind::Return,
+        },);
+      }
+      work.extend(
+        cfg
+          .successors(block,)
+          .filter(|edge| matches!(edge.kind, EdgeKind::Fallthrough | EdgeKind::Taken),)
+          .filter_map(|edge| edge.to,),
+      );
+    }
+  }
+  returns.sort_by_key(|edge| (edge.from, edge.to,),);
+  returns.dedup();
+  cfg.edges.extend(returns,);
+  Ok(cfg,)
+}
+
+/// Returns the sorted offsets of the instructions in `insts` which start a
+/// [`BasicBlock`] of a program entered at `entry_point`.
+pub(crate) fn leaders(insts:&[(u32, Instruction,)], entry_point:u32,) -> Vec<u32,> {
+  let is_start = |at:u32| insts.binary_search_by_key(&at, |(offset, _,)| *offset,).is_ok();
+
+  let mut leaders = vec![0, entry_point];
+  for (at, inst,) in insts {
+    leaders.extend(inst.resolve_target(*at,),);
+    if ends_block(inst,) {
+      leaders.push(at + inst.opcode().instruction_len() as u32,);
+    }
+  }
+  leaders.retain(|at| is_start(*at,),);
+  leaders.sort_unstable();
+  leaders.dedup();
+  leaders
+}
+
+/// Whether the instruction ends a [`BasicBlock`].
+pub(crate) fn ends_block(inst:&Instruction,) -> bool {
+  inst.opcode().is_terminator()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord,)]
+/// A function in a [`CallGraph`].
+pub enum CallNode {
+  /// The function starting at a symbol.
+  Function(String,),
+  /// Code at an offset no symbol starts at. Calls to the middle of a function
+  /// and code before the first symbol are attributed to these.
+  Unknown(u32,),
+  /// A function in another program, called through an
+  /// [`Extern`](crate::symbols::Extern).
+  Extern(String,),
+  /// The host function behind a `SysCall` index.
+  SysCall(u8,),
+  /// Wherever a `CallR` goes.
+  Indirect,
+}
+
+impl Display for CallNode {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      CallNode::Function(name,) => write!(f, "{}", name),
+      CallNode::Unknown(at,) => write!(f, "Unknown({})", at),
+      CallNode::Extern(name,) => write!(f, "extern {}", name),
+      CallNode::SysCall(idx,) => write!(f, "SysCall {}", idx),
+      CallNode::Indirect => write!(f, "indirect"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+/// Which functions each function of a [`Program`] calls, made by
+/// [`call_graph`].
+pub struct CallGraph {
+  calls:BTreeMap<CallNode, BTreeSet<CallNode,>,>,
+}
+
+impl CallGraph {
+  /// Iterates over every node which calls or is called, in order.
+  pub fn nodes(&self,) -> impl Iterator<Item = &CallNode,> {
+    self
+      .calls
+      .keys()
+      .chain(self.calls.values().flatten(),)
+      .collect::<BTreeSet<_,>>()
+      .into_iter()
+  }
+
+  /// Iterates over the nodes `caller` calls.
+  pub fn callees(&self, caller:&CallNode,) -> impl Iterator<Item = &CallNode,> {
+    self.calls.get(caller,).into_iter().flatten()
+  }
+
+  /// Iterates over the nodes which call `callee`.
+  pub fn callers<'a,>(&'a self, callee:&'a CallNode,) -> impl Iterator<Item = &'a CallNode,> {
+    self
+      .calls
+      .iter()
+      .filter(move |(_, callees,)| callees.contains(callee,),)
+      .map(|(caller, _,)| caller,)
+  }
+
+  /// Whether `node` calls itself directly.
+  pub fn is_recursive(&self, node:&CallNode,) -> bool {
+    self.callees(node,).any(|callee| callee == node,)
+  }
+
+  /// Iterates over the nodes which call themselves directly.
+  pub fn recursive(&self,) -> impl Iterator<Item = &CallNode,> {
+    self.calls.keys().filter(|node| self.is_recursive(node,),)
+  }
+
+  /// Renders the graph in Graphviz dot format. `SysCall`s are leaves labelled
+  /// by their index.
+  pub fn to_dot(&self,) -> String {
+    self.to_dot_with(None,)
+  }
+
+  /// Renders the graph in Graphviz dot format. `SysCall`s in `syscalls` are
+  /// labelled by name.
+  pub fn to_dot_with(&self, syscalls:Option<&SyscallTable,>,) -> String {
+    let label = |node:&CallNode| match (node, syscalls,) {
+      (CallNode::SysCall(idx,), Some(table,),) => match table.get(*idx,) {
+        Some(syscall,) => syscall.name.clone(),
+        None => node.to_string(),
+      },
+      _ => node.to_string(),
+    };
+    let nodes = self.nodes().collect::<Vec<_,>>();
+    let id = |node:&CallNode| nodes.binary_search(&node,).unwrap();
+    let mut dot = String::from("digraph calls {\n  node [fontname=monospace];\n",);
+    for (idx, node,) in nodes.iter().enumerate() {
+      let shape = match node {
+        CallNode::SysCall(_,) => "box",
+        CallNode::Unknown(_,) | CallNode::Indirect => "diamond",
+        _ => "ellipse",
+      };
+      dot.push_str(&format!(
+        "  n{} [label=\"{}\", shape={}];\n",
+        idx,
+        label(node,).replace('"', "\\\"",),
+        shape
+      ),);
+    }
+    for (caller, callees,) in &self.calls {
+      for callee in callees {
+        dot.push_str(&format!("  n{} -> n{};\n", id(caller,), id(callee,)),);
+      }
+    }
+    dot.push_str("}\n",);
+    dot
+  }
+}
+
+/// Finds the functions each function in the [`Program`] calls.
+///
+/// Instructions belong to the function of the last symbol in `symbols` at or
+/// before them. `Call`s to a symbol are edges to its function and `Call`s to
+/// any other offset are edges to [`CallNode::Unknown`]. Calls to externs,
+/// `CallR`, and `SysCall` are edges to [`CallNode::Extern`],
+/// [`CallNode::Indirect`], and [`CallNode::SysCall`] leaves.
+///
+/// Errors if the program cannot be decoded.
+pub fn call_graph(program:&Program, symbols:&SymbolTable,) -> Result<CallGraph, DecodeError,> {
+  let starts = symbols.iter().collect::<Vec<_,>>();
+  let function = |at:u32| match starts.partition_point(|sym| sym.offset <= at,) {
+    0 => CallNode::Unknown(0,),
+    i => CallNode::Function(starts[i - 1].name.clone(),),
+  };
+  let callee = |at:u32| match starts.iter().find(|sym| sym.offset == at,) {
+    Some(sym,) => CallNode::Function(sym.name.clone(),),
+    None => CallNode::Unknown(at,),
+  };
+
+  // Every function is a node even if it calls nothing
+  let mut graph = CallGraph {
+    calls:starts
+      .iter()
+      .map(|sym| (CallNode::Function(sym.name.clone(),), BTreeSet::new(),),)
+      .collect(),
+  };
+  for item in program.instructions() {
+    let (at, inst,) = item?;
+    let node = match (inst, symbols.extern_at(at,),) {
+      (Instruction::Call { .. }, Some(name,),) => CallNode::Extern(name.to_string(),),
+      (Instruction::Call { target, }, None,) => callee(target,),
+      (Instruction::CallR { .. }, _,) => CallNode::Indirect,
+      (Instruction::SysCall { idx, }, _,) => CallNode::SysCall(idx,),
+      _ => continue,
+    };
+    graph.calls.entry(function(at,),).or_default().insert(node,);
+  }
+  Ok(graph,)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq,)]
+/// How often an [`OpCode`] appears in a [`ProgramStats`].
+pub struct OpCodeStats {
+  /// Number of instructions with the opcode.
+  pub count:usize,
+  /// Bytes taken up by those instructions, including their operands.
+  pub bytes:usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// The instructions of one or more [`Program`]s counted by [`OpCode`], made
+/// by [`Program::stats`].
+pub struct ProgramStats {
+  /// Indexed by opcode.
+  opcodes:[OpCodeStats; OpCode::ALL.len()],
+}
+
+impl Default for ProgramStats {
+  fn default() -> Self {
+    ProgramStats {
+      opcodes:[OpCodeStats::default(); OpCode::ALL.len()],
+    }
+  }
+}
+
+impl ProgramStats {
+  pub(crate) fn add(&mut self, op:OpCode,) {
+    let stats = &mut self.opcodes[op as usize];
+    stats.count += 1;
+    stats.bytes += op.instruction_len();
+  }
+
+  /// Adds the counts in `other` to these, for totals across many programs.
+  pub fn merge(&mut self, other:&ProgramStats,) {
+    for (stats, other,) in self.opcodes.iter_mut().zip(&other.opcodes,) {
+      stats.count += other.count;
+      stats.bytes += other.bytes;
+    }
+  }
+
+  /// Returns the counts for `op`.
+  pub fn get(&self, op:OpCode,) -> OpCodeStats {
+    self.opcodes[op as usize]
+  }
+
+  /// Total number of instructions.
+  pub fn instruction_count(&self,) -> usize {
+    self.opcodes.iter().map(|stats| stats.count,).sum()
+  }
+
+  /// Total bytes of code.
+  pub fn bytes(&self,) -> usize {
+    self.opcodes.iter().map(|stats| stats.bytes,).sum()
+  }
+
+  /// Mean length of an instruction in bytes, or 0 if there are none.
+  pub fn average_len(&self,) -> f64 {
+    match self.instruction_count() {
+      0 => 0.0,
+      count => self.bytes() as f64 / count as f64,
+    }
+  }
+
+  /// Returns the opcodes which appear at least once, those taking up the
+  /// most bytes first. Ties are ordered by count and then by opcode.
+  pub fn sorted(&self,) -> Vec<(OpCode, OpCodeStats,),> {
+    let mut sorted = OpCode::ALL
+      .into_iter()
+      .map(|op| (op, self.get(op,),),)
+      .filter(|(_, stats,)| stats.count > 0,)
+      .collect::<Vec<_,>>();
+    sorted.sort_by_key(|&(op, stats,)| (Reverse(stats.bytes,), Reverse(stats.count,), op as u8,),);
+    sorted
+  }
+}
+
+impl Display for ProgramStats {
+  /// Prints a table of [`ProgramStats::sorted`] followed by the totals.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    writeln!(
+      f,
+      "{:<12} {:>8} {:>8} {:>6}",
+      "OpCode", "Count", "Bytes", "Bytes%"
+    )?;
+    let bytes = self.bytes().max(1,) as f64;
+    for (op, stats,) in self.sorted() {
+      writeln!(
+        f,
+        "{:<12} {:>8} {:>8} {:>5.1}%",
+        op.to_string(),
+        stats.count,
+        stats.bytes,
+        stats.bytes as f64 * 100.0 / bytes
+      )?;
+    }
+    writeln!(
+      f,
+      "{:<12} {:>8} {:>8}",
+      "Total",
+      self.instruction_count(),
+      self.bytes()
+    )?;
+    writeln!(f, "Average length: {:.2}", self.average_len())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    call_graph, cfg, register_usage, stack_depth, Access, CallNode, Edge, EdgeKind, OpCodeStats, StackIssue,
+  };
+  use crate::{
+    builder::ProgramBuilder,
+    errors::DecodeError,
+    instruction::Instruction,
+    memory::STACK_SIZE,
+    opcodes::{CmpFlag, OpCode},
+    program::Program,
+    registers::{reg, Register},
+    syscalls::{Syscall, SyscallTable},
+  };
+  use alloc::{string::ToString, vec, vec::Vec};
+
+  #[test]
+  fn register_usage_of_program() {
+    let mut p = Program::new();
+    p.emit_add_ri(reg(16,), reg(5,), 1.0,);
+    p.emit_cmp_ri(CmpFlag::Eq, reg(16,), 2.0,);
+    p.emit_jz(Register::EQ, 0,);
+    p.emit_push(reg(16,),);
+    p.emit_call(0,);
+    p.emit(Instruction::RMem {
+      dst:reg(20,),
+      src:reg(16,),
+      imm_offset:0,
+      reg_offset:Register::PC,
+    },);
+    p.emit_load_int(Register::LOOP, 3,);
+    p.emit_ret(0,);
+
+    let usage = register_usage(&p,).unwrap();
+    let explicit = |at| Access { at, implicit:false, };
+    let implicit = |at| Access { at, implicit:true, };
+    assert_eq!(usage.reads(reg(5,),), [explicit(0)]);
+    assert_eq!(usage.writes(reg(5,),), []);
+    assert_eq!(usage.writes(reg(16,),), [explicit(0)]);
+    assert_eq!(usage.reads(reg(16,),), [explicit(7), explicit(20), explicit(27)]);
+
+    // Cmp writes EQ, the stack instructions move SP, and jumps write PC
+    assert_eq!(usage.writes(Register::EQ,), [implicit(7)]);
+    assert_eq!(usage.reads(Register::EQ,), [explicit(14)]);
+    assert_eq!(
+      usage.writes(Register::SP,),
+      [implicit(20), implicit(22), implicit(41)]
+    );
+    assert_eq!(
+      usage.reads(Register::SP,),
+      [implicit(20), implicit(22), implicit(41)]
+    );
+    assert_eq!(usage.reads(Register::PC,), [implicit(22)]);
+    assert_eq!(
+      usage.writes(Register::PC,),
+      [implicit(14), implicit(22), implicit(41)]
+    );
+
+    assert_eq!(usage.max_register_used(), Some(reg(20)));
+    assert_eq!(usage.writes_to_reserved(), [(Register::LOOP, 35)]);
+    assert_eq!(
+      usage.unused_argument_registers(),
+      (6..=15).map(reg).collect::<Vec<_,>>()
+    );
+    assert!(register_usage(&Program::from([0xFF,]),).is_err());
+  }
+
+  #[test]
+  fn balanced_stack() {
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    b.emit(Instruction::Push { src:reg(16,), },);
+    b.emit(Instruction::PushI { imm:1.0, },);
+    b.call(func,);
+    b.emit(Instruction::Hlt,);
+    b.bind(func,).unwrap();
+    b.emit(Instruction::Enter { locals:2, },);
+    b.emit(Instruction::Push { src:reg(16,), },);
+    b.emit(Instruction::Pop,);
+    b.emit(Instruction::Leave,);
+    b.emit(Instruction::Ret { args:2, },);
+    let p = b.finish().unwrap();
+
+    // 2 arguments, the return address, FP, 2 locals, and a push
+    let report = stack_depth(&p,).unwrap();
+    assert_eq!(report.max_depth, Some(7));
+    assert!(report.is_ok());
+  }
+
+  #[test]
+  fn unbalanced_stack() {
+    // A push on only one side of a branch
+    let mut b = ProgramBuilder::new();
+    let skip = b.new_label();
+    b.jz(Register::EQ, skip,);
+    b.emit(Instruction::Push { src:reg(16,), },);
+    b.bind(skip,).unwrap();
+    b.emit(Instruction::Hlt,);
+    let report = stack_depth(&b.finish().unwrap(),).unwrap();
+    assert_eq!(report.issues, [StackIssue::Unbalanced { at:8, depths:(0, 1,) }]);
+
+    // A push in a loop
+    let mut b = ProgramBuilder::new();
+    let top = b.new_label();
+    b.bind(top,).unwrap();
+    b.emit(Instruction::Push { src:reg(16,), },);
+    b.jnz(Register::EQ, top,);
+    b.emit(Instruction::Hlt,);
+    let report = stack_depth(&b.finish().unwrap(),).unwrap();
+    assert_eq!(report.max_depth, None);
+    assert_eq!(report.issues, [StackIssue::Unbounded { at:0, growth:1 }]);
+
+    // Too many pushes
+    let mut p = Program::new();
+    for _ in 0..STACK_SIZE + 1 {
+      p.emit(Instruction::PushI { imm:0.0, },);
+    }
+    p.emit(Instruction::PopN { count:22, },);
+    p.emit(Instruction::Hlt,);
+    let report = stack_depth(&p,).unwrap();
+    assert_eq!(report.max_depth, Some(21));
+    assert_eq!(
+      report.issues,
+      [
+        StackIssue::Overflow { at:100, depth:21 },
+        StackIssue::Underflow { at:105, depth:-1 },
+      ]
+    );
+
+    // A function which returns with a local still on the stack, a Leave with
+    // no Enter, and a recursive call
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    let recurse = b.named_label("recurse",);
+    b.call(func,);
+    b.call(recurse,);
+    b.emit(Instruction::Leave,);
+    b.emit(Instruction::Hlt,);
+    b.bind(func,).unwrap();
+    b.emit(Instruction::Push { src:reg(16,), },);
+    b.emit(Instruction::Ret { args:0, },);
+    b.bind(recurse,).unwrap();
+    b.call(recurse,);
+    b.emit(Instruction::Ret { args:0, },);
+    let report = stack_depth(&b.finish().unwrap(),).unwrap();
+    assert_eq!(report.max_depth, None);
+    assert_eq!(
+      report.issues,
+      [
+        StackIssue::UnmatchedLeave { at:10 },
+        StackIssue::UnbalancedReturn { at:14, depth:1 },
+        StackIssue::Recursive { at:16, target:16 },
+      ]
+    );
+  }
+
+  #[test]
+  fn control_flow_graph() {
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    let top = b.new_label();
+    b.bind(top,).unwrap();
+    b.emit(Instruction::Dec { dst:reg(16,), },);
+    b.jnz(reg(16,), top,);
+    b.call(func,);
+    b.emit(Instruction::Hlt,);
+    // Never reached
+    b.emit(Instruction::Noop,);
+    b.bind(func,).unwrap();
+    b.emit(Instruction::JmpR { src:reg(17,), },);
+    b.emit(Instruction::Ret { args:0, },);
+    let p = b.finish().unwrap();
+
+    let cfg = cfg(&p,).unwrap();
+    let starts = cfg
+      .blocks()
+      .iter()
+      .map(|block| (block.start, block.end,),)
+      .collect::<Vec<_,>>();
+    assert_eq!(starts, [(0, 8), (8, 13), (13, 14), (14, 15), (15, 17), (17, 19)]);
+    assert_eq!(cfg.blocks()[0].insts.len(), 2);
+    assert_eq!(cfg.block_at(3,), Some(0));
+    assert_eq!(cfg.block_at(19,), None);
+
+    let edge = |from, to, kind| Edge { from, to, kind, };
+    assert_eq!(
+      cfg.edges(),
+      [
+        edge(0, Some(1,), EdgeKind::Fallthrough,),
+        edge(0, Some(0,), EdgeKind::Taken,),
+        edge(1, Some(4,), EdgeKind::Call,),
+        edge(1, Some(2,), EdgeKind::Fallthrough,),
+        edge(3, Some(4,), EdgeKind::Fallthrough,),
+        edge(4, None, EdgeKind::Unknown,),
+      ]
+    );
+
+    let dot = cfg.to_dot();
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("  b0 [label=\"0000: Dec $16\\l0002: Jnz $16, 0\\l\"];\n"));
+    assert!(dot.contains("  b0 -> b0 [label=\"taken\"];\n"));
+    assert!(dot.contains("  b4 -> unknown [label=\"unknown\"];\n"));
+  }
+
+  #[test]
+  fn return_edges() {
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    let skip = b.new_label();
+    b.call(func,);
+    b.call(func,);
+    b.emit(Instruction::Hlt,);
+    b.bind(func,).unwrap();
+    b.jz(Register::EQ, skip,);
+    b.emit(Instruction::Ret { args:0, },);
+    b.bind(skip,).unwrap();
+    b.emit(Instruction::Ret { args:1, },);
+    let cfg = cfg(&b.finish().unwrap(),).unwrap();
+
+    let returns = cfg
+      .edges()
+      .iter()
+      .filter(|edge| edge.kind == EdgeKind::Return,)
+      .map(|edge| (edge.from, edge.to.unwrap(),),)
+      .collect::<Vec<_,>>();
+    assert_eq!(returns, [(4, 1), (4, 2), (5, 1), (5, 2)]);
+  }
+
+  #[test]
+  fn opcode_stats() {
+    let mut p = Program::new();
+    p.emit_load(reg(16,), 1.0,);
+    p.emit_load(reg(17,), 2.0,);
+    p.emit_add_ri(reg(16,), reg(17,), 1.0,);
+    p.emit(Instruction::Hlt,);
+    let mut stats = p.stats().unwrap();
+    assert_eq!(stats.get(OpCode::Load), OpCodeStats { count:2, bytes:12 });
+    assert_eq!(stats.instruction_count(), 4);
+    assert_eq!(stats.bytes(), p.len());
+    assert_eq!(stats.average_len(), 5.0);
+    assert_eq!(
+      stats.to_string(),
+      "\
+OpCode          Count    Bytes Bytes%
+Load                2       12  60.0%
+Add_RI              1        7  35.0%
+Hlt                 1        1   5.0%
+Total               4       20
+Average length: 5.00
+"
+    );
+
+    stats.merge(&Program::from(vec![OpCode::Hlt as u8],).stats().unwrap(),);
+    assert_eq!(stats.get(OpCode::Hlt), OpCodeStats { count:2, bytes:2 });
+    assert_eq!(stats.instruction_count(), 5);
+
+    p.push(0xFF,);
+    assert!(matches!(
+      p.stats(),
+      Err(DecodeError::InvalidOpCode { byte:0xFF, .. })
+    ));
+  }
+
+  #[test]
+  fn call_graph_of_program() {
+    let mut b = ProgramBuilder::new();
+    let fact = b.named_label("fact",);
+    b.call(fact,);
+    b.call_extern("print",);
+    b.emit(Instruction::SysCall { idx:3, },);
+    b.emit(Instruction::Hlt,);
+    b.bind(fact,).unwrap();
+    b.call(fact,);
+    b.emit(Instruction::CallR { src:reg(16,), },);
+    b.emit(Instruction::Call { target:1, },);
+    b.emit(Instruction::Ret { args:0, },);
+    let leaf = b.offset();
+    b.emit(Instruction::Ret { args:0, },);
+    let p = b.finish().unwrap();
+
+    // Only called labels are symbols so name the others here
+    let mut symbols = p.symbols().clone();
+    symbols.insert("main", 0,).unwrap();
+    symbols.insert("leaf", leaf,).unwrap();
+    let graph = call_graph(&p, &symbols,).unwrap();
+    let function = |name:&str| CallNode::Function(name.to_string(),);
+    assert_eq!(
+      graph.callees(&function("main",),).cloned().collect::<Vec<_,>>(),
+      [
+        function("fact",),
+        CallNode::Extern("print".to_string(),),
+        CallNode::SysCall(3,)
+      ]
+    );
+    assert_eq!(
+      graph.callees(&function("fact",),).cloned().collect::<Vec<_,>>(),
+      [function("fact",), CallNode::Unknown(1,), CallNode::Indirect]
+    );
+    assert_eq!(graph.callees(&function("leaf",),).count(), 0);
+    assert_eq!(graph.callers(&function("leaf",),).count(), 0);
+    assert_eq!(graph.recursive().collect::<Vec<_,>>(), [&function("fact",)]);
+
+    let mut syscalls = SyscallTable::new();
+    syscalls
+      .insert(Syscall {
+        index:3,
+        name:"print_entity".to_string(),
+        args:0,
+        doc:None,
+      },)
+      .unwrap();
+    let dot = graph.to_dot_with(Some(&syscalls,),);
+    assert!(dot.contains("[label=\"print_entity\", shape=box];"));
+    assert!(graph.to_dot().contains("[label=\"SysCall 3\", shape=box];"));
+    assert_eq!(dot.matches(" -> ",).count(), 6);
+  }
+}
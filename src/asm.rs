@@ -0,0 +1,633 @@
+use crate::{
+  errors::{AsmError, AsmErrorKind},
+  instruction::{Instruction, OperandReader},
+  opcodes::{CmpFlag, OpCode},
+  program::Program,
+  registers::Register,
+  syscalls::SyscallTable,
+};
+use alloc::{collections::BTreeMap, string::ToString, vec::Vec};
+
+/// Offsets of the labels defined in a source file.
+type Labels<'a,> = BTreeMap<&'a str, u32,>;
+
+/// Assembles the listing format printed by [`Program`]'s `Display`
+/// implementation back into a [`Program`].
+///
+/// Each line holds one instruction: the opcode's mnemonic followed by its
+/// comma separated operands. The syntax is extended with:
+/// - `name:` at the start of a line, which defines a label at the offset of the
+///   line's instruction.
+/// - Label names, optionally written `<name>`, in place of the target of a jump
+///   or call. Relative jumps assemble to the label's offset from the next
+///   instruction. Labels which are called are added to the program's
+///   [`SymbolTable`](crate::symbols::SymbolTable).
+/// - `;` which comments out the rest of the line.
+/// - Hex integers such as `0x1F` anywhere an integer is expected.
+pub fn assemble(src:&str,) -> Result<Program, AsmError,> {
+  assemble_with(src, &SyscallTable::new(),)
+}
+
+/// Assembles `src` like [`assemble`] but additionally allows the operand of a
+/// `SysCall` to be the name of a syscall in `syscalls`. The argument count
+/// printed after the name by
+/// [`Program::with_syscalls`](crate::program::Program::with_syscalls) is
+/// ignored.
+pub fn assemble_with(src:&str, syscalls:&SyscallTable,) -> Result<Program, AsmError,> {
+  // Labels may be used before they are defined so the first pass only finds
+  // the offset of each label
+  let (_, labels,) = assemble_pass(src, None, syscalls,)?;
+  let (program, _,) = assemble_pass(src, Some(&labels,), syscalls,)?;
+  Ok(program,)
+}
+
+/// Assembles `src`, resolving label references with `labels`.
+///
+/// If `labels` is `None` every label reference assembles to 0.
+fn assemble_pass<'a,>(
+  src:&'a str,
+  labels:Option<&Labels<'a,>,>,
+  syscalls:&SyscallTable,
+) -> Result<(Program, Labels<'a,>,), AsmError,> {
+  let mut program = Program::new();
+  let mut defined = Labels::new();
+
+  for (idx, text,) in src.lines().enumerate() {
+    let mut line = Line::new(idx + 1, text, labels, syscalls,);
+
+    if let Some((column, name,),) = line.label {
+      if !is_label_name(name,) {
+        return Err(line.error(AsmErrorKind::InvalidLabel, column, name,),);
+      }
+      if defined.insert(name, program.len() as u32,).is_some() {
+        return Err(line.error(AsmErrorKind::DuplicateLabel, column, name,),);
+      }
+    }
+
+    let Some((column, mnemonic,),) = line.mnemonic
+    else {
+      continue;
+    };
+    let op = match mnemonic.parse::<OpCode>() {
+      Ok(op,) => op,
+      Err(_,) => return Err(line.error(AsmErrorKind::UnknownMnemonic, column, mnemonic,),),
+    };
+    line.jump = matches!(
+      op,
+      OpCode::Jmp
+        | OpCode::Jz
+        | OpCode::Jnz
+        | OpCode::Call
+        | OpCode::JmpCmpRR
+        | OpCode::JmpCmpRI
+        | OpCode::JmpRel
+        | OpCode::JzRel
+        | OpCode::JnzRel
+    );
+    line.end_offset = program.len() as u32 + op.instruction_len() as u32;
+    line.syscall = op == OpCode::SysCall;
+
+    let inst = Instruction::read_operands(op, &mut line,)?;
+    line.finish()?;
+    program.emit(inst,);
+
+    if let (Instruction::Call { target, }, Some(&(column, token,),),) = (inst, line.operands.first(),) {
+      let name = strip_brackets(token,);
+      if is_label_name(name,) && program.symbols().get(name,).is_none() {
+        // Only the length of a new label name can be rejected
+        program
+          .symbols_mut()
+          .insert(name, target,)
+          .map_err(|_| line.error(AsmErrorKind::LabelTooLong, column, name,),)?;
+      }
+    }
+  }
+
+  Ok((program, defined,),)
+}
+
+/// Labels start with a letter or `_` followed by letters, digits, or `_`.
+pub(crate) fn is_label_name(name:&str,) -> bool {
+  let mut chars = name.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_',)
+}
+
+/// Removes the `<>` around a symbol printed by the annotated listing.
+fn strip_brackets(token:&str,) -> &str {
+  token
+    .strip_prefix('<',)
+    .and_then(|name| name.strip_suffix('>',),)
+    .unwrap_or(token,)
+}
+
+/// Parses a decimal or `0x` prefixed hex integer.
+fn parse_int(token:&str,) -> Option<u64,> {
+  match token.strip_prefix("0x",).or_else(|| token.strip_prefix("0X",),) {
+    Some(hex,) => u64::from_str_radix(hex, 16,).ok(),
+    None => token.parse().ok(),
+  }
+}
+
+/// A single line of assembly split into tokens.
+struct Line<'a, 'l,> {
+  line:usize,
+  /// Column one past the end of the line's code.
+  end:usize,
+  label:Option<(usize, &'a str,),>,
+  mnemonic:Option<(usize, &'a str,),>,
+  /// Operands and the column each one starts at.
+  operands:Vec<(usize, &'a str,),>,
+  next:usize,
+  labels:Option<&'l Labels<'a,>,>,
+  /// Whether the line's `u32` or `i32` operand is a jump target which may be a
+  /// label.
+  jump:bool,
+  /// Offset of the instruction after the line's, which relative jumps are
+  /// measured from.
+  end_offset:u32,
+  syscalls:&'l SyscallTable,
+  /// Whether the line's `u8` operand is a syscall index which may be a name.
+  syscall:bool,
+}
+
+impl<'a, 'l,> Line<'a, 'l,> {
+  fn new(line:usize, text:&'a str, labels:Option<&'l Labels<'a,>,>, syscalls:&'l SyscallTable,) -> Self {
+    let mut this = Line {
+      line,
+      end:0,
+      label:None,
+      mnemonic:None,
+      operands:Vec::new(),
+      next:0,
+      labels,
+      jump:false,
+      end_offset:0,
+      syscalls,
+      syscall:false,
+    };
+
+    let code = match text.find(';',) {
+      Some(comment,) => &text[..comment],
+      None => text,
+    };
+    let code = code.trim_end();
+    this.end = code.len() + 1;
+
+    // Position of the next token in `code`
+    let mut pos = code.len() - code.trim_start().len();
+    let word = |pos:usize| {
+      let rest = &code[pos..];
+      &rest[..rest.find(char::is_whitespace,).unwrap_or(rest.len(),)]
+    };
+    let skip_space = |pos:usize| code.len() - code[pos..].trim_start().len();
+
+    if let Some(name,) = word(pos,).strip_suffix(':',) {
+      this.label = Some((pos + 1, name,),);
+      pos = skip_space(pos + name.len() + 1,);
+    }
+    if pos == code.len() {
+      return this;
+    }
+
+    let mnemonic = word(pos,);
+    this.mnemonic = Some((pos + 1, mnemonic,),);
+    pos += mnemonic.len();
+
+    if !code[pos..].trim().is_empty() {
+      for piece in code[pos..].split(',',) {
+        let leading = piece.len() - piece.trim_start().len();
+        this.operands.push((pos + leading + 1, piece.trim(),),);
+        pos += piece.len() + 1;
+      }
+    }
+
+    this
+  }
+
+  fn error(&self, kind:AsmErrorKind, column:usize, token:&str,) -> AsmError {
+    AsmError {
+      line:self.line,
+      column,
+      token:token.to_string(),
+      kind,
+    }
+  }
+
+  /// Returns the next operand and the column it starts at.
+  fn next_operand(&mut self,) -> Result<(usize, &'a str,), AsmError,> {
+    match self.operands.get(self.next,) {
+      Some(&operand,) => {
+        self.next += 1;
+        Ok(operand,)
+      }
+      None => Err(self.error(AsmErrorKind::MissingOperand, self.end, "",),),
+    }
+  }
+
+  /// Reads an integer operand no larger than `max`.
+  fn int(&mut self, max:u32,) -> Result<u32, AsmError,> {
+    let (column, token,) = self.next_operand()?;
+    match parse_int(token,) {
+      Some(int,) if int <= max as u64 => Ok(int as u32,),
+      _ => Err(self.error(AsmErrorKind::ExpectedInteger, column, token,),),
+    }
+  }
+
+  /// Reads the next operand as a label if it is a jump target naming one.
+  ///
+  /// Returns `None` without consuming the operand otherwise.
+  fn label(&mut self,) -> Option<Result<u32, AsmError,>,> {
+    let &(column, token,) = self.operands.get(self.next,)?;
+    let token = strip_brackets(token,);
+    if !self.jump || !is_label_name(token,) {
+      return None;
+    }
+
+    self.next += 1;
+    Some(match self.labels {
+      Some(labels,) => labels
+        .get(token,)
+        .copied()
+        .ok_or_else(|| self.error(AsmErrorKind::UndefinedLabel, column, token,),),
+      None => Ok(0,),
+    },)
+  }
+
+  /// Reads the next operand as a syscall name if it is a syscall index naming
+  /// one. Anything after the name is ignored.
+  ///
+  /// Returns `None` without consuming the operand otherwise.
+  fn syscall_name(&mut self,) -> Option<Result<u8, AsmError,>,> {
+    let &(column, token,) = self.operands.get(self.next,)?;
+    let name = token.split_whitespace().next()?;
+    if !self.syscall || !is_label_name(name,) {
+      return None;
+    }
+
+    self.next += 1;
+    Some(
+      self
+        .syscalls
+        .by_name(name,)
+        .map(|syscall| syscall.index,)
+        .ok_or_else(|| self.error(AsmErrorKind::UnknownSyscall, column, name,),),
+    )
+  }
+
+  /// Errors if the line has operands which were not read.
+  fn finish(&self,) -> Result<(), AsmError,> {
+    match self.operands.get(self.next,) {
+      Some(&(column, token,),) => Err(self.error(AsmErrorKind::UnexpectedOperand, column, token,),),
+      None => Ok((),),
+    }
+  }
+}
+
+impl OperandReader for Line<'_, '_,> {
+  type Error = AsmError;
+
+  fn reg(&mut self,) -> Result<Register, AsmError,> {
+    let (column, token,) = self.next_operand()?;
+    // Reserved registers may be written by name or by number but the `$` is
+    // required in assembly
+    let reg = token.strip_prefix('$',).and_then(|_| token.parse().ok(),);
+    reg.ok_or_else(|| self.error(AsmErrorKind::ExpectedRegister, column, token,),)
+  }
+
+  fn u8(&mut self,) -> Result<u8, AsmError,> {
+    match self.syscall_name() {
+      Some(idx,) => idx,
+      None => self.int(u8::MAX as u32,).map(|int| int as u8,),
+    }
+  }
+
+  fn u32(&mut self,) -> Result<u32, AsmError,> {
+    match self.label() {
+      Some(target,) => target,
+      None => self.int(u32::MAX,),
+    }
+  }
+
+  fn i32(&mut self,) -> Result<i32, AsmError,> {
+    if let Some(target,) = self.label() {
+      return target.map(|target| target.wrapping_sub(self.end_offset,) as i32,);
+    }
+
+    let (column, token,) = self.next_operand()?;
+    let int = match token.strip_prefix('-',) {
+      Some(int,) => parse_int(int,).map(|int| -(int as i64),),
+      None => parse_int(token,).map(|int| int as i64,),
+    };
+    int
+      .and_then(|int| i32::try_from(int,).ok(),)
+      .ok_or_else(|| self.error(AsmErrorKind::ExpectedInteger, column, token,),)
+  }
+
+  fn f32(&mut self,) -> Result<f32, AsmError,> {
+    let (column, token,) = self.next_operand()?;
+    match token.parse() {
+      Ok(float,) => Ok(float,),
+      Err(_,) => match token.strip_prefix('-',) {
+        Some(hex,) => parse_int(hex,).map(|int| -(int as f32),),
+        None => parse_int(token,).map(|int| int as f32,),
+      }
+      .ok_or_else(|| self.error(AsmErrorKind::ExpectedFloat, column, token,),),
+    }
+  }
+
+  fn flag(&mut self,) -> Result<CmpFlag, AsmError,> {
+    let (column, token,) = self.next_operand()?;
+    token
+      .parse()
+      .map_err(|_| self.error(AsmErrorKind::ExpectedFlag, column, token,),)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::assemble;
+  use crate::{
+    errors::{AsmError, AsmErrorKind, ReassembleError},
+    instruction::Instruction,
+    opcodes::{CmpFlag, OpCode},
+    program::Program,
+    registers::{reg, Register},
+    symbols::MAX_NAME_LEN,
+  };
+  use alloc::{format, string::ToString, vec};
+
+  #[test]
+  fn assemble_listing() {
+    let program = assemble("Load $14, 1.5\n\n  Cmp_RI GEQ, $14, -2\nJz $EQ, 0\nRet 2\n",).unwrap();
+
+    let mut expected = Program::new();
+    expected.emit_load(reg(14,), 1.5,);
+    expected.emit_cmp_ri(CmpFlag::Geq, reg(14,), -2.0,);
+    expected.emit_jz(reg(2,), 0,);
+    expected.emit_ret(2,);
+    assert_eq!(program, expected);
+
+    let by_name = assemble("Copy $PC, $SP\nWriteStr $EQ, $LOOP\nPush $FP",).unwrap();
+    let by_number = assemble("Copy $0, $1\nWriteStr $2, $3\nPush $4",).unwrap();
+    assert_eq!(by_name, by_number);
+  }
+
+  #[test]
+  fn labels_comments_and_hex() {
+    let src = "
+      ; Count $14 down to zero
+      Load $0xE, 0x10
+      top: Sub_RI $14, $14, 1 ; decrement
+      Cmp_RI EQ, $14, 0
+      Jnz $EQ, end
+      Jmp top
+      end:
+      Hlt
+    ";
+
+    let mut expected = Program::new();
+    expected.emit_load(reg(14,), 16.0,);
+    let top = expected.emit_sub_ri(reg(14,), reg(14,), 1.0,);
+    expected.emit_cmp_ri(CmpFlag::Eq, reg(14,), 0.0,);
+    let jnz = expected.emit_jnz(reg(2,), 0,);
+    expected.emit_jmp(top,);
+    let end = expected.emit_hlt();
+    expected.patch_u32(jnz + 2, end,).unwrap();
+
+    assert_eq!(assemble(src,).unwrap(), expected);
+  }
+
+  #[test]
+  fn relative_jump_labels() {
+    let src = "
+      top: Sub_RI $14, $14, 1
+      JnzRel $14, top
+      JzRel $EQ, end
+      Hlt
+      end: JmpRel -0x1
+    ";
+
+    let mut expected = Program::new();
+    expected.emit_sub_ri(reg(14,), reg(14,), 1.0,);
+    expected.emit_jnz_rel(reg(14,), -13,);
+    expected.emit_jz_rel(reg(2,), 1,);
+    expected.emit_hlt();
+    expected.emit_jmp_rel(-1,);
+
+    assert_eq!(assemble(src,).unwrap(), expected);
+  }
+
+  #[test]
+  fn label_errors() {
+    assert_eq!(
+      assemble("a:\nHlt\n  a: Hlt",),
+      Err(AsmError {
+        line:3,
+        column:3,
+        token:"a".to_string(),
+        kind:AsmErrorKind::DuplicateLabel,
+      })
+    );
+    assert_eq!(
+      assemble("Hlt\nJmp missing ; nowhere",),
+      Err(AsmError {
+        line:2,
+        column:5,
+        token:"missing".to_string(),
+        kind:AsmErrorKind::UndefinedLabel,
+      })
+    );
+    assert_eq!(
+      assemble("1a: Hlt",),
+      Err(AsmError {
+        line:1,
+        column:1,
+        token:"1a".to_string(),
+        kind:AsmErrorKind::InvalidLabel,
+      })
+    );
+    // Called labels become symbols, whose names are limited
+    let name = "a".repeat(MAX_NAME_LEN + 1,);
+    assert_eq!(
+      assemble(&format!(
+        "Call {}
+Hlt
+{}: Ret 0",
+        name, name
+      ),),
+      Err(AsmError {
+        line:1,
+        column:6,
+        token:name,
+        kind:AsmErrorKind::LabelTooLong,
+      })
+    );
+    let name = "a".repeat(MAX_NAME_LEN,);
+    assert!(assemble(&format!(
+      "Call {}
+Hlt
+{}: Ret 0",
+      name, name
+    ),)
+    .is_ok());
+  }
+
+  #[test]
+  fn reassemble_display_output() {
+    // Generate programs containing every opcode with varied operands using a
+    // small linear congruential generator so the corpus is reproducible
+    let mut seed = 0x2545_F491_u32;
+    let mut next = || {
+      seed = seed.wrapping_mul(1_664_525,).wrapping_add(1_013_904_223,);
+      seed
+    };
+
+    for _ in 0..50 {
+      let mut program = Program::new();
+      for op in OpCode::iter() {
+        let mut operands = RandomOperands(&mut next,);
+        program.emit(Instruction::read_operands(op, &mut operands,).unwrap(),);
+      }
+      assert_eq!(program.reassemble_check(), Ok(()), "{}", program);
+    }
+
+    // Every opcode with all of its operands zero
+    let mut program = Program::new();
+    for op in OpCode::iter() {
+      let mut operands = RandomOperands(&mut || 0,);
+      program.emit(Instruction::read_operands(op, &mut operands,).unwrap(),);
+    }
+    assert_eq!(program.reassemble_check(), Ok(()));
+  }
+
+  #[test]
+  fn reassemble_check_reports_first_difference() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 0.1,);
+    // NaN payloads are not printed
+    program.emit_load(reg(17,), f32::from_bits(0x7FC0_0001,),);
+    program.emit(Instruction::Hlt,);
+    assert_eq!(
+      program.reassemble_check(),
+      Err(ReassembleError::Mismatch {
+        offset:6,
+        text:"Load $17, NaN".to_string(),
+        original:vec![1, 17, 1, 0, 0xC0, 0x7F],
+        reassembled:vec![1, 17, 0, 0, 0xC0, 0x7F],
+      })
+    );
+    assert_eq!(
+      program.reassemble_check().unwrap_err().to_string(),
+      "`Load $17, NaN` at offset 6 is [01 11 01 00 C0 7F] but reassembles to [01 11 00 00 C0 7F]"
+    );
+
+    program.push(0xFF,);
+    assert!(matches!(
+      program.reassemble_check(),
+      Err(ReassembleError::Asm(_,))
+    ));
+  }
+
+  #[test]
+  fn errors_report_position() {
+    assert_eq!(
+      assemble("Hlt\nLoad $14, 1\nMove $1, $2",),
+      Err(AsmError {
+        line:3,
+        column:1,
+        token:"Move".to_string(),
+        kind:AsmErrorKind::UnknownMnemonic,
+      })
+    );
+    assert_eq!(
+      assemble("  Add_RR $14, 15, $16",),
+      Err(AsmError {
+        line:1,
+        column:15,
+        token:"15".to_string(),
+        kind:AsmErrorKind::ExpectedRegister,
+      })
+    );
+    assert_eq!(
+      assemble("Cmp_RR NE, $14, $15",),
+      Err(AsmError {
+        line:1,
+        column:8,
+        token:"NE".to_string(),
+        kind:AsmErrorKind::ExpectedFlag,
+      })
+    );
+    assert_eq!(
+      assemble("Load $14, one",),
+      Err(AsmError {
+        line:1,
+        column:11,
+        token:"one".to_string(),
+        kind:AsmErrorKind::ExpectedFloat,
+      })
+    );
+    assert_eq!(
+      assemble("Ret 256",),
+      Err(AsmError {
+        line:1,
+        column:5,
+        token:"256".to_string(),
+        kind:AsmErrorKind::ExpectedInteger,
+      })
+    );
+    assert_eq!(assemble("Push $14",).map(|p| p.len()), Ok(2));
+    assert_eq!(
+      assemble("Push",),
+      Err(AsmError {
+        line:1,
+        column:5,
+        token:"".to_string(),
+        kind:AsmErrorKind::MissingOperand,
+      })
+    );
+    assert_eq!(
+      assemble("Pop $14",),
+      Err(AsmError {
+        line:1,
+        column:5,
+        token:"$14".to_string(),
+        kind:AsmErrorKind::UnexpectedOperand,
+      })
+    );
+  }
+
+  /// Produces arbitrary operands from a random number generator.
+  struct RandomOperands<'a, F:FnMut() -> u32,>(&'a mut F,);
+
+  impl<F:FnMut() -> u32,> crate::instruction::OperandReader for RandomOperands<'_, F,> {
+    type Error = ();
+
+    fn reg(&mut self,) -> Result<Register, (),> {
+      Ok(Register::new((self.0)() as u8,),)
+    }
+
+    fn u8(&mut self,) -> Result<u8, (),> {
+      Ok((self.0)() as u8,)
+    }
+
+    fn u32(&mut self,) -> Result<u32, (),> {
+      Ok((self.0)(),)
+    }
+
+    fn i32(&mut self,) -> Result<i32, (),> {
+      Ok((self.0)() as i32,)
+    }
+
+    fn f32(&mut self,) -> Result<f32, (),> {
+      // Avoid NaN since NaN != NaN would make otherwise identical programs
+      // compare unequal
+      let f = f32::from_bits((self.0)(),);
+      Ok(if f.is_nan() { 0.1 } else { f },)
+    }
+
+    fn flag(&mut self,) -> Result<CmpFlag, (),> {
+      Ok(CmpFlag::ALL[(self.0)() as usize % CmpFlag::ALL.len()],)
+    }
+  }
+}
@@ -0,0 +1,474 @@
+use crate::{
+  errors::LabelError, instruction::Instruction, opcodes::CmpFlag, program::Program, registers::Register,
+  relocs::RelocKind,
+};
+use alloc::{
+  format,
+  string::{String, ToString},
+  vec::Vec,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// A jump destination created by [`ProgramBuilder::new_label`] which is given
+/// an offset by [`ProgramBuilder::bind`].
+pub struct Label(u32,);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+/// A string in a [`ProgramBuilder`]'s constant pool created by
+/// [`ProgramBuilder::intern_str`].
+pub struct StrId(u32,);
+
+#[derive(Debug, Clone,)]
+struct LabelInfo {
+  name:Option<String,>,
+  offset:Option<u32,>,
+}
+
+#[derive(Debug, Clone, Default,)]
+/// Builds a [`Program`] whose jumps target [`Label`]s instead of offsets.
+///
+/// Jumps to a label may be emitted before the label is bound. Their targets
+/// are filled in by [`ProgramBuilder::finish`], which also records a
+/// [`Relocation`](crate::relocs::Relocation) for every absolute jump, call,
+/// and data address so the program can be rebased or linked.
+pub struct ProgramBuilder {
+  program:Program,
+  labels:Vec<LabelInfo,>,
+  /// Offsets of 4 byte jump operands, the label they should hold, and for
+  /// relative jumps the offset they are measured from.
+  fixups:Vec<(u32, Label, Option<u32,>,),>,
+  /// Whether `jmp`, `jz`, and `jnz` emit their relative forms.
+  relative:bool,
+  /// Interned strings indexed by [`StrId`].
+  strings:Vec<String,>,
+  /// Offsets of `LoadU32` operands which should hold a string's address.
+  str_fixups:Vec<(u32, StrId,),>,
+  /// Named labels which have been called.
+  functions:Vec<Label,>,
+  /// Offsets of calls to functions defined in other programs and the names
+  /// of the functions.
+  externs:Vec<(u32, String,),>,
+}
+
+impl ProgramBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Makes [`ProgramBuilder::jmp`], [`ProgramBuilder::jz`], and
+  /// [`ProgramBuilder::jnz`] emit `JmpRel`, `JzRel`, and `JnzRel`. Programs
+  /// built from relative jumps can be moved or concatenated without
+  /// relocating them.
+  pub fn relative_jumps(&mut self, relative:bool,) {
+    self.relative = relative;
+  }
+
+  /// Offset the next emitted instruction will start at.
+  pub fn offset(&self,) -> u32 {
+    self.program.len() as u32
+  }
+
+  /// Creates a new unbound [`Label`].
+  pub fn new_label(&mut self,) -> Label {
+    self.labels.push(LabelInfo {
+      name:None,
+      offset:None,
+    },);
+    Label(self.labels.len() as u32 - 1,)
+  }
+
+  /// Creates a new unbound [`Label`] which errors refer to by `name`.
+  pub fn named_label(&mut self, name:&str,) -> Label {
+    self.labels.push(LabelInfo {
+      name:Some(name.to_string(),),
+      offset:None,
+    },);
+    Label(self.labels.len() as u32 - 1,)
+  }
+
+  /// Binds the [`Label`] to the offset of the next emitted instruction.
+  ///
+  /// Errors if the label has already been bound.
+  pub fn bind(&mut self, label:Label,) -> Result<(), LabelError,> {
+    let offset = self.offset();
+    let info = &mut self.labels[label.0 as usize];
+    match info.offset {
+      Some(_,) => Err(LabelError::AlreadyBound(self.label_name(label,),),),
+      None => {
+        info.offset = Some(offset,);
+        Ok((),)
+      }
+    }
+  }
+
+  /// Records that the instructions emitted from now on were compiled from
+  /// `line` and `column` of `file`, until the next call. See
+  /// [`Program::debug_info`].
+  pub fn set_source_loc(&mut self, file:&str, line:u32, column:u32,) {
+    self.program.set_source_loc(file, line, column,);
+  }
+
+  /// Emits an [`Instruction`]. Returns the offset it starts at.
+  pub fn emit(&mut self, inst:Instruction,) -> u32 {
+    self.program.emit(inst,)
+  }
+
+  /// Emits a `Jmp` or `JmpRel` to the [`Label`]. Returns the offset it starts
+  /// at.
+  pub fn jmp(&mut self, label:Label,) -> u32 {
+    let offset = match self.relative {
+      true => self.program.emit_jmp_rel(0,),
+      false => self.program.emit_jmp(0,),
+    };
+    self.fixup(offset + 1, label,);
+    offset
+  }
+
+  /// Emits a `Jz` or `JzRel` to the [`Label`]. Returns the offset it starts
+  /// at.
+  pub fn jz(&mut self, cond:Register, label:Label,) -> u32 {
+    let offset = match self.relative {
+      true => self.program.emit_jz_rel(cond, 0,),
+      false => self.program.emit_jz(cond, 0,),
+    };
+    self.fixup(offset + 2, label,);
+    offset
+  }
+
+  /// Emits a `Jnz` or `JnzRel` to the [`Label`]. Returns the offset it starts
+  /// at.
+  pub fn jnz(&mut self, cond:Register, label:Label,) -> u32 {
+    let offset = match self.relative {
+      true => self.program.emit_jnz_rel(cond, 0,),
+      false => self.program.emit_jnz(cond, 0,),
+    };
+    self.fixup(offset + 2, label,);
+    offset
+  }
+
+  /// Emits a `Call` to the [`Label`]. Returns the offset it starts at.
+  ///
+  /// Named labels which are called are added to the program's
+  /// [`SymbolTable`](crate::symbols::SymbolTable).
+  pub fn call(&mut self, label:Label,) -> u32 {
+    let offset = self.program.emit_call(0,);
+    self.fixups.push((offset + 1, label, None,),);
+    if self.labels[label.0 as usize].name.is_some() && !self.functions.contains(&label,) {
+      self.functions.push(label,);
+    }
+    offset
+  }
+
+  /// Emits a `Call` to the function named `name` in another program. The
+  /// call is recorded as an extern in the program's
+  /// [`SymbolTable`](crate::symbols::SymbolTable) and its target is filled in
+  /// by [`Program::link`]. Returns the offset it starts at.
+  pub fn call_extern(&mut self, name:&str,) -> u32 {
+    let offset = self.program.emit_call(0,);
+    self.externs.push((offset, name.to_string(),),);
+    offset
+  }
+
+  /// Emits a `JmpCmp_RR` to the [`Label`]. Returns the offset it starts at.
+  pub fn jmp_cmp_rr(&mut self, flag:CmpFlag, a:Register, b:Register, label:Label,) -> u32 {
+    let offset = self.program.emit_jmp_cmp_rr(flag, a, b, 0,);
+    self.fixups.push((offset + 4, label, None,),);
+    offset
+  }
+
+  /// Emits a `JmpCmp_RI` to the [`Label`]. Returns the offset it starts at.
+  pub fn jmp_cmp_ri(&mut self, flag:CmpFlag, src:Register, imm:f32, label:Label,) -> u32 {
+    let offset = self.program.emit_jmp_cmp_ri(flag, src, imm, 0,);
+    self.fixups.push((offset + 7, label, None,),);
+    offset
+  }
+
+  /// Adds `s` to the constant pool unless an identical string is already
+  /// there.
+  pub fn intern_str(&mut self, s:&str,) -> StrId {
+    match self.strings.iter().position(|interned| interned == s,) {
+      Some(id,) => StrId(id as u32,),
+      None => {
+        self.strings.push(s.to_string(),);
+        StrId(self.strings.len() as u32 - 1,)
+      }
+    }
+  }
+
+  /// Emits `LoadU32`s which point `ptr` at the interned string and set `len`
+  /// to its length followed by a `WriteStr`. Returns the offset of the first
+  /// instruction.
+  ///
+  /// Strings are stored one byte per memory cell so the length is the
+  /// string's length in bytes. The string's address is filled in by
+  /// [`ProgramBuilder::finish`].
+  pub fn emit_write_str(&mut self, ptr:Register, len:Register, s:StrId,) -> u32 {
+    let offset = self.program.emit_load_int(ptr, 0,);
+    self.str_fixups.push((offset + 2, s,),);
+    self
+      .program
+      .emit_load_int(len, self.strings[s.0 as usize].len() as u32,);
+    self.program.emit_write_str(ptr, len,);
+    offset
+  }
+
+  /// Fills in the target of every jump to a [`Label`], appends the constant
+  /// pool to the data segment, and returns the finished [`Program`]. The
+  /// program's [`Program::relocations`] list every absolute address filled
+  /// in.
+  ///
+  /// Errors with the names of any labels which were never bound, or if a
+  /// called named label or an extern function's name is not a valid symbol
+  /// name.
+  pub fn finish(self,) -> Result<Program, LabelError,> {
+    let unbound = (0..self.labels.len() as u32)
+      .map(Label,)
+      .filter(|label| self.labels[label.0 as usize].offset.is_none(),)
+      .map(|label| self.label_name(label,),)
+      .collect::<Vec<_,>>();
+    if !unbound.is_empty() {
+      return Err(LabelError::Unbound(unbound,),);
+    }
+
+    let mut program = self.program;
+    for (at, label, base,) in self.fixups {
+      let target = self.labels[label.0 as usize].offset.unwrap();
+      let value = match base {
+        Some(base,) => target.wrapping_sub(base,),
+        None => {
+          program.add_relocation(at, RelocKind::Code,);
+          target
+        }
+      };
+      program.patch_once_u32(at, value,).unwrap();
+    }
+
+    for label in self.functions {
+      let info = &self.labels[label.0 as usize];
+      let name = info.name.as_deref().unwrap();
+      if program.symbols().get(name,).is_none() {
+        program
+          .symbols_mut()
+          .insert(name, info.offset.unwrap(),)
+          .map_err(LabelError::Symbol,)?;
+      }
+    }
+
+    for (at, name,) in self.externs {
+      program
+        .symbols_mut()
+        .add_extern(at, &name,)
+        .map_err(LabelError::Symbol,)?;
+      program.add_relocation(at + 1, RelocKind::Code,);
+    }
+
+    let handles = self
+      .strings
+      .iter()
+      .map(|s| program.add_data(s.as_bytes(),),)
+      .collect::<Vec<_,>>();
+    for (at, s,) in self.str_fixups {
+      let address = program.data_address(handles[s.0 as usize],);
+      program.patch_once_u32(at, address.get(),).unwrap();
+      program.add_relocation(at, RelocKind::Data,);
+    }
+    Ok(program,)
+  }
+
+  /// Records a jump operand at `at` which should hold the [`Label`]'s offset.
+  /// The jump must be the most recently emitted instruction.
+  fn fixup(&mut self, at:u32, label:Label,) {
+    let base = self.relative.then(|| self.offset(),);
+    self.fixups.push((at, label, base,),);
+  }
+
+  fn label_name(&self, label:Label,) -> String {
+    match &self.labels[label.0 as usize].name {
+      Some(name,) => name.clone(),
+      None => format!("L{}", label.0),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::ProgramBuilder;
+  use crate::{
+    errors::{LabelError, SymbolError},
+    instruction::Instruction,
+    opcodes::CmpFlag,
+    registers::{reg, Register},
+  };
+  use alloc::{format, string::ToString, vec};
+
+  #[test]
+  fn backpatch_labels() {
+    let mut b = ProgramBuilder::new();
+    let top = b.named_label("top",);
+    let end = b.named_label("end",);
+
+    b.bind(top,).unwrap();
+    b.emit(Instruction::SubRI {
+      dst:reg(14,),
+      src:reg(14,),
+      imm:1.0,
+    },);
+    b.jz(Register::EQ, end,);
+    b.jmp(top,);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Hlt,);
+
+    let program = b.finish().unwrap();
+    assert_eq!(
+      format!("{}", program),
+      "Sub_RI $14, $14, 1.0\nJz $EQ, 18\nJmp 0\nHlt\n"
+    );
+    assert_eq!(program.validate_strict(), Ok(()));
+  }
+
+  #[test]
+  fn call_label() {
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    b.call(func,);
+    b.emit(Instruction::Hlt,);
+    b.bind(func,).unwrap();
+    b.emit(Instruction::Ret { args:0, },);
+
+    let program = b.finish().unwrap();
+    assert_eq!(format!("{}", program), "Call 6\nHlt\nRet 0\n");
+    assert_eq!(program.symbols().get("func",), Some(6));
+  }
+
+  #[test]
+  fn jmp_cmp_labels() {
+    let mut b = ProgramBuilder::new();
+    let top = b.named_label("top",);
+    let end = b.named_label("end",);
+    b.bind(top,).unwrap();
+    b.jmp_cmp_rr(CmpFlag::Eq, reg(14,), reg(15,), end,);
+    b.emit(Instruction::AddRI {
+      dst:reg(14,),
+      src:reg(14,),
+      imm:1.0,
+    },);
+    b.jmp_cmp_ri(CmpFlag::Lt, reg(14,), 10.0, top,);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Hlt,);
+
+    let program = b.finish().unwrap();
+    assert_eq!(
+      format!("{}", program),
+      "JmpCmp_RR EQ, $14, $15, 26
+Add_RI $14, $14, 1.0
+JmpCmp_RI LT, $14, 10.0, 0
+Hlt
+"
+    );
+  }
+
+  #[test]
+  fn relative_jump_labels() {
+    let mut b = ProgramBuilder::new();
+    b.relative_jumps(true,);
+    let top = b.named_label("top",);
+    let end = b.named_label("end",);
+    b.bind(top,).unwrap();
+    b.jz(Register::EQ, end,);
+    b.jnz(Register::EQ, top,);
+    b.jmp(top,);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Hlt,);
+
+    let program = b.finish().unwrap();
+    assert_eq!(
+      format!("{}", program),
+      "JzRel $EQ, 11\nJnzRel $EQ, -12\nJmpRel -17\nHlt\n"
+    );
+    assert_eq!(program.validate(), Ok(()));
+  }
+
+  #[test]
+  fn interned_strings() {
+    let mut b = ProgramBuilder::new();
+    let hello = b.intern_str("hello",);
+    let bye = b.intern_str("bye",);
+    assert_eq!(b.intern_str("hello",), hello);
+    b.emit_write_str(reg(14,), reg(15,), hello,);
+    b.emit_write_str(reg(14,), reg(15,), bye,);
+    b.emit_write_str(reg(16,), reg(17,), hello,);
+    b.emit(Instruction::Hlt,);
+
+    let program = b.finish().unwrap();
+    let (base, data,) = program.data_segment().unwrap();
+    assert_eq!(data, b"hellobye");
+    let base = base.get();
+    assert_eq!(
+      format!("{}", program),
+      format!(
+        "LoadU32 $14, {}
+LoadU32 $15, 5
+WriteStr $14, $15
+LoadU32 $14, {}
+LoadU32 $15, 3
+WriteStr $14, $15
+LoadU32 $16, {}
+LoadU32 $17, 5
+WriteStr $16, $17
+Hlt
+; data at 0x{:04X} (8 bytes)
+;   0x{:04X}:  68 65 6C 6C 6F 62 79 65                          |hellobye|
+",
+        base,
+        base + 5,
+        base,
+        base,
+        base
+      )
+    );
+  }
+
+  #[test]
+  fn unbound_labels_error() {
+    let mut b = ProgramBuilder::new();
+    let a = b.new_label();
+    let exit = b.named_label("exit",);
+    b.jmp(a,);
+    b.jnz(Register::EQ, exit,);
+
+    assert_eq!(
+      b.finish().unwrap_err(),
+      LabelError::Unbound(vec!["L0".to_string(), "exit".to_string()],)
+    );
+  }
+
+  #[test]
+  fn binding_twice_errors() {
+    let mut b = ProgramBuilder::new();
+    let l = b.named_label("twice",);
+    b.bind(l,).unwrap();
+    b.emit(Instruction::Noop,);
+
+    assert_eq!(b.bind(l,), Err(LabelError::AlreadyBound("twice".to_string())));
+  }
+
+  #[test]
+  fn invalid_symbol_names_error() {
+    let mut b = ProgramBuilder::new();
+    let f = b.named_label("my-func",);
+    b.call(f,);
+    b.bind(f,).unwrap();
+    b.emit(Instruction::Hlt,);
+    assert_eq!(
+      b.finish().unwrap_err(),
+      LabelError::Symbol(SymbolError::InvalidName("my-func".to_string()))
+    );
+
+    let mut b = ProgramBuilder::new();
+    b.call_extern("draw.sprite",);
+    assert_eq!(
+      b.finish().unwrap_err(),
+      LabelError::Symbol(SymbolError::InvalidName("draw.sprite".to_string()))
+    );
+  }
+}
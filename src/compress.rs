@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+use miniz_oxide::{deflate::compress_to_vec_zlib, inflate::decompress_to_vec_zlib_with_limit};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+/// How hard to compress the code and data of a saved program, from
+/// [`Level::FASTEST`] to [`Level::BEST`].
+pub struct Level(u8,);
+
+impl Level {
+  /// Compresses quickly at the cost of larger files.
+  pub const FASTEST:Level = Level(1,);
+  /// A balance between speed and size.
+  pub const DEFAULT:Level = Level(6,);
+  /// Produces the smallest files.
+  pub const BEST:Level = Level(9,);
+
+  /// Returns the level numbered `level`, from 0 for no compression to 9 for
+  /// [`Level::BEST`]. Higher numbers are clamped to 9.
+  pub fn new(level:u8,) -> Self {
+    Level(level.min(Level::BEST.0,),)
+  }
+
+  /// Returns the number of the level.
+  pub fn get(self,) -> u8 {
+    self.0
+  }
+}
+
+impl Default for Level {
+  fn default() -> Self {
+    Level::DEFAULT
+  }
+}
+
+/// Compresses `bytes` into a zlib stream.
+pub(crate) fn compress(bytes:&[u8], level:Level,) -> Vec<u8,> {
+  compress_to_vec_zlib(bytes, level.0,)
+}
+
+/// Decompresses a zlib stream written by [`compress`], or returns `None` if
+/// the stream is corrupt or decompresses to more than a program can hold.
+pub(crate) fn decompress(bytes:&[u8],) -> Option<Vec<u8,>,> {
+  decompress_to_vec_zlib_with_limit(bytes, u32::MAX as usize,).ok()
+}
+
+#[cfg(test)]
+mod test {
+  use super::Level;
+  use crate::{
+    errors::ProgramError,
+    format::SectionKind,
+    instruction::Instruction,
+    program::{Program, SaveOptions},
+    registers::reg,
+  };
+
+  /// A large program of repeated loads and writes like a compiler generates.
+  fn repetitive_program() -> Program {
+    let mut program = Program::new();
+    for i in 0..20_000 {
+      program.emit_load(reg(17,), (i % 16) as f32,);
+      program.emit(Instruction::WMem {
+        dst:reg(16,),
+        src:reg(17,),
+        imm_offset:i % 64,
+        reg_offset:reg(18,),
+      },);
+    }
+    program.emit(Instruction::Hlt,);
+    program.add_data(&[7; 4096],);
+    program
+  }
+
+  #[test]
+  fn compressed_round_trip() {
+    let program = repetitive_program();
+    let plain = program.to_file_bytes();
+    for level in [Level::new(0,), Level::FASTEST, Level::DEFAULT, Level::BEST,] {
+      let options = SaveOptions {
+        compression:Some(level,),
+      };
+      let bytes = program.to_file_bytes_with(options,);
+      assert_eq!(Program::from_file_bytes(&bytes,).unwrap(), program);
+      if level > Level::new(0,) {
+        assert!(
+          bytes.len() * 10 < plain.len(),
+          "{} of {}",
+          bytes.len(),
+          plain.len()
+        );
+      }
+    }
+    assert_eq!(program.to_file_bytes_with(SaveOptions::default(),), plain);
+    assert_eq!(Level::new(200,), Level::BEST);
+  }
+
+  #[test]
+  #[cfg(all(feature = "std", feature = "test-util"))]
+  fn compressed_generated_program() {
+    let program = crate::generator::ProgramGenerator::new(7, 100_000,).generate();
+    let options = SaveOptions {
+      compression:Some(Level::DEFAULT,),
+    };
+    let bytes = program.to_file_bytes_with(options,);
+    assert!(bytes.len() < program.to_file_bytes().len());
+    assert_eq!(Program::from_file_bytes(&bytes,).unwrap(), program);
+
+    let path = std::env::temp_dir().join("spdr_isa_compressed_program.spdr",);
+    program.save_with_options(&path, options,).unwrap();
+    assert_eq!(std::fs::read(&path,).unwrap(), bytes);
+    assert_eq!(Program::load(&path,).unwrap(), program);
+    std::fs::remove_file(&path,).unwrap();
+  }
+
+  #[test]
+  fn corrupt_compressed_stream() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Hlt,);
+    let options = SaveOptions {
+      compression:Some(Level::DEFAULT,),
+    };
+    let mut bytes = program.to_file_bytes_with(options,);
+
+    // Flip a byte inside the compressed code, then fix up the checksum so
+    // only the stream is wrong
+    let code = crate::format::HEADER_LEN + crate::format::SECTION_ENTRY_LEN;
+    bytes[code + 4] ^= 0xFF;
+    let end = bytes.len() - crate::format::CHECKSUM_LEN;
+    let checksum = crate::format::crc32(&bytes[..end],);
+    bytes[end..].copy_from_slice(&checksum.to_le_bytes(),);
+    assert!(matches!(
+      Program::from_file_bytes(&bytes,),
+      Err(ProgramError::CorruptCompression(SectionKind::Code))
+    ));
+  }
+}
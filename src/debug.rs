@@ -0,0 +1,291 @@
+use alloc::{
+  string::{String, ToString},
+  vec::Vec,
+};
+use core::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// A position in a source file, returned by [`DebugInfo::lookup`].
+pub struct SourceLoc<'a,> {
+  pub file:&'a str,
+  /// Line number, starting from 1.
+  pub line:u32,
+  /// Column number, starting from 1, or 0 if it is unknown.
+  pub column:u32,
+}
+
+impl Display for SourceLoc<'_,> {
+  /// Prints the location as `file:line:column`, leaving out an unknown
+  /// column.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "{}:{}", self.file, self.line)?;
+    match self.column {
+      0 => Ok((),),
+      column => write!(f, ":{}", column),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// The source position of the code starting at `offset`, which lasts until
+/// the next entry.
+pub struct LineEntry {
+  pub offset:u32,
+  /// Index of the file in [`DebugInfo::files`].
+  pub file:u32,
+  pub line:u32,
+  pub column:u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+/// Maps offsets in a [`Program`](crate::program::Program)'s code to the source
+/// lines they were compiled from, saved with the program and ignored by
+/// everything else.
+///
+/// Holds a table of source files and [`LineEntry`]s sorted by offset. Each
+/// entry covers the code from its offset up to the next entry's, so only the
+/// first instruction of each line needs one.
+pub struct DebugInfo {
+  files:Vec<String,>,
+  /// Sorted by offset, with at most one entry per offset.
+  entries:Vec<LineEntry,>,
+}
+
+impl DebugInfo {
+  pub fn new() -> Self {
+    DebugInfo::default()
+  }
+
+  /// Adds `file` to the file table unless it is already there. Returns its
+  /// index.
+  pub fn add_file(&mut self, file:&str,) -> u32 {
+    match self.files.iter().position(|known| known == file,) {
+      Some(id,) => id as u32,
+      None => {
+        self.files.push(file.to_string(),);
+        self.files.len() as u32 - 1
+      }
+    }
+  }
+
+  /// Returns the file table.
+  pub fn files(&self,) -> &[String] {
+    &self.files
+  }
+
+  /// Returns the entries in order of their offsets.
+  pub fn entries(&self,) -> &[LineEntry] {
+    &self.entries
+  }
+
+  /// Records that the code from `offset` up to the next entry was compiled
+  /// from `line` and `column` of `file`, replacing any entry already at
+  /// `offset`.
+  pub fn record(&mut self, offset:u32, file:&str, line:u32, column:u32,) {
+    let entry = LineEntry {
+      offset,
+      file:self.add_file(file,),
+      line,
+      column,
+    };
+    let i = self.entries.partition_point(|entry| entry.offset < offset,);
+    match self.entries.get_mut(i,) {
+      Some(old,) if old.offset == offset => *old = entry,
+      _ => self.entries.insert(i, entry,),
+    }
+  }
+
+  /// Returns the source position of the code at `offset`, which is that of
+  /// the last entry at or before it. Returns `None` if there is no such entry.
+  pub fn lookup(&self, offset:u32,) -> Option<SourceLoc<'_,>,> {
+    let i = self.entries.partition_point(|entry| entry.offset <= offset,);
+    let entry = self.entries.get(i.checked_sub(1,)?,)?;
+    Some(SourceLoc {
+      file:&self.files[entry.file as usize],
+      line:entry.line,
+      column:entry.column,
+    },)
+  }
+
+  pub fn len(&self,) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self,) -> bool {
+    self.files.is_empty() && self.entries.is_empty()
+  }
+
+  /// Moves every entry to the offset `relocate` returns for it, which must
+  /// not reorder them. Of entries moved to the same offset only the last is
+  /// kept.
+  pub(crate) fn relocate(&mut self, relocate:impl Fn(u32,) -> u32,) {
+    let mut kept = Vec::<LineEntry,>::with_capacity(self.entries.len(),);
+    for mut entry in self.entries.drain(..,) {
+      entry.offset = relocate(entry.offset,);
+      match kept.last_mut() {
+        Some(last,) if last.offset == entry.offset => *last = entry,
+        _ => kept.push(entry,),
+      }
+    }
+    self.entries = kept;
+  }
+
+  /// Adds the entries of `other`, moved by `base`.
+  pub(crate) fn merge(&mut self, other:&DebugInfo, base:u32,) {
+    for entry in &other.entries {
+      let file = &other.files[entry.file as usize];
+      self.record(base + entry.offset, file, entry.line, entry.column,);
+    }
+  }
+
+  /// Encodes the debug info as the contents of a debug section, which is
+  /// empty if there is no debug info.
+  ///
+  /// The section starts with the number of files as a little-endian u32 and
+  /// each file's length as a little-endian u32 followed by its path. The rest
+  /// of the section is the entries, each of which is its offset, file, line,
+  /// and column as little-endian u32s.
+  pub(crate) fn to_bytes(&self,) -> Vec<u8,> {
+    let mut bytes = Vec::new();
+    if self.is_empty() {
+      return bytes;
+    }
+    bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes(),);
+    for file in &self.files {
+      bytes.extend_from_slice(&(file.len() as u32).to_le_bytes(),);
+      bytes.extend_from_slice(file.as_bytes(),);
+    }
+    for entry in &self.entries {
+      for field in [entry.offset, entry.file, entry.line, entry.column,] {
+        bytes.extend_from_slice(&field.to_le_bytes(),);
+      }
+    }
+    bytes
+  }
+
+  /// Decodes debug info encoded by [`DebugInfo::to_bytes`]. Returns `None` if
+  /// the bytes are not valid debug info.
+  pub(crate) fn from_bytes(bytes:&[u8],) -> Option<Self,> {
+    let mut info = DebugInfo::new();
+    if bytes.is_empty() {
+      return Some(info,);
+    }
+    let read_u32 =
+      |bytes:&[u8], at:usize| Some(u32::from_le_bytes(bytes.get(at..at + 4,)?.try_into().unwrap(),),);
+    let count = read_u32(bytes, 0,)?;
+    let mut bytes = &bytes[4..];
+    for _ in 0..count {
+      let len = read_u32(bytes, 0,)? as usize;
+      let file = core::str::from_utf8(bytes.get(4..4 + len,)?,).ok()?;
+      info.files.push(file.to_string(),);
+      bytes = &bytes[4 + len..];
+    }
+
+    let chunks = bytes.chunks_exact(16,);
+    if !chunks.remainder().is_empty() {
+      return None;
+    }
+    for chunk in chunks {
+      let entry = LineEntry {
+        offset:read_u32(chunk, 0,)?,
+        file:read_u32(chunk, 4,)?,
+        line:read_u32(chunk, 8,)?,
+        column:read_u32(chunk, 12,)?,
+      };
+      let sorted = info.entries.last().is_none_or(|last| last.offset < entry.offset,);
+      if !sorted || entry.file as usize >= info.files.len() {
+        return None;
+      }
+      info.entries.push(entry,);
+    }
+    Some(info,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{DebugInfo, SourceLoc};
+  use crate::{builder::ProgramBuilder, instruction::Instruction, program::Program, registers::reg};
+  use alloc::string::ToString;
+
+  #[test]
+  fn lookup_source_lines() {
+    let mut info = DebugInfo::new();
+    assert_eq!(info.lookup(0,), None);
+    info.record(12, "enemy_ai.script", 38, 5,);
+    info.record(0, "enemy_ai.script", 37, 1,);
+    info.record(20, "util.script", 4, 0,);
+    info.record(12, "enemy_ai.script", 39, 5,);
+    assert_eq!(info.files(), ["enemy_ai.script", "util.script"]);
+    assert_eq!(info.len(), 3);
+
+    let loc = |file, line, column| Some(SourceLoc { file, line, column, },);
+    assert_eq!(info.lookup(0,), loc("enemy_ai.script", 37, 1));
+    assert_eq!(info.lookup(11,), loc("enemy_ai.script", 37, 1));
+    assert_eq!(info.lookup(12,), loc("enemy_ai.script", 39, 5));
+    assert_eq!(info.lookup(u32::MAX,), loc("util.script", 4, 0));
+    assert_eq!(info.lookup(13,).unwrap().to_string(), "enemy_ai.script:39:5");
+    assert_eq!(info.lookup(20,).unwrap().to_string(), "util.script:4");
+
+    let bytes = info.to_bytes();
+    assert_eq!(DebugInfo::from_bytes(&bytes), Some(info.clone()));
+    assert_eq!(DebugInfo::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert!(DebugInfo::new().to_bytes().is_empty());
+    // Entries must be sorted
+    let mut unsorted = bytes.clone();
+    let entries = unsorted.len() - 3 * 16;
+    unsorted[entries] = 30;
+    assert_eq!(DebugInfo::from_bytes(&unsorted), None);
+  }
+
+  #[test]
+  fn builder_records_debug_info() {
+    let mut b = ProgramBuilder::new();
+    b.set_source_loc("enemy_ai.script", 36, 1,);
+    b.emit(Instruction::Noop,);
+    b.set_source_loc("enemy_ai.script", 37, 3,);
+    let load = b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:1.5,
+    },);
+    b.emit(Instruction::DbgReg { src:reg(16,), },);
+    b.emit(Instruction::Hlt,);
+    let mut program = b.finish().unwrap();
+    assert_eq!(program.debug_info().lookup(0,).unwrap().line, 36);
+    assert_eq!(program.debug_info().lookup(load + 3,).unwrap().line, 37);
+
+    assert_eq!(
+      program.disassemble_annotated(),
+      "; enemy_ai.script:36\n\
+       0x0000:  23                  Noop\n\
+       ; enemy_ai.script:37\n\
+       0x0001:  01 10 00 00 C0 3F   Load $16, 1.5\n\
+       0x0007:  56 10               DbgReg $16\n\
+       0x0009:  00                  Hlt\n"
+    );
+
+    // Saved with the program but ignored by equality
+    let loaded = Program::from_file_bytes(&program.to_file_bytes(),).unwrap();
+    assert_eq!(loaded.debug_info(), program.debug_info());
+
+    // Removing instructions keeps entries on the instructions they describe
+    let stripped = program.strip_debug().unwrap();
+    assert_eq!(stripped.debug_info(), program.debug_info());
+    let mut moved = program.clone();
+    moved.remove_at(0,).unwrap();
+    assert_eq!(moved.debug_info().lookup(0,).unwrap().line, 37);
+    assert_eq!(moved.debug_info().len(), 1);
+    let linked = Program::link(&[program.clone(), program.clone(),],).unwrap();
+    assert_eq!(
+      linked.debug_info().lookup(program.len() as u32,).unwrap().line,
+      36
+    );
+
+    let info = program.strip_debug_info();
+    assert_eq!(info.len(), 2);
+    assert!(program.debug_info().is_empty());
+    assert_eq!(program.to_string(), program.disassemble_with(Default::default()));
+    assert_eq!(program, loaded);
+    assert!(program.to_file_bytes().len() < loaded.to_file_bytes().len());
+  }
+}
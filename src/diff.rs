@@ -0,0 +1,339 @@
+use crate::{errors::DecodeError, instruction::Instruction, program::Program};
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+/// Lines of unchanged instructions printed around each change.
+const CONTEXT:usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq,)]
+/// How an instruction differs between two [`Program`]s.
+pub enum Change {
+  /// The instruction is in both programs.
+  Same(Instruction,),
+  /// The instruction is only in the old program.
+  Removed(Instruction,),
+  /// The instruction is only in the new program.
+  Inserted(Instruction,),
+  /// The instruction has the same opcode in both programs but different
+  /// operands.
+  Changed { before:Instruction, after:Instruction, },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq,)]
+/// One line of a [`ProgramDiff`].
+pub struct DiffEntry {
+  /// Offset of the instruction in the old program. Inserted instructions have
+  /// the offset of the next instruction which is not.
+  pub old:u32,
+  /// Offset of the instruction in the new program. Removed instructions have
+  /// the offset of the next instruction which is not.
+  pub new:u32,
+  pub change:Change,
+}
+
+#[derive(Debug, Clone, PartialEq,)]
+/// The instructions added, removed, and changed between two [`Program`]s,
+/// made by [`Program::diff`].
+pub struct ProgramDiff {
+  entries:Vec<DiffEntry,>,
+}
+
+impl ProgramDiff {
+  /// Returns every instruction of both programs in order, including the
+  /// unchanged ones.
+  pub fn entries(&self,) -> &[DiffEntry] {
+    &self.entries
+  }
+
+  /// Iterates over the entries which are not [`Change::Same`].
+  pub fn changes(&self,) -> impl Iterator<Item = &DiffEntry,> {
+    self
+      .entries
+      .iter()
+      .filter(|entry| !matches!(entry.change, Change::Same(_,)),)
+  }
+
+  /// Whether the programs have the same instructions.
+  pub fn is_empty(&self,) -> bool {
+    self.changes().next().is_none()
+  }
+}
+
+impl Display for ProgramDiff {
+  /// Prints hunks of changed instructions in the style of a unified diff,
+  /// each with up to three unchanged instructions around it. Removed lines
+  /// start with `-` and the old offset, inserted lines with `+` and the new
+  /// offset.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    let changed = self
+      .entries
+      .iter()
+      .enumerate()
+      .filter(|(_, entry,)| !matches!(entry.change, Change::Same(_,)),)
+      .map(|(i, _,)| i,);
+    // Merge the context around each change into hunks
+    let mut hunks = Vec::<(usize, usize,),>::new();
+    for i in changed {
+      let start = i.saturating_sub(CONTEXT,);
+      let end = (i + CONTEXT + 1).min(self.entries.len(),);
+      match hunks.last_mut() {
+        Some(hunk,) if hunk.1 >= start => hunk.1 = end,
+        _ => hunks.push((start, end,),),
+      }
+    }
+
+    for (start, end,) in hunks {
+      let first = &self.entries[start];
+      writeln!(f, "@@ -{} +{} @@", first.old, first.new)?;
+      for entry in &self.entries[start..end] {
+        match &entry.change {
+          Change::Same(inst,) => writeln!(f, " {:04}: {}", entry.old, inst)?,
+          Change::Removed(inst,) => writeln!(f, "-{:04}: {}", entry.old, inst)?,
+          Change::Inserted(inst,) => writeln!(f, "+{:04}: {}", entry.new, inst)?,
+          Change::Changed { before, after, } => {
+            writeln!(f, "-{:04}: {}", entry.old, before)?;
+            writeln!(f, "+{:04}: {}", entry.new, after)?;
+          }
+        }
+      }
+    }
+    Ok((),)
+  }
+}
+
+/// An instruction and its offset and bytes in a program.
+type Line<'a,> = (u32, Instruction, &'a [u8],);
+
+/// Diffs the instructions of two programs. See [`Program::diff`].
+pub(crate) fn diff(old:&Program, new:&Program,) -> Result<ProgramDiff, DecodeError,> {
+  let (old, new,) = (&lines(old,)?, &lines(new,)?,);
+  let mut entries = Vec::new();
+  // Removed and inserted lines since the last unchanged one
+  let mut removed = Vec::new();
+  let mut inserted = Vec::new();
+  for edit in myers(old, new,) {
+    match edit {
+      Edit::Same(i, j,) => {
+        pair(&mut entries, &mut removed, &mut inserted,);
+        entries.push(DiffEntry {
+          old:old[i].0,
+          new:new[j].0,
+          change:Change::Same(old[i].1,),
+        },);
+      }
+      Edit::Removed(i,) => removed.push(old[i],),
+      Edit::Inserted(j,) => inserted.push(new[j],),
+    }
+  }
+  pair(&mut entries, &mut removed, &mut inserted,);
+
+  // Fill in the offsets of lines missing from one of the programs
+  let (mut old_end, mut new_end,) = (end(old,), end(new,),);
+  for entry in entries.iter_mut().rev() {
+    match entry.change {
+      Change::Removed(_,) => entry.new = new_end,
+      Change::Inserted(_,) => entry.old = old_end,
+      _ => {}
+    }
+    old_end = entry.old;
+    new_end = entry.new;
+  }
+  Ok(ProgramDiff { entries, },)
+}
+
+fn lines(program:&Program,) -> Result<Vec<Line<'_,>,>, DecodeError,> {
+  program
+    .instructions()
+    .map(|item| {
+      let (at, inst,) = item?;
+      let bytes = &program.code()[at as usize..at as usize + inst.opcode().instruction_len()];
+      Ok((at, inst, bytes,),)
+    },)
+    .collect()
+}
+
+/// Offset just past the last instruction of a program.
+fn end(lines:&[Line],) -> u32 {
+  lines.last().map_or(0, |(at, _, bytes,)| at + bytes.len() as u32,)
+}
+
+/// Adds a run of removed and inserted lines to `entries`. Each removed line
+/// is paired with the next inserted line with the same opcode as a
+/// [`Change::Changed`].
+fn pair(entries:&mut Vec<DiffEntry,>, removed:&mut Vec<Line,>, inserted:&mut Vec<Line,>,) {
+  let mut inserted = inserted.drain(..,);
+  for (old, before, _,) in removed.drain(..,) {
+    let position = inserted
+      .as_slice()
+      .iter()
+      .position(|(_, after, _,)| after.opcode() == before.opcode(),);
+    let Some(position,) = position
+    else {
+      entries.push(DiffEntry {
+        old,
+        new:0,
+        change:Change::Removed(before,),
+      },);
+      continue;
+    };
+    for (new, after, _,) in inserted.by_ref().take(position,) {
+      entries.push(DiffEntry {
+        old:0,
+        new,
+        change:Change::Inserted(after,),
+      },);
+    }
+    let (new, after, _,) = inserted.next().unwrap();
+    entries.push(DiffEntry {
+      old,
+      new,
+      change:Change::Changed { before, after, },
+    },);
+  }
+  for (new, after, _,) in inserted {
+    entries.push(DiffEntry {
+      old:0,
+      new,
+      change:Change::Inserted(after,),
+    },);
+  }
+}
+
+/// A step of the shortest edit script between two sequences, with indices
+/// into them.
+enum Edit {
+  Same(usize, usize,),
+  Removed(usize,),
+  Inserted(usize,),
+}
+
+/// Returns the shortest edit script turning `old` into `new` using Myers'
+/// algorithm. Lines are equal when their bytes are.
+fn myers(old:&[Line], new:&[Line],) -> Vec<Edit,> {
+  let (n, m,) = (old.len() as isize, new.len() as isize,);
+  let max = n + m;
+  // `v[k]` is the furthest old index reached on diagonal `k`, offset by `max`
+  let mut v = vec![0isize; 2 * max as usize + 2];
+  let idx = |k:isize| (k + max) as usize;
+  let mut trace = Vec::new();
+  'search: for d in 0..=max {
+    trace.push(v.clone(),);
+    for k in (-d..=d).step_by(2,) {
+      let mut x = if k == -d || (k != d && v[idx(k - 1,)] < v[idx(k + 1,)]) {
+        v[idx(k + 1,)]
+      }
+      else {
+        v[idx(k - 1,)] + 1
+      };
+      let mut y = x - k;
+      while x < n && y < m && old[x as usize].2 == new[y as usize].2 {
+        x += 1;
+        y += 1;
+      }
+      v[idx(k,)] = x;
+      if x >= n && y >= m {
+        break 'search;
+      }
+    }
+  }
+
+  let mut edits = Vec::new();
+  let (mut x, mut y,) = (n, m,);
+  for (d, v,) in trace.iter().enumerate().rev() {
+    let d = d as isize;
+    let k = x - y;
+    let prev_k = if k == -d || (k != d && v[idx(k - 1,)] < v[idx(k + 1,)]) {
+      k + 1
+    }
+    else {
+      k - 1
+    };
+    let prev_x = v[idx(prev_k,)];
+    let prev_y = prev_x - prev_k;
+    while x > prev_x && y > prev_y {
+      x -= 1;
+      y -= 1;
+      edits.push(Edit::Same(x as usize, y as usize,),);
+    }
+    if d > 0 {
+      if x == prev_x {
+        edits.push(Edit::Inserted(prev_y as usize,),);
+      }
+      else {
+        edits.push(Edit::Removed(prev_x as usize,),);
+      }
+    }
+    x = prev_x;
+    y = prev_y;
+  }
+  edits.reverse();
+  edits
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Change, DiffEntry};
+  use crate::{instruction::Instruction, program::Program, registers::reg};
+  use alloc::{string::ToString, vec::Vec};
+
+  #[test]
+  fn diff_programs() {
+    let mut old = Program::new();
+    old.emit_load(reg(16,), 1.0,);
+    old.emit_load(reg(17,), 2.0,);
+    old.emit(Instruction::Noop,);
+    old.emit_add_rr(reg(18,), reg(16,), reg(17,),);
+    old.emit(Instruction::Hlt,);
+    assert!(old.diff(&old,).unwrap().is_empty());
+
+    let mut new = Program::new();
+    new.emit_load(reg(16,), 1.0,);
+    new.emit(Instruction::Inc { dst:reg(16,), },);
+    new.emit_load(reg(17,), 5.0,);
+    new.emit_add_rr(reg(18,), reg(16,), reg(17,),);
+    new.emit(Instruction::Hlt,);
+    let diff = old.diff(&new,).unwrap();
+    assert_eq!(
+      diff.changes().copied().collect::<Vec<_,>>(),
+      [
+        DiffEntry {
+          old:6,
+          new:6,
+          change:Change::Inserted(Instruction::Inc { dst:reg(16,), },),
+        },
+        DiffEntry {
+          old:6,
+          new:8,
+          change:Change::Changed {
+            before:Instruction::Load {
+              dst:reg(17,),
+              imm:2.0,
+            },
+            after:Instruction::Load {
+              dst:reg(17,),
+              imm:5.0,
+            },
+          },
+        },
+        DiffEntry {
+          old:12,
+          new:14,
+          change:Change::Removed(Instruction::Noop,),
+        },
+      ]
+    );
+    assert_eq!(
+      diff.to_string(),
+      "\
+@@ -0 +0 @@
+ 0000: Load $16, 1.0
++0006: Inc $16
+-0006: Load $17, 2.0
++0008: Load $17, 5.0
+-0012: Noop
+ 0013: Add_RR $18, $16, $17
+ 0017: Hlt
+"
+    );
+  }
+}
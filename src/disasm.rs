@@ -0,0 +1,244 @@
+use crate::{
+  instruction::Instruction,
+  opcodes::{OpCategory, OpCode},
+  program::{listing_lines, write_hex},
+};
+use alloc::string::{String, ToString};
+use core::fmt::{Display, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+/// Options for
+/// [`Program::disassemble_with`](crate::program::Program::disassemble_with).
+///
+/// The default lists only the instructions, matching the instruction lines of
+/// [`Program`](crate::program::Program)'s `Display` implementation.
+pub struct DisasmOptions {
+  /// Colors mnemonics by category, registers, immediates, and jump targets
+  /// with ANSI escape codes. Ignored without the `color` feature.
+  pub color:bool,
+  /// Prefixes each line with the instruction's offset.
+  pub offsets:bool,
+  /// Prefixes each line with the instruction's raw bytes.
+  pub bytes:bool,
+}
+
+/// What a piece of a listing is, which decides its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum Style {
+  /// Mnemonics which compute on or compare registers.
+  Arithmetic,
+  /// Mnemonics which jump, call, return, or stop.
+  Control,
+  /// Mnemonics which access memory.
+  Memory,
+  /// Mnemonics which push to or pop from the stack.
+  Stack,
+  /// Mnemonics which talk to the host.
+  Io,
+  Register,
+  Immediate,
+  /// The target or relative offset of a jump or call.
+  Target,
+  /// Offsets, raw bytes, and mnemonics which do nothing.
+  Dim,
+  /// Bytes which could not be decoded.
+  Invalid,
+}
+
+impl Style {
+  /// The SGR parameters of the style's ANSI escape code.
+  fn sgr(self,) -> &'static str {
+    match self {
+      Style::Arithmetic => "36",
+      Style::Control => "1;35",
+      Style::Memory => "33",
+      Style::Stack => "34",
+      Style::Io => "94",
+      Style::Register => "32",
+      Style::Immediate => "93",
+      Style::Target => "4;35",
+      Style::Dim => "2",
+      Style::Invalid => "1;31",
+    }
+  }
+
+  /// Returns the style of `op`'s mnemonic.
+  fn of(op:OpCode,) -> Style {
+    match op.category() {
+      OpCategory::Arithmetic | OpCategory::Compare => Style::Arithmetic,
+      OpCategory::ControlFlow => Style::Control,
+      OpCategory::Memory => Style::Memory,
+      OpCategory::Stack => Style::Stack,
+      OpCategory::Io => Style::Io,
+      OpCategory::Misc => Style::Dim,
+    }
+  }
+}
+
+/// Writes text in [`Style`]s, or plainly if color is off.
+struct Painter {
+  color:bool,
+}
+
+impl Painter {
+  fn new(color:bool,) -> Self {
+    Painter {
+      color:color && cfg!(feature = "color"),
+    }
+  }
+
+  fn paint(&self, f:&mut impl Write, style:Style, text:impl Display,) -> core::fmt::Result {
+    match self.color {
+      true => write!(f, "\x1b[{}m{}\x1b[0m", style.sgr(), text),
+      false => write!(f, "{}", text),
+    }
+  }
+}
+
+/// Lists `code` as described by `options`.
+pub(crate) fn disassemble(code:&[u8], options:DisasmOptions,) -> String {
+  let mut text = String::new();
+  // Writing to a `String` cannot fail
+  let _ = write_listing(&mut text, code, options,);
+  text
+}
+
+fn write_listing(f:&mut impl Write, code:&[u8], options:DisasmOptions,) -> core::fmt::Result {
+  let painter = Painter::new(options.color,);
+  // Pad the raw bytes to the longest instruction so the mnemonics line up
+  let width = listing_lines(code,)
+    .map(|(_, len, _,)| len * 3 - 1,)
+    .max()
+    .unwrap_or_default();
+  for (offset, len, inst,) in listing_lines(code,) {
+    if options.offsets {
+      painter.paint(f, Style::Dim, format_args!("0x{:04X}:", offset),)?;
+      f.write_str("  ",)?;
+    }
+    if options.bytes {
+      let mut hex = String::new();
+      write_hex(&mut hex, &code[offset..offset + len],)?;
+      painter.paint(f, Style::Dim, hex,)?;
+      write!(f, "{:1$}   ", "", width - (len * 3 - 1))?;
+    }
+    match inst {
+      Some(inst,) => write_instruction(f, &painter, &inst,)?,
+      None => painter.paint(f, Style::Invalid, format_args!("??? (0x{:02X})", code[offset]),)?,
+    }
+    writeln!(f)?;
+  }
+  Ok((),)
+}
+
+/// Writes `inst` as its `Display` implementation formats it, painting each
+/// operand.
+fn write_instruction(f:&mut impl Write, painter:&Painter, inst:&Instruction,) -> core::fmt::Result {
+  let text = inst.to_string();
+  let (mnemonic, operands,) = text.split_once(' ',).unwrap_or((&text, "",),);
+  painter.paint(f, Style::of(inst.opcode(),), mnemonic,)?;
+
+  let jumps = inst.jump_target().is_some() || inst.relative_offset().is_some();
+  let count = operands.split(", ",).count();
+  for (i, operand,) in operands
+    .split(", ",)
+    .enumerate()
+    .filter(|(_, operand,)| !operand.is_empty(),)
+  {
+    f.write_str(if i == 0 { " " } else { ", " },)?;
+    let style = match operand {
+      _ if jumps && i == count - 1 => Style::Target,
+      _ if operand.starts_with('$',) => Style::Register,
+      // Comparison flags
+      _ if operand.bytes().all(|byte| byte.is_ascii_uppercase(),) => {
+        f.write_str(operand,)?;
+        continue;
+      }
+      _ => Style::Immediate,
+    };
+    painter.paint(f, style, operand,)?;
+  }
+  Ok((),)
+}
+
+#[cfg(test)]
+mod test {
+  use super::DisasmOptions;
+  use crate::{
+    instruction::Instruction,
+    opcodes::CmpFlag,
+    program::Program,
+    registers::{reg, Register},
+  };
+  use alloc::string::ToString;
+
+  fn program() -> Program {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::CmpRI {
+      flag:CmpFlag::Lt,
+      src:reg(16,),
+      imm:2.0,
+    },);
+    program.emit(Instruction::Jnz {
+      cond:Register::EQ,
+      target:0,
+    },);
+    program.emit(Instruction::Push { src:reg(16,), },);
+    program.push(0xFF,);
+    program.emit(Instruction::Hlt,);
+    program
+  }
+
+  #[test]
+  fn plain_listing_matches_display() {
+    let program = program();
+    assert_eq!(
+      program.disassemble_with(DisasmOptions::default(),),
+      program.to_string()
+    );
+
+    let listing = program.disassemble_with(DisasmOptions {
+      color:false,
+      offsets:true,
+      bytes:true,
+    },);
+    assert_eq!(listing, program.disassemble_annotated());
+  }
+
+  #[test]
+  #[cfg(feature = "color")]
+  fn colored_listing() {
+    let program = program();
+    let options = DisasmOptions {
+      color:true,
+      offsets:true,
+      bytes:false,
+    };
+    let listing = program.disassemble_with(options,);
+    assert_eq!(
+      listing.lines().collect::<Vec<_,>>(),
+      [
+        "\x1b[2m0x0000:\x1b[0m  \x1b[36mLoad\x1b[0m \x1b[32m$16\x1b[0m, \x1b[93m1.5\x1b[0m",
+        "\x1b[2m0x0006:\x1b[0m  \x1b[36mCmp_RI\x1b[0m LT, \x1b[32m$16\x1b[0m, \x1b[93m2.0\x1b[0m",
+        "\x1b[2m0x000D:\x1b[0m  \x1b[1;35mJnz\x1b[0m \x1b[32m$EQ\x1b[0m, \x1b[4;35m0\x1b[0m",
+        "\x1b[2m0x0013:\x1b[0m  \x1b[34mPush\x1b[0m \x1b[32m$16\x1b[0m",
+        "\x1b[2m0x0015:\x1b[0m  \x1b[1;31m??? (0xFF)\x1b[0m",
+        "\x1b[2m0x0016:\x1b[0m  \x1b[1;35mHlt\x1b[0m",
+      ]
+    );
+
+    // Without escape codes it is the plain listing
+    let plain = program.disassemble_with(DisasmOptions {
+      color:false,
+      ..options
+    },);
+    let mut stripped = String::new();
+    let mut rest = listing.as_str();
+    while let Some((before, after,),) = rest.split_once('\x1b',) {
+      stripped.push_str(before,);
+      rest = &after[after.find('m',).unwrap() + 1..];
+    }
+    stripped.push_str(rest,);
+    assert_eq!(stripped, plain);
+  }
+}
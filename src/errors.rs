@@ -0,0 +1,983 @@
+use crate::{
+  format::SectionKind,
+  memory::Address,
+  metadata::{MAX_KEY_LEN, MAX_VALUE_LEN},
+  opcodes::{IsaVersion, OpCode},
+  program::Program,
+  registers::{Register, FIRST_FREE_REGISTER, REGISTER_NAMES, REG_COUNT},
+  symbols::MAX_NAME_LEN,
+};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Display;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced while decoding the bytes of a [`Program`].
+///
+/// `offset` is the position of the offending instruction in the program if it
+/// is known.
+pub enum DecodeError {
+  /// The byte is not a valid [`OpCode`].
+  InvalidOpCode { byte:u8, offset:Option<u32,>, },
+  /// The byte is not a valid [`CmpFlag`](crate::opcodes::CmpFlag).
+  InvalidCmpFlag { byte:u8, offset:Option<u32,>, },
+  /// The bytes ended before all of the instruction's operands could be read.
+  Truncated { op:OpCode, offset:Option<u32,>, },
+  /// There were no bytes to decode.
+  Empty { offset:Option<u32,>, },
+}
+
+impl DecodeError {
+  /// Returns the offset of the instruction which failed to decode.
+  pub fn offset(&self,) -> Option<u32,> {
+    match *self {
+      DecodeError::InvalidOpCode { offset, .. }
+      | DecodeError::InvalidCmpFlag { offset, .. }
+      | DecodeError::Truncated { offset, .. }
+      | DecodeError::Empty { offset, } => offset,
+    }
+  }
+
+  /// Returns a copy of the error with its offset set to `offset`.
+  pub fn with_offset(self, offset:u32,) -> Self {
+    let offset = Some(offset,);
+    match self {
+      DecodeError::InvalidOpCode { byte, .. } => DecodeError::InvalidOpCode { byte, offset, },
+      DecodeError::InvalidCmpFlag { byte, .. } => DecodeError::InvalidCmpFlag { byte, offset, },
+      DecodeError::Truncated { op, .. } => DecodeError::Truncated { op, offset, },
+      DecodeError::Empty { .. } => DecodeError::Empty { offset, },
+    }
+  }
+}
+
+impl Display for DecodeError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      DecodeError::InvalidOpCode { byte, .. } => write!(f, "{} is not a valid OpCode", byte)?,
+      DecodeError::InvalidCmpFlag { byte, .. } => write!(f, "{} is not a valid CmpFlag", byte)?,
+      DecodeError::Truncated { op, .. } => write!(f, "{} instruction is missing operand bytes", op)?,
+      DecodeError::Empty { .. } => write!(f, "expected an instruction but found no bytes")?,
+    }
+    match self.offset() {
+      Some(offset,) => write!(f, " (offset {})", offset),
+      None => Ok((),),
+    }
+  }
+}
+
+impl core::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Structural problems found by
+/// [`Program::validate`](crate::program::Program::validate).
+pub enum ValidationError {
+  /// An instruction could not be decoded.
+  Decode(DecodeError,),
+  /// The jump or call at `at` targets the end of the program or an index past
+  /// it.
+  JumpOutOfBounds { at:u32, target:u32, },
+  /// The jump or call at `at` targets an index which is not the start of an
+  /// instruction.
+  JumpIntoOperand { at:u32, target:u32, },
+  /// The program does not end with a `Hlt` or `Ret`.
+  MissingTerminator { at:u32, },
+  /// The `SysCall` at `at` calls an index missing from the
+  /// [`SyscallTable`](crate::syscalls::SyscallTable) it was validated
+  /// against.
+  UnknownSyscall { at:u32, idx:u8, },
+  /// The memory access at `at` is offset by `register`, which the `Load` at
+  /// `load` set to a value that is not exactly a `u32`.
+  InexactOffset { at:u32, register:u8, load:u32, },
+  /// The memory access at `at` reads or writes at least `address`, which is
+  /// past the end of the [`MemoryLayout`](crate::memory::MemoryLayout) it was
+  /// validated against.
+  AddressOutOfBounds { at:u32, address:Address, },
+}
+
+impl ValidationError {
+  /// Returns the offset of the instruction the error was found in.
+  pub fn offset(&self,) -> u32 {
+    match *self {
+      ValidationError::Decode(err,) => err.offset().unwrap_or_default(),
+      ValidationError::JumpOutOfBounds { at, .. }
+      | ValidationError::JumpIntoOperand { at, .. }
+      | ValidationError::MissingTerminator { at, }
+      | ValidationError::InexactOffset { at, .. }
+      | ValidationError::AddressOutOfBounds { at, .. }
+      | ValidationError::UnknownSyscall { at, .. } => at,
+    }
+  }
+
+  /// Displays the error as found in `program`. Invalid opcodes are followed
+  /// by a hex dump and a [`Program::disassemble_around`] listing of the code
+  /// around them, to help find where a corrupt file went wrong.
+  pub fn display_in<'a,>(&'a self, program:&'a Program,) -> ValidationDisplay<'a,> {
+    ValidationDisplay { err:self, program, }
+  }
+}
+
+impl Display for ValidationError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      ValidationError::Decode(err,) => write!(f, "{}", err),
+      ValidationError::JumpOutOfBounds { at, target, } => {
+        write!(
+          f,
+          "instruction at offset {} targets {} which is past the end of the program",
+          at, target
+        )
+      }
+      ValidationError::JumpIntoOperand { at, target, } => write!(
+        f,
+        "instruction at offset {} targets {} which is not the start of an instruction",
+        at, target
+      ),
+      ValidationError::MissingTerminator { at, } => {
+        write!(f, "program ends at offset {} without a Hlt or Ret", at)
+      }
+      ValidationError::UnknownSyscall { at, idx, } => {
+        write!(f, "instruction at offset {} calls unknown syscall {}", at, idx)
+      }
+      ValidationError::InexactOffset { at, register, load, } => write!(
+        f,
+        "instruction at offset {} uses register {} as an offset but the Load at offset {} did not \
+         set it to an exact integer",
+        at, register, load
+      ),
+      ValidationError::AddressOutOfBounds { at, address, } => write!(
+        f,
+        "instruction at offset {} accesses {} or above which is past the end of memory",
+        at, address
+      ),
+    }
+  }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// A [`ValidationError`] displayed with the code around it. Created by
+/// [`ValidationError::display_in`].
+pub struct ValidationDisplay<'a,> {
+  err:&'a ValidationError,
+  program:&'a Program,
+}
+
+impl Display for ValidationDisplay<'_,> {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "{}", self.err)?;
+    let ValidationError::Decode(DecodeError::InvalidOpCode {
+      offset: Some(offset,),
+      ..
+    },) = *self.err
+    else {
+      return Ok((),);
+    };
+    let row = offset / 16 * 16;
+    writeln!(f)?;
+    f.write_str(&self.program.hex_dump(Some(row..row + 16,),),)?;
+    f.write_str(&self.program.disassemble_around(offset, 2,),)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced by misusing [`Label`](crate::builder::Label)s in a
+/// [`ProgramBuilder`](crate::builder::ProgramBuilder).
+pub enum LabelError {
+  /// The named label was bound more than once.
+  AlreadyBound(String,),
+  /// The named labels were jumped to but never bound.
+  Unbound(Vec<String,>,),
+  /// A called label or extern function cannot be added to the program's
+  /// [`SymbolTable`](crate::symbols::SymbolTable).
+  Symbol(SymbolError,),
+}
+
+impl Display for LabelError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      LabelError::AlreadyBound(name,) => write!(f, "label {} is bound more than once", name),
+      LabelError::Unbound(names,) => write!(f, "labels are never bound: {}", names.join(", ")),
+      LabelError::Symbol(err,) => write!(f, "{}", err),
+    }
+  }
+}
+
+impl core::error::Error for LabelError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      LabelError::Symbol(err,) => Some(err,),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced when reading or patching the operands of a [`Program`] in
+/// place.
+pub enum PatchError {
+  /// The 4 bytes starting at `offset` run past the end of a program `len`
+  /// bytes long.
+  OutOfBounds { offset:u32, len:usize, },
+  /// The bytes at `offset` were already patched to `current`.
+  AlreadyPatched { offset:u32, current:u32, },
+  /// A breakpoint was set or an instruction was edited at `offset` which is
+  /// not the start of an instruction.
+  NotAnInstruction { offset:u32, },
+  /// A breakpoint was cleared at `offset` which does not hold a `Trap`.
+  NoBreakpoint { offset:u32, },
+  /// A breakpoint was set at `offset` which already holds a `Trap`.
+  BreakpointSet { offset:u32, },
+  /// The program could not be decoded to find the targets an edit moves.
+  Decode(DecodeError,),
+  /// An instruction `new` bytes long cannot replace the one `old` bytes long
+  /// at `offset` in place.
+  TooWide { offset:u32, old:usize, new:usize, },
+}
+
+impl Display for PatchError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      PatchError::OutOfBounds { offset, len, } => {
+        write!(
+          f,
+          "offset {} is out of bounds for a 4 byte operand in a program of length {}",
+          offset, len
+        )
+      }
+      PatchError::AlreadyPatched { offset, current, } => {
+        write!(
+          f,
+          "operand at offset {} was already patched to {}",
+          offset, current
+        )
+      }
+      PatchError::NotAnInstruction { offset, } => {
+        write!(f, "offset {} is not the start of an instruction", offset)
+      }
+      PatchError::NoBreakpoint { offset, } => write!(f, "offset {} does not hold a breakpoint", offset),
+      PatchError::BreakpointSet { offset, } => write!(f, "offset {} already holds a breakpoint", offset),
+      PatchError::Decode(err,) => write!(f, "could not decode the program to edit it: {}", err),
+      PatchError::TooWide { offset, old, new, } => {
+        write!(
+          f,
+          "a {} byte instruction does not fit in place of the {} byte instruction at offset {}",
+          new, old, offset
+        )
+      }
+    }
+  }
+}
+
+impl core::error::Error for PatchError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      PatchError::Decode(err,) => Some(err,),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced when taking part of a [`Program`]'s code.
+pub enum SliceError {
+  /// The range `start..end` is backwards or runs past the end of a program
+  /// `len` bytes long.
+  OutOfBounds { start:u32, end:u32, len:usize, },
+  /// An end of the range is at `offset`, which is not the start of an
+  /// instruction.
+  NotAnInstruction { offset:u32, },
+  /// The program could not be decoded to find where its instructions start.
+  Decode(DecodeError,),
+}
+
+impl Display for SliceError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      SliceError::OutOfBounds { start, end, len, } => write!(
+        f,
+        "range {}..{} is out of bounds for a program of length {}",
+        start, end, len
+      ),
+      SliceError::NotAnInstruction { offset, } => {
+        write!(f, "offset {} is not the start of an instruction", offset)
+      }
+      SliceError::Decode(err,) => write!(f, "{}", err),
+    }
+  }
+}
+
+impl core::error::Error for SliceError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      SliceError::Decode(err,) => Some(err,),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug,)]
+/// Errors produced when saving, loading, or decoding a [`Program`].
+pub enum ProgramError {
+  /// Reading or writing the file at `path` failed. `path` is `None` when the
+  /// program was read from or written to something other than a file.
+  #[cfg(feature = "std")]
+  Io {
+    path:Option<PathBuf,>,
+    source:std::io::Error,
+  },
+  /// Saving the program to `path` failed at `stage`. The file at `path` is
+  /// left as it was.
+  #[cfg(feature = "std")]
+  Save {
+    stage:SaveStage,
+    path:PathBuf,
+    source:std::io::Error,
+  },
+  /// The file does not start with the spdr magic bytes.
+  NotASpdrFile,
+  /// The file was written in a format version this crate cannot read.
+  UnsupportedVersion { found:u16, supported:u16, },
+  /// The program requires a newer [`IsaVersion`] than this crate supports.
+  UnsupportedIsa { file:IsaVersion, supported:IsaVersion, },
+  /// The file's header has flags this crate does not know.
+  UnknownFlags(u16,),
+  /// The file's code and data are compressed but this crate was built without
+  /// the `compress` feature.
+  Compressed,
+  /// The compressed contents of the section cannot be decompressed.
+  CorruptCompression(SectionKind,),
+  /// The file ends before the length recorded in its header.
+  Truncated { expected:usize, found:usize, },
+  /// The checksum stored in the file does not match its contents.
+  ChecksumMismatch { expected:u32, actual:u32, },
+  /// The byte at `offset` is not a valid [`OpCode`].
+  InvalidOpCode { offset:Option<u32,>, byte:u8, },
+  /// The instruction at `offset` has an invalid
+  /// [`CmpFlag`](crate::opcodes::CmpFlag).
+  InvalidCmpFlag { offset:Option<u32,>, byte:u8, },
+  /// The code ends before all of the operands of the instruction at `offset`.
+  TruncatedInstruction {
+    offset:Option<u32,>, op:Option<OpCode,>,
+  },
+  /// The file has a section of a kind this crate does not know. Reported as
+  /// a warning by
+  /// [`Program::load_with_warnings`](crate::program::Program::load_with_warnings)
+  /// and skipped.
+  UnknownSection { kind:u16, offset:u32, },
+  /// The file has more than one section of the same kind.
+  DuplicateSection(SectionKind,),
+  /// The file does not have a section it requires.
+  MissingSection(SectionKind,),
+  /// The section's contents cannot be read.
+  MalformedSection(SectionKind,),
+}
+
+#[cfg(feature = "std")]
+impl ProgramError {
+  /// Attaches `path` to an [`ProgramError::Io`] error which does not have
+  /// one.
+  pub(crate) fn with_path(self, path:&Path,) -> Self {
+    match self {
+      ProgramError::Io { path: None, source, } => ProgramError::Io {
+        path:Some(path.to_path_buf(),),
+        source,
+      },
+      err => err,
+    }
+  }
+}
+
+impl Display for ProgramError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      #[cfg(feature = "std")]
+      ProgramError::Io {
+        path: Some(path,),
+        source,
+      } => write!(f, "{}: {}", path.display(), source),
+      #[cfg(feature = "std")]
+      ProgramError::Io { path: None, source, } => write!(f, "{}", source),
+      #[cfg(feature = "std")]
+      ProgramError::Save { stage, path, source, } => {
+        write!(f, "{}: failed to {}: {}", path.display(), stage, source)
+      }
+      ProgramError::NotASpdrFile => write!(f, "file is not a spdr program"),
+      ProgramError::UnsupportedVersion { found, supported, } => {
+        write!(
+          f,
+          "file format version {} is not supported (supported version is {})",
+          found, supported
+        )
+      }
+      ProgramError::UnsupportedIsa { file, supported, } => {
+        write!(
+          f,
+          "program requires ISA version {} but the newest supported version is {}",
+          file, supported
+        )
+      }
+      ProgramError::UnknownFlags(flags,) => write!(f, "file has unknown header flags 0x{:04X}", flags),
+      ProgramError::Compressed => {
+        write!(
+          f,
+          "file is compressed but this build lacks the `compress` feature"
+        )
+      }
+      ProgramError::CorruptCompression(kind,) => {
+        write!(f, "file's compressed {:?} section is corrupt", kind)
+      }
+      ProgramError::Truncated { expected, found, } => {
+        write!(
+          f,
+          "file is truncated: expected {} bytes but found {}",
+          expected, found
+        )
+      }
+      ProgramError::ChecksumMismatch { expected, actual, } => write!(
+        f,
+        "file is corrupted: expected checksum {:#010X} but the contents have checksum {:#010X}",
+        expected, actual
+      ),
+      ProgramError::InvalidOpCode { offset, byte, } => {
+        write!(
+          f,
+          "{}",
+          DecodeError::InvalidOpCode {
+            byte:*byte,
+            offset:*offset,
+          }
+        )
+      }
+      ProgramError::InvalidCmpFlag { offset, byte, } => {
+        write!(
+          f,
+          "{}",
+          DecodeError::InvalidCmpFlag {
+            byte:*byte,
+            offset:*offset,
+          }
+        )
+      }
+      ProgramError::TruncatedInstruction {
+        offset,
+        op: Some(op,),
+      } => {
+        write!(
+          f,
+          "{}",
+          DecodeError::Truncated {
+            op:*op,
+            offset:*offset,
+          }
+        )
+      }
+      ProgramError::TruncatedInstruction { offset, op: None, } => {
+        write!(f, "{}", DecodeError::Empty { offset:*offset, })
+      }
+      ProgramError::UnknownSection { kind, offset, } => write!(
+        f,
+        "skipped the section at offset {} because its kind ({}) is unknown",
+        offset, kind
+      ),
+      ProgramError::DuplicateSection(kind,) => {
+        write!(f, "file has more than one {:?} section", kind)
+      }
+      ProgramError::MissingSection(kind,) => write!(f, "file has no {:?} section", kind),
+      ProgramError::MalformedSection(kind,) => write!(f, "file's {:?} section is malformed", kind),
+    }
+  }
+}
+
+impl core::error::Error for ProgramError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      #[cfg(feature = "std")]
+      ProgramError::Io { source, .. } | ProgramError::Save { source, .. } => Some(source,),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// The steps of [`Program::save`](crate::program::Program::save), reported by
+/// [`ProgramError::Save`].
+pub enum SaveStage {
+  /// Creating the temporary file next to the destination.
+  Create,
+  /// Writing the program to the temporary file.
+  Write,
+  /// Flushing the temporary file to disk.
+  Sync,
+  /// Replacing the destination with the temporary file.
+  Rename,
+}
+
+#[cfg(feature = "std")]
+impl Display for SaveStage {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      SaveStage::Create => write!(f, "create the temporary file"),
+      SaveStage::Write => write!(f, "write the temporary file"),
+      SaveStage::Sync => write!(f, "sync the temporary file"),
+      SaveStage::Rename => write!(f, "replace the file"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error,> for ProgramError {
+  fn from(source:std::io::Error,) -> Self {
+    ProgramError::Io { path:None, source, }
+  }
+}
+
+impl From<DecodeError,> for ProgramError {
+  fn from(value:DecodeError,) -> Self {
+    match value {
+      DecodeError::InvalidOpCode { byte, offset, } => ProgramError::InvalidOpCode { offset, byte, },
+      DecodeError::InvalidCmpFlag { byte, offset, } => ProgramError::InvalidCmpFlag { offset, byte, },
+      DecodeError::Truncated { op, offset, } => ProgramError::TruncatedInstruction {
+        offset, op:Some(op,),
+      },
+      DecodeError::Empty { offset, } => ProgramError::TruncatedInstruction { offset, op:None, },
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Error produced when parsing an [`OpCode`] or
+/// [`CmpFlag`](crate::opcodes::CmpFlag) from its name.
+pub struct ParseMnemonicError {
+  /// The string which failed to parse.
+  pub input:String,
+  /// The closest valid name if one is similar to `input`.
+  pub suggestion:Option<String,>,
+}
+
+impl Display for ParseMnemonicError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "unknown mnemonic `{}`", self.input)?;
+    match &self.suggestion {
+      Some(suggestion,) => write!(f, "; did you mean `{}`?", suggestion),
+      None => Ok((),),
+    }
+  }
+}
+
+impl core::error::Error for ParseMnemonicError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// The kinds of problems [`AsmError`] reports.
+pub enum AsmErrorKind {
+  /// The token is not the name of an [`OpCode`].
+  UnknownMnemonic,
+  /// The token is not a register such as `$14`, `$0x0E`, or `$EQ`.
+  ExpectedRegister,
+  /// The token is not an integer which fits in the operand.
+  ExpectedInteger,
+  /// The token is not a number.
+  ExpectedFloat,
+  /// The token is not a [`CmpFlag`](crate::opcodes::CmpFlag).
+  ExpectedFlag,
+  /// The line ended before all of the instruction's operands.
+  MissingOperand,
+  /// The line has more operands than the instruction takes.
+  UnexpectedOperand,
+  /// The token before a `:` is not a valid label name.
+  InvalidLabel,
+  /// The label is defined more than once.
+  DuplicateLabel,
+  /// The label is used but never defined.
+  UndefinedLabel,
+  /// The label named by a `Call` is longer than [`MAX_NAME_LEN`] bytes, so it
+  /// cannot be added to the program's symbols.
+  LabelTooLong,
+  /// The `SysCall` names a syscall missing from the
+  /// [`SyscallTable`](crate::syscalls::SyscallTable).
+  UnknownSyscall,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced while assembling text into a [`Program`].
+///
+/// `line` and `column` are 1-based and point at the start of `token`.
+pub struct AsmError {
+  pub line:usize,
+  pub column:usize,
+  pub token:String,
+  pub kind:AsmErrorKind,
+}
+
+impl Display for AsmError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "{}:{}: ", self.line, self.column)?;
+    match self.kind {
+      AsmErrorKind::UnknownMnemonic => write!(f, "unknown instruction `{}`", self.token),
+      AsmErrorKind::ExpectedRegister => write!(f, "expected a register but found `{}`", self.token),
+      AsmErrorKind::ExpectedInteger => write!(f, "expected an integer but found `{}`", self.token),
+      AsmErrorKind::ExpectedFloat => write!(f, "expected a number but found `{}`", self.token),
+      AsmErrorKind::ExpectedFlag => write!(f, "expected a comparison flag but found `{}`", self.token),
+      AsmErrorKind::MissingOperand => write!(f, "expected another operand"),
+      AsmErrorKind::UnexpectedOperand => write!(f, "unexpected operand `{}`", self.token),
+      AsmErrorKind::InvalidLabel => write!(f, "`{}` is not a valid label name", self.token),
+      AsmErrorKind::DuplicateLabel => write!(f, "label `{}` is defined more than once", self.token),
+      AsmErrorKind::UndefinedLabel => write!(f, "label `{}` is never defined", self.token),
+      AsmErrorKind::LabelTooLong => write!(f, "label `{}` is longer than {} bytes", self.token, MAX_NAME_LEN),
+      AsmErrorKind::UnknownSyscall => write!(f, "unknown syscall `{}`", self.token),
+    }
+  }
+}
+
+impl core::error::Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Ways a [`Program`]'s listing can fail to assemble back into the same code,
+/// found by [`Program::reassemble_check`].
+pub enum ReassembleError {
+  /// The listing did not assemble.
+  Asm(AsmError,),
+  /// The instruction at `offset`, listed as `text`, assembled to
+  /// `reassembled` instead of its `original` bytes.
+  Mismatch {
+    offset:u32,
+    text:String,
+    original:Vec<u8,>,
+    reassembled:Vec<u8,>,
+  },
+}
+
+impl Display for ReassembleError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      ReassembleError::Asm(err,) => write!(f, "the listing does not assemble: {}", err),
+      ReassembleError::Mismatch {
+        offset,
+        text,
+        original,
+        reassembled,
+      } => {
+        let hex = |bytes:&[u8]| {
+          bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte),)
+            .collect::<Vec<_,>>()
+            .join(" ",)
+        };
+        write!(
+          f,
+          "`{}` at offset {} is [{}] but reassembles to [{}]",
+          text,
+          offset,
+          hex(original,),
+          hex(reassembled,)
+        )
+      }
+    }
+  }
+}
+
+impl core::error::Error for ReassembleError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      ReassembleError::Asm(err,) => Some(err,),
+      ReassembleError::Mismatch { .. } => None,
+    }
+  }
+}
+
+#[derive(Debug,)]
+/// Errors produced when building, saving, or loading a
+/// [`SyscallTable`](crate::syscalls::SyscallTable).
+pub enum SyscallError {
+  /// Reading or writing the table's sidecar file failed.
+  #[cfg(feature = "std")]
+  Io(std::io::Error,),
+  /// A syscall's name is not a valid identifier.
+  InvalidName(String,),
+  /// Two syscalls share an index.
+  DuplicateIndex(u8,),
+  /// Two syscalls share a name.
+  DuplicateName(String,),
+  /// Line `line` of a sidecar file is not `index name args [doc]`.
+  InvalidLine { line:usize, text:String, },
+}
+
+impl Display for SyscallError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      #[cfg(feature = "std")]
+      SyscallError::Io(source,) => write!(f, "{}", source),
+      SyscallError::InvalidName(name,) => write!(f, "`{}` is not a valid syscall name", name),
+      SyscallError::DuplicateIndex(index,) => write!(f, "syscall index {} is used more than once", index),
+      SyscallError::DuplicateName(name,) => write!(f, "syscall `{}` is defined more than once", name),
+      SyscallError::InvalidLine { line, text, } => {
+        write!(f, "line {} is not a syscall definition: `{}`", line, text.trim())
+      }
+    }
+  }
+}
+
+impl core::error::Error for SyscallError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      #[cfg(feature = "std")]
+      SyscallError::Io(source,) => Some(source,),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error,> for SyscallError {
+  fn from(source:std::io::Error,) -> Self {
+    SyscallError::Io(source,)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced when creating or parsing a [`Register`].
+pub enum RegisterError {
+  /// The register's number does not fit in a `u8`.
+  TooLarge(String,),
+  /// The text is neither a register number nor the name of a reserved
+  /// register.
+  UnknownName(String,),
+}
+
+impl Display for RegisterError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      RegisterError::TooLarge(number,) => write!(
+        f,
+        "register {} does not exist because the VM only has {} registers",
+        number, REG_COUNT
+      ),
+      RegisterError::UnknownName(name,) => write!(
+        f,
+        "\"{}\" is not a register number or one of the reserved registers {}",
+        name,
+        REGISTER_NAMES.join(", ")
+      ),
+    }
+  }
+}
+
+impl core::error::Error for RegisterError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced by a
+/// [`RegisterAllocator`](crate::registers::RegisterAllocator).
+pub enum AllocError {
+  /// Fewer than `requested` contiguous general purpose registers are free.
+  Exhausted { requested:usize, },
+  /// The register was freed without being allocated or reserved.
+  NotInUse(Register,),
+  /// The register was reserved while already allocated or reserved.
+  InUse(Register,),
+}
+
+impl Display for AllocError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      AllocError::Exhausted { requested, } => write!(
+        f,
+        "could not allocate {} contiguous registers because too few of the {} general purpose registers are free",
+        requested,
+        REG_COUNT - FIRST_FREE_REGISTER
+      ),
+      AllocError::NotInUse(reg,) => write!(f, "register {} was freed but is not in use", reg),
+      AllocError::InUse(reg,) => write!(f, "register {} was reserved but is already in use", reg),
+    }
+  }
+}
+
+impl core::error::Error for AllocError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced by
+/// [`CallingConvention::lower_call`](crate::registers::CallingConvention::lower_call).
+pub enum CallError {
+  /// More arguments would be passed on the stack than a `Ret` can pop.
+  TooManyArgs { stack:usize, },
+}
+
+impl Display for CallError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      CallError::TooManyArgs { stack, } => write!(
+        f,
+        "{} arguments would be passed on the stack but Ret can only pop {}",
+        stack,
+        u8::MAX
+      ),
+    }
+  }
+}
+
+impl core::error::Error for CallError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced when creating a
+/// [`MemoryLayout`](crate::memory::MemoryLayout).
+pub enum LayoutError {
+  /// The stack is longer than memory.
+  StackTooLarge { stack_size:u32, mem_size:u32, },
+  /// The heap starts before the end of the stack.
+  HeapInStack { heap_start:u32, stack_size:u32, },
+  /// The heap starts past the end of memory.
+  HeapOutOfBounds { heap_start:u32, mem_size:u32, },
+}
+
+impl Display for LayoutError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      LayoutError::StackTooLarge {
+        stack_size, mem_size,
+      } => write!(
+        f,
+        "a stack of {} cells does not fit in {} cells of memory",
+        stack_size, mem_size
+      ),
+      LayoutError::HeapInStack {
+        heap_start,
+        stack_size,
+      } => write!(
+        f,
+        "the heap starts at {} which is inside the {} cell stack",
+        heap_start, stack_size
+      ),
+      LayoutError::HeapOutOfBounds {
+        heap_start, mem_size,
+      } => write!(
+        f,
+        "the heap starts at {} which is past the end of {} cells of memory",
+        heap_start, mem_size
+      ),
+    }
+  }
+}
+
+impl core::error::Error for LayoutError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Errors produced when creating an [`Address`].
+pub enum AddressError {
+  /// `addr` is not less than the memory's size.
+  OutOfBounds { addr:u32, mem_size:u32, },
+}
+
+impl Display for AddressError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      AddressError::OutOfBounds { addr, mem_size, } => write!(
+        f,
+        "address 0x{:04X} is past the end of {} cells of memory",
+        addr, mem_size
+      ),
+    }
+  }
+}
+
+impl core::error::Error for AddressError {}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced when adding to a
+/// [`SymbolTable`](crate::symbols::SymbolTable).
+pub enum SymbolError {
+  /// The name is not a valid identifier.
+  InvalidName(String,),
+  /// The name is already in the table.
+  DuplicateName(String,),
+  /// The name is longer than [`MAX_NAME_LEN`] bytes.
+  NameTooLong(String,),
+}
+
+impl Display for SymbolError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      SymbolError::InvalidName(name,) => write!(f, "\"{}\" is not a valid symbol name", name),
+      SymbolError::DuplicateName(name,) => write!(f, "symbol \"{}\" is defined more than once", name),
+      SymbolError::NameTooLong(name,) => write!(
+        f,
+        "symbol \"{}\" is {} bytes but names may be at most {} bytes",
+        name,
+        name.len(),
+        MAX_NAME_LEN
+      ),
+    }
+  }
+}
+
+impl core::error::Error for SymbolError {}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced when adding entries to a
+/// [`ProgramMetadata`](crate::metadata::ProgramMetadata).
+pub enum MetadataError {
+  /// Keys cannot be empty.
+  EmptyKey,
+  /// The key is `len` bytes, longer than [`MAX_KEY_LEN`].
+  KeyTooLong { len:usize, },
+  /// The value of `key` is `len` bytes, longer than [`MAX_VALUE_LEN`].
+  ValueTooLong { key:String, len:usize, },
+}
+
+impl Display for MetadataError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      MetadataError::EmptyKey => write!(f, "metadata keys cannot be empty"),
+      MetadataError::KeyTooLong { len, } => write!(
+        f,
+        "metadata key is {} bytes but at most {} are allowed",
+        len, MAX_KEY_LEN
+      ),
+      MetadataError::ValueTooLong { key, len, } => write!(
+        f,
+        "metadata value of \"{}\" is {} bytes but at most {} are allowed",
+        key, len, MAX_VALUE_LEN
+      ),
+    }
+  }
+}
+
+impl core::error::Error for MetadataError {}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Errors produced by [`Program::link`](crate::program::Program::link).
+pub enum LinkError {
+  /// A relocation in the part at index `part` runs past the end of its code.
+  Relocation { part:usize, err:PatchError, },
+  /// More than one part defines the symbol.
+  DuplicateSymbol(String,),
+  /// An extern call names a symbol no part defines.
+  UnresolvedSymbol(String,),
+  /// The extern naming `name` is at `at` in the linked program, which is not
+  /// the start of a `Call`.
+  NotACall { at:u32, name:String, },
+}
+
+impl Display for LinkError {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match self {
+      LinkError::Relocation { part, err, } => write!(f, "part {}: {}", part, err),
+      LinkError::DuplicateSymbol(name,) => write!(f, "symbol \"{}\" is defined by more than one part", name),
+      LinkError::UnresolvedSymbol(name,) => write!(f, "symbol \"{}\" is not defined by any part", name),
+      LinkError::NotACall { at, name, } => write!(
+        f,
+        "extern call to \"{}\" at offset {} is not the start of a Call",
+        name, at
+      ),
+    }
+  }
+}
+
+impl core::error::Error for LinkError {
+  fn source(&self,) -> Option<&(dyn core::error::Error + 'static),> {
+    match self {
+      LinkError::Relocation { err, .. } => Some(err,),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,206 @@
+use crate::{errors::ValidationError, opcodes::OpCode, program::Program};
+use core::{ffi::c_char, panic::AssertUnwindSafe, slice};
+use std::{ffi::CString, panic::catch_unwind};
+
+// Status codes returned by the `spdr_*` functions. Keep in sync with
+// `include/spdr_isa.h`.
+
+/// The call succeeded and the program is valid.
+pub const SPDR_OK:i32 = 0;
+/// A required pointer argument was null.
+pub const SPDR_NULL_POINTER:i32 = -1;
+/// The call panicked. This is a bug in spdr-isa.
+pub const SPDR_PANIC:i32 = -2;
+/// An instruction could not be decoded.
+pub const SPDR_DECODE_ERROR:i32 = 1;
+/// A jump targets the end of the program or past it.
+pub const SPDR_JUMP_OUT_OF_BOUNDS:i32 = 2;
+/// A jump targets the middle of an instruction.
+pub const SPDR_JUMP_INTO_OPERAND:i32 = 3;
+/// A memory access is offset by a register loaded with an inexact value.
+pub const SPDR_INEXACT_OFFSET:i32 = 5;
+/// The program does not end with a `Hlt` or `Ret`.
+pub const SPDR_MISSING_TERMINATOR:i32 = 6;
+/// A `SysCall` calls an unknown syscall.
+pub const SPDR_UNKNOWN_SYSCALL:i32 = 7;
+/// A memory access is past the end of memory.
+pub const SPDR_ADDRESS_OUT_OF_BOUNDS:i32 = 8;
+
+/// Returns the status code C callers see for `err`.
+fn status(err:&ValidationError,) -> i32 {
+  match err {
+    ValidationError::Decode(_,) => SPDR_DECODE_ERROR,
+    ValidationError::JumpOutOfBounds { .. } => SPDR_JUMP_OUT_OF_BOUNDS,
+    ValidationError::JumpIntoOperand { .. } => SPDR_JUMP_INTO_OPERAND,
+    ValidationError::InexactOffset { .. } => SPDR_INEXACT_OFFSET,
+    ValidationError::MissingTerminator { .. } => SPDR_MISSING_TERMINATOR,
+    ValidationError::UnknownSyscall { .. } => SPDR_UNKNOWN_SYSCALL,
+    ValidationError::AddressOutOfBounds { .. } => SPDR_ADDRESS_OUT_OF_BOUNDS,
+  }
+}
+
+/// Borrows the `len` bytes at `code`. A null `code` is only allowed when
+/// `len` is 0.
+///
+/// # Safety
+///
+/// If `code` is not null it must point to `len` readable bytes.
+unsafe fn code<'a,>(code:*const u8, len:usize,) -> Option<&'a [u8],> {
+  match code.is_null() {
+    true if len == 0 => Some(&[],),
+    true => None,
+    false => Some(unsafe { slice::from_raw_parts(code, len,) },),
+  }
+}
+
+/// Runs `f`, returning [`SPDR_PANIC`] if it panics.
+fn guard(f:impl FnOnce() -> i32,) -> i32 {
+  catch_unwind(AssertUnwindSafe(f,),).unwrap_or(SPDR_PANIC,)
+}
+
+/// Disassembles the `len` bytes of code at `code` into the listing
+/// [`Program`]'s `Display` prints and stores it in `*out` as a nul-terminated
+/// string. Free the string with [`spdr_string_free`].
+///
+/// Returns [`SPDR_OK`], or [`SPDR_NULL_POINTER`] without touching `*out`.
+/// Bytes which do not decode are listed as `???` lines rather than failing.
+///
+/// # Safety
+///
+/// `code` must point to `len` readable bytes, or be null if `len` is 0. `out`
+/// must point to writable storage for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn spdr_disassemble(code:*const u8, len:usize, out:*mut *mut c_char,) -> i32 {
+  guard(|| {
+    let Some(bytes,) = (unsafe { self::code(code, len,) })
+    else {
+      return SPDR_NULL_POINTER;
+    };
+    if out.is_null() {
+      return SPDR_NULL_POINTER;
+    }
+    let listing = Program::from(bytes,).to_string();
+    // Listings never contain nul bytes
+    let listing = CString::new(listing,).unwrap_or_default();
+    unsafe { *out = listing.into_raw() };
+    SPDR_OK
+  },)
+}
+
+/// Frees a string returned by [`spdr_disassemble`]. Does nothing if `s` is
+/// null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by [`spdr_disassemble`] which has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn spdr_string_free(s:*mut c_char,) {
+  if !s.is_null() {
+    drop(unsafe { CString::from_raw(s,) },);
+  }
+}
+
+/// Validates the `len` bytes of code at `code` with [`Program::validate`].
+///
+/// Returns [`SPDR_OK`] if the code is valid. Otherwise returns the status of
+/// the first problem and stores the offset of the instruction it was found in
+/// in `*offset`, if `offset` is not null.
+///
+/// # Safety
+///
+/// `code` must point to `len` readable bytes, or be null if `len` is 0.
+/// `offset` must be null or point to a writable `uint32_t`.
+#[no_mangle]
+pub unsafe extern "C" fn spdr_validate(code:*const u8, len:usize, offset:*mut u32,) -> i32 {
+  guard(|| {
+    let Some(bytes,) = (unsafe { self::code(code, len,) })
+    else {
+      return SPDR_NULL_POINTER;
+    };
+    let Err(errors,) = Program::from(bytes,).validate()
+    else {
+      return SPDR_OK;
+    };
+    let first = &errors[0];
+    if !offset.is_null() {
+      unsafe { *offset = first.offset() };
+    }
+    status(first,)
+  },)
+}
+
+/// Returns the length in bytes of an instruction with the opcode `opcode`,
+/// including the opcode, or 0 if `opcode` is not an opcode.
+#[no_mangle]
+pub extern "C" fn spdr_instruction_len(opcode:u8,) -> usize {
+  OpCode::try_from(opcode,).map_or(0, |op| op.instruction_len(),)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    spdr_disassemble, spdr_instruction_len, spdr_string_free, spdr_validate, SPDR_JUMP_OUT_OF_BOUNDS,
+    SPDR_NULL_POINTER, SPDR_OK,
+  };
+  use crate::{asm::assemble, instruction::Instruction, opcodes::OpCode, program::Program, registers::reg};
+  use core::{ffi::c_char, ptr};
+  use std::ffi::CStr;
+
+  // Called through function pointers so the calls use the C ABI
+  type Disassemble = unsafe extern "C" fn(*const u8, usize, *mut *mut c_char,) -> i32;
+  type Validate = unsafe extern "C" fn(*const u8, usize, *mut u32,) -> i32;
+
+  #[test]
+  fn disassemble_and_validate_through_c_abi() {
+    let disassemble:Disassemble = spdr_disassemble;
+    let validate:Validate = spdr_validate;
+    let instruction_len:extern "C" fn(u8,) -> usize = spdr_instruction_len;
+
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Jmp { target:0, },);
+    program.emit(Instruction::Hlt,);
+
+    let mut out = ptr::null_mut();
+    assert_eq!(
+      unsafe { disassemble(program.as_slice().as_ptr(), program.len(), &mut out,) },
+      SPDR_OK
+    );
+    let listing = unsafe { CStr::from_ptr(out,) }.to_str().unwrap().to_string();
+    unsafe { spdr_string_free(out,) };
+    assert_eq!(listing, program.to_string());
+    assert_eq!(assemble(&listing,).unwrap().as_slice(), program.as_slice());
+
+    let mut offset = u32::MAX;
+    assert_eq!(
+      unsafe { validate(program.as_slice().as_ptr(), program.len(), &mut offset,) },
+      SPDR_OK
+    );
+    program.emit(Instruction::Jmp { target:100, },);
+    assert_eq!(
+      unsafe { validate(program.as_slice().as_ptr(), program.len(), &mut offset,) },
+      SPDR_JUMP_OUT_OF_BOUNDS
+    );
+    assert_eq!(offset, 12);
+
+    assert_eq!(instruction_len(u8::from(OpCode::Load,),), 6);
+    assert_eq!(instruction_len(u8::MAX,), 0);
+  }
+
+  #[test]
+  fn null_pointers_are_rejected() {
+    let mut out = ptr::null_mut();
+    unsafe {
+      assert_eq!(spdr_disassemble(ptr::null(), 4, &mut out,), SPDR_NULL_POINTER);
+      assert_eq!(
+        spdr_disassemble([0].as_ptr(), 1, ptr::null_mut(),),
+        SPDR_NULL_POINTER
+      );
+      assert_eq!(spdr_validate(ptr::null(), 4, ptr::null_mut(),), SPDR_NULL_POINTER);
+      assert_eq!(spdr_validate(ptr::null(), 0, ptr::null_mut(),), SPDR_OK);
+      spdr_string_free(ptr::null_mut(),);
+    }
+    assert!(out.is_null());
+  }
+}
@@ -0,0 +1,544 @@
+use crate::{
+  errors::ProgramError,
+  opcodes::{IsaVersion, CURRENT_ISA_VERSION},
+};
+use alloc::{vec, vec::Vec};
+
+// Layout of a saved program:
+// - `MAGIC`
+// - `FORMAT_VERSION` as a little-endian u16
+// - The `IsaVersion` the program requires as a little-endian u16
+// - Flags as a little-endian u16, of which only `COMPRESSED` may be set
+// - Entry point as a little-endian u32
+// - Number of sections as a little-endian u16
+// - The section table. Each entry is a `SectionKind` as a little-endian u16
+//   followed by the section's offset from the start of the file and its length
+//   as little-endian u32s
+// - The sections in the order the table lists them
+// - CRC32 checksum of every preceding byte as a little-endian u32
+//
+// The data section holds the address the data is copied to as a little-endian
+// u32 followed by the data. When `COMPRESSED` is set, the code and the data
+// following the address are zlib streams. The symbols, debug, relocations,
+// and metadata sections are only present when the program has symbols, debug
+// info, relocations, or metadata.
+
+/// Bytes every saved [`Program`](crate::program::Program) starts with.
+pub const MAGIC:[u8; 4] = *b"SPDR";
+
+/// Version of the file format written by
+/// [`Program::save`](crate::program::Program::save).
+pub const FORMAT_VERSION:u16 = 8;
+
+/// Length of the header preceding the section table in a saved program.
+pub const HEADER_LEN:usize = 16;
+
+/// Header flag set when the code and data sections are compressed.
+pub const COMPRESSED:u16 = 1;
+
+/// Length of each entry in the section table.
+pub const SECTION_ENTRY_LEN:usize = 10;
+
+/// Length of the checksum following the sections in a saved program.
+pub const CHECKSUM_LEN:usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,)]
+#[repr(u16)]
+/// Kinds of section a saved program may contain. Sections are saved in the
+/// order of their kinds.
+pub enum SectionKind {
+  /// The program's code.
+  Code = 0,
+  /// The program's data segment.
+  Data = 1,
+  /// Names of functions and labels.
+  Symbols = 2,
+  /// Information for debuggers.
+  Debug = 3,
+  /// Addresses in the code which must be updated when it is moved.
+  Relocations = 4,
+  /// Keys and values describing where the program came from.
+  Metadata = 5,
+}
+
+impl TryFrom<u16,> for SectionKind {
+  type Error = u16;
+
+  fn try_from(value:u16,) -> Result<Self, Self::Error,> {
+    match value {
+      0 => Ok(SectionKind::Code,),
+      1 => Ok(SectionKind::Data,),
+      2 => Ok(SectionKind::Symbols,),
+      3 => Ok(SectionKind::Debug,),
+      4 => Ok(SectionKind::Relocations,),
+      5 => Ok(SectionKind::Metadata,),
+      kind => Err(kind,),
+    }
+  }
+}
+
+/// Lookup table for [`crc32`] generated from the reversed IEEE polynomial.
+const CRC32_TABLE:[u32; 256] = {
+  let mut table = [0; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = match crc & 1 {
+        1 => (crc >> 1) ^ 0xEDB8_8320,
+        _ => crc >> 1,
+      };
+      bit += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+};
+
+/// Computes the CRC32 (IEEE) checksum of `bytes`.
+pub fn crc32(bytes:&[u8],) -> u32 {
+  !bytes.iter().fold(!0u32, |crc, byte| {
+    CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+  },)
+}
+
+/// The parts of a saved program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub(crate) struct Sections<'a,> {
+  pub isa_version:IsaVersion,
+  /// Whether `code` and `data` are compressed.
+  pub compressed:bool,
+  pub entry_point:u32,
+  pub code:&'a [u8],
+  /// Address the data is copied to or `None` if there is no data section.
+  pub data_base:Option<u32,>,
+  pub data:&'a [u8],
+  /// An encoded [`SymbolTable`](crate::symbols::SymbolTable).
+  pub symbols:&'a [u8],
+  /// Encoded [`Relocation`](crate::relocs::Relocation)s.
+  pub relocations:&'a [u8],
+  /// An encoded [`ProgramMetadata`](crate::metadata::ProgramMetadata).
+  pub metadata:&'a [u8],
+  /// An encoded [`DebugInfo`](crate::debug::DebugInfo).
+  pub debug:&'a [u8],
+}
+
+/// Lays out the header, section table, sections, and checksum of a file
+/// holding `sections`.
+fn write_sections(
+  isa_version:IsaVersion, flags:u16, entry_point:u32, sections:&[(u16, &[u8],)],
+) -> Vec<u8,> {
+  let table_len = sections.len() * SECTION_ENTRY_LEN;
+  let mut bytes = Vec::with_capacity(HEADER_LEN + table_len + CHECKSUM_LEN,);
+  bytes.extend_from_slice(&MAGIC,);
+  bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes(),);
+  bytes.extend_from_slice(&isa_version.0.to_le_bytes(),);
+  bytes.extend_from_slice(&flags.to_le_bytes(),);
+  bytes.extend_from_slice(&entry_point.to_le_bytes(),);
+  bytes.extend_from_slice(&(sections.len() as u16).to_le_bytes(),);
+
+  let mut offset = HEADER_LEN + table_len;
+  for (kind, section,) in sections {
+    bytes.extend_from_slice(&kind.to_le_bytes(),);
+    bytes.extend_from_slice(&(offset as u32).to_le_bytes(),);
+    bytes.extend_from_slice(&(section.len() as u32).to_le_bytes(),);
+    offset += section.len();
+  }
+  for (_, section,) in sections {
+    bytes.extend_from_slice(section,);
+  }
+  let checksum = crc32(&bytes,);
+  bytes.extend_from_slice(&checksum.to_le_bytes(),);
+  bytes
+}
+
+/// Wraps the sections in a header and checksum. The symbols, debug,
+/// relocations, and metadata sections are only written if they are not empty
+/// so identical programs produce identical files.
+pub(crate) fn encode(sections:Sections,) -> Vec<u8,> {
+  let mut data = Vec::new();
+  let mut table = vec![(SectionKind::Code as u16, sections.code,)];
+  if let Some(data_base,) = sections.data_base {
+    data.extend_from_slice(&data_base.to_le_bytes(),);
+    data.extend_from_slice(sections.data,);
+    table.push((SectionKind::Data as u16, data.as_slice(),),);
+  }
+  if !sections.symbols.is_empty() {
+    table.push((SectionKind::Symbols as u16, sections.symbols,),);
+  }
+  if !sections.debug.is_empty() {
+    table.push((SectionKind::Debug as u16, sections.debug,),);
+  }
+  if !sections.relocations.is_empty() {
+    table.push((SectionKind::Relocations as u16, sections.relocations,),);
+  }
+  if !sections.metadata.is_empty() {
+    table.push((SectionKind::Metadata as u16, sections.metadata,),);
+  }
+  let flags = match sections.compressed {
+    true => COMPRESSED,
+    false => 0,
+  };
+  write_sections(sections.isa_version, flags, sections.entry_point, &table,)
+}
+
+/// Checks the header of a saved program and returns its sections.
+///
+/// Sections of unknown kinds are skipped and reported as
+/// [`ProgramError::UnknownSection`] in the returned list of warnings.
+///
+/// If `verify` is false the checksum is neither checked nor required to be
+/// present.
+pub(crate) fn decode(
+  bytes:&[u8],
+  verify:bool,
+) -> Result<(Sections<'_,>, Vec<ProgramError,>,), ProgramError,> {
+  if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+    return Err(ProgramError::NotASpdrFile,);
+  }
+  if bytes.len() < HEADER_LEN {
+    return Err(ProgramError::Truncated {
+      expected:HEADER_LEN,
+      found:bytes.len(),
+    },);
+  }
+
+  let version = u16::from_le_bytes([bytes[4], bytes[5],],);
+  if version != FORMAT_VERSION {
+    return Err(ProgramError::UnsupportedVersion {
+      found:version,
+      supported:FORMAT_VERSION,
+    },);
+  }
+
+  let isa_version = IsaVersion(u16::from_le_bytes([bytes[6], bytes[7],],),);
+  if isa_version > CURRENT_ISA_VERSION {
+    return Err(ProgramError::UnsupportedIsa {
+      file:isa_version,
+      supported:CURRENT_ISA_VERSION,
+    },);
+  }
+
+  let flags = u16::from_le_bytes([bytes[8], bytes[9],],);
+  if flags & !COMPRESSED != 0 {
+    return Err(ProgramError::UnknownFlags(flags,),);
+  }
+
+  let u32_at = |at:usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap(),);
+  let entry_point = u32_at(10,);
+  let count = u16::from_le_bytes([bytes[14], bytes[15],],) as usize;
+  let table_end = HEADER_LEN + count * SECTION_ENTRY_LEN;
+  if bytes.len() < table_end {
+    return Err(ProgramError::Truncated {
+      expected:table_end,
+      found:bytes.len(),
+    },);
+  }
+
+  let mut sections = Sections {
+    isa_version,
+    compressed:flags & COMPRESSED != 0,
+    entry_point,
+    code:&[],
+    data_base:None,
+    data:&[],
+    symbols:&[],
+    relocations:&[],
+    metadata:&[],
+    debug:&[],
+  };
+  let mut seen = Vec::new();
+  let mut warnings = Vec::new();
+  let mut end = table_end;
+  for entry in bytes[HEADER_LEN..table_end].chunks(SECTION_ENTRY_LEN,) {
+    let kind = u16::from_le_bytes([entry[0], entry[1],],);
+    let offset = u32::from_le_bytes(entry[2..6].try_into().unwrap(),) as usize;
+    let len = u32::from_le_bytes(entry[6..10].try_into().unwrap(),) as usize;
+    // The sum overflows on 32 bit targets for sections which cannot fit anyway
+    let Some(section,) = offset.checked_add(len,).and_then(|end| bytes.get(offset..end,),)
+    else {
+      return Err(ProgramError::Truncated {
+        expected:offset.saturating_add(len,).saturating_add(CHECKSUM_LEN,),
+        found:bytes.len(),
+      },);
+    };
+    end = end.max(offset + len,);
+
+    let kind = match SectionKind::try_from(kind,) {
+      Ok(kind,) => kind,
+      Err(kind,) => {
+        warnings.push(ProgramError::UnknownSection {
+          kind,
+          offset:offset as u32,
+        },);
+        continue;
+      }
+    };
+    if seen.contains(&kind,) {
+      return Err(ProgramError::DuplicateSection(kind,),);
+    }
+    seen.push(kind,);
+    match kind {
+      SectionKind::Code => sections.code = section,
+      SectionKind::Data => {
+        if section.len() < 4 {
+          return Err(ProgramError::MalformedSection(kind,),);
+        }
+        sections.data_base = Some(u32::from_le_bytes(section[..4].try_into().unwrap(),),);
+        sections.data = &section[4..];
+      }
+      SectionKind::Symbols => sections.symbols = section,
+      SectionKind::Relocations => sections.relocations = section,
+      SectionKind::Metadata => sections.metadata = section,
+      SectionKind::Debug => sections.debug = section,
+    }
+  }
+  if !seen.contains(&SectionKind::Code,) {
+    return Err(ProgramError::MissingSection(SectionKind::Code,),);
+  }
+  if !verify {
+    return Ok((sections, warnings,),);
+  }
+
+  let expected = match bytes.get(end..end + CHECKSUM_LEN,) {
+    Some(checksum,) => u32::from_le_bytes(checksum.try_into().unwrap(),),
+    None => {
+      return Err(ProgramError::Truncated {
+        expected:end + CHECKSUM_LEN,
+        found:bytes.len(),
+      },)
+    }
+  };
+  let actual = crc32(&bytes[..end],);
+  match expected == actual {
+    true => Ok((sections, warnings,),),
+    false => Err(ProgramError::ChecksumMismatch { expected, actual, },),
+  }
+}
+
+/// Returns the code and data of `sections`, decompressing them if they are
+/// compressed.
+pub(crate) fn decompress(sections:&Sections,) -> Result<(Vec<u8,>, Vec<u8,>,), ProgramError,> {
+  if !sections.compressed {
+    return Ok((sections.code.to_vec(), sections.data.to_vec(),),);
+  }
+  #[cfg(feature = "compress")]
+  {
+    use crate::compress;
+
+    let code =
+      compress::decompress(sections.code,).ok_or(ProgramError::CorruptCompression(SectionKind::Code,),)?;
+    let data = match sections.data_base {
+      Some(_,) => {
+        compress::decompress(sections.data,).ok_or(ProgramError::CorruptCompression(SectionKind::Data,),)?
+      }
+      None => Vec::new(),
+    };
+    Ok((code, data,),)
+  }
+  #[cfg(not(feature = "compress"))]
+  Err(ProgramError::Compressed,)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    crc32, decode, encode, write_sections, SectionKind, Sections, CHECKSUM_LEN, FORMAT_VERSION, HEADER_LEN,
+    SECTION_ENTRY_LEN,
+  };
+  use crate::{
+    errors::ProgramError,
+    opcodes::{IsaVersion, CURRENT_ISA_VERSION},
+  };
+  use alloc::vec::Vec;
+
+  /// Encodes a program with `code` and no data.
+  fn encode_code(code:&[u8],) -> Vec<u8,> {
+    encode(Sections {
+      isa_version:IsaVersion(1,),
+      compressed:false,
+      entry_point:0,
+      code,
+      data_base:None,
+      data:&[],
+      symbols:&[],
+      relocations:&[],
+      metadata:&[],
+      debug:&[],
+    },)
+  }
+
+  /// Length of a file holding 3 bytes of code and nothing else.
+  const CODE_FILE_LEN:usize = HEADER_LEN + SECTION_ENTRY_LEN + 3 + CHECKSUM_LEN;
+
+  #[test]
+  fn header_round_trip() {
+    let bytes = encode_code(&[1, 2, 3,],);
+    assert_eq!(&bytes[..4], b"SPDR");
+    assert_eq!(bytes.len(), CODE_FILE_LEN);
+    assert_eq!(decode(&bytes, true,).unwrap().0.code, &[1, 2, 3]);
+
+    let sections = Sections {
+      isa_version:IsaVersion(3,),
+      compressed:false,
+      entry_point:2,
+      code:&[1, 2, 3,],
+      data_base:Some(20,),
+      data:b"hi",
+      symbols:&[1, 2,],
+      relocations:&[1, 0, 0, 0, 0,],
+      metadata:&[1, b'k', 1, 0, b'v',],
+      debug:&[0, 0, 0, 0,],
+    };
+    let bytes = encode(sections,);
+    assert_eq!(
+      bytes.len(),
+      CODE_FILE_LEN + 5 * SECTION_ENTRY_LEN + 4 + 2 + 2 + 5 + 5 + 4
+    );
+    let (decoded, warnings,) = decode(&bytes, true,).unwrap();
+    assert_eq!(decoded, sections);
+    assert!(warnings.is_empty());
+  }
+
+  #[test]
+  fn unknown_sections_are_skipped() {
+    let bytes = write_sections(
+      IsaVersion(1,),
+      0,
+      1,
+      &[
+        (7, &[9, 9,],),
+        (SectionKind::Code as u16, &[1, 2, 3,],),
+        (SectionKind::Debug as u16, &[],),
+      ],
+    );
+    let (sections, warnings,) = decode(&bytes, true,).unwrap();
+    assert_eq!(sections.entry_point, 1);
+    assert_eq!(sections.code, &[1, 2, 3]);
+    assert!(matches!(
+      warnings[..],
+      [ProgramError::UnknownSection { kind:7, offset }] if offset as usize == HEADER_LEN + 3 * SECTION_ENTRY_LEN
+    ));
+
+    let bytes = write_sections(
+      IsaVersion(1,),
+      0,
+      0,
+      &[(SectionKind::Data as u16, &[0, 0, 0, 0,],),],
+    );
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::MissingSection(SectionKind::Code))
+    ));
+    let bytes = write_sections(IsaVersion(1,), 0, 0, &[(0, &[],), (0, &[],),],);
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::DuplicateSection(SectionKind::Code))
+    ));
+    let bytes = write_sections(IsaVersion(1,), 0, 0, &[(0, &[],), (1, &[0, 0,],),],);
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::MalformedSection(SectionKind::Data))
+    ));
+  }
+
+  #[test]
+  fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789",), 0xCBF4_3926);
+    assert_eq!(crc32(&[],), 0);
+  }
+
+  #[test]
+  fn checksum_mismatch() {
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    let code = HEADER_LEN + SECTION_ENTRY_LEN;
+    bytes[code + 1] = 9;
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::ChecksumMismatch { expected, actual }) if expected != actual
+    ));
+    assert_eq!(decode(&bytes, false,).unwrap().0.code, &[1, 9, 3]);
+
+    // The checksum covers the header too
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    bytes[10] = 1;
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::ChecksumMismatch { .. })
+    ));
+
+    let bytes = encode_code(&[1, 2, 3,],);
+    let half_written = &bytes[..bytes.len() - 2];
+    assert!(matches!(
+      decode(half_written, true,),
+      Err(ProgramError::Truncated { .. })
+    ));
+    assert_eq!(decode(half_written, false,).unwrap().0.code, &[1, 2, 3]);
+  }
+
+  #[test]
+  fn reject_bad_headers() {
+    assert!(matches!(
+      decode(&[0, 15, 20, 90,], true,),
+      Err(ProgramError::NotASpdrFile)
+    ));
+
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    bytes[4] = 9;
+    assert!(matches!(
+      decode(&bytes, true,),
+      Err(ProgramError::UnsupportedVersion {
+        found:9,
+        supported:FORMAT_VERSION
+      })
+    ));
+
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    bytes[6..8].copy_from_slice(&(CURRENT_ISA_VERSION.0 + 1).to_le_bytes(),);
+    assert!(matches!(
+      decode(&bytes, false,),
+      Err(ProgramError::UnsupportedIsa { file, supported:CURRENT_ISA_VERSION })
+        if file.0 == CURRENT_ISA_VERSION.0 + 1
+    ));
+
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    bytes[9] = 0x80;
+    assert!(matches!(
+      decode(&bytes, false,),
+      Err(ProgramError::UnknownFlags(0x8000))
+    ));
+
+    let bytes = encode_code(&[1, 2, 3,],);
+    assert!(matches!(
+      decode(&bytes[..28], true,),
+      Err(ProgramError::Truncated {
+        expected:CODE_FILE_LEN,
+        found:28
+      })
+    ));
+    assert!(matches!(
+      decode(&bytes[..16], true,),
+      Err(ProgramError::Truncated {
+        expected:26,
+        found:16
+      })
+    ));
+    assert!(matches!(
+      decode(&bytes[..6], true,),
+      Err(ProgramError::Truncated {
+        expected:HEADER_LEN,
+        found:6
+      })
+    ));
+
+    // Sections may claim to end past the address space
+    let mut bytes = encode_code(&[1, 2, 3,],);
+    bytes[HEADER_LEN + 2..HEADER_LEN + SECTION_ENTRY_LEN].fill(0xFF,);
+    assert!(matches!(
+      decode(&bytes, false,),
+      Err(ProgramError::Truncated { found, .. }) if found == CODE_FILE_LEN
+    ));
+  }
+}
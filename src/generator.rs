@@ -0,0 +1,359 @@
+use crate::{
+  builder::{Label, ProgramBuilder},
+  instruction::{Instruction, OperandReader},
+  memory::MemoryLayout,
+  opcodes::{CmpFlag, OpCategory, OpCode},
+  program::Program,
+  registers::{Register, FIRST_FREE_REGISTER},
+};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+/// Register generated memory accesses are relative to. It is set once at the
+/// start of the program and never written again.
+const BASE:Register = Register::new(FIRST_FREE_REGISTER as u8,);
+
+/// Number of registers after [`BASE`] that generated instructions use.
+const REGISTERS:u8 = 15;
+
+/// Most items generated programs have on the stack at once, well under
+/// [`STACK_SIZE`](crate::memory::STACK_SIZE).
+const MAX_STACK_DEPTH:u32 = 8;
+
+/// Memory accesses are at most this many cells past [`BASE`].
+const MAX_MEMORY_OFFSET:u32 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// How often a [`ProgramGenerator`] picks each kind of instruction, relative
+/// to the others.
+pub struct Weights {
+  /// Arithmetic, comparisons, `Load`, `Copy`, and `Swap`.
+  pub arithmetic:u32,
+  /// Forward jumps, conditional and not.
+  pub branch:u32,
+  /// `RMem` and `WMem`.
+  pub memory:u32,
+  /// `Push`, `PushI`, `Pop`, and `PopR`.
+  pub stack:u32,
+}
+
+impl Weights {
+  /// Mostly arithmetic with the occasional other instruction.
+  pub const ARITHMETIC_HEAVY:Weights = Weights {
+    arithmetic:12,
+    branch:1,
+    memory:1,
+    stack:1,
+  };
+  /// About a third jumps.
+  pub const BRANCH_HEAVY:Weights = Weights {
+    arithmetic:4,
+    branch:3,
+    memory:1,
+    stack:1,
+  };
+  /// About half memory accesses.
+  pub const MEMORY_HEAVY:Weights = Weights {
+    arithmetic:3,
+    branch:1,
+    memory:5,
+    stack:1,
+  };
+}
+
+impl Default for Weights {
+  fn default() -> Self {
+    Weights {
+      arithmetic:4,
+      branch:1,
+      memory:1,
+      stack:1,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// Generates pseudo-random [`Program`]s for benchmarking. The same
+/// generator always generates the same bytes, on every platform.
+///
+/// Programs pass [`Program::validate`] and always run to their final `Hlt`:
+/// - The first instruction points `$16` at the start of the
+///   [`MemoryLayout::default`] heap. Memory accesses read and write the first
+///   1024 cells after it.
+/// - Every other instruction uses only `$17` to `$31`.
+/// - Jumps only go forward, to the start of a generated instruction.
+/// - The stack holds at most 8 items. It is empty before every jump and jump
+///   target and is emptied before the `Hlt`, so every path through the program
+///   pushes and pops the same number of items without popping an empty stack.
+pub struct ProgramGenerator {
+  /// Seed of the pseudo-random instruction choices.
+  pub seed:u64,
+  /// Number of instructions to generate. The generated program has one more
+  /// at the start to set up memory accesses and may have more at the end to
+  /// empty the stack, followed by a `Hlt`.
+  pub len:usize,
+  pub weights:Weights,
+}
+
+impl ProgramGenerator {
+  /// Creates a generator of programs with `len` instructions mixed by the
+  /// default [`Weights`].
+  pub fn new(seed:u64, len:usize,) -> Self {
+    ProgramGenerator {
+      seed,
+      len,
+      weights:Weights::default(),
+    }
+  }
+
+  /// Generates the program.
+  pub fn generate(&self,) -> Program {
+    let mut rng = Rng(self.seed,);
+    let arithmetic = OpCode::iter()
+      .filter(|op| matches!(op.category(), OpCategory::Arithmetic | OpCategory::Compare),)
+      .collect::<Vec<_,>>();
+
+    // Choose every instruction first so jumps know which later instructions
+    // start with an empty stack
+    let mut slots = Vec::with_capacity(self.len,);
+    let mut depth = 0u32;
+    for _ in 0..self.len {
+      let slot = match self.pick(&mut rng,) {
+        Kind::Branch if depth == 0 => Slot::Branch,
+        Kind::Stack => {
+          let push = depth == 0 || (depth < MAX_STACK_DEPTH && rng.below(2,) == 0);
+          let inst = match (push, rng.below(2,) == 0,) {
+            (true, true,) => Instruction::Push { src:rng.register(), },
+            (true, false,) => Instruction::PushI { imm:rng.f32(), },
+            (false, true,) => Instruction::Pop,
+            (false, false,) => Instruction::PopR { dst:rng.register(), },
+          };
+          Slot::Inst(inst,)
+        }
+        Kind::Memory => {
+          let imm_offset = rng.below(MAX_MEMORY_OFFSET,);
+          Slot::Inst(match rng.below(2,) == 0 {
+            true => Instruction::RMem {
+              dst:rng.register(),
+              src:BASE,
+              imm_offset,
+              reg_offset:Register::PC,
+            },
+            false => Instruction::WMem {
+              dst:BASE,
+              src:rng.register(),
+              imm_offset,
+              reg_offset:Register::PC,
+            },
+          },)
+        }
+        // Jumps with items on the stack become arithmetic
+        Kind::Arithmetic | Kind::Branch => {
+          let op = arithmetic[rng.below(arithmetic.len() as u32,) as usize];
+          let Ok(inst,) = Instruction::read_operands(op, &mut rng,);
+          Slot::Inst(inst,)
+        }
+      };
+      match slot {
+        Slot::Inst(Instruction::Push { .. } | Instruction::PushI { .. },) => depth += 1,
+        Slot::Inst(Instruction::Pop | Instruction::PopR { .. },) => depth -= 1,
+        _ => {}
+      }
+      slots.push((slot, depth == 0,),);
+    }
+    // Whether each slot, and the closing `Hlt`, starts with an empty stack
+    let mut empty = Vec::with_capacity(slots.len() + 1,);
+    empty.push(true,);
+    empty.extend(slots.iter().map(|(_, empty,)| *empty,),);
+    let targets = (0..empty.len()).filter(|idx| empty[*idx],).collect::<Vec<_,>>();
+
+    let mut builder = ProgramBuilder::new();
+    builder.emit(Instruction::LoadU32 {
+      dst:BASE,
+      imm:MemoryLayout::default().heap_start(),
+    },);
+    let labels = (0..=slots.len())
+      .map(|_| builder.new_label(),)
+      .collect::<Vec<_,>>();
+    for (idx, (slot, _,),) in slots.into_iter().enumerate() {
+      builder.bind(labels[idx],).expect("each label is bound once",);
+      let inst = match slot {
+        Slot::Inst(inst,) => inst,
+        Slot::Branch => {
+          // Any later slot starting with an empty stack, at least the `Hlt`
+          let first = targets.partition_point(|target| *target <= idx,);
+          let later = targets.len() - first;
+          let label = labels[targets[first + rng.below(later as u32,) as usize]];
+          self.branch(&mut builder, &mut rng, label,);
+          continue;
+        }
+      };
+      builder.emit(inst,);
+    }
+    builder
+      .bind(labels[labels.len() - 1],)
+      .expect("each label is bound once",);
+    for _ in 0..depth {
+      builder.emit(Instruction::Pop,);
+    }
+    builder.emit(Instruction::Hlt,);
+    builder.finish().expect("every label is bound",)
+  }
+
+  /// Picks the kind of the next instruction.
+  fn pick(&self, rng:&mut Rng,) -> Kind {
+    let weights = self.weights;
+    let kinds = [
+      (Kind::Arithmetic, weights.arithmetic,),
+      (Kind::Branch, weights.branch,),
+      (Kind::Memory, weights.memory,),
+      (Kind::Stack, weights.stack,),
+    ];
+    let total = kinds.iter().map(|(_, weight,)| weight,).sum::<u32>();
+    if total == 0 {
+      return Kind::Arithmetic;
+    }
+    let mut roll = rng.below(total,);
+    for (kind, weight,) in kinds {
+      if roll < weight {
+        return kind;
+      }
+      roll -= weight;
+    }
+    unreachable!("the roll is less than the total weight")
+  }
+
+  /// Emits a jump of a random kind to `label`.
+  fn branch(&self, builder:&mut ProgramBuilder, rng:&mut Rng, label:Label,) {
+    let flag = CmpFlag::ALL[rng.below(CmpFlag::ALL.len() as u32,) as usize];
+    match rng.below(5,) {
+      0 => builder.jmp(label,),
+      1 => builder.jz(Register::EQ, label,),
+      2 => builder.jnz(rng.register(), label,),
+      3 => builder.jmp_cmp_rr(flag, rng.register(), rng.register(), label,),
+      _ => builder.jmp_cmp_ri(flag, rng.register(), rng.f32(), label,),
+    };
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum Kind {
+  Arithmetic,
+  Branch,
+  Memory,
+  Stack,
+}
+
+/// A chosen instruction, or a jump whose target is chosen once every
+/// instruction is.
+enum Slot {
+  Inst(Instruction,),
+  Branch,
+}
+
+/// SplitMix64, which is small, fast, and the same everywhere.
+struct Rng(u64,);
+
+impl Rng {
+  fn next(&mut self,) -> u64 {
+    self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15,);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9,);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB,);
+    z ^ (z >> 31)
+  }
+
+  /// Returns a number less than `n`, which must not be 0.
+  fn below(&mut self, n:u32,) -> u32 {
+    (((self.next() >> 32) * n as u64) >> 32) as u32
+  }
+
+  /// Returns one of the registers generated instructions use.
+  fn register(&mut self,) -> Register {
+    Register::new(BASE.index() + 1 + self.below(REGISTERS as u32,) as u8,)
+  }
+
+  /// Returns a float with at most three decimal places between -100 and 100.
+  fn f32(&mut self,) -> f32 {
+    (self.below(200_001,) as i32 - 100_000) as f32 / 1000.0
+  }
+}
+
+impl OperandReader for Rng {
+  type Error = Infallible;
+
+  fn reg(&mut self,) -> Result<Register, Infallible,> {
+    Ok(self.register(),)
+  }
+
+  fn u8(&mut self,) -> Result<u8, Infallible,> {
+    Ok(self.below(256,) as u8,)
+  }
+
+  fn u32(&mut self,) -> Result<u32, Infallible,> {
+    Ok(self.next() as u32,)
+  }
+
+  fn i32(&mut self,) -> Result<i32, Infallible,> {
+    Ok(self.next() as i32,)
+  }
+
+  fn f32(&mut self,) -> Result<f32, Infallible,> {
+    Ok(Rng::f32(self,),)
+  }
+
+  fn flag(&mut self,) -> Result<CmpFlag, Infallible,> {
+    Ok(CmpFlag::ALL[self.below(CmpFlag::ALL.len() as u32,) as usize],)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{ProgramGenerator, Weights};
+  use crate::{analysis::stack_depth, opcodes::OpCategory};
+
+  #[test]
+  fn generated_programs_are_reproducible_and_valid() {
+    for weights in [
+      Weights::default(),
+      Weights::ARITHMETIC_HEAVY,
+      Weights::BRANCH_HEAVY,
+      Weights::MEMORY_HEAVY,
+    ] {
+      for seed in 0..8 {
+        let generator = ProgramGenerator {
+          seed,
+          len:500,
+          weights,
+        };
+        let program = generator.generate();
+        assert_eq!(program.validate(), Ok(()));
+        assert_eq!(program.validate_strict(), Ok(()));
+        assert!(stack_depth(&program,).unwrap().is_ok());
+        assert_eq!(program.to_file_bytes(), generator.generate().to_file_bytes());
+        assert!(program.instructions().count() > 500);
+      }
+    }
+
+    let branchy = ProgramGenerator {
+      weights:Weights::BRANCH_HEAVY,
+      ..ProgramGenerator::new(7, 1000,)
+    };
+    let count = |generator:ProgramGenerator, category| {
+      generator
+        .generate()
+        .instructions()
+        .filter(|item| item.unwrap().1.opcode().category() == category,)
+        .count()
+    };
+    assert!(
+      count(branchy, OpCategory::ControlFlow,)
+        > count(ProgramGenerator::new(7, 1000,), OpCategory::ControlFlow,)
+    );
+    assert_ne!(
+      ProgramGenerator::new(1, 100,).generate(),
+      ProgramGenerator::new(2, 100,).generate()
+    );
+    assert_eq!(ProgramGenerator::new(1, 0,).generate().len(), 7);
+  }
+}
@@ -0,0 +1,1923 @@
+use crate::{
+  errors::DecodeError,
+  memory::Address,
+  opcodes::{CmpFlag, IsaVersion, OpCode, OperandSlot},
+  program::Program,
+  registers::Register,
+  symbols::SymbolTable,
+};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq,)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// A decoded VM instruction.
+///
+/// Each variant corresponds to an [`OpCode`] and carries that opcode's
+/// operands. See the [`OpCode`] documentation for the meaning of each
+/// operand.
+pub enum Instruction {
+  Hlt,
+  Load {
+    dst:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  Copy {
+    dst:Register,
+    src:Register,
+  },
+  MemCpy {
+    dst:Register,
+    src:Register,
+  },
+  AddRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  SubRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  RvSubRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  MulRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  DivRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  RvDivRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  PowRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  RvPowRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  AddRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  SubRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  MulRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  DivRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  PowRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  CmpRI {
+    flag:CmpFlag,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  CmpRR {
+    flag:CmpFlag,
+    a:Register,
+    b:Register,
+  },
+  Not {
+    dst:Register,
+    src:Register,
+  },
+  Jmp {
+    target:u32,
+  },
+  Jz {
+    cond:Register,
+    target:u32,
+  },
+  Jnz {
+    cond:Register,
+    target:u32,
+  },
+  Call {
+    target:u32,
+  },
+  SysCall {
+    idx:u8,
+  },
+  Ret {
+    args:u8,
+  },
+  Alloc {
+    dst:Register,
+    len:Register,
+  },
+  Realloc {
+    dst:Register,
+    len:Register,
+  },
+  Dealloc {
+    src:Register,
+  },
+  RMem {
+    dst:Register,
+    src:Register,
+    imm_offset:u32,
+    reg_offset:Register,
+  },
+  WMem {
+    dst:Register,
+    src:Register,
+    imm_offset:u32,
+    reg_offset:Register,
+  },
+  WriteStr {
+    ptr:Register,
+    len:Register,
+  },
+  Push {
+    src:Register,
+  },
+  Pop,
+  PopR {
+    dst:Register,
+  },
+  Noop,
+  ModRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  RvModRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  ModRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  Neg {
+    dst:Register,
+    src:Register,
+  },
+  Abs {
+    dst:Register,
+    src:Register,
+  },
+  Sqrt {
+    dst:Register,
+    src:Register,
+  },
+  Floor {
+    dst:Register,
+    src:Register,
+  },
+  Ceil {
+    dst:Register,
+    src:Register,
+  },
+  Round {
+    dst:Register,
+    src:Register,
+  },
+  Trunc {
+    dst:Register,
+    src:Register,
+  },
+  AndRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  OrRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  XorRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  AndRI {
+    dst:Register,
+    src:Register,
+    imm:u32,
+  },
+  OrRI {
+    dst:Register,
+    src:Register,
+    imm:u32,
+  },
+  XorRI {
+    dst:Register,
+    src:Register,
+    imm:u32,
+  },
+  ShlRI {
+    dst:Register,
+    src:Register,
+    imm:u32,
+  },
+  ShrRI {
+    dst:Register,
+    src:Register,
+    imm:u32,
+  },
+  ShlRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  ShrRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  MinRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  MaxRR {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  MinRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  MaxRI {
+    dst:Register,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  Sin {
+    dst:Register,
+    src:Register,
+  },
+  Cos {
+    dst:Register,
+    src:Register,
+  },
+  Tan {
+    dst:Register,
+    src:Register,
+  },
+  Atan2 {
+    dst:Register,
+    a:Register,
+    b:Register,
+  },
+  Ln {
+    dst:Register,
+    src:Register,
+  },
+  Log2 {
+    dst:Register,
+    src:Register,
+  },
+  Exp {
+    dst:Register,
+    src:Register,
+  },
+  JmpCmpRR {
+    flag:CmpFlag,
+    a:Register,
+    b:Register,
+    target:u32,
+  },
+  JmpCmpRI {
+    flag:CmpFlag,
+    src:Register,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+    target:u32,
+  },
+  JmpR {
+    src:Register,
+  },
+  CallR {
+    src:Register,
+  },
+  JmpRel {
+    offset:i32,
+  },
+  JzRel {
+    cond:Register,
+    offset:i32,
+  },
+  JnzRel {
+    cond:Register,
+    offset:i32,
+  },
+  Inc {
+    dst:Register,
+  },
+  Dec {
+    dst:Register,
+  },
+  Swap {
+    dst:Register,
+    src:Register,
+  },
+  LoadU32 {
+    dst:Register,
+    imm:u32,
+  },
+  PushI {
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = finite_f32))]
+    imm:f32,
+  },
+  PopN {
+    count:u8,
+  },
+  Enter {
+    locals:u8,
+  },
+  Leave,
+  MemCpyN {
+    dst:Register,
+    src:Register,
+    len:Register,
+    dst_offset:u32,
+    src_offset:u32,
+  },
+  MemSet {
+    dst:Register,
+    val:Register,
+    len:Register,
+  },
+  Trap,
+  ReadStr {
+    ptr:Register,
+    len:Register,
+  },
+  DbgReg {
+    src:Register,
+  },
+}
+
+impl Display for Instruction {
+  /// Prints the instruction as it appears in a [`Program`]'s listing: the
+  /// mnemonic followed by its comma separated operands, as in
+  /// `Add_RI $16, $17, 1.5`.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    let op = self.opcode();
+    match *self {
+      Instruction::Load { dst, imm, } => write!(f, "{} {}, {}", op, dst, Float(imm,)),
+      Instruction::MemCpyN {
+        dst,
+        src,
+        len,
+        dst_offset,
+        src_offset,
+      } => write!(
+        f,
+        "{} {}, {}, {}, {}, {}",
+        op, dst, src, len, dst_offset, src_offset
+      ),
+      Instruction::LoadU32 { dst, imm, } => write!(f, "{} {}, {}", op, dst, imm),
+      Instruction::AddRI { dst, src, imm, }
+      | Instruction::SubRI { dst, src, imm, }
+      | Instruction::MulRI { dst, src, imm, }
+      | Instruction::DivRI { dst, src, imm, }
+      | Instruction::PowRI { dst, src, imm, }
+      | Instruction::RvSubRI { dst, src, imm, }
+      | Instruction::RvDivRI { dst, src, imm, }
+      | Instruction::RvPowRI { dst, src, imm, }
+      | Instruction::ModRI { dst, src, imm, }
+      | Instruction::RvModRI { dst, src, imm, }
+      | Instruction::MinRI { dst, src, imm, }
+      | Instruction::MaxRI { dst, src, imm, } => {
+        write!(f, "{} {}, {}, {}", op, dst, src, Float(imm,))
+      }
+      Instruction::AndRI { dst, src, imm, }
+      | Instruction::OrRI { dst, src, imm, }
+      | Instruction::XorRI { dst, src, imm, }
+      | Instruction::ShlRI { dst, src, imm, }
+      | Instruction::ShrRI { dst, src, imm, } => {
+        write!(f, "{} {}, {}, {}", op, dst, src, imm)
+      }
+      Instruction::AddRR { dst, a, b, }
+      | Instruction::SubRR { dst, a, b, }
+      | Instruction::MulRR { dst, a, b, }
+      | Instruction::DivRR { dst, a, b, }
+      | Instruction::PowRR { dst, a, b, }
+      | Instruction::ModRR { dst, a, b, }
+      | Instruction::AndRR { dst, a, b, }
+      | Instruction::OrRR { dst, a, b, }
+      | Instruction::XorRR { dst, a, b, }
+      | Instruction::ShlRR { dst, a, b, }
+      | Instruction::ShrRR { dst, a, b, }
+      | Instruction::MinRR { dst, a, b, }
+      | Instruction::MaxRR { dst, a, b, }
+      | Instruction::Atan2 { dst, a, b, }
+      | Instruction::MemSet { dst, val: a, len: b, } => {
+        write!(f, "{} {}, {}, {}", op, dst, a, b)
+      }
+      Instruction::Jmp { target, } | Instruction::Call { target, } => write!(f, "{} {}", op, target),
+      Instruction::Jnz { cond, target, } | Instruction::Jz { cond, target, } => {
+        write!(f, "{} {}, {}", op, cond, target)
+      }
+      Instruction::JmpRel { offset, } => write!(f, "{} {}", op, offset),
+      Instruction::PushI { imm, } => write!(f, "{} {}", op, Float(imm,)),
+      Instruction::JzRel { cond, offset, } | Instruction::JnzRel { cond, offset, } => {
+        write!(f, "{} {}, {}", op, cond, offset)
+      }
+      Instruction::CmpRI { flag, src, imm, } => {
+        write!(f, "{} {}, {}, {}", op, flag, src, Float(imm,))
+      }
+      Instruction::CmpRR { flag, a, b, } => write!(f, "{} {}, {}, {}", op, flag, a, b),
+      Instruction::JmpCmpRR { flag, a, b, target, } => {
+        write!(f, "{} {}, {}, {}, {}", op, flag, a, b, target)
+      }
+      Instruction::JmpCmpRI {
+        flag,
+        src,
+        imm,
+        target,
+      } => {
+        write!(f, "{} {}, {}, {}, {}", op, flag, src, Float(imm,), target)
+      }
+      Instruction::Copy { dst: a, src: b, }
+      | Instruction::MemCpy { dst: a, src: b, }
+      | Instruction::Not { dst: a, src: b, }
+      | Instruction::Neg { dst: a, src: b, }
+      | Instruction::Abs { dst: a, src: b, }
+      | Instruction::Sqrt { dst: a, src: b, }
+      | Instruction::Floor { dst: a, src: b, }
+      | Instruction::Ceil { dst: a, src: b, }
+      | Instruction::Round { dst: a, src: b, }
+      | Instruction::Trunc { dst: a, src: b, }
+      | Instruction::Sin { dst: a, src: b, }
+      | Instruction::Cos { dst: a, src: b, }
+      | Instruction::Tan { dst: a, src: b, }
+      | Instruction::Ln { dst: a, src: b, }
+      | Instruction::Log2 { dst: a, src: b, }
+      | Instruction::Exp { dst: a, src: b, }
+      | Instruction::Swap { dst: a, src: b, }
+      | Instruction::WriteStr { ptr: a, len: b, }
+      | Instruction::ReadStr { ptr: a, len: b, }
+      | Instruction::Alloc { dst: a, len: b, }
+      | Instruction::Realloc { dst: a, len: b, } => write!(f, "{} {}, {}", op, a, b),
+      Instruction::SysCall { idx, }
+      | Instruction::Ret { args: idx, }
+      | Instruction::PopN { count: idx, }
+      | Instruction::Enter { locals: idx, } => {
+        write!(f, "{} {}", op, idx)
+      }
+      Instruction::RMem {
+        dst,
+        src,
+        imm_offset,
+        reg_offset,
+      }
+      | Instruction::WMem {
+        dst,
+        src,
+        imm_offset,
+        reg_offset,
+      } => {
+        write!(f, "{} {}, {}, {}, {}", op, dst, src, imm_offset, reg_offset)
+      }
+      Instruction::Dealloc { src: reg, }
+      | Instruction::Push { src: reg, }
+      | Instruction::JmpR { src: reg, }
+      | Instruction::CallR { src: reg, }
+      | Instruction::DbgReg { src: reg, }
+      | Instruction::PopR { dst: reg, }
+      | Instruction::Inc { dst: reg, }
+      | Instruction::Dec { dst: reg, } => write!(f, "{} {}", op, reg),
+      Instruction::Hlt | Instruction::Pop | Instruction::Noop | Instruction::Leave | Instruction::Trap => {
+        write!(f, "{}", op)
+      }
+    }
+  }
+}
+
+/// Prints a float immediate so the assembler parses it back to the same
+/// `f32`: the shortest digits which round-trip, always with a decimal point,
+/// as in `1.0` or `1.0e20`, and `NaN`, `inf`, or `-inf` otherwise.
+struct Float(f32,);
+
+impl Display for Float {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    // `Debug` prints the shortest round-tripping digits, and a `.0` on whole
+    // numbers unless it switches to an exponent
+    let mut out = PointBeforeExponent { f, point:false, };
+    core::fmt::Write::write_fmt(&mut out, format_args!("{:?}", self.0),)
+  }
+}
+
+/// Passes the `Debug` text of a float on to `f`, adding a `.0` before the
+/// exponent if the mantissa has no decimal point.
+struct PointBeforeExponent<'a, 'b,> {
+  f:&'a mut core::fmt::Formatter<'b,>,
+  /// Whether a decimal point has been written.
+  point:bool,
+}
+
+impl core::fmt::Write for PointBeforeExponent<'_, '_,> {
+  fn write_str(&mut self, s:&str,) -> core::fmt::Result {
+    self.point |= s.contains('.',);
+    match s.split_once('e',) {
+      Some((mantissa, exponent,),) if !self.point => write!(self.f, "{}.0e{}", mantissa, exponent),
+      _ => self.f.write_str(s,),
+    }
+  }
+}
+
+/// Generates only finite immediates so arbitrary instructions compare equal
+/// after a round trip.
+#[cfg(feature = "arbitrary")]
+fn finite_f32(u:&mut arbitrary::Unstructured,) -> arbitrary::Result<f32,> {
+  let imm = u.arbitrary::<f32>()?;
+  Ok(if imm.is_finite() { imm } else { 0.0 },)
+}
+
+impl Instruction {
+  /// Formats the instruction like its `Display` implementation but with jump
+  /// and call targets which a symbol in `symbols` names printed as `<name>`,
+  /// as in `Call <main>`. [`asm::assemble`](crate::asm::assemble) accepts the
+  /// names where the source defines a label for them.
+  ///
+  /// Relative jumps are printed as offsets since their targets depend on where
+  /// the instruction is.
+  pub fn to_asm_string(&self, symbols:Option<&SymbolTable,>,) -> String {
+    let text = format!("{}", self);
+    let symbol = symbols
+      .zip(self.jump_target(),)
+      .and_then(|(symbols, target,)| symbols.at(target,),);
+    match (symbol, text.rsplit_once(' ',),) {
+      // The target is always the last operand
+      (Some(symbol,), Some((rest, _,),),) => format!("{} <{}>", rest, symbol.name),
+      _ => text,
+    }
+  }
+
+  /// Returns the [`OpCode`] of the instruction.
+  pub fn opcode(&self,) -> OpCode {
+    match self {
+      Instruction::Hlt => OpCode::Hlt,
+      Instruction::Load { .. } => OpCode::Load,
+      Instruction::Copy { .. } => OpCode::Copy,
+      Instruction::MemCpy { .. } => OpCode::MemCpy,
+      Instruction::AddRI { .. } => OpCode::AddRI,
+      Instruction::SubRI { .. } => OpCode::SubRI,
+      Instruction::RvSubRI { .. } => OpCode::RvSubRI,
+      Instruction::MulRI { .. } => OpCode::MulRI,
+      Instruction::DivRI { .. } => OpCode::DivRI,
+      Instruction::RvDivRI { .. } => OpCode::RvDivRI,
+      Instruction::PowRI { .. } => OpCode::PowRI,
+      Instruction::RvPowRI { .. } => OpCode::RvPowRI,
+      Instruction::AddRR { .. } => OpCode::AddRR,
+      Instruction::SubRR { .. } => OpCode::SubRR,
+      Instruction::MulRR { .. } => OpCode::MulRR,
+      Instruction::DivRR { .. } => OpCode::DivRR,
+      Instruction::PowRR { .. } => OpCode::PowRR,
+      Instruction::CmpRI { .. } => OpCode::CmpRI,
+      Instruction::CmpRR { .. } => OpCode::CmpRR,
+      Instruction::Not { .. } => OpCode::Not,
+      Instruction::Jmp { .. } => OpCode::Jmp,
+      Instruction::Jz { .. } => OpCode::Jz,
+      Instruction::Jnz { .. } => OpCode::Jnz,
+      Instruction::Call { .. } => OpCode::Call,
+      Instruction::SysCall { .. } => OpCode::SysCall,
+      Instruction::Ret { .. } => OpCode::Ret,
+      Instruction::Alloc { .. } => OpCode::Alloc,
+      Instruction::Realloc { .. } => OpCode::Realloc,
+      Instruction::Dealloc { .. } => OpCode::Dealloc,
+      Instruction::RMem { .. } => OpCode::RMem,
+      Instruction::WMem { .. } => OpCode::WMem,
+      Instruction::WriteStr { .. } => OpCode::WriteStr,
+      Instruction::Push { .. } => OpCode::Push,
+      Instruction::Pop => OpCode::Pop,
+      Instruction::PopR { .. } => OpCode::PopR,
+      Instruction::Noop => OpCode::Noop,
+      Instruction::ModRI { .. } => OpCode::ModRI,
+      Instruction::RvModRI { .. } => OpCode::RvModRI,
+      Instruction::ModRR { .. } => OpCode::ModRR,
+      Instruction::Neg { .. } => OpCode::Neg,
+      Instruction::Abs { .. } => OpCode::Abs,
+      Instruction::Sqrt { .. } => OpCode::Sqrt,
+      Instruction::Floor { .. } => OpCode::Floor,
+      Instruction::Ceil { .. } => OpCode::Ceil,
+      Instruction::Round { .. } => OpCode::Round,
+      Instruction::Trunc { .. } => OpCode::Trunc,
+      Instruction::AndRR { .. } => OpCode::AndRR,
+      Instruction::OrRR { .. } => OpCode::OrRR,
+      Instruction::XorRR { .. } => OpCode::XorRR,
+      Instruction::AndRI { .. } => OpCode::AndRI,
+      Instruction::OrRI { .. } => OpCode::OrRI,
+      Instruction::XorRI { .. } => OpCode::XorRI,
+      Instruction::ShlRI { .. } => OpCode::ShlRI,
+      Instruction::ShrRI { .. } => OpCode::ShrRI,
+      Instruction::ShlRR { .. } => OpCode::ShlRR,
+      Instruction::ShrRR { .. } => OpCode::ShrRR,
+      Instruction::MinRR { .. } => OpCode::MinRR,
+      Instruction::MaxRR { .. } => OpCode::MaxRR,
+      Instruction::MinRI { .. } => OpCode::MinRI,
+      Instruction::MaxRI { .. } => OpCode::MaxRI,
+      Instruction::Sin { .. } => OpCode::Sin,
+      Instruction::Cos { .. } => OpCode::Cos,
+      Instruction::Tan { .. } => OpCode::Tan,
+      Instruction::Atan2 { .. } => OpCode::Atan2,
+      Instruction::Ln { .. } => OpCode::Ln,
+      Instruction::Log2 { .. } => OpCode::Log2,
+      Instruction::Exp { .. } => OpCode::Exp,
+      Instruction::JmpCmpRR { .. } => OpCode::JmpCmpRR,
+      Instruction::JmpCmpRI { .. } => OpCode::JmpCmpRI,
+      Instruction::JmpR { .. } => OpCode::JmpR,
+      Instruction::CallR { .. } => OpCode::CallR,
+      Instruction::JmpRel { .. } => OpCode::JmpRel,
+      Instruction::JzRel { .. } => OpCode::JzRel,
+      Instruction::JnzRel { .. } => OpCode::JnzRel,
+      Instruction::Inc { .. } => OpCode::Inc,
+      Instruction::Dec { .. } => OpCode::Dec,
+      Instruction::Swap { .. } => OpCode::Swap,
+      Instruction::LoadU32 { .. } => OpCode::LoadU32,
+      Instruction::PushI { .. } => OpCode::PushI,
+      Instruction::PopN { .. } => OpCode::PopN,
+      Instruction::Enter { .. } => OpCode::Enter,
+      Instruction::Leave => OpCode::Leave,
+      Instruction::MemCpyN { .. } => OpCode::MemCpyN,
+      Instruction::MemSet { .. } => OpCode::MemSet,
+      Instruction::Trap => OpCode::Trap,
+      Instruction::ReadStr { .. } => OpCode::ReadStr,
+      Instruction::DbgReg { .. } => OpCode::DbgReg,
+    }
+  }
+
+  /// Returns the first [`IsaVersion`] with the instruction's opcode and
+  /// comparison flag.
+  pub fn isa_version(&self,) -> IsaVersion {
+    let op = self.opcode().isa_version();
+    match *self {
+      Instruction::CmpRI { flag, .. }
+      | Instruction::CmpRR { flag, .. }
+      | Instruction::JmpCmpRR { flag, .. }
+      | Instruction::JmpCmpRI { flag, .. } => op.max(flag.isa_version(),),
+      _ => op,
+    }
+  }
+
+  /// Returns the register operands of the instruction in the order they are
+  /// encoded.
+  pub fn registers(&self,) -> Vec<Register,> {
+    match *self {
+      Instruction::Hlt
+      | Instruction::Pop
+      | Instruction::Noop
+      | Instruction::Jmp { .. }
+      | Instruction::Call { .. }
+      | Instruction::JmpRel { .. }
+      | Instruction::PushI { .. }
+      | Instruction::SysCall { .. }
+      | Instruction::Ret { .. }
+      | Instruction::PopN { .. }
+      | Instruction::Enter { .. }
+      | Instruction::Leave
+      | Instruction::Trap => Vec::new(),
+      Instruction::Load { dst, .. } | Instruction::LoadU32 { dst, .. } => vec![dst],
+      Instruction::AddRI { dst, src, .. }
+      | Instruction::SubRI { dst, src, .. }
+      | Instruction::RvSubRI { dst, src, .. }
+      | Instruction::MulRI { dst, src, .. }
+      | Instruction::DivRI { dst, src, .. }
+      | Instruction::RvDivRI { dst, src, .. }
+      | Instruction::PowRI { dst, src, .. }
+      | Instruction::RvPowRI { dst, src, .. }
+      | Instruction::ModRI { dst, src, .. }
+      | Instruction::RvModRI { dst, src, .. }
+      | Instruction::AndRI { dst, src, .. }
+      | Instruction::OrRI { dst, src, .. }
+      | Instruction::XorRI { dst, src, .. }
+      | Instruction::ShlRI { dst, src, .. }
+      | Instruction::ShrRI { dst, src, .. }
+      | Instruction::MinRI { dst, src, .. }
+      | Instruction::MaxRI { dst, src, .. }
+      | Instruction::Copy { dst, src, }
+      | Instruction::MemCpy { dst, src, }
+      | Instruction::Not { dst, src, }
+      | Instruction::Neg { dst, src, }
+      | Instruction::Abs { dst, src, }
+      | Instruction::Sqrt { dst, src, }
+      | Instruction::Floor { dst, src, }
+      | Instruction::Ceil { dst, src, }
+      | Instruction::Round { dst, src, }
+      | Instruction::Trunc { dst, src, }
+      | Instruction::Sin { dst, src, }
+      | Instruction::Cos { dst, src, }
+      | Instruction::Tan { dst, src, }
+      | Instruction::Ln { dst, src, }
+      | Instruction::Log2 { dst, src, }
+      | Instruction::Exp { dst, src, }
+      | Instruction::Swap { dst, src, } => vec![dst, src],
+      Instruction::AddRR { dst, a, b, }
+      | Instruction::SubRR { dst, a, b, }
+      | Instruction::MulRR { dst, a, b, }
+      | Instruction::DivRR { dst, a, b, }
+      | Instruction::PowRR { dst, a, b, }
+      | Instruction::ModRR { dst, a, b, }
+      | Instruction::AndRR { dst, a, b, }
+      | Instruction::OrRR { dst, a, b, }
+      | Instruction::XorRR { dst, a, b, }
+      | Instruction::ShlRR { dst, a, b, }
+      | Instruction::ShrRR { dst, a, b, }
+      | Instruction::MinRR { dst, a, b, }
+      | Instruction::MaxRR { dst, a, b, }
+      | Instruction::Atan2 { dst, a, b, }
+      | Instruction::MemSet { dst, val: a, len: b, } => vec![dst, a, b],
+      Instruction::CmpRI { src, .. } => vec![src],
+      Instruction::CmpRR { a, b, .. } => vec![a, b],
+      Instruction::JmpCmpRR { a, b, .. } => vec![a, b],
+      Instruction::JmpCmpRI { src, .. } => vec![src],
+      Instruction::Jz { cond, .. }
+      | Instruction::Jnz { cond, .. }
+      | Instruction::JzRel { cond, .. }
+      | Instruction::JnzRel { cond, .. } => vec![cond],
+      Instruction::Alloc { dst, len, } | Instruction::Realloc { dst, len, } => vec![dst, len],
+      Instruction::WriteStr { ptr, len, } | Instruction::ReadStr { ptr, len, } => vec![ptr, len],
+      Instruction::Dealloc { src, }
+      | Instruction::Push { src, }
+      | Instruction::JmpR { src, }
+      | Instruction::CallR { src, }
+      | Instruction::DbgReg { src, } => vec![src],
+      Instruction::PopR { dst, } | Instruction::Inc { dst, } | Instruction::Dec { dst, } => vec![dst],
+      Instruction::MemCpyN { dst, src, len, .. } => vec![dst, src, len],
+      Instruction::RMem {
+        dst, src, reg_offset,
+      ..
+      }
+      | Instruction::WMem {
+        dst, src, reg_offset,
+      ..
+      } => {
+        vec![dst, src, reg_offset]
+      }
+    }
+  }
+
+  /// Returns the register operands the instruction reads. A `RMem` or `WMem`
+  /// register offset of [`PC`](crate::registers::PC) means there is no offset
+  /// so it is not read.
+  pub fn reads(&self,) -> Vec<Register,> {
+    let mut regs = self.registers();
+    match *self {
+      Instruction::ReadStr { .. } => regs.clear(),
+      Instruction::RMem { reg_offset, .. } | Instruction::WMem { reg_offset, .. }
+        if reg_offset == Register::PC =>
+      {
+        regs.pop();
+      }
+      _ => {}
+    }
+    if self.overwrites_first_operand() {
+      regs.remove(0,);
+    }
+    regs
+  }
+
+  /// Returns the register operands the instruction writes.
+  pub fn writes(&self,) -> Vec<Register,> {
+    let mut regs = self.registers();
+    match self.opcode().writes_register() {
+      Some(OperandSlot::First,) => regs.truncate(1,),
+      Some(OperandSlot::FirstTwo,) => regs.truncate(2,),
+      None => regs.clear(),
+    }
+    regs
+  }
+
+  /// Returns the registers the instruction reads which are not operands, such
+  /// as the [`SP`](crate::registers::SP) read by a `Push`.
+  pub fn implicit_reads(&self,) -> Vec<Register,> {
+    match self.opcode() {
+      OpCode::Call | OpCode::CallR => vec![Register::PC, Register::SP],
+      OpCode::Ret | OpCode::Push | OpCode::PushI | OpCode::Pop | OpCode::PopR | OpCode::PopN => {
+        vec![Register::SP]
+      }
+      OpCode::Enter => vec![Register::SP, Register::FP],
+      OpCode::Leave => vec![Register::FP],
+      _ => Vec::new(),
+    }
+  }
+
+  /// Returns the registers the instruction writes which are not operands,
+  /// such as the [`EQ`](crate::registers::EQ) written by `Cmp_RR`.
+  ///
+  /// Every instruction advances [`PC`](crate::registers::PC). Only the
+  /// instructions which may jump are counted as writing it.
+  pub fn implicit_writes(&self,) -> Vec<Register,> {
+    match self.opcode() {
+      OpCode::CmpRI | OpCode::CmpRR => vec![Register::EQ],
+      OpCode::Jmp
+      | OpCode::Jz
+      | OpCode::Jnz
+      | OpCode::JmpRel
+      | OpCode::JzRel
+      | OpCode::JnzRel
+      | OpCode::JmpCmpRR
+      | OpCode::JmpCmpRI
+      | OpCode::JmpR => vec![Register::PC],
+      OpCode::Call | OpCode::CallR | OpCode::Ret => vec![Register::PC, Register::SP],
+      OpCode::Push | OpCode::PushI | OpCode::Pop | OpCode::PopR | OpCode::PopN => vec![Register::SP],
+      OpCode::Enter | OpCode::Leave => vec![Register::SP, Register::FP],
+      _ => Vec::new(),
+    }
+  }
+
+  /// Whether the instruction overwrites its first register operand without
+  /// reading it.
+  fn overwrites_first_operand(&self,) -> bool {
+    matches!(
+      self.opcode(),
+      OpCode::Load
+        | OpCode::LoadU32
+        | OpCode::Copy
+        | OpCode::AddRI
+        | OpCode::SubRI
+        | OpCode::RvSubRI
+        | OpCode::MulRI
+        | OpCode::DivRI
+        | OpCode::RvDivRI
+        | OpCode::PowRI
+        | OpCode::RvPowRI
+        | OpCode::ModRI
+        | OpCode::RvModRI
+        | OpCode::AndRI
+        | OpCode::OrRI
+        | OpCode::XorRI
+        | OpCode::ShlRI
+        | OpCode::ShrRI
+        | OpCode::MinRI
+        | OpCode::MaxRI
+        | OpCode::AddRR
+        | OpCode::SubRR
+        | OpCode::MulRR
+        | OpCode::DivRR
+        | OpCode::PowRR
+        | OpCode::ModRR
+        | OpCode::AndRR
+        | OpCode::OrRR
+        | OpCode::XorRR
+        | OpCode::ShlRR
+        | OpCode::ShrRR
+        | OpCode::MinRR
+        | OpCode::MaxRR
+        | OpCode::Atan2
+        | OpCode::Not
+        | OpCode::Neg
+        | OpCode::Abs
+        | OpCode::Sqrt
+        | OpCode::Floor
+        | OpCode::Ceil
+        | OpCode::Round
+        | OpCode::Trunc
+        | OpCode::Sin
+        | OpCode::Cos
+        | OpCode::Tan
+        | OpCode::Ln
+        | OpCode::Log2
+        | OpCode::Exp
+        | OpCode::Alloc
+        | OpCode::PopR
+        | OpCode::RMem
+    )
+  }
+
+  /// Returns the program index the instruction transfers control to, if it
+  /// is a jump or a call.
+  pub fn jump_target(&self,) -> Option<u32,> {
+    match *self {
+      Instruction::Jmp { target, } | Instruction::Jz { target, .. } | Instruction::Jnz { target, .. } => {
+        Some(target,)
+      }
+      Instruction::Call { target, } => Some(target,),
+      Instruction::JmpCmpRR { target, .. } | Instruction::JmpCmpRI { target, .. } => Some(target,),
+      _ => None,
+    }
+  }
+
+  /// Returns a mutable reference to the target of a jump or call with an
+  /// absolute target.
+  pub fn jump_target_mut(&mut self,) -> Option<&mut u32,> {
+    match self {
+      Instruction::Jmp { target, }
+      | Instruction::Jz { target, .. }
+      | Instruction::Jnz { target, .. }
+      | Instruction::Call { target, }
+      | Instruction::JmpCmpRR { target, .. }
+      | Instruction::JmpCmpRI { target, .. } => Some(target,),
+      _ => None,
+    }
+  }
+
+  /// Returns the signed offset of a relative jump. Offsets are measured from
+  /// the start of the next instruction.
+  pub fn relative_offset(&self,) -> Option<i32,> {
+    match *self {
+      Instruction::JmpRel { offset, }
+      | Instruction::JzRel { offset, .. }
+      | Instruction::JnzRel { offset, .. } => Some(offset,),
+      _ => None,
+    }
+  }
+
+  /// Returns the program index the instruction transfers control to if it
+  /// starts at `at`. Unlike [`Instruction::jump_target`] this includes
+  /// relative jumps.
+  pub fn resolve_target(&self, at:u32,) -> Option<u32,> {
+    match self.relative_offset() {
+      Some(offset,) => {
+        let next = at.wrapping_add(self.opcode().instruction_len() as u32,);
+        Some(next.wrapping_add_signed(offset,),)
+      }
+      None => self.jump_target(),
+    }
+  }
+
+  /// Returns [`Instruction::resolve_target`] as an [`Address`] in the
+  /// program.
+  pub fn target_address(&self, at:u32,) -> Option<Address,> {
+    self.resolve_target(at,).map(Address::new_unchecked,)
+  }
+
+  /// Returns the immediate offset of an `RMem` or `WMem`. This is the lowest
+  /// address the instruction can access.
+  pub fn imm_address(&self,) -> Option<Address,> {
+    match *self {
+      Instruction::RMem { imm_offset, .. } | Instruction::WMem { imm_offset, .. } => {
+        Some(Address::new_unchecked(imm_offset,),)
+      }
+      _ => None,
+    }
+  }
+
+  /// Appends the instruction's opcode and operands to the end of the
+  /// [`Program`].
+  pub fn encode(&self, program:&mut Program,) {
+    program.push(self.opcode().into(),);
+    match *self {
+      Instruction::Hlt | Instruction::Pop | Instruction::Noop | Instruction::Leave | Instruction::Trap => {}
+      Instruction::LoadU32 { dst, imm, } => {
+        program.push(dst.into(),);
+        program.push_u32(imm,);
+      }
+      Instruction::Load { dst, imm, } => {
+        program.push(dst.into(),);
+        program.push_f32(imm,);
+      }
+      Instruction::AddRI { dst, src, imm, }
+      | Instruction::SubRI { dst, src, imm, }
+      | Instruction::RvSubRI { dst, src, imm, }
+      | Instruction::MulRI { dst, src, imm, }
+      | Instruction::DivRI { dst, src, imm, }
+      | Instruction::RvDivRI { dst, src, imm, }
+      | Instruction::PowRI { dst, src, imm, }
+      | Instruction::RvPowRI { dst, src, imm, }
+      | Instruction::ModRI { dst, src, imm, }
+      | Instruction::RvModRI { dst, src, imm, }
+      | Instruction::MinRI { dst, src, imm, }
+      | Instruction::MaxRI { dst, src, imm, } => {
+        program.extend_from_slice(&[dst.into(), src.into(),],);
+        program.push_f32(imm,);
+      }
+      Instruction::AndRI { dst, src, imm, }
+      | Instruction::OrRI { dst, src, imm, }
+      | Instruction::XorRI { dst, src, imm, }
+      | Instruction::ShlRI { dst, src, imm, }
+      | Instruction::ShrRI { dst, src, imm, } => {
+        program.extend_from_slice(&[dst.into(), src.into(),],);
+        program.push_u32(imm,);
+      }
+      Instruction::AddRR { dst, a, b, }
+      | Instruction::SubRR { dst, a, b, }
+      | Instruction::MulRR { dst, a, b, }
+      | Instruction::DivRR { dst, a, b, }
+      | Instruction::PowRR { dst, a, b, }
+      | Instruction::ModRR { dst, a, b, }
+      | Instruction::AndRR { dst, a, b, }
+      | Instruction::OrRR { dst, a, b, }
+      | Instruction::XorRR { dst, a, b, }
+      | Instruction::ShlRR { dst, a, b, }
+      | Instruction::ShrRR { dst, a, b, }
+      | Instruction::MinRR { dst, a, b, }
+      | Instruction::MaxRR { dst, a, b, }
+      | Instruction::Atan2 { dst, a, b, }
+      | Instruction::MemSet { dst, val: a, len: b, } => {
+        program.extend_from_slice(&[dst.into(), a.into(), b.into(),],)
+      }
+      Instruction::CmpRI { flag, src, imm, } => {
+        program.extend_from_slice(&[flag.into(), src.into(),],);
+        program.push_f32(imm,);
+      }
+      Instruction::CmpRR { flag, a, b, } => program.extend_from_slice(&[flag.into(), a.into(), b.into(),],),
+      Instruction::JmpCmpRR { flag, a, b, target, } => {
+        program.extend_from_slice(&[flag.into(), a.into(), b.into(),],);
+        program.push_u32(target,);
+      }
+      Instruction::JmpCmpRI {
+        flag,
+        src,
+        imm,
+        target,
+      } => {
+        program.extend_from_slice(&[flag.into(), src.into(),],);
+        program.push_f32(imm,);
+        program.push_u32(target,);
+      }
+      Instruction::Copy { dst, src, }
+      | Instruction::MemCpy { dst, src, }
+      | Instruction::Not { dst, src, }
+      | Instruction::Neg { dst, src, }
+      | Instruction::Abs { dst, src, }
+      | Instruction::Sqrt { dst, src, }
+      | Instruction::Floor { dst, src, }
+      | Instruction::Ceil { dst, src, }
+      | Instruction::Round { dst, src, }
+      | Instruction::Trunc { dst, src, }
+      | Instruction::Sin { dst, src, }
+      | Instruction::Cos { dst, src, }
+      | Instruction::Tan { dst, src, }
+      | Instruction::Ln { dst, src, }
+      | Instruction::Log2 { dst, src, }
+      | Instruction::Exp { dst, src, }
+      | Instruction::Swap { dst, src, } => program.extend_from_slice(&[dst.into(), src.into(),],),
+      Instruction::Alloc { dst, len, } | Instruction::Realloc { dst, len, } => {
+        program.extend_from_slice(&[dst.into(), len.into(),],)
+      }
+      Instruction::WriteStr { ptr, len, } | Instruction::ReadStr { ptr, len, } => {
+        program.extend_from_slice(&[ptr.into(), len.into(),],)
+      }
+      Instruction::Jmp { target, } => program.push_u32(target,),
+      Instruction::Jz { cond, target, } | Instruction::Jnz { cond, target, } => {
+        program.push(cond.into(),);
+        program.push_u32(target,);
+      }
+      Instruction::Call { target, } => program.push_u32(target,),
+      Instruction::JmpRel { offset, } => program.push_i32(offset,),
+      Instruction::PushI { imm, } => program.push_f32(imm,),
+      Instruction::JzRel { cond, offset, } | Instruction::JnzRel { cond, offset, } => {
+        program.push(cond.into(),);
+        program.push_i32(offset,);
+      }
+      Instruction::SysCall { idx, } => program.push(idx,),
+      Instruction::Ret { args, } => program.push(args,),
+      Instruction::PopN { count, } => program.push(count,),
+      Instruction::MemCpyN {
+        dst,
+        src,
+        len,
+        dst_offset,
+        src_offset,
+      } => {
+        program.extend_from_slice(&[dst.into(), src.into(), len.into(),],);
+        program.push_u32(dst_offset,);
+        program.push_u32(src_offset,);
+      }
+      Instruction::Enter { locals, } => program.push(locals,),
+      Instruction::Dealloc { src, }
+      | Instruction::Push { src, }
+      | Instruction::JmpR { src, }
+      | Instruction::CallR { src, }
+      | Instruction::DbgReg { src, } => program.push(src.into(),),
+      Instruction::PopR { dst, } | Instruction::Inc { dst, } | Instruction::Dec { dst, } => {
+        program.push(dst.into(),)
+      }
+      Instruction::RMem {
+        dst,
+        src,
+        imm_offset,
+        reg_offset,
+      }
+      | Instruction::WMem {
+        dst,
+        src,
+        imm_offset,
+        reg_offset,
+      } => {
+        program.extend_from_slice(&[dst.into(), src.into(),],);
+        program.push_u32(imm_offset,);
+        program.push(reg_offset.into(),);
+      }
+    }
+  }
+
+  /// Decodes the instruction at the start of `bytes`.
+  ///
+  /// Returns the [`Instruction`] and the number of bytes it occupies
+  /// (including the opcode). Errors if the first byte is not a valid
+  /// [`OpCode`] or if `bytes` ends before all of the instruction's operands
+  /// have been read.
+  pub fn decode(bytes:&[u8],) -> Result<(Instruction, usize,), DecodeError,> {
+    let mut src = ByteReader {
+      bytes,
+      pos:0,
+      op:None,
+    };
+    let op = OpCode::try_from(src.u8()?,)?;
+    src.op = Some(op,);
+    if bytes.len() < op.instruction_len() {
+      return Err(DecodeError::Truncated { op, offset:None, },);
+    }
+
+    let inst = Instruction::read_operands(op, &mut src,)?;
+    debug_assert_eq!(src.pos, op.instruction_len());
+
+    Ok((inst, op.instruction_len(),),)
+  }
+
+  /// Reads the operands of an `op` instruction from `src` in the order they
+  /// are encoded.
+  pub(crate) fn read_operands<R:OperandReader,>(op:OpCode, src:&mut R,) -> Result<Self, R::Error,> {
+    Ok(match op {
+      OpCode::Hlt => Instruction::Hlt,
+      OpCode::Load => Instruction::Load {
+        dst:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::Copy => Instruction::Copy {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::MemCpy => Instruction::MemCpy {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::AddRI => Instruction::AddRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::SubRI => Instruction::SubRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::RvSubRI => Instruction::RvSubRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::MulRI => Instruction::MulRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::DivRI => Instruction::DivRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::RvDivRI => Instruction::RvDivRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::PowRI => Instruction::PowRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::RvPowRI => Instruction::RvPowRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::AddRR => Instruction::AddRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::SubRR => Instruction::SubRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::MulRR => Instruction::MulRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::DivRR => Instruction::DivRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::PowRR => Instruction::PowRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::CmpRI => Instruction::CmpRI {
+        flag:src.flag()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::CmpRR => Instruction::CmpRR {
+        flag:src.flag()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::Not => Instruction::Not {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Jmp => Instruction::Jmp { target:src.u32()?, },
+      OpCode::Jz => Instruction::Jz {
+        cond:src.reg()?,
+        target:src.u32()?,
+      },
+      OpCode::Jnz => Instruction::Jnz {
+        cond:src.reg()?,
+        target:src.u32()?,
+      },
+      OpCode::Call => Instruction::Call { target:src.u32()?, },
+      OpCode::SysCall => Instruction::SysCall { idx:src.u8()?, },
+      OpCode::Ret => Instruction::Ret { args:src.u8()?, },
+      OpCode::Alloc => Instruction::Alloc {
+        dst:src.reg()?,
+        len:src.reg()?,
+      },
+      OpCode::Realloc => Instruction::Realloc {
+        dst:src.reg()?,
+        len:src.reg()?,
+      },
+      OpCode::Dealloc => Instruction::Dealloc { src:src.reg()?, },
+      OpCode::RMem => Instruction::RMem {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm_offset:src.u32()?,
+        reg_offset:src.reg()?,
+      },
+      OpCode::WMem => Instruction::WMem {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm_offset:src.u32()?,
+        reg_offset:src.reg()?,
+      },
+      OpCode::WriteStr => Instruction::WriteStr {
+        ptr:src.reg()?,
+        len:src.reg()?,
+      },
+      OpCode::Push => Instruction::Push { src:src.reg()?, },
+      OpCode::Pop => Instruction::Pop,
+      OpCode::PopR => Instruction::PopR { dst:src.reg()?, },
+      OpCode::Noop => Instruction::Noop,
+      OpCode::ModRI => Instruction::ModRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::RvModRI => Instruction::RvModRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::ModRR => Instruction::ModRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::Neg => Instruction::Neg {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Abs => Instruction::Abs {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Sqrt => Instruction::Sqrt {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Floor => Instruction::Floor {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Ceil => Instruction::Ceil {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Round => Instruction::Round {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Trunc => Instruction::Trunc {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::AndRR => Instruction::AndRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::OrRR => Instruction::OrRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::XorRR => Instruction::XorRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::AndRI => Instruction::AndRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::OrRI => Instruction::OrRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::XorRI => Instruction::XorRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::ShlRI => Instruction::ShlRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::ShrRI => Instruction::ShrRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::ShlRR => Instruction::ShlRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::ShrRR => Instruction::ShrRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::MinRR => Instruction::MinRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::MaxRR => Instruction::MaxRR {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::MinRI => Instruction::MinRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::MaxRI => Instruction::MaxRI {
+        dst:src.reg()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+      },
+      OpCode::Sin => Instruction::Sin {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Cos => Instruction::Cos {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Tan => Instruction::Tan {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Atan2 => Instruction::Atan2 {
+        dst:src.reg()?,
+        a:src.reg()?,
+        b:src.reg()?,
+      },
+      OpCode::Ln => Instruction::Ln {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Log2 => Instruction::Log2 {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::Exp => Instruction::Exp {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::JmpCmpRR => Instruction::JmpCmpRR {
+        flag:src.flag()?,
+        a:src.reg()?,
+        b:src.reg()?,
+        target:src.u32()?,
+      },
+      OpCode::JmpCmpRI => Instruction::JmpCmpRI {
+        flag:src.flag()?,
+        src:src.reg()?,
+        imm:src.f32()?,
+        target:src.u32()?,
+      },
+      OpCode::JmpR => Instruction::JmpR { src:src.reg()?, },
+      OpCode::CallR => Instruction::CallR { src:src.reg()?, },
+      OpCode::JmpRel => Instruction::JmpRel { offset:src.i32()?, },
+      OpCode::JzRel => Instruction::JzRel {
+        cond:src.reg()?,
+        offset:src.i32()?,
+      },
+      OpCode::JnzRel => Instruction::JnzRel {
+        cond:src.reg()?,
+        offset:src.i32()?,
+      },
+      OpCode::Inc => Instruction::Inc { dst:src.reg()?, },
+      OpCode::Dec => Instruction::Dec { dst:src.reg()?, },
+      OpCode::Swap => Instruction::Swap {
+        dst:src.reg()?,
+        src:src.reg()?,
+      },
+      OpCode::LoadU32 => Instruction::LoadU32 {
+        dst:src.reg()?,
+        imm:src.u32()?,
+      },
+      OpCode::PushI => Instruction::PushI { imm:src.f32()?, },
+      OpCode::PopN => Instruction::PopN { count:src.u8()?, },
+      OpCode::Enter => Instruction::Enter { locals:src.u8()?, },
+      OpCode::Leave => Instruction::Leave,
+      OpCode::MemCpyN => Instruction::MemCpyN {
+        dst:src.reg()?,
+        src:src.reg()?,
+        len:src.reg()?,
+        dst_offset:src.u32()?,
+        src_offset:src.u32()?,
+      },
+      OpCode::MemSet => Instruction::MemSet {
+        dst:src.reg()?,
+        val:src.reg()?,
+        len:src.reg()?,
+      },
+      OpCode::Trap => Instruction::Trap,
+      OpCode::ReadStr => Instruction::ReadStr {
+        ptr:src.reg()?,
+        len:src.reg()?,
+      },
+      OpCode::DbgReg => Instruction::DbgReg { src:src.reg()?, },
+    },)
+  }
+}
+
+/// A source of instruction operands.
+///
+/// Implemented by everything which builds [`Instruction`]s from some external
+/// representation so that they all agree on the order of each instruction's
+/// operands.
+pub(crate) trait OperandReader {
+  type Error;
+
+  /// Reads a register operand.
+  fn reg(&mut self,) -> Result<Register, Self::Error,>;
+
+  /// Reads a one byte integer operand.
+  fn u8(&mut self,) -> Result<u8, Self::Error,>;
+
+  /// Reads a four byte integer operand.
+  fn u32(&mut self,) -> Result<u32, Self::Error,>;
+
+  /// Reads a four byte signed integer operand.
+  fn i32(&mut self,) -> Result<i32, Self::Error,>;
+
+  /// Reads a four byte float operand.
+  fn f32(&mut self,) -> Result<f32, Self::Error,>;
+
+  /// Reads a comparison flag operand.
+  fn flag(&mut self,) -> Result<CmpFlag, Self::Error,>;
+}
+
+/// Reads operands out of a byte slice, erroring instead of panicking when the
+/// slice runs out.
+struct ByteReader<'a,> {
+  bytes:&'a [u8],
+  pos:usize,
+  op:Option<OpCode,>,
+}
+
+impl ByteReader<'_,> {
+  fn take<const N: usize,>(&mut self,) -> Result<[u8; N], DecodeError,> {
+    match self.bytes.get(self.pos..self.pos + N,) {
+      Some(chunk,) => {
+        self.pos += N;
+        Ok(chunk.try_into().unwrap(),)
+      }
+      None => match self.op {
+        Some(op,) => Err(DecodeError::Truncated { op, offset:None, },),
+        None => Err(DecodeError::Empty { offset:None, },),
+      },
+    }
+  }
+}
+
+impl OperandReader for ByteReader<'_,> {
+  type Error = DecodeError;
+
+  fn reg(&mut self,) -> Result<Register, DecodeError,> {
+    Ok(Register::new(self.u8()?,),)
+  }
+
+  fn u8(&mut self,) -> Result<u8, DecodeError,> {
+    Ok(self.take::<1>()?[0],)
+  }
+
+  fn u32(&mut self,) -> Result<u32, DecodeError,> {
+    Ok(u32::from_le_bytes(self.take::<4>()?,),)
+  }
+
+  fn i32(&mut self,) -> Result<i32, DecodeError,> {
+    Ok(i32::from_le_bytes(self.take::<4>()?,),)
+  }
+
+  fn f32(&mut self,) -> Result<f32, DecodeError,> {
+    Ok(f32::from_le_bytes(self.take::<4>()?,),)
+  }
+
+  fn flag(&mut self,) -> Result<CmpFlag, DecodeError,> {
+    CmpFlag::try_from(self.u8()?,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Instruction, OperandReader};
+  use crate::{
+    asm,
+    errors::DecodeError,
+    opcodes::{CmpFlag, OpCode},
+    program::Program,
+    registers::{reg, Register},
+  };
+  use alloc::{
+    format,
+    string::{String, ToString},
+  };
+  use core::convert::Infallible;
+
+  /// Counts the immediate operands read from it.
+  #[derive(Default,)]
+  struct Immediates(usize,);
+
+  impl OperandReader for Immediates {
+    type Error = Infallible;
+
+    fn reg(&mut self,) -> Result<Register, Infallible,> {
+      Ok(reg(16,),)
+    }
+
+    fn u8(&mut self,) -> Result<u8, Infallible,> {
+      self.0 += 1;
+      Ok(0,)
+    }
+
+    fn u32(&mut self,) -> Result<u32, Infallible,> {
+      self.0 += 1;
+      Ok(0,)
+    }
+
+    fn i32(&mut self,) -> Result<i32, Infallible,> {
+      self.0 += 1;
+      Ok(0,)
+    }
+
+    fn f32(&mut self,) -> Result<f32, Infallible,> {
+      self.0 += 1;
+      Ok(0.0,)
+    }
+
+    fn flag(&mut self,) -> Result<CmpFlag, Infallible,> {
+      Ok(CmpFlag::Eq,)
+    }
+  }
+
+  #[test]
+  fn format_single_instructions() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Call { target:12, },);
+    program.emit(Instruction::Jz {
+      cond:Register::EQ,
+      target:0,
+    },);
+    program.emit(Instruction::Hlt,);
+    program.symbols_mut().insert("main", 0,).unwrap();
+
+    // The listing is each instruction's `Display` on its own line
+    let lines = program
+      .instructions()
+      .map(|item| format!("{}\n", item.unwrap().1),)
+      .collect::<String>();
+    assert_eq!(lines, program.to_string());
+
+    let symbols = Some(program.symbols(),);
+    let jz = Instruction::Jz {
+      cond:Register::EQ,
+      target:0,
+    };
+    assert_eq!(jz.to_string(), "Jz $EQ, 0");
+    assert_eq!(jz.to_asm_string(symbols,), "Jz $EQ, <main>");
+    assert_eq!(jz.to_asm_string(None,), "Jz $EQ, 0");
+    // Targets without a symbol and relative jumps are printed as numbers
+    assert_eq!(
+      Instruction::Call { target:12, }.to_asm_string(symbols,),
+      "Call 12"
+    );
+    assert_eq!(
+      Instruction::JmpRel { offset:-6, }.to_asm_string(symbols,),
+      "JmpRel -6"
+    );
+    assert_eq!(Instruction::Hlt.to_asm_string(symbols,), "Hlt");
+  }
+
+  #[test]
+  fn format_float_immediates() {
+    let floats = [
+      (1.0, "1.0",),
+      (-0.0, "-0.0",),
+      (0.1, "0.1",),
+      (f32::from_bits(0.3f32.to_bits() + 1,), "0.30000004",),
+      (1e20, "1.0e20",),
+      (-1e20, "-1.0e20",),
+      (1.5e-10, "1.5e-10",),
+      (f32::MAX, "3.4028235e38",),
+      (f32::MIN_POSITIVE / 2.0, "5.877472e-39",),
+      (f32::INFINITY, "inf",),
+      (f32::NEG_INFINITY, "-inf",),
+    ];
+    for (imm, text,) in floats {
+      let inst = Instruction::PushI { imm, };
+      assert_eq!(inst.to_string(), format!("PushI {}", text));
+
+      let program = asm::assemble(&inst.to_string(),).unwrap();
+      let (_, parsed,) = program.instructions().next().unwrap().unwrap();
+      let Instruction::PushI { imm: parsed, } = parsed
+      else {
+        panic!("{} assembled to {}", inst, parsed);
+      };
+      assert_eq!(parsed.to_bits(), imm.to_bits(), "{}", text);
+    }
+
+    let nan = Instruction::PushI { imm:f32::NAN, };
+    assert_eq!(nan.to_string(), "PushI NaN");
+    let program = asm::assemble(&nan.to_string(),).unwrap();
+    assert_eq!(program.reassemble_check(), Ok(()));
+  }
+
+  #[test]
+  fn opcode_predicates_match_operands() {
+    for op in OpCode::iter() {
+      let mut src = Immediates::default();
+      let Ok(inst,) = Instruction::read_operands(op, &mut src,);
+      assert_eq!(op.has_immediate(), src.0 > 0, "{}", op);
+      // Terminators transfer control somewhere other than the next instruction
+      let jumps = inst.resolve_target(0,).is_some()
+        || matches!(op, OpCode::Hlt | OpCode::Ret | OpCode::JmpR | OpCode::CallR);
+      assert_eq!(op.is_terminator(), jumps, "{}", op);
+    }
+  }
+
+  #[test]
+  fn encode_decode_round_trip() {
+    let insts = [
+      Instruction::Hlt,
+      Instruction::Load {
+        dst:reg(14,),
+        imm:1.5,
+      },
+      Instruction::MemCpy {
+        dst:reg(14,),
+        src:reg(15,),
+      },
+      Instruction::RvDivRI {
+        dst:reg(14,),
+        src:reg(15,),
+        imm:-2.0,
+      },
+      Instruction::PowRR {
+        dst:reg(14,),
+        a:reg(15,),
+        b:reg(16,),
+      },
+      Instruction::CmpRI {
+        flag:CmpFlag::Geq,
+        src:reg(14,),
+        imm:3.0,
+      },
+      Instruction::CmpRR {
+        flag:CmpFlag::Lt,
+        a:reg(14,),
+        b:reg(15,),
+      },
+      Instruction::Jnz {
+        cond:reg(2,),
+        target:70000,
+      },
+      Instruction::Call { target:9, },
+      Instruction::RMem {
+        dst:reg(14,),
+        src:reg(15,),
+        imm_offset:300,
+        reg_offset:reg(16,),
+      },
+      Instruction::WriteStr {
+        ptr:reg(15,),
+        len:reg(16,),
+      },
+      Instruction::Pop,
+    ];
+
+    let mut program = Program::new();
+    for inst in insts {
+      inst.encode(&mut program,);
+    }
+
+    let mut bytes = program.as_slice();
+    for inst in insts {
+      let (decoded, len,) = Instruction::decode(bytes,).unwrap();
+      assert_eq!(decoded, inst);
+      bytes = &bytes[len..];
+    }
+    assert!(bytes.is_empty());
+  }
+
+  #[cfg(feature = "arbitrary")]
+  #[test]
+  fn arbitrary_instructions_round_trip() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    // Deterministic noise for `Unstructured` to draw from
+    let mut state = 0x2545_f491_u32;
+    let noise = (0..4096)
+      .map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+      },)
+      .collect::<Vec<_,>>();
+
+    let mut u = Unstructured::new(&noise,);
+    let mut count = 0;
+    while !u.is_empty() {
+      let program = Program::arbitrary(&mut u,).unwrap();
+      let insts = program
+        .instructions()
+        .map(|item| item.unwrap().1,)
+        .collect::<Vec<_,>>();
+      count += insts.len();
+
+      // Immediates are finite so every instruction equals itself after
+      // decoding
+      let mut encoded = Program::new();
+      for inst in &insts {
+        inst.encode(&mut encoded,);
+      }
+      let decoded = encoded
+        .instructions()
+        .map(|item| item.unwrap().1,)
+        .collect::<Vec<_,>>();
+      assert_eq!(decoded, insts);
+      let _ = program.to_string();
+    }
+    assert!(count > 0);
+  }
+
+  #[test]
+  fn decode_reports_bytes_consumed() {
+    let (inst, len,) = Instruction::decode(&[OpCode::WMem.into(), 14, 15, 1, 0, 0, 0, 16, 0,],).unwrap();
+    assert_eq!(
+      inst,
+      Instruction::WMem {
+        dst:reg(14),
+        src:reg(15),
+        imm_offset:1,
+        reg_offset:reg(16),
+      }
+    );
+    assert_eq!(len, 8);
+  }
+
+  #[test]
+  fn decode_errors_on_truncated_operands() {
+    assert_eq!(
+      Instruction::decode(&[OpCode::Load.into(), 14, 0, 0,],),
+      Err(DecodeError::Truncated {
+        op:OpCode::Load,
+        offset:None,
+      },)
+    );
+    assert_eq!(
+      Instruction::decode(&[OpCode::AddRR.into(), 14, 15,],),
+      Err(DecodeError::Truncated {
+        op:OpCode::AddRR,
+        offset:None,
+      },)
+    );
+    assert_eq!(
+      Instruction::decode(&[],),
+      Err(DecodeError::Empty { offset:None, },)
+    );
+  }
+
+  #[test]
+  fn decode_errors_on_invalid_bytes() {
+    assert_eq!(
+      Instruction::decode(&[200,],),
+      Err(DecodeError::InvalidOpCode {
+        byte:200,
+        offset:None,
+      },)
+    );
+    assert_eq!(
+      Instruction::decode(&[OpCode::CmpRR.into(), 200, 14, 15,],),
+      Err(DecodeError::InvalidCmpFlag {
+        byte:200,
+        offset:None,
+      },)
+    );
+  }
+
+  #[test]
+  fn operands_are_little_endian() {
+    let bytes = [OpCode::Load.into(), 16, 0x00, 0x00, 0x80, 0x3F,];
+    let (inst, len,) = Instruction::decode(&bytes,).unwrap();
+    assert_eq!(len, 6);
+    assert_eq!(
+      inst,
+      Instruction::Load {
+        dst:reg(16,),
+        imm:1.0,
+      }
+    );
+    let bytes = [OpCode::JmpRel.into(), 0xFE, 0xFF, 0xFF, 0xFF,];
+    assert_eq!(
+      Instruction::decode(&bytes,).unwrap().0,
+      Instruction::JmpRel { offset:-2, }
+    );
+
+    let mut p = Program::new();
+    p.emit(Instruction::LoadU32 {
+      dst:reg(16,),
+      imm:0x12345678,
+    },);
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:1.0,
+    },);
+    #[rustfmt::skip]
+    assert_eq!(
+      p.code(),
+      [
+        OpCode::LoadU32.into(), 16, 0x78, 0x56, 0x34, 0x12,
+        OpCode::Load.into(), 16, 0x00, 0x00, 0x80, 0x3F,
+      ]
+    );
+  }
+}
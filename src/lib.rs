@@ -1,5 +1,35 @@
-#![feature(iter_next_chunk)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
+pub mod asm;
+pub mod builder;
+#[cfg(feature = "compress")]
+pub mod compress;
+pub mod debug;
+pub mod diff;
+pub mod disasm;
+pub mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+#[cfg(feature = "test-util")]
+pub mod generator;
+pub mod instruction;
 pub mod memory;
+pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 pub mod opcodes;
+pub mod optimize;
 pub mod program;
 pub mod registers;
+pub mod relocs;
+pub mod shared;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+pub mod symbols;
+pub mod syscalls;
+#[cfg(feature = "wasm")]
+pub mod wasm;
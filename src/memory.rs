@@ -1,6 +1,235 @@
+use crate::errors::{AddressError, LayoutError};
+use core::fmt::Display;
+
 /// Length of the [`VM`](https://github.com/Barca545/galaxy)'s memory array.
 pub const MEM_SIZE:usize = u16::MAX as usize;
 
 /// Length of the portion of the [`VM`](https://github.com/Barca545/galaxy)'s memory array used as the
 /// "stack". Valid addresses are mem0-mem19.
 pub const STACK_SIZE:usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// How a [`VM`](https://github.com/Barca545/galaxy)'s memory array is divided.
+///
+/// The stack occupies the start of memory and the heap runs from `heap_start`
+/// to the end. [`MemoryLayout::default`] matches [`MEM_SIZE`] and
+/// [`STACK_SIZE`].
+pub struct MemoryLayout {
+  mem_size:u32,
+  stack_size:u32,
+  heap_start:u32,
+}
+
+impl Default for MemoryLayout {
+  fn default() -> Self {
+    Self {
+      mem_size:MEM_SIZE as u32,
+      stack_size:STACK_SIZE as u32,
+      heap_start:STACK_SIZE as u32,
+    }
+  }
+}
+
+impl MemoryLayout {
+  /// Errors if the stack does not fit in memory or the heap does not start
+  /// between the end of the stack and the end of memory.
+  pub const fn new(mem_size:u32, stack_size:u32, heap_start:u32,) -> Result<Self, LayoutError,> {
+    if stack_size > mem_size {
+      return Err(LayoutError::StackTooLarge {
+        stack_size, mem_size,
+      },);
+    }
+    if heap_start < stack_size {
+      return Err(LayoutError::HeapInStack {
+        heap_start,
+        stack_size,
+      },);
+    }
+    if heap_start > mem_size {
+      return Err(LayoutError::HeapOutOfBounds {
+        heap_start, mem_size,
+      },);
+    }
+    Ok(Self {
+      mem_size,
+      stack_size,
+      heap_start,
+    },)
+  }
+
+  /// Creates a layout whose heap starts directly after the stack.
+  pub const fn with_stack(mem_size:u32, stack_size:u32,) -> Result<Self, LayoutError,> {
+    Self::new(mem_size, stack_size, stack_size,)
+  }
+
+  /// Length of the memory array.
+  pub const fn mem_size(&self,) -> u32 {
+    self.mem_size
+  }
+
+  /// Length of the stack.
+  pub const fn stack_size(&self,) -> u32 {
+    self.stack_size
+  }
+
+  /// First address of the heap.
+  pub const fn heap_start(&self,) -> u32 {
+    self.heap_start
+  }
+
+  /// Length of the heap.
+  pub const fn heap_len(&self,) -> u32 {
+    self.mem_size - self.heap_start
+  }
+
+  /// Returns whether `addr` is inside memory.
+  pub const fn contains(&self, addr:u32,) -> bool {
+    addr < self.mem_size
+  }
+
+  /// Returns whether `addr` is on the stack.
+  pub const fn is_stack_addr(&self, addr:u32,) -> bool {
+    addr < self.stack_size
+  }
+
+  /// Returns whether `addr` is on the heap.
+  pub const fn is_heap_addr(&self, addr:u32,) -> bool {
+    self.heap_start <= addr && addr < self.mem_size
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(transparent)
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[repr(transparent)]
+/// A location in the [`VM`](https://github.com/Barca545/galaxy)'s memory or
+/// a [`Program`](crate::program::Program). Printed in hex (`0x002A`).
+///
+/// [`Address::new`] and [`Address::try_from`] only create addresses inside a
+/// [`MemoryLayout`].
+pub struct Address(u32,);
+
+impl Address {
+  /// Errors if `addr` is past the end of `layout`'s memory.
+  pub const fn new(addr:u32, layout:&MemoryLayout,) -> Result<Address, AddressError,> {
+    match layout.contains(addr,) {
+      true => Ok(Address(addr,),),
+      false => Err(AddressError::OutOfBounds {
+        addr,
+        mem_size:layout.mem_size,
+      },),
+    }
+  }
+
+  /// Creates an address without checking it is inside memory. Used for
+  /// program offsets and for reporting out of bounds addresses.
+  pub(crate) const fn new_unchecked(addr:u32,) -> Address {
+    Address(addr,)
+  }
+
+  /// Returns the address `offset` cells after this one or `None` if it
+  /// overflows a `u32`.
+  pub const fn checked_add(self, offset:u32,) -> Option<Address,> {
+    match self.0.checked_add(offset,) {
+      Some(addr,) => Some(Address(addr,),),
+      None => None,
+    }
+  }
+
+  /// Returns the address as a `u32`.
+  pub const fn get(self,) -> u32 {
+    self.0
+  }
+}
+
+impl TryFrom<u32,> for Address {
+  type Error = AddressError;
+
+  /// Checks the address is inside the [`MemoryLayout::default`].
+  fn try_from(value:u32,) -> Result<Self, Self::Error,> {
+    Address::new(value, &MemoryLayout::default(),)
+  }
+}
+
+impl From<Address,> for u32 {
+  fn from(value:Address,) -> Self {
+    value.0
+  }
+}
+
+impl Display for Address {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "0x{:04X}", self.0)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Address, MemoryLayout, MEM_SIZE, STACK_SIZE};
+  use crate::errors::{AddressError, LayoutError};
+  use alloc::string::ToString;
+
+  #[test]
+  fn memory_layouts() {
+    let layout = MemoryLayout::default();
+    assert_eq!(layout.mem_size() as usize, MEM_SIZE);
+    assert_eq!(layout.stack_size() as usize, STACK_SIZE);
+    assert_eq!(layout.heap_len() as usize, MEM_SIZE - STACK_SIZE);
+    assert!(layout.is_stack_addr(19,));
+    assert!(!layout.is_stack_addr(20,));
+    assert!(layout.is_heap_addr(20,));
+    assert!(!layout.is_heap_addr(MEM_SIZE as u32,));
+
+    // A gap between the stack and heap belongs to neither
+    let layout = MemoryLayout::new(1024, 64, 128,).unwrap();
+    assert!(!layout.is_stack_addr(100,) && !layout.is_heap_addr(100,));
+    assert!(layout.contains(100,));
+    assert_eq!(layout.heap_len(), 896);
+
+    assert_eq!(
+      MemoryLayout::with_stack(16, 32,),
+      Err(LayoutError::StackTooLarge {
+        stack_size:32,
+        mem_size:16
+      })
+    );
+    assert_eq!(
+      MemoryLayout::new(1024, 64, 32,),
+      Err(LayoutError::HeapInStack {
+        heap_start:32,
+        stack_size:64
+      })
+    );
+    assert_eq!(
+      MemoryLayout::new(1024, 64, 2048,),
+      Err(LayoutError::HeapOutOfBounds {
+        heap_start:2048,
+        mem_size:1024
+      })
+    );
+  }
+
+  #[test]
+  fn addresses() {
+    let layout = MemoryLayout::with_stack(1024, 64,).unwrap();
+    assert_eq!(Address::new(1023, &layout,).map(Address::get,), Ok(1023));
+    assert_eq!(
+      Address::new(1024, &layout,),
+      Err(AddressError::OutOfBounds {
+        addr:1024,
+        mem_size:1024
+      })
+    );
+    assert!(Address::try_from(1024,).is_ok());
+    assert!(Address::try_from(MEM_SIZE as u32,).is_err());
+
+    let addr = Address::try_from(42,).unwrap();
+    assert_eq!(addr.checked_add(8,).map(u32::from,), Some(50));
+    assert_eq!(addr.checked_add(u32::MAX,), None);
+    assert_eq!(addr.to_string(), "0x002A");
+  }
+}
@@ -0,0 +1,243 @@
+use crate::errors::MetadataError;
+use alloc::{
+  collections::BTreeMap,
+  string::{String, ToString},
+  vec::Vec,
+};
+use core::fmt::Write;
+
+/// Longest key in bytes a [`ProgramMetadata`] accepts.
+pub const MAX_KEY_LEN:usize = u8::MAX as usize;
+
+/// Longest value in bytes a [`ProgramMetadata`] accepts.
+pub const MAX_VALUE_LEN:usize = u16::MAX as usize;
+
+/// Key of [`ProgramMetadata::name`].
+pub const NAME:&str = "name";
+/// Key of [`ProgramMetadata::source_path`].
+pub const SOURCE_PATH:&str = "source_path";
+/// Key of [`ProgramMetadata::source_hash`].
+pub const SOURCE_HASH:&str = "source_hash";
+/// Key of [`ProgramMetadata::compiler_version`].
+pub const COMPILER_VERSION:&str = "compiler_version";
+/// Key of [`ProgramMetadata::build_timestamp`].
+pub const BUILD_TIMESTAMP:&str = "build_timestamp";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+/// String keys and values describing where a
+/// [`Program`](crate::program::Program) came from, saved with it and ignored
+/// by everything else.
+///
+/// A few well-known keys have typed accessors, like
+/// [`ProgramMetadata::source_hash`]; any other key is free for tools to use.
+/// Keys are at most [`MAX_KEY_LEN`] bytes and values at most
+/// [`MAX_VALUE_LEN`].
+pub struct ProgramMetadata {
+  entries:BTreeMap<String, String,>,
+}
+
+impl ProgramMetadata {
+  pub fn new() -> Self {
+    ProgramMetadata::default()
+  }
+
+  /// Returns the value of `key`.
+  pub fn get(&self, key:&str,) -> Option<&str,> {
+    self.entries.get(key,).map(String::as_str,)
+  }
+
+  /// Sets `key` to `value`, returning its previous value.
+  ///
+  /// Errors if the key is empty or either is too long.
+  pub fn insert(&mut self, key:&str, value:&str,) -> Result<Option<String,>, MetadataError,> {
+    if key.is_empty() {
+      return Err(MetadataError::EmptyKey,);
+    }
+    if key.len() > MAX_KEY_LEN {
+      return Err(MetadataError::KeyTooLong { len:key.len(), },);
+    }
+    if value.len() > MAX_VALUE_LEN {
+      return Err(MetadataError::ValueTooLong {
+        key:key.to_string(),
+        len:value.len(),
+      },);
+    }
+    Ok(self.entries.insert(key.to_string(), value.to_string(),),)
+  }
+
+  /// Removes `key`, returning its value.
+  pub fn remove(&mut self, key:&str,) -> Option<String,> {
+    self.entries.remove(key,)
+  }
+
+  /// Iterates over the keys and values in order of their keys.
+  pub fn iter(&self,) -> impl Iterator<Item = (&str, &str,),> {
+    self
+      .entries
+      .iter()
+      .map(|(key, value,)| (key.as_str(), value.as_str(),),)
+  }
+
+  pub fn len(&self,) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self,) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Returns the program's name.
+  pub fn name(&self,) -> Option<&str,> {
+    self.get(NAME,)
+  }
+
+  pub fn set_name(&mut self, name:&str,) -> Result<(), MetadataError,> {
+    self.insert(NAME, name,).map(|_| (),)
+  }
+
+  /// Returns the path of the source the program was compiled from.
+  pub fn source_path(&self,) -> Option<&str,> {
+    self.get(SOURCE_PATH,)
+  }
+
+  pub fn set_source_path(&mut self, path:&str,) -> Result<(), MetadataError,> {
+    self.insert(SOURCE_PATH, path,).map(|_| (),)
+  }
+
+  /// Returns the hash of the source the program was compiled from, or `None`
+  /// if it is missing or not hex.
+  pub fn source_hash(&self,) -> Option<Vec<u8,>,> {
+    let hex = self.get(SOURCE_HASH,)?;
+    if hex.len() % 2 != 0 {
+      return None;
+    }
+    (0..hex.len())
+      .step_by(2,)
+      .map(|i| u8::from_str_radix(hex.get(i..i + 2,)?, 16,).ok(),)
+      .collect()
+  }
+
+  /// Sets the hash of the source, which is saved as lowercase hex.
+  pub fn set_source_hash(&mut self, hash:&[u8],) -> Result<(), MetadataError,> {
+    let mut hex = String::with_capacity(hash.len() * 2,);
+    for byte in hash {
+      // Writing to a `String` cannot fail
+      let _ = write!(hex, "{:02x}", byte);
+    }
+    self.insert(SOURCE_HASH, &hex,).map(|_| (),)
+  }
+
+  /// Returns the version of the compiler which built the program.
+  pub fn compiler_version(&self,) -> Option<&str,> {
+    self.get(COMPILER_VERSION,)
+  }
+
+  pub fn set_compiler_version(&mut self, version:&str,) -> Result<(), MetadataError,> {
+    self.insert(COMPILER_VERSION, version,).map(|_| (),)
+  }
+
+  /// Returns when the program was built in seconds since the Unix epoch, or
+  /// `None` if it is missing or not a number.
+  pub fn build_timestamp(&self,) -> Option<u64,> {
+    self.get(BUILD_TIMESTAMP,)?.parse().ok()
+  }
+
+  pub fn set_build_timestamp(&mut self, seconds:u64,) {
+    // A u64 is always short enough
+    self
+      .entries
+      .insert(BUILD_TIMESTAMP.to_string(), seconds.to_string(),);
+  }
+
+  /// Encodes the metadata as the contents of a metadata section, which is
+  /// empty if there is no metadata.
+  ///
+  /// Each entry is the length of its key as a u8, the key, the length of its
+  /// value as a little-endian u16, and the value.
+  pub(crate) fn to_bytes(&self,) -> Vec<u8,> {
+    let mut bytes = Vec::new();
+    for (key, value,) in &self.entries {
+      bytes.push(key.len() as u8,);
+      bytes.extend_from_slice(key.as_bytes(),);
+      bytes.extend_from_slice(&(value.len() as u16).to_le_bytes(),);
+      bytes.extend_from_slice(value.as_bytes(),);
+    }
+    bytes
+  }
+
+  /// Decodes metadata encoded by [`ProgramMetadata::to_bytes`]. Returns `None`
+  /// if the bytes are not valid metadata.
+  pub(crate) fn from_bytes(mut bytes:&[u8],) -> Option<Self,> {
+    let mut metadata = ProgramMetadata::new();
+    while !bytes.is_empty() {
+      let key_len = bytes[0] as usize;
+      let key = core::str::from_utf8(bytes.get(1..1 + key_len,)?,).ok()?;
+      bytes = &bytes[1 + key_len..];
+      let value_len = u16::from_le_bytes(bytes.get(..2,)?.try_into().unwrap(),) as usize;
+      let value = core::str::from_utf8(bytes.get(2..2 + value_len,)?,).ok()?;
+      bytes = &bytes[2 + value_len..];
+      if metadata.insert(key, value,).ok()?.is_some() {
+        return None;
+      }
+    }
+    Some(metadata,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{ProgramMetadata, MAX_KEY_LEN, MAX_VALUE_LEN};
+  use crate::{errors::MetadataError, program::Program};
+  use alloc::{string::ToString, vec};
+
+  #[test]
+  fn metadata_round_trip() {
+    let mut metadata = ProgramMetadata::new();
+    metadata.set_name("level_1",).unwrap();
+    metadata.set_source_path("scripts/level_1.spdr.s",).unwrap();
+    metadata.set_source_hash(&[0xDE, 0xAD, 0x00, 0x0F,],).unwrap();
+    metadata.set_compiler_version("spdrc 0.3.1",).unwrap();
+    metadata.set_build_timestamp(1_700_000_000,);
+    assert_eq!(metadata.insert("asset.bundle", "world",), Ok(None));
+    assert_eq!(metadata.get("source_hash"), Some("dead000f"));
+    assert_eq!(metadata.source_hash(), Some(vec![0xDE, 0xAD, 0x00, 0x0F]));
+    assert_eq!(metadata.build_timestamp(), Some(1_700_000_000));
+    assert_eq!(metadata.len(), 6);
+
+    let bytes = metadata.to_bytes();
+    assert_eq!(ProgramMetadata::from_bytes(&bytes), Some(metadata.clone()));
+    assert_eq!(ProgramMetadata::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert!(ProgramMetadata::new().to_bytes().is_empty());
+
+    // Saved with the program but ignored by equality
+    let mut program = Program::from([0,],);
+    program.set_metadata(metadata.clone(),);
+    assert_eq!(program, Program::from([0,]));
+    let loaded = Program::from_file_bytes(&program.to_file_bytes(),).unwrap();
+    assert_eq!(loaded.metadata(), &metadata);
+    assert_eq!(loaded.metadata().name(), Some("level_1"));
+
+    metadata.insert("source_hash", "not hex",).unwrap();
+    assert_eq!(metadata.source_hash(), None);
+  }
+
+  #[test]
+  fn metadata_limits() {
+    let mut metadata = ProgramMetadata::new();
+    let long = "k".repeat(MAX_KEY_LEN + 1,);
+    assert_eq!(
+      metadata.insert(&long, "v",),
+      Err(MetadataError::KeyTooLong { len:MAX_KEY_LEN + 1 })
+    );
+    assert_eq!(metadata.insert("", "v",), Err(MetadataError::EmptyKey));
+    assert!(metadata.insert(&long[1..], &"v".repeat(MAX_VALUE_LEN,),).is_ok());
+    assert_eq!(
+      metadata.set_name(&"v".repeat(MAX_VALUE_LEN + 1,),),
+      Err(MetadataError::ValueTooLong {
+        key:"name".to_string(),
+        len:MAX_VALUE_LEN + 1
+      })
+    );
+    assert_eq!(ProgramMetadata::from_bytes(&metadata.to_bytes()), Some(metadata));
+  }
+}
@@ -0,0 +1,306 @@
+use crate::{
+  debug::DebugInfo,
+  errors::ProgramError,
+  format,
+  memory::{Address, MemoryLayout},
+  metadata::ProgramMetadata,
+  program::{Instructions, Listing, Program},
+  relocs::{self, Relocation},
+  symbols::SymbolTable,
+};
+use alloc::vec::Vec;
+use core::{
+  fmt::Display,
+  ops::{Deref, Range},
+};
+use std::{fs::File, path::Path};
+
+/// The bytes of a saved program, mapped into memory where the platform
+/// supports it.
+enum Bytes {
+  #[cfg(any(unix, windows))]
+  Mapped(memmap2::Mmap,),
+  Read(Vec<u8,>,),
+}
+
+impl Deref for Bytes {
+  type Target = [u8];
+
+  fn deref(&self,) -> &[u8] {
+    match self {
+      #[cfg(any(unix, windows))]
+      Bytes::Mapped(map,) => map,
+      Bytes::Read(bytes,) => bytes,
+    }
+  }
+}
+
+/// A saved [`Program`] whose code is read straight from the file it was
+/// loaded from rather than copied. Created by [`Program::load_mmap`].
+///
+/// Files with compressed code are decompressed into memory instead.
+///
+/// Derefs to the program's code, so it can be indexed and sliced like
+/// [`Program::as_slice`]. Use [`MappedProgram::to_program`] for an owned
+/// [`Program`] which can be changed.
+///
+/// The file is mapped rather than read on platforms which support it.
+/// Changing or truncating the file while it is mapped, from this process or
+/// any other, changes the bytes under the `MappedProgram` and may crash the
+/// process when they are read, which is why mapping one is `unsafe`.
+pub struct MappedProgram {
+  bytes:Bytes,
+  code:Range<usize,>,
+  data:Range<usize,>,
+  data_base:Address,
+  entry_point:u32,
+  symbols:SymbolTable,
+  relocations:Vec<Relocation,>,
+  metadata:ProgramMetadata,
+  debug_info:DebugInfo,
+}
+
+impl MappedProgram {
+  /// Maps the saved program at `source`, checking its header and checksum and
+  /// decoding every section.
+  ///
+  /// # Safety
+  ///
+  /// Nothing, in this process or any other, may change or truncate the file
+  /// at `source` until the returned `MappedProgram` is dropped.
+  pub(crate) unsafe fn open(source:&Path,) -> Result<Self, ProgramError,> {
+    let file = File::open(source,).map_err(|err| ProgramError::from(err,).with_path(source,),)?;
+    // SAFETY: The caller keeps the file unchanged while it is mapped
+    let mut bytes =
+      unsafe { MappedProgram::map(file,) }.map_err(|err| ProgramError::from(err,).with_path(source,),)?;
+    if format::decode(&bytes, true,)?.0.compressed {
+      // Compressed code cannot be read in place
+      let program = Program::from_sections(format::decode(&bytes, false,)?.0,)?;
+      bytes = Bytes::Read(program.to_file_bytes(),);
+    }
+
+    let (sections, _,) = format::decode(&bytes, false,)?;
+    let symbols = SymbolTable::from_bytes(sections.symbols,)
+      .ok_or(ProgramError::MalformedSection(format::SectionKind::Symbols,),)?;
+    let relocations = relocs::from_bytes(sections.relocations,)
+      .ok_or(ProgramError::MalformedSection(format::SectionKind::Relocations,),)?;
+    let metadata = ProgramMetadata::from_bytes(sections.metadata,)
+      .ok_or(ProgramError::MalformedSection(format::SectionKind::Metadata,),)?;
+    let debug_info = DebugInfo::from_bytes(sections.debug,)
+      .ok_or(ProgramError::MalformedSection(format::SectionKind::Debug,),)?;
+    // Sections borrow from `bytes`, so remember where they are instead
+    let range = |section:&[u8]| {
+      let start = section.as_ptr() as usize - bytes.as_ptr() as usize;
+      start..start + section.len()
+    };
+    Ok(MappedProgram {
+      code:range(sections.code,),
+      data:range(sections.data,),
+      data_base:Address::new_unchecked(
+        sections
+          .data_base
+          .unwrap_or(MemoryLayout::default().heap_start(),),
+      ),
+      entry_point:sections.entry_point,
+      symbols,
+      relocations,
+      metadata,
+      debug_info,
+      bytes,
+    },)
+  }
+
+  /// Maps `file`, or reads it where the platform cannot map files.
+  ///
+  /// # Safety
+  ///
+  /// The file must not change while the returned bytes are alive.
+  #[cfg(any(unix, windows))]
+  unsafe fn map(file:File,) -> std::io::Result<Bytes,> {
+    // SAFETY: The caller keeps the file unchanged while it is mapped
+    let map = unsafe { memmap2::Mmap::map(&file,)? };
+    Ok(Bytes::Mapped(map,),)
+  }
+
+  #[cfg(not(any(unix, windows)))]
+  unsafe fn map(mut file:File,) -> std::io::Result<Bytes,> {
+    use std::io::Read;
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes,)?;
+    Ok(Bytes::Read(bytes,),)
+  }
+
+  /// Returns the program's code.
+  pub fn code(&self,) -> &[u8] {
+    &self.bytes[self.code.clone()]
+  }
+
+  /// Returns the offset execution starts at.
+  pub fn entry_point(&self,) -> u32 {
+    self.entry_point
+  }
+
+  /// Returns the program's symbols.
+  pub fn symbols(&self,) -> &SymbolTable {
+    &self.symbols
+  }
+
+  /// Returns the program's [`Relocation`]s in order of their offsets.
+  pub fn relocations(&self,) -> &[Relocation] {
+    &self.relocations
+  }
+
+  /// Returns the program's metadata.
+  pub fn metadata(&self,) -> &ProgramMetadata {
+    &self.metadata
+  }
+
+  /// Returns the source lines the program's code was compiled from.
+  pub fn debug_info(&self,) -> &DebugInfo {
+    &self.debug_info
+  }
+
+  /// Returns the address the data segment is copied to and its bytes, or
+  /// `None` if the program has no data. See [`Program::data_segment`].
+  pub fn data_segment(&self,) -> Option<(Address, &[u8],),> {
+    let data = &self.bytes[self.data.clone()];
+    (!data.is_empty()).then_some((self.data_base, data,),)
+  }
+
+  /// Iterates over the program's instructions. See [`Program::instructions`].
+  pub fn instructions(&self,) -> Instructions<'_,> {
+    Instructions::new(self.code(),)
+  }
+
+  /// Copies the program into an owned [`Program`], decoding its sections
+  /// again.
+  pub fn to_program(&self,) -> Result<Program, ProgramError,> {
+    Program::from_sections(format::decode(&self.bytes, false,)?.0,)
+  }
+}
+
+impl Deref for MappedProgram {
+  type Target = [u8];
+
+  fn deref(&self,) -> &[u8] {
+    self.code()
+  }
+}
+
+impl Display for MappedProgram {
+  /// Prints the same listing as [`Program`]'s `Display` implementation,
+  /// including the alternate form.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    Display::fmt(
+      &Listing::new(self.code(), &self.symbols, self.data_segment(),).with_debug_info(&self.debug_info,),
+      f,
+    )
+  }
+}
+
+impl TryFrom<&MappedProgram,> for Program {
+  type Error = ProgramError;
+
+  fn try_from(value:&MappedProgram,) -> Result<Self, Self::Error,> {
+    value.to_program()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{
+    errors::ProgramError,
+    format::{self, SectionKind, Sections},
+    instruction::Instruction,
+    opcodes::CURRENT_ISA_VERSION,
+    program::Program,
+    registers::reg,
+    relocs::RelocKind,
+  };
+  use eyre::Result;
+
+  #[test]
+  fn load_mapped_program() -> Result<(),> {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Call { target:0, },);
+    program.add_relocation(7, RelocKind::Code,);
+    program.emit(Instruction::Hlt,);
+    program.add_data(b"spdr",);
+    program.set_entry_point(6,);
+    program.metadata_mut().set_name("mapped",)?;
+    program.debug_info_mut().record(6, "mapped.script", 2, 1,);
+
+    let path = std::env::temp_dir().join("spdr_isa_load_mapped_program.spdr",);
+    program.save(&path,)?;
+    // SAFETY: Nothing else writes to the file, and it is only changed below
+    // once `mapped` is dropped
+    let mapped = unsafe { Program::load_mmap(&path,)? };
+
+    assert_eq!(&*mapped, program.as_slice());
+    assert_eq!(mapped[6], program[6]);
+    assert_eq!(mapped.entry_point(), 6);
+    assert_eq!(mapped.metadata(), program.metadata());
+    assert_eq!(mapped.debug_info(), program.debug_info());
+    assert_eq!(mapped.data_segment(), program.data_segment());
+    assert_eq!(
+      mapped.instructions().collect::<Vec<_,>>(),
+      program.instructions().collect::<Vec<_,>>()
+    );
+    assert_eq!(mapped.to_string(), program.to_string());
+    assert_eq!(format!("{:#}", mapped), format!("{:#}", program));
+    assert_eq!(mapped.relocations(), program.relocations());
+    assert_eq!(mapped.to_program()?.to_file_bytes(), program.to_file_bytes());
+    assert_eq!(Program::try_from(&mapped,)?, program);
+    // Unmap the file before changing it
+    drop(mapped,);
+
+    #[cfg(feature = "compress")]
+    {
+      use crate::{compress::Level, program::SaveOptions};
+
+      let options = SaveOptions {
+        compression:Some(Level::DEFAULT,),
+      };
+      program.save_with_options(&path, options,)?;
+      // SAFETY: As above
+      let mapped = unsafe { Program::load_mmap(&path,)? };
+      assert_eq!(&*mapped, program.as_slice());
+      assert_eq!(mapped.data_segment(), program.data_segment());
+      assert_eq!(mapped.to_program()?, program);
+    }
+
+    // Every section is checked when the file is mapped
+    let bad_relocations = format::encode(Sections {
+      isa_version:CURRENT_ISA_VERSION,
+      compressed:false,
+      entry_point:0,
+      code:&[0,],
+      data_base:None,
+      data:&[],
+      symbols:&[],
+      relocations:&[0, 0, 0, 0, 9,],
+      metadata:&[],
+      debug:&[],
+    },);
+    std::fs::write(&path, bad_relocations,)?;
+    // SAFETY: Files which fail to load are unmapped before returning
+    assert!(matches!(
+      unsafe { Program::load_mmap(&path,) },
+      Err(ProgramError::MalformedSection(SectionKind::Relocations))
+    ));
+
+    std::fs::write(&path, b"not a program",)?;
+    assert!(matches!(
+      unsafe { Program::load_mmap(&path,) },
+      Err(ProgramError::NotASpdrFile)
+    ));
+    std::fs::remove_file(&path,)?;
+    assert!(matches!(
+      unsafe { Program::load_mmap(&path,) },
+      Err(ProgramError::Io { path:Some(_,), .. })
+    ));
+    Ok((),)
+  }
+}
@@ -1,15 +1,22 @@
+use crate::errors::{DecodeError, ParseMnemonicError};
+use alloc::{string::ToString, vec, vec::Vec};
+use core::{fmt::Display, str::FromStr};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
-use std::fmt::Display;
 
-// Refactor:
-// - Should MemCpy also take offsets?
-
-#[derive(FromPrimitive, ToPrimitive, Debug,)]
-/// Unless otherwise stated all immediate arguments are 4 bytes.
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq, Eq,)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[repr(u8)]
+/// Unless otherwise stated all immediate arguments are 4 bytes, encoded
+/// little-endian.
+///
+/// The discriminant of each variant is the byte it is encoded as. Saved
+/// programs depend on these values so existing variants must never be
+/// renumbered and new opcodes must only be appended.
 pub enum OpCode {
   /// # Halt program execution
-  Hlt,
+  Hlt = 0,
   /// # Load Data
   ///
   /// Load the immediate `I0` into register `R0`.
@@ -19,7 +26,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Destination register.
   /// - `I0`: Source immediate.
-  Load,
+  Load = 1,
   /// # Copy Memory
   ///
   /// Copy the value in `R0` into `Rd`.
@@ -29,7 +36,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Destination register.
   /// - `R0`: Source register.
-  Copy,
+  Copy = 2,
   /// # Memory Copy
   ///
   /// Writes the value stored in the memory address stored in `Rd` into the
@@ -40,7 +47,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Destination memory address.
   /// - `R0`: Source memory address.
-  MemCpy,
+  MemCpy = 3,
   /// # Add Register and Immediate
   ///
   /// Format: `ADD Rd R0 I0`
@@ -49,7 +56,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  AddRI,
+  AddRI = 4,
   /// # Subtract Immediate from Register  
   ///
   /// Format: `SUB Rd R0 I0`
@@ -58,7 +65,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  SubRI,
+  SubRI = 5,
   /// # Subtract Register from Immediate
   ///
   /// Format: `RVSUB Rd R0 I0`
@@ -67,7 +74,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  RvSubRI,
+  RvSubRI = 6,
   /// # Multiply Register and Immediate
   ///
   /// Format: `MUL Rd R0 I0`
@@ -76,7 +83,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  MulRI,
+  MulRI = 7,
   /// # Divide Register by Immediate
   ///
   /// Format: `DIV Rd R0 I0`
@@ -85,7 +92,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  DivRI,
+  DivRI = 8,
   /// # Divide Immediate by Register
   ///
   /// Format: `RVDIV Rd R0 I0`
@@ -94,7 +101,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  RvDivRI,
+  RvDivRI = 9,
   /// # Raise Register by Immediate
   ///
   /// Format: `POW Rd R0 I0`
@@ -103,7 +110,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  PowRI,
+  PowRI = 10,
   /// # Raise Immediate by Register
   ///
   /// Format: `RVPOW Rd R0 I0`
@@ -112,7 +119,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  RvPowRI,
+  RvPowRI = 11,
   /// # Add Register and Register
   ///
   /// Format: `ADD Rd R0 R1`
@@ -121,7 +128,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `R1`: Register operand.
-  AddRR,
+  AddRR = 12,
   /// # Subtract Register and Register
   ///
   /// Format: `SUB Rd R0 R2`
@@ -130,7 +137,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `R1`: Immediate operand.
-  SubRR,
+  SubRR = 13,
   /// # Multiply Register and Register
   ///
   /// Format: `MUL Rd R0 R1`
@@ -139,7 +146,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `R1: Register operand.
-  MulRR,
+  MulRR = 14,
   /// # Divide Register and Register
   ///
   /// Format: `DIV Rd R0 R1`
@@ -148,7 +155,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Register operand.
   /// - `R1`: Register operand.
-  DivRR,
+  DivRR = 15,
   /// # Raise Register by Register
   ///
   /// Format: `POW Rd R0 R1`
@@ -157,7 +164,7 @@ pub enum OpCode {
   /// - `Rd`: Destination.
   /// - `R0`: Memory operand.
   /// - `R1`: Register operand.
-  PowRR,
+  PowRR = 16,
   /// # Compare Register and Immediate
   /// Checks whether two values are equal and stores the result in
   /// [`REQ`](crate::registers::EQ).
@@ -168,7 +175,7 @@ pub enum OpCode {
   /// - `Fl`: Flag indicating which comparison operation to perform.
   /// - `R0`: Register operand.
   /// - `I0`: Immediate operand.
-  CmpRI,
+  CmpRI = 17,
   /// # Compare Register and Register
   /// Checks whether two values are equal and stores the result in
   /// [`REQ`](crate::registers::EQ).
@@ -179,7 +186,7 @@ pub enum OpCode {
   /// - `Fl`: Flag indicating which comparison operation to perform.
   /// - `R0`: Register operand.
   /// - `R1`: Register operand.
-  CmpRR,
+  CmpRR = 18,
   /// # Bitwise Not
   ///
   /// Format:`NOT Rd R0`
@@ -187,14 +194,14 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Destination.
   /// - `R0`: value being negated.
-  Not,
+  Not = 19,
   /// # Unconditional Jump
   ///
   /// Format: JMP Idx
   ///
   /// Arguments:
   /// - `Idx`: Destination program index as a `u32`
-  Jmp,
+  Jmp = 20,
   /// # Jump if Zero
   ///
   /// Format: `JZ R0 IDX`
@@ -202,7 +209,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `R0`: Register holding the check.
   /// - `Idx`: Destination program index as a `u32`.
-  Jz,
+  Jz = 21,
   /// # Jump if Not Zero
   ///
   /// Format: `JNZ R0 IDX`
@@ -210,14 +217,14 @@ pub enum OpCode {
   /// Arguments:
   /// - `R0`: Register holding the check.
   /// - `Idx`: Destination program index as a `u32`.
-  Jnz,
+  Jnz = 22,
   /// # Call a Function
   ///
   /// Format: `CALL IDX`
   ///
   /// Arguments:
-  /// - `Idx`: Location of the function pointer as a `u8`.
-  Call,
+  /// - `Idx`: Location of the function pointer as a `u32`.
+  Call = 23,
   /// # System call
   ///
   /// Call an external function.
@@ -226,7 +233,7 @@ pub enum OpCode {
   ///
   /// Arguments:
   /// - `Idx`: Index of the external function being called as a `u8`.
-  SysCall,
+  SysCall = 24,
   /// # Return from a function call
   ///
   /// Pop the return address of the top of the stack and set the PC equal to it.
@@ -238,7 +245,7 @@ pub enum OpCode {
   /// - `Idx`: The number of function arguments to clean up as a u8.
   // Ret takes a u8 because the stack is only 20 cells long so it will never fill up even a u8 let alone a
   // u32.
-  Ret,
+  Ret = 25,
   /// # Allocate Memory
   ///
   /// Allocates a slab of memory capable of holding `R0` values. Returns a
@@ -249,7 +256,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Register storing the destination.
   /// - `R0`: Register storing the number of values to store.
-  Alloc,
+  Alloc = 26,
   /// # Reallocate Memory
   ///
   /// Reallocates a slab of memory.
@@ -259,7 +266,7 @@ pub enum OpCode {
   /// Arguments:
   /// - `Rd`: Register storing the previous allocation.
   /// - `R0`: Register storing the number of values to store.
-  Realloc,
+  Realloc = 27,
   /// # Deallocate Memory
   ///
   /// Deallocates a slab of memory.
@@ -268,7 +275,7 @@ pub enum OpCode {
   ///
   /// Arguments:
   /// - `R0`: Register storing the slab to deallocate.
-  Dealloc,
+  Dealloc = 28,
   /// # Read Memory
   ///
   /// Loads the value stored at the pointer in `R0 + I0 + R1` into `Rd`.
@@ -283,7 +290,7 @@ pub enum OpCode {
   ///
   /// Note: If there is no register offset, R2 will be zero and ignored. Zero
   /// (REQ) is used because it will never store an offset.
-  RMem,
+  RMem = 29,
   /// # Write Memory
   ///
   /// Writes the value stored in `R0 + I0 + R1` into the memory address stored
@@ -299,18 +306,18 @@ pub enum OpCode {
   ///
   /// Note: If there is no register offset, R1 will be zero and ignored. R1 == 0
   /// (the PC register) is used because it will never store an offset.
-  WMem,
-  /// # Read String
+  WMem = 30,
+  /// # Write String
   ///
   /// Given a pointer and a len prints a string into the VM's `stdout` (usually
   /// the terminal).
   ///
-  /// Format: `READSTR R0 R1`
+  /// Format: `WRITESTR R0 R1`
   ///
   /// Arguments:
   /// - `R0`: Register storing the pointer to the string's start.
   /// - `R1`: Register storing the string's length.
-  WriteStr,
+  WriteStr = 31,
   /// # Push to Stack
   ///
   /// Pushes the argument onto the top of stack.
@@ -319,13 +326,16 @@ pub enum OpCode {
   ///
   /// Arguments:
   /// - `R0`: Register holding the value to push.
-  Push,
+  ///
+  /// Note: Pushing onto a stack which already holds
+  /// [`STACK_SIZE`](crate::memory::STACK_SIZE) items overflows it.
+  Push = 32,
   /// # Pop From Stack
   ///
   /// Format: Takes no args
   ///
   /// Removes the item on the top of the stack.
-  Pop,
+  Pop = 33,
   /// # Pop Read From Stack
   ///
   /// Removes the item on the top of the stack and places it into a register.
@@ -334,9 +344,1088 @@ pub enum OpCode {
   ///
   /// Arguments:
   /// `R0`: The register to place the popped value.
-  PopR,
+  PopR = 34,
   /// # No Operation
-  Noop,
+  Noop = 35,
+  /// # Remainder of Register and Immediate
+  ///
+  /// Computes `R0 % I0` using floating point remainder (Rust's `f32::rem`).
+  ///
+  /// Format: `MOD Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand.
+  ModRI = 36,
+  /// # Remainder of Immediate and Register
+  ///
+  /// Computes `I0 % R0` using floating point remainder (Rust's `f32::rem`).
+  ///
+  /// Format: `RVMOD Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand.
+  RvModRI = 37,
+  /// # Remainder of Register and Register
+  ///
+  /// Computes `R0 % R1` using floating point remainder (Rust's `f32::rem`).
+  ///
+  /// Format: `MOD Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  ModRR = 38,
+  /// # Negate
+  ///
+  /// Stores `-R0` in `Rd`.
+  ///
+  /// Format: `NEG Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Neg = 39,
+  /// # Absolute Value
+  ///
+  /// Stores the absolute value of `R0` in `Rd`.
+  ///
+  /// Format: `ABS Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Abs = 40,
+  /// # Square Root
+  ///
+  /// Stores the square root of `R0` in `Rd`. The square root of a negative
+  /// number (other than `-0.0`) is NaN, matching Rust's `f32::sqrt`.
+  ///
+  /// Format: `SQRT Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Sqrt = 41,
+  /// # Round Down
+  ///
+  /// Stores the largest integer less than or equal to `R0` in `Rd`.
+  ///
+  /// Format: `FLOOR Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Floor = 42,
+  /// # Round Up
+  ///
+  /// Stores the smallest integer greater than or equal to `R0` in `Rd`.
+  ///
+  /// Format: `CEIL Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Ceil = 43,
+  /// # Round to Nearest
+  ///
+  /// Stores the integer nearest to `R0` in `Rd`. Halfway cases are rounded away
+  /// from zero (`2.5` becomes `3.0` and `-2.5` becomes `-3.0`), matching Rust's
+  /// `f32::round`.
+  ///
+  /// Format: `ROUND Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Round = 44,
+  /// # Round Toward Zero
+  ///
+  /// Stores the integer part of `R0` in `Rd`, discarding any fractional part.
+  ///
+  /// Format: `TRUNC Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Trunc = 45,
+  /// # Bitwise And of Register and Register
+  ///
+  /// Computes `R0 & R1`. Operates on each operand converted to a `u32` as if by
+  /// Rust's `as` (truncated toward zero, saturated at the bounds of a `u32`,
+  /// and NaN becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `AND Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  AndRR = 46,
+  /// # Bitwise Or of Register and Register
+  ///
+  /// Computes `R0 | R1`. Operates on each operand converted to a `u32` as if by
+  /// Rust's `as` (truncated toward zero, saturated at the bounds of a `u32`,
+  /// and NaN becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `OR Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  OrRR = 47,
+  /// # Bitwise Exclusive Or of Register and Register
+  ///
+  /// Computes `R0 ^ R1`. Operates on each operand converted to a `u32` as if by
+  /// Rust's `as` (truncated toward zero, saturated at the bounds of a `u32`,
+  /// and NaN becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `XOR Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  XorRR = 48,
+  /// # Bitwise And of Register and Immediate
+  ///
+  /// Computes `R0 & I0`. Operates on `R0` converted to a `u32` as if by Rust's
+  /// `as` (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `AND Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand as a 4 byte little-endian `u32`.
+  AndRI = 49,
+  /// # Bitwise Or of Register and Immediate
+  ///
+  /// Computes `R0 | I0`. Operates on `R0` converted to a `u32` as if by Rust's
+  /// `as` (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `OR Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand as a 4 byte little-endian `u32`.
+  OrRI = 50,
+  /// # Bitwise Exclusive Or of Register and Immediate
+  ///
+  /// Computes `R0 ^ I0`. Operates on `R0` converted to a `u32` as if by Rust's
+  /// `as` (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`). The result is converted back to an `f32`.
+  ///
+  /// Format: `XOR Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand as a 4 byte little-endian `u32`.
+  XorRI = 51,
+  /// # Shift Register Left by Immediate
+  ///
+  /// Computes `R0 << I0`. `R0` is converted to a `u32` as if by Rust's `as`
+  /// (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`) and the result is converted back to an `f32`. Shifting by
+  /// 32 or more bits produces `0`.
+  ///
+  /// Format: `SHL Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Number of bits to shift by as a 4 byte little-endian `u32`.
+  ShlRI = 52,
+  /// # Shift Register Right by Immediate
+  ///
+  /// Computes `R0 >> I0`. `R0` is converted to a `u32` as if by Rust's `as`
+  /// (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`) and the result is converted back to an `f32`. Shifting by
+  /// 32 or more bits produces `0`.
+  ///
+  /// Format: `SHR Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Number of bits to shift by as a 4 byte little-endian `u32`.
+  ShrRI = 53,
+  /// # Shift Register Left by Register
+  ///
+  /// Computes `R0 << R1`. `R0` is converted to a `u32` as if by Rust's `as`
+  /// (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`) and the result is converted back to an `f32`. Shifting by
+  /// 32 or more bits produces `0`. The shift amount in `R1` is converted the
+  /// same way.
+  ///
+  /// Format: `SHL Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register holding the number of bits to shift by.
+  ShlRR = 54,
+  /// # Shift Register Right by Register
+  ///
+  /// Computes `R0 >> R1`. `R0` is converted to a `u32` as if by Rust's `as`
+  /// (truncated toward zero, saturated at the bounds of a `u32`, and NaN
+  /// becoming `0`) and the result is converted back to an `f32`. Shifting by
+  /// 32 or more bits produces `0`. The shift amount in `R1` is converted the
+  /// same way.
+  ///
+  /// Format: `SHR Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register holding the number of bits to shift by.
+  ShrRR = 55,
+  /// # Minimum of Register and Register
+  ///
+  /// Stores the smaller of `R0` and `R1` in `Rd`. If exactly one operand is NaN
+  /// the other operand is stored, matching Rust's `f32::min` and IEEE 754
+  /// `minNum`. If both are NaN the result is NaN.
+  ///
+  /// Format: `MIN Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  MinRR = 56,
+  /// # Maximum of Register and Register
+  ///
+  /// Stores the larger of `R0` and `R1` in `Rd`. If exactly one operand is NaN
+  /// the other operand is stored, matching Rust's `f32::max` and IEEE 754
+  /// `maxNum`. If both are NaN the result is NaN.
+  ///
+  /// Format: `MAX Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  MaxRR = 57,
+  /// # Minimum of Register and Immediate
+  ///
+  /// Stores the smaller of `R0` and `I0` in `Rd`. If exactly one operand is NaN
+  /// the other operand is stored, matching Rust's `f32::min` and IEEE 754
+  /// `minNum`. If both are NaN the result is NaN.
+  ///
+  /// Format: `MIN Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand.
+  MinRI = 58,
+  /// # Maximum of Register and Immediate
+  ///
+  /// Stores the larger of `R0` and `I0` in `Rd`. If exactly one operand is NaN
+  /// the other operand is stored, matching Rust's `f32::max` and IEEE 754
+  /// `maxNum`. If both are NaN the result is NaN.
+  ///
+  /// Format: `MAX Rd R0 I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand.
+  MaxRI = 59,
+  /// # Sine
+  ///
+  /// Stores the sine of `R0` in `Rd`. Angles are in radians.
+  ///
+  /// Format: `SIN Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Angle in radians.
+  Sin = 60,
+  /// # Cosine
+  ///
+  /// Stores the cosine of `R0` in `Rd`. Angles are in radians.
+  ///
+  /// Format: `COS Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Angle in radians.
+  Cos = 61,
+  /// # Tangent
+  ///
+  /// Stores the tangent of `R0` in `Rd`. Angles are in radians.
+  ///
+  /// Format: `TAN Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Angle in radians.
+  Tan = 62,
+  /// # Two Argument Arctangent
+  ///
+  /// Stores the angle in radians between the positive x axis and the point
+  /// (`R1`, `R0`) in `Rd`, matching Rust's `R0.atan2(R1)`. The result is in the
+  /// range `[-pi, pi]`.
+  ///
+  /// Format: `ATAN2 Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: The y coordinate.
+  /// - `R1`: The x coordinate.
+  Atan2 = 63,
+  /// # Natural Logarithm
+  ///
+  /// Stores the natural logarithm of `R0` in `Rd`. Like Rust's `f32::ln`, the
+  /// logarithm of `0.0` is negative infinity and the logarithm of a negative
+  /// number is NaN.
+  ///
+  /// Format: `LN Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Ln = 64,
+  /// # Base 2 Logarithm
+  ///
+  /// Stores the base 2 logarithm of `R0` in `Rd`. Like Rust's `f32::log2`, the
+  /// logarithm of `0.0` is negative infinity and the logarithm of a negative
+  /// number is NaN.
+  ///
+  /// Format: `LOG2 Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Log2 = 65,
+  /// # Exponential
+  ///
+  /// Stores `e^R0` in `Rd`, matching Rust's `f32::exp`.
+  ///
+  /// Format: `EXP Rd R0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination.
+  /// - `R0`: Register operand.
+  Exp = 66,
+  /// # Compare Registers and Jump
+  ///
+  /// Compares `R0` and `R1` like `Cmp_RR` and jumps to `Idx` if the comparison
+  /// is true. Unlike `Cmp_RR` it does not write to
+  /// [`EQ`](crate::registers::EQ).
+  ///
+  /// Format: `JCMP Fl R0 R1 Idx`
+  ///
+  /// Arguments:
+  /// - `Fl`: Flag indicating which comparison operation to perform.
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  /// - `Idx`: Destination program index as a `u32`.
+  JmpCmpRR = 67,
+  /// # Compare Register and Immediate and Jump
+  ///
+  /// Compares `R0` and `I0` like `Cmp_RI` and jumps to `Idx` if the comparison
+  /// is true. Unlike `Cmp_RI` it does not write to
+  /// [`EQ`](crate::registers::EQ).
+  ///
+  /// Format: `JCMP Fl R0 I0 Idx`
+  ///
+  /// Arguments:
+  /// - `Fl`: Flag indicating which comparison operation to perform.
+  /// - `R0`: Register operand.
+  /// - `I0`: Immediate operand.
+  /// - `Idx`: Destination program index as a `u32`.
+  JmpCmpRI = 68,
+  /// # Jump to Register
+  ///
+  /// Jumps to the program index held in `R0`. The register's value is truncated
+  /// from an `f32` to a `u32`.
+  ///
+  /// Format: `JMPR R0`
+  ///
+  /// Arguments:
+  /// - `R0`: Register holding the destination program index.
+  JmpR = 69,
+  /// # Call a Function in a Register
+  ///
+  /// Calls the function whose program index is held in `R0`. The register's
+  /// value is truncated from an `f32` to a `u32`.
+  ///
+  /// Format: `CALLR R0`
+  ///
+  /// Arguments:
+  /// - `R0`: Register holding the location of the function pointer.
+  CallR = 70,
+  /// # Relative Jump
+  ///
+  /// Jumps `Off` bytes from the start of the next instruction.
+  ///
+  /// Format: `JMPREL Off`
+  ///
+  /// Arguments:
+  /// - `Off`: Signed offset to the destination as an `i32`.
+  JmpRel = 71,
+  /// # Relative Jump if Zero
+  ///
+  /// Jumps `Off` bytes from the start of the next instruction if `R0` is zero.
+  ///
+  /// Format: `JZREL R0 Off`
+  ///
+  /// Arguments:
+  /// - `R0`: Register holding the check.
+  /// - `Off`: Signed offset to the destination as an `i32`.
+  JzRel = 72,
+  /// # Relative Jump if Not Zero
+  ///
+  /// Jumps `Off` bytes from the start of the next instruction if `R0` is not
+  /// zero.
+  ///
+  /// Format: `JNZREL R0 Off`
+  ///
+  /// Arguments:
+  /// - `R0`: Register holding the check.
+  /// - `Off`: Signed offset to the destination as an `i32`.
+  JnzRel = 73,
+  /// # Increment
+  ///
+  /// Adds 1 to `R0` in place. Equivalent to `Add_RI R0 R0 1` in 2 bytes instead
+  /// of 7.
+  ///
+  /// Format: `INC R0`
+  ///
+  /// Arguments:
+  /// - `R0`: Register to increment.
+  Inc = 74,
+  /// # Decrement
+  ///
+  /// Subtracts 1 from `R0` in place. Equivalent to `Sub_RI R0 R0 1` in 2 bytes
+  /// instead of 7.
+  ///
+  /// Format: `DEC R0`
+  ///
+  /// Arguments:
+  /// - `R0`: Register to decrement.
+  Dec = 75,
+  /// # Swap Registers
+  ///
+  /// Exchanges the contents of `R0` and `R1`.
+  ///
+  /// Format: `SWAP R0 R1`
+  ///
+  /// Arguments:
+  /// - `R0`: Register operand.
+  /// - `R1`: Register operand.
+  Swap = 76,
+  /// # Load Integer
+  ///
+  /// Load the integer `I0` into register `R0`. Unlike `Load` the value is
+  /// stored exactly, so it is suitable for memory addresses, counts, and
+  /// program indices.
+  ///
+  /// Format: `LOADU32 Rd I0`
+  ///
+  /// Arguments:
+  /// - `Rd`: Destination register.
+  /// - `I0`: Source immediate as a little endian `u32`.
+  LoadU32 = 77,
+  /// # Push Immediate to Stack
+  ///
+  /// Pushes the immediate onto the top of stack without going through a
+  /// register.
+  ///
+  /// Format: `PUSHI I0`
+  ///
+  /// Arguments:
+  /// - `I0`: Immediate to push as an `f32`.
+  ///
+  /// Note: Like `Push`, pushing onto a stack which already holds
+  /// [`STACK_SIZE`](crate::memory::STACK_SIZE) items overflows it.
+  PushI = 78,
+  /// # Pop Many From Stack
+  ///
+  /// Removes the top `I0` items from the stack.
+  ///
+  /// Format: `POPN I0`
+  ///
+  /// Arguments:
+  /// - `I0`: The number of items to remove as a u8.
+  ///
+  /// Note: `Ret` expects the return address on top of the stack and only cleans
+  /// up the arguments beneath it, so a function's locals should be dropped with
+  /// `PopN` before it returns.
+  PopN = 79,
+  /// # Enter Stack Frame
+  ///
+  /// Pushes [`FP`](crate::registers::FP), points `FP` at the top of the stack,
+  /// and reserves `I0` slots above it for the function's locals.
+  ///
+  /// Format: `ENTER I0`
+  ///
+  /// Arguments:
+  /// - `I0`: The number of local slots to reserve as a u8.
+  ///
+  /// Note: Every `Enter` must be paired with a `Leave` before the function
+  /// returns.
+  Enter = 80,
+  /// # Leave Stack Frame
+  ///
+  /// Drops the current frame's locals by setting [`SP`](crate::registers::SP)
+  /// to [`FP`](crate::registers::FP) and then pops the caller's `FP`. Undoes
+  /// the most recent `Enter`.
+  ///
+  /// Format: Takes no args
+  Leave = 81,
+  /// # Memory Copy Many
+  ///
+  /// Copies `R1` consecutive values starting at the memory address `R0 + I1` to
+  /// the memory starting at `Rd + I0`.
+  ///
+  /// Format: `MEMCPYN Rd R0 R1 I0 I1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Register storing the destination memory address.
+  /// - `R0`: Register storing the source memory address.
+  /// - `R1`: Register storing the number of values to copy.
+  /// - `I0`: Offset added to the destination address as a u32.
+  /// - `I1`: Offset added to the source address as a u32.
+  MemCpyN = 82,
+  /// # Memory Set
+  ///
+  /// Stores `R0` in each of the `R1` consecutive memory addresses starting at
+  /// the address stored in `Rd`.
+  ///
+  /// Format: `MEMSET Rd R0 R1`
+  ///
+  /// Arguments:
+  /// - `Rd`: Register storing the destination memory address.
+  /// - `R0`: Register storing the value to store.
+  /// - `R1`: Register storing the number of addresses to fill.
+  ///
+  /// Note: A count of zero writes nothing. Filling past the end of the
+  /// allocation `Rd` points into is an error whose handling is left to the
+  /// VM.
+  MemSet = 83,
+  /// # Trap
+  ///
+  /// Suspends execution and hands control to an attached debugger. Execution
+  /// can be resumed afterwards.
+  ///
+  /// Format: Takes no args
+  ///
+  /// Note: [`Program::set_breakpoint`](crate::program::Program::set_breakpoint)
+  /// patches a `Trap` over the first byte of an existing instruction.
+  Trap = 84,
+  /// # Read String
+  ///
+  /// Reads a line from the VM's `stdin` (usually the terminal) into a freshly
+  /// allocated slab of memory.
+  ///
+  /// Format: `READSTR R0 R1`
+  ///
+  /// Arguments:
+  /// - `R0`: Register receiving the pointer to the string's start.
+  /// - `R1`: Register receiving the string's length.
+  ///
+  /// Note: The program owns the allocation and is responsible for freeing it
+  /// with `Dealloc`.
+  ReadStr = 85,
+  /// # Debug Print Register
+  ///
+  /// Writes the register index and its `f32` value to the VM's `stdout`.
+  /// Intended for development builds and removed by
+  /// [`Program::strip_debug`](crate::program::Program::strip_debug).
+  ///
+  /// Format: `DBGREG R0`
+  ///
+  /// Arguments:
+  /// - `R0`: Register to print.
+  DbgReg = 86,
+}
+
+impl OpCode {
+  /// Number of [`OpCode`]s.
+  pub const COUNT:usize = Self::ALL.len();
+
+  /// Every [`OpCode`] in the order of their discriminants.
+  pub const ALL:[OpCode; 87] = [
+    OpCode::Hlt,
+    OpCode::Load,
+    OpCode::Copy,
+    OpCode::MemCpy,
+    OpCode::AddRI,
+    OpCode::SubRI,
+    OpCode::RvSubRI,
+    OpCode::MulRI,
+    OpCode::DivRI,
+    OpCode::RvDivRI,
+    OpCode::PowRI,
+    OpCode::RvPowRI,
+    OpCode::AddRR,
+    OpCode::SubRR,
+    OpCode::MulRR,
+    OpCode::DivRR,
+    OpCode::PowRR,
+    OpCode::CmpRI,
+    OpCode::CmpRR,
+    OpCode::Not,
+    OpCode::Jmp,
+    OpCode::Jz,
+    OpCode::Jnz,
+    OpCode::Call,
+    OpCode::SysCall,
+    OpCode::Ret,
+    OpCode::Alloc,
+    OpCode::Realloc,
+    OpCode::Dealloc,
+    OpCode::RMem,
+    OpCode::WMem,
+    OpCode::WriteStr,
+    OpCode::Push,
+    OpCode::Pop,
+    OpCode::PopR,
+    OpCode::Noop,
+    OpCode::ModRI,
+    OpCode::RvModRI,
+    OpCode::ModRR,
+    OpCode::Neg,
+    OpCode::Abs,
+    OpCode::Sqrt,
+    OpCode::Floor,
+    OpCode::Ceil,
+    OpCode::Round,
+    OpCode::Trunc,
+    OpCode::AndRR,
+    OpCode::OrRR,
+    OpCode::XorRR,
+    OpCode::AndRI,
+    OpCode::OrRI,
+    OpCode::XorRI,
+    OpCode::ShlRI,
+    OpCode::ShrRI,
+    OpCode::ShlRR,
+    OpCode::ShrRR,
+    OpCode::MinRR,
+    OpCode::MaxRR,
+    OpCode::MinRI,
+    OpCode::MaxRI,
+    OpCode::Sin,
+    OpCode::Cos,
+    OpCode::Tan,
+    OpCode::Atan2,
+    OpCode::Ln,
+    OpCode::Log2,
+    OpCode::Exp,
+    OpCode::JmpCmpRR,
+    OpCode::JmpCmpRI,
+    OpCode::JmpR,
+    OpCode::CallR,
+    OpCode::JmpRel,
+    OpCode::JzRel,
+    OpCode::JnzRel,
+    OpCode::Inc,
+    OpCode::Dec,
+    OpCode::Swap,
+    OpCode::LoadU32,
+    OpCode::PushI,
+    OpCode::PopN,
+    OpCode::Enter,
+    OpCode::Leave,
+    OpCode::MemCpyN,
+    OpCode::MemSet,
+    OpCode::Trap,
+    OpCode::ReadStr,
+    OpCode::DbgReg,
+  ];
+
+  /// Iterates over every [`OpCode`] in the order of their discriminants.
+  pub fn iter() -> impl Iterator<Item = OpCode,> {
+    Self::ALL.into_iter()
+  }
+
+  /// Number of operand bytes following the opcode in an encoded instruction.
+  ///
+  /// Every opcode currently has operands of a fixed size.
+  pub fn operand_len(&self,) -> usize {
+    match self {
+      OpCode::Hlt | OpCode::Pop | OpCode::Noop | OpCode::Leave | OpCode::Trap => 0,
+      OpCode::SysCall
+      | OpCode::Ret
+      | OpCode::Dealloc
+      | OpCode::Push
+      | OpCode::PopR
+      | OpCode::JmpR
+      | OpCode::CallR
+      | OpCode::Inc
+      | OpCode::Dec
+      | OpCode::PopN
+      | OpCode::Enter
+      | OpCode::DbgReg => 1,
+      OpCode::Copy
+      | OpCode::MemCpy
+      | OpCode::Not
+      | OpCode::Alloc
+      | OpCode::Realloc
+      | OpCode::WriteStr
+      | OpCode::Neg
+      | OpCode::Abs
+      | OpCode::Sqrt
+      | OpCode::Floor
+      | OpCode::Ceil
+      | OpCode::Round
+      | OpCode::Trunc
+      | OpCode::Sin
+      | OpCode::Cos
+      | OpCode::Tan
+      | OpCode::Ln
+      | OpCode::Log2
+      | OpCode::Exp
+      | OpCode::Swap
+      | OpCode::ReadStr => 2,
+      OpCode::AddRR
+      | OpCode::SubRR
+      | OpCode::MulRR
+      | OpCode::DivRR
+      | OpCode::PowRR
+      | OpCode::CmpRR
+      | OpCode::ModRR
+      | OpCode::AndRR
+      | OpCode::OrRR
+      | OpCode::XorRR
+      | OpCode::ShlRR
+      | OpCode::ShrRR
+      | OpCode::MinRR
+      | OpCode::MaxRR
+      | OpCode::Atan2
+      | OpCode::MemSet => 3,
+      OpCode::Jmp | OpCode::Call | OpCode::JmpRel | OpCode::PushI => 4,
+      OpCode::Load | OpCode::Jz | OpCode::Jnz | OpCode::JzRel | OpCode::JnzRel | OpCode::LoadU32 => 5,
+      OpCode::AddRI
+      | OpCode::SubRI
+      | OpCode::RvSubRI
+      | OpCode::MulRI
+      | OpCode::DivRI
+      | OpCode::RvDivRI
+      | OpCode::PowRI
+      | OpCode::RvPowRI
+      | OpCode::CmpRI
+      | OpCode::ModRI
+      | OpCode::RvModRI
+      | OpCode::AndRI
+      | OpCode::OrRI
+      | OpCode::XorRI
+      | OpCode::ShlRI
+      | OpCode::ShrRI
+      | OpCode::MinRI
+      | OpCode::MaxRI => 6,
+      OpCode::RMem | OpCode::WMem | OpCode::JmpCmpRR => 7,
+      OpCode::JmpCmpRI => 10,
+      OpCode::MemCpyN => 11,
+    }
+  }
+
+  /// Number of bytes in an encoded instruction including the opcode.
+  pub fn instruction_len(&self,) -> usize {
+    1 + self.operand_len()
+  }
+
+  /// Returns the kind of work the opcode does.
+  pub fn category(&self,) -> OpCategory {
+    // No catch-all arm so new opcodes must be classified
+    match self {
+      OpCode::Load
+      | OpCode::LoadU32
+      | OpCode::Copy
+      | OpCode::Swap
+      | OpCode::AddRI
+      | OpCode::SubRI
+      | OpCode::RvSubRI
+      | OpCode::MulRI
+      | OpCode::DivRI
+      | OpCode::RvDivRI
+      | OpCode::PowRI
+      | OpCode::RvPowRI
+      | OpCode::ModRI
+      | OpCode::RvModRI
+      | OpCode::AddRR
+      | OpCode::SubRR
+      | OpCode::MulRR
+      | OpCode::DivRR
+      | OpCode::PowRR
+      | OpCode::ModRR
+      | OpCode::AndRR
+      | OpCode::OrRR
+      | OpCode::XorRR
+      | OpCode::AndRI
+      | OpCode::OrRI
+      | OpCode::XorRI
+      | OpCode::ShlRI
+      | OpCode::ShrRI
+      | OpCode::ShlRR
+      | OpCode::ShrRR
+      | OpCode::MinRR
+      | OpCode::MaxRR
+      | OpCode::MinRI
+      | OpCode::MaxRI
+      | OpCode::Not
+      | OpCode::Neg
+      | OpCode::Abs
+      | OpCode::Sqrt
+      | OpCode::Floor
+      | OpCode::Ceil
+      | OpCode::Round
+      | OpCode::Trunc
+      | OpCode::Sin
+      | OpCode::Cos
+      | OpCode::Tan
+      | OpCode::Atan2
+      | OpCode::Ln
+      | OpCode::Log2
+      | OpCode::Exp
+      | OpCode::Inc
+      | OpCode::Dec => OpCategory::Arithmetic,
+      OpCode::CmpRI | OpCode::CmpRR => OpCategory::Compare,
+      OpCode::Hlt
+      | OpCode::Jmp
+      | OpCode::Jz
+      | OpCode::Jnz
+      | OpCode::JmpRel
+      | OpCode::JzRel
+      | OpCode::JnzRel
+      | OpCode::JmpCmpRR
+      | OpCode::JmpCmpRI
+      | OpCode::JmpR
+      | OpCode::Call
+      | OpCode::CallR
+      | OpCode::Ret => OpCategory::ControlFlow,
+      OpCode::RMem
+      | OpCode::WMem
+      | OpCode::MemCpy
+      | OpCode::MemCpyN
+      | OpCode::MemSet
+      | OpCode::Alloc
+      | OpCode::Realloc
+      | OpCode::Dealloc => OpCategory::Memory,
+      OpCode::Push
+      | OpCode::PushI
+      | OpCode::Pop
+      | OpCode::PopR
+      | OpCode::PopN
+      | OpCode::Enter
+      | OpCode::Leave => OpCategory::Stack,
+      OpCode::SysCall | OpCode::WriteStr | OpCode::ReadStr | OpCode::DbgReg => OpCategory::Io,
+      OpCode::Noop | OpCode::Trap => OpCategory::Misc,
+    }
+  }
+
+  /// Whether the opcode ends a basic block. These are the
+  /// [`OpCategory::ControlFlow`] opcodes: every jump and call, `Ret`, and
+  /// `Hlt`.
+  pub fn is_terminator(&self,) -> bool {
+    self.category() == OpCategory::ControlFlow
+  }
+
+  /// Whether the opcode is a jump, conditional or not. Calls and returns are
+  /// not branches.
+  pub fn is_branch(&self,) -> bool {
+    matches!(
+      self,
+      OpCode::Jmp
+        | OpCode::Jz
+        | OpCode::Jnz
+        | OpCode::JmpRel
+        | OpCode::JzRel
+        | OpCode::JnzRel
+        | OpCode::JmpCmpRR
+        | OpCode::JmpCmpRI
+        | OpCode::JmpR
+    )
+  }
+
+  /// Whether any of the opcode's operands is an immediate rather than a
+  /// register or comparison flag. Jump targets, memory offsets, and the
+  /// counts of `SysCall`, `Ret`, `PopN`, and `Enter` are immediates.
+  pub fn has_immediate(&self,) -> bool {
+    matches!(
+      self,
+      OpCode::Load
+        | OpCode::LoadU32
+        | OpCode::AddRI
+        | OpCode::SubRI
+        | OpCode::RvSubRI
+        | OpCode::MulRI
+        | OpCode::DivRI
+        | OpCode::RvDivRI
+        | OpCode::PowRI
+        | OpCode::RvPowRI
+        | OpCode::CmpRI
+        | OpCode::ModRI
+        | OpCode::RvModRI
+        | OpCode::AndRI
+        | OpCode::OrRI
+        | OpCode::XorRI
+        | OpCode::ShlRI
+        | OpCode::ShrRI
+        | OpCode::MinRI
+        | OpCode::MaxRI
+        | OpCode::Jmp
+        | OpCode::Jz
+        | OpCode::Jnz
+        | OpCode::JmpRel
+        | OpCode::JzRel
+        | OpCode::JnzRel
+        | OpCode::JmpCmpRR
+        | OpCode::JmpCmpRI
+        | OpCode::Call
+        | OpCode::PushI
+        | OpCode::SysCall
+        | OpCode::Ret
+        | OpCode::PopN
+        | OpCode::Enter
+        | OpCode::RMem
+        | OpCode::WMem
+        | OpCode::MemCpyN
+    )
+  }
+
+  /// Returns which of the opcode's register operands it writes, or `None` if
+  /// it writes none. Registers written implicitly, like the
+  /// [`EQ`](crate::registers::EQ) written by `Cmp_RR`, are not operands; see
+  /// [`Instruction::implicit_writes`](crate::instruction::Instruction::implicit_writes).
+  pub fn writes_register(&self,) -> Option<OperandSlot,> {
+    match self {
+      OpCode::Swap | OpCode::ReadStr => Some(OperandSlot::FirstTwo,),
+      OpCode::Inc | OpCode::Dec | OpCode::Realloc | OpCode::Alloc | OpCode::PopR | OpCode::RMem => {
+        Some(OperandSlot::First,)
+      }
+      op if op.category() == OpCategory::Arithmetic => Some(OperandSlot::First,),
+      _ => None,
+    }
+  }
+
+  /// Returns the first [`IsaVersion`] with the opcode.
+  pub fn isa_version(&self,) -> IsaVersion {
+    let version = match self {
+      OpCode::Hlt
+      | OpCode::Load
+      | OpCode::Copy
+      | OpCode::MemCpy
+      | OpCode::AddRI
+      | OpCode::SubRI
+      | OpCode::RvSubRI
+      | OpCode::MulRI
+      | OpCode::DivRI
+      | OpCode::RvDivRI
+      | OpCode::PowRI
+      | OpCode::RvPowRI
+      | OpCode::AddRR
+      | OpCode::SubRR
+      | OpCode::MulRR
+      | OpCode::DivRR
+      | OpCode::PowRR
+      | OpCode::CmpRI
+      | OpCode::CmpRR
+      | OpCode::Not
+      | OpCode::Jmp
+      | OpCode::Jz
+      | OpCode::Jnz
+      | OpCode::Call
+      | OpCode::SysCall
+      | OpCode::Ret
+      | OpCode::Alloc
+      | OpCode::Realloc
+      | OpCode::Dealloc
+      | OpCode::RMem
+      | OpCode::WMem
+      | OpCode::WriteStr
+      | OpCode::Push
+      | OpCode::Pop
+      | OpCode::PopR
+      | OpCode::Noop => 1,
+      OpCode::ModRI | OpCode::RvModRI | OpCode::ModRR => 2,
+      OpCode::Neg | OpCode::Abs | OpCode::Sqrt => 3,
+      OpCode::Floor | OpCode::Ceil | OpCode::Round | OpCode::Trunc => 4,
+      OpCode::AndRR | OpCode::OrRR | OpCode::XorRR | OpCode::AndRI | OpCode::OrRI | OpCode::XorRI => 5,
+      OpCode::ShlRI | OpCode::ShrRI | OpCode::ShlRR | OpCode::ShrRR => 6,
+      OpCode::MinRR | OpCode::MaxRR | OpCode::MinRI | OpCode::MaxRI => 7,
+      OpCode::Sin | OpCode::Cos | OpCode::Tan | OpCode::Atan2 => 8,
+      OpCode::Ln | OpCode::Log2 | OpCode::Exp => 9,
+      // Version 10 added `CmpFlag::Neq`
+      OpCode::JmpCmpRR | OpCode::JmpCmpRI => 11,
+      OpCode::JmpR | OpCode::CallR => 12,
+      OpCode::JmpRel | OpCode::JzRel | OpCode::JnzRel => 13,
+      OpCode::Inc | OpCode::Dec => 14,
+      OpCode::Swap => 15,
+      OpCode::LoadU32 => 16,
+      OpCode::PushI => 17,
+      OpCode::PopN => 18,
+      OpCode::Enter | OpCode::Leave => 19,
+      OpCode::MemCpyN => 20,
+      OpCode::MemSet => 21,
+      OpCode::Trap => 22,
+      OpCode::ReadStr => 23,
+      OpCode::DbgReg => 24,
+    };
+    IsaVersion(version,)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+/// The kind of work an [`OpCode`] does. Returned by [`OpCode::category`].
+pub enum OpCategory {
+  /// Computes a value into a register from registers and immediates,
+  /// including `Load`, `Copy`, and `Swap`.
+  Arithmetic,
+  /// Compares values and sets [`EQ`](crate::registers::EQ).
+  Compare,
+  /// Jumps, calls, returns, or halts.
+  ControlFlow,
+  /// Reads, writes, or allocates heap memory.
+  Memory,
+  /// Pushes to or pops from the stack, or sets up or tears down a frame.
+  Stack,
+  /// Talks to the host through syscalls, strings, or debug output.
+  Io,
+  /// Does nothing to the program's state, such as `Noop` and `Trap`.
+  Misc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+/// Which register operands of an instruction it writes. Returned by
+/// [`OpCode::writes_register`].
+pub enum OperandSlot {
+  /// The first register operand, usually the destination.
+  First,
+  /// The first two register operands, as written by `Swap` and `ReadStr`.
+  FirstTwo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+/// A version of the instruction set. Each version adds opcodes or
+/// [`CmpFlag`]s to the one before it, so a VM which supports a version runs
+/// programs requiring it or any earlier version.
+///
+/// Saved programs record the version they require, returned by
+/// [`Program::required_isa_version`](crate::program::Program::required_isa_version).
+pub struct IsaVersion(pub u16,);
+
+/// The newest [`IsaVersion`], which has every [`OpCode`] and [`CmpFlag`] this
+/// crate knows.
+pub const CURRENT_ISA_VERSION:IsaVersion = IsaVersion(24,);
+
+impl Display for IsaVersion {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    write!(f, "{}", self.0)
+  }
 }
 
 impl From<OpCode,> for u8 {
@@ -345,17 +1434,22 @@ impl From<OpCode,> for u8 {
   }
 }
 
-impl From<u8,> for OpCode {
-  fn from(value:u8,) -> Self {
+impl TryFrom<u8,> for OpCode {
+  type Error = DecodeError;
+
+  fn try_from(value:u8,) -> Result<Self, Self::Error,> {
     match FromPrimitive::from_u8(value,) {
-      Some(op,) => op,
-      None => panic!("{} is not a valid OpCode", value),
+      Some(op,) => Ok(op,),
+      None => Err(DecodeError::InvalidOpCode {
+        byte:value,
+        offset:None,
+      },),
     }
   }
 }
 
 impl Display for OpCode {
-  fn fmt(&self, f:&mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
     match self {
       OpCode::Hlt => write!(f, "Hlt"),
       OpCode::Load => write!(f, "Load"),
@@ -393,17 +1487,109 @@ impl Display for OpCode {
       OpCode::PopR => write!(f, "PopR"),
       OpCode::Noop => write!(f, "Noop"),
       OpCode::WriteStr => write!(f, "WriteStr"),
+      OpCode::ModRI => write!(f, "Mod_RI"),
+      OpCode::RvModRI => write!(f, "RvMod_RI"),
+      OpCode::ModRR => write!(f, "Mod_RR"),
+      OpCode::Neg => write!(f, "Neg"),
+      OpCode::Abs => write!(f, "Abs"),
+      OpCode::Sqrt => write!(f, "Sqrt"),
+      OpCode::Floor => write!(f, "Floor"),
+      OpCode::Ceil => write!(f, "Ceil"),
+      OpCode::Round => write!(f, "Round"),
+      OpCode::Trunc => write!(f, "Trunc"),
+      OpCode::AndRR => write!(f, "And_RR"),
+      OpCode::OrRR => write!(f, "Or_RR"),
+      OpCode::XorRR => write!(f, "Xor_RR"),
+      OpCode::AndRI => write!(f, "And_RI"),
+      OpCode::OrRI => write!(f, "Or_RI"),
+      OpCode::XorRI => write!(f, "Xor_RI"),
+      OpCode::ShlRI => write!(f, "Shl_RI"),
+      OpCode::ShrRI => write!(f, "Shr_RI"),
+      OpCode::ShlRR => write!(f, "Shl_RR"),
+      OpCode::ShrRR => write!(f, "Shr_RR"),
+      OpCode::MinRR => write!(f, "Min_RR"),
+      OpCode::MaxRR => write!(f, "Max_RR"),
+      OpCode::MinRI => write!(f, "Min_RI"),
+      OpCode::MaxRI => write!(f, "Max_RI"),
+      OpCode::Sin => write!(f, "Sin"),
+      OpCode::Cos => write!(f, "Cos"),
+      OpCode::Tan => write!(f, "Tan"),
+      OpCode::Atan2 => write!(f, "Atan2"),
+      OpCode::Ln => write!(f, "Ln"),
+      OpCode::Log2 => write!(f, "Log2"),
+      OpCode::Exp => write!(f, "Exp"),
+      OpCode::JmpCmpRR => write!(f, "JmpCmp_RR"),
+      OpCode::JmpCmpRI => write!(f, "JmpCmp_RI"),
+      OpCode::JmpR => write!(f, "JmpR"),
+      OpCode::CallR => write!(f, "CallR"),
+      OpCode::JmpRel => write!(f, "JmpRel"),
+      OpCode::JzRel => write!(f, "JzRel"),
+      OpCode::JnzRel => write!(f, "JnzRel"),
+      OpCode::Inc => write!(f, "Inc"),
+      OpCode::Dec => write!(f, "Dec"),
+      OpCode::Swap => write!(f, "Swap"),
+      OpCode::LoadU32 => write!(f, "LoadU32"),
+      OpCode::PushI => write!(f, "PushI"),
+      OpCode::PopN => write!(f, "PopN"),
+      OpCode::Enter => write!(f, "Enter"),
+      OpCode::Leave => write!(f, "Leave"),
+      OpCode::MemCpyN => write!(f, "MemCpyN"),
+      OpCode::MemSet => write!(f, "MemSet"),
+      OpCode::Trap => write!(f, "Trap"),
+      OpCode::ReadStr => write!(f, "ReadStr"),
+      OpCode::DbgReg => write!(f, "DbgReg"),
     }
   }
 }
 
-#[derive(Debug, FromPrimitive,)]
+#[derive(Debug, FromPrimitive, Clone, Copy, PartialEq, Eq,)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[repr(u8)]
+/// The comparison performed by `Cmp_RI` and `Cmp_RR`.
+///
+/// Like [`OpCode`], the discriminants are part of the encoding so new flags
+/// must only be appended.
 pub enum CmpFlag {
-  Eq,
-  Gt,
-  Lt,
-  Geq,
-  Leq,
+  Eq = 0,
+  Gt = 1,
+  Lt = 2,
+  Geq = 3,
+  Leq = 4,
+  Neq = 5,
+}
+
+impl CmpFlag {
+  /// Every [`CmpFlag`] in the order of their discriminants.
+  pub const ALL:[CmpFlag; 6] = [
+    CmpFlag::Eq,
+    CmpFlag::Gt,
+    CmpFlag::Lt,
+    CmpFlag::Geq,
+    CmpFlag::Leq,
+    CmpFlag::Neq,
+  ];
+
+  /// Returns the flag whose comparison is true exactly when this flag's is
+  /// false for ordered (non-NaN) operands.
+  pub fn inverse(self,) -> CmpFlag {
+    match self {
+      CmpFlag::Eq => CmpFlag::Neq,
+      CmpFlag::Neq => CmpFlag::Eq,
+      CmpFlag::Gt => CmpFlag::Leq,
+      CmpFlag::Leq => CmpFlag::Gt,
+      CmpFlag::Lt => CmpFlag::Geq,
+      CmpFlag::Geq => CmpFlag::Lt,
+    }
+  }
+
+  /// Returns the first [`IsaVersion`] with the flag.
+  pub fn isa_version(self,) -> IsaVersion {
+    match self {
+      CmpFlag::Eq | CmpFlag::Gt | CmpFlag::Lt | CmpFlag::Geq | CmpFlag::Leq => IsaVersion(1,),
+      CmpFlag::Neq => IsaVersion(10,),
+    }
+  }
 }
 
 impl From<CmpFlag,> for u8 {
@@ -412,23 +1598,344 @@ impl From<CmpFlag,> for u8 {
   }
 }
 
-impl From<u8,> for CmpFlag {
-  fn from(value:u8,) -> Self {
+impl TryFrom<u8,> for CmpFlag {
+  type Error = DecodeError;
+
+  fn try_from(value:u8,) -> Result<Self, Self::Error,> {
     match FromPrimitive::from_u8(value,) {
-      Some(op,) => op,
-      None => panic!("{} is not a valid Cmpflag", value),
+      Some(flag,) => Ok(flag,),
+      None => Err(DecodeError::InvalidCmpFlag {
+        byte:value,
+        offset:None,
+      },),
     }
   }
 }
 
 impl Display for CmpFlag {
-  fn fmt(&self, f:&mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
     match self {
       CmpFlag::Eq => write!(f, "EQ"),
       CmpFlag::Gt => write!(f, "GT"),
       CmpFlag::Lt => write!(f, "LT"),
       CmpFlag::Geq => write!(f, "GEQ"),
       CmpFlag::Leq => write!(f, "LEQ"),
+      CmpFlag::Neq => write!(f, "NEQ"),
+    }
+  }
+}
+
+impl FromStr for OpCode {
+  type Err = ParseMnemonicError;
+
+  /// Parses the `Display` name of an [`OpCode`] ignoring case.
+  fn from_str(s:&str,) -> Result<Self, Self::Err,> {
+    parse_name(s, &OpCode::ALL,)
+  }
+}
+
+impl FromStr for CmpFlag {
+  type Err = ParseMnemonicError;
+
+  /// Parses the `Display` name of a [`CmpFlag`] ignoring case.
+  fn from_str(s:&str,) -> Result<Self, Self::Err,> {
+    parse_name(s, &CmpFlag::ALL,)
+  }
+}
+
+/// Finds the item whose `Display` name matches `s` ignoring case. If there is
+/// none the error suggests the closest name.
+fn parse_name<T:Display + Copy,>(s:&str, items:&[T],) -> Result<T, ParseMnemonicError,> {
+  if let Some(item,) = items
+    .iter()
+    .find(|item| item.to_string().eq_ignore_ascii_case(s,),)
+  {
+    return Ok(*item,);
+  }
+
+  // Only suggest names which are a couple of typos away
+  let input = s.to_ascii_lowercase();
+  let suggestion = items
+    .iter()
+    .map(|item| item.to_string(),)
+    .map(|name| (edit_distance(&input, &name.to_ascii_lowercase(),), name,),)
+    .filter(|(distance, _,)| *distance <= 2,)
+    .min_by_key(|(distance, _,)| *distance,)
+    .map(|(_, name,)| name,);
+
+  Err(ParseMnemonicError {
+    input:s.to_string(),
+    suggestion,
+  },)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a:&str, b:&str,) -> usize {
+  let b = b.chars().collect::<Vec<_,>>();
+  let mut prev = (0..=b.len()).collect::<Vec<_,>>();
+  for (i, ca,) in a.chars().enumerate() {
+    let mut curr = vec![i + 1];
+    for (j, cb,) in b.iter().enumerate() {
+      let substitute = prev[j] + (ca != *cb) as usize;
+      curr.push(substitute.min(prev[j + 1] + 1,).min(curr[j] + 1,),);
+    }
+    prev = curr;
+  }
+  prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+  use super::{CmpFlag, IsaVersion, OpCategory, OpCode, OperandSlot, CURRENT_ISA_VERSION};
+  use crate::errors::ParseMnemonicError;
+  use alloc::string::ToString;
+  use core::str::FromStr;
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde_opcode_names() {
+    assert_eq!(serde_json::to_string(&OpCode::AddRR,).unwrap(), r#""AddRR""#);
+    assert_eq!(
+      serde_json::from_str::<OpCode,>(r#""AddRR""#).unwrap(),
+      OpCode::AddRR
+    );
+    assert_eq!(
+      serde_json::from_str::<CmpFlag,>(r#""Neq""#).unwrap(),
+      CmpFlag::Neq
+    );
+    assert!(serde_json::from_str::<OpCode,>(r#""Add""#).is_err());
+    assert!(serde_json::from_str::<OpCode,>("3").is_err());
+    for op in OpCode::iter() {
+      let bytes = postcard::to_allocvec(&op,).unwrap();
+      assert_eq!(postcard::from_bytes::<OpCode,>(&bytes,).unwrap(), op);
+    }
+  }
+
+  #[test]
+  fn isa_versions_follow_discriminants() {
+    // Opcodes are appended, so later opcodes are never in older versions
+    for pair in OpCode::ALL.windows(2,) {
+      assert!(pair[0].isa_version() <= pair[1].isa_version(), "{}", pair[1]);
+    }
+    assert_eq!(OpCode::Noop.isa_version(), IsaVersion(1));
+    assert_eq!(OpCode::MinRR.isa_version(), IsaVersion(7));
+    assert_eq!(CmpFlag::Leq.isa_version(), IsaVersion(1));
+    assert_eq!(CmpFlag::Neq.isa_version(), IsaVersion(10));
+
+    let newest = OpCode::iter()
+      .map(|op| op.isa_version(),)
+      .chain(CmpFlag::ALL.map(CmpFlag::isa_version,),)
+      .max();
+    assert_eq!(newest, Some(CURRENT_ISA_VERSION));
+  }
+
+  #[test]
+  fn every_opcode_is_classified() {
+    let count = |category| OpCode::iter().filter(|op| op.category() == category,).count();
+    assert_eq!(
+      [
+        OpCategory::Arithmetic,
+        OpCategory::Compare,
+        OpCategory::ControlFlow,
+        OpCategory::Memory,
+        OpCategory::Stack,
+        OpCategory::Io,
+        OpCategory::Misc,
+      ]
+      .map(count,),
+      [51, 2, 13, 8, 7, 4, 2]
+    );
+    assert_eq!(OpCode::CmpRR.category(), OpCategory::Compare);
+    assert_eq!(OpCode::Trap.category(), OpCategory::Misc);
+
+    for op in OpCode::iter() {
+      // Branches are terminators and every other terminator calls, returns,
+      // or halts
+      if op.is_branch() {
+        assert!(op.is_terminator(), "{}", op);
+      }
+      else if op.is_terminator() {
+        assert!(
+          matches!(op, OpCode::Call | OpCode::CallR | OpCode::Ret | OpCode::Hlt),
+          "{}",
+          op
+        );
+      }
+      // Every opcode writing a register has one
+      if op.writes_register().is_some() {
+        assert!(op.operand_len() > 0, "{}", op);
+      }
+    }
+    assert_eq!(OpCode::Swap.writes_register(), Some(OperandSlot::FirstTwo));
+    assert_eq!(OpCode::AddRI.writes_register(), Some(OperandSlot::First));
+    assert_eq!(OpCode::WMem.writes_register(), None);
+    assert!(OpCode::RMem.has_immediate());
+    assert!(!OpCode::AddRR.has_immediate());
+  }
+
+  #[test]
+  fn all_opcodes_are_contiguous() {
+    for (idx, op,) in OpCode::iter().enumerate() {
+      assert_eq!(u8::from(op,) as usize, idx);
+      assert_eq!(OpCode::try_from(idx as u8,), Ok(op));
+    }
+    assert_eq!(OpCode::iter().count(), OpCode::COUNT);
+    assert!(OpCode::try_from(OpCode::COUNT as u8,).is_err());
+  }
+
+  #[test]
+  fn inverse_cmp_flags() {
+    for flag in CmpFlag::ALL {
+      assert_ne!(flag.inverse(), flag);
+      assert_eq!(flag.inverse().inverse(), flag);
+    }
+    assert_eq!(CmpFlag::Gt.inverse(), CmpFlag::Leq);
+    assert_eq!(CmpFlag::Neq.inverse(), CmpFlag::Eq);
+  }
+
+  #[test]
+  fn parse_display_names() {
+    for op in OpCode::iter() {
+      assert_eq!(OpCode::from_str(&op.to_string(),), Ok(op));
+      assert_eq!(OpCode::from_str(&op.to_string().to_uppercase(),), Ok(op));
+    }
+    for flag in CmpFlag::ALL {
+      assert_eq!(CmpFlag::from_str(&flag.to_string(),), Ok(flag));
+      assert_eq!(CmpFlag::from_str(&flag.to_string().to_lowercase(),), Ok(flag));
+    }
+
+    assert_eq!(
+      "Ad_RI".parse::<OpCode>(),
+      Err(ParseMnemonicError {
+        input:"Ad_RI".to_string(),
+        suggestion:Some("Add_RI".to_string()),
+      })
+    );
+    assert_eq!(
+      "Frobnicate".parse::<OpCode>(),
+      Err(ParseMnemonicError {
+        input:"Frobnicate".to_string(),
+        suggestion:None,
+      })
+    );
+    assert_eq!(
+      "GTE".parse::<CmpFlag>().unwrap_err().suggestion,
+      Some("GT".to_string())
+    );
+  }
+
+  #[test]
+  fn discriminants_are_stable() {
+    // Changing any of these breaks every saved program
+    #[rustfmt::skip]
+    let opcodes = [
+      (OpCode::Hlt, 0,),
+      (OpCode::Load, 1,),
+      (OpCode::Copy, 2,),
+      (OpCode::MemCpy, 3,),
+      (OpCode::AddRI, 4,),
+      (OpCode::SubRI, 5,),
+      (OpCode::RvSubRI, 6,),
+      (OpCode::MulRI, 7,),
+      (OpCode::DivRI, 8,),
+      (OpCode::RvDivRI, 9,),
+      (OpCode::PowRI, 10,),
+      (OpCode::RvPowRI, 11,),
+      (OpCode::AddRR, 12,),
+      (OpCode::SubRR, 13,),
+      (OpCode::MulRR, 14,),
+      (OpCode::DivRR, 15,),
+      (OpCode::PowRR, 16,),
+      (OpCode::CmpRI, 17,),
+      (OpCode::CmpRR, 18,),
+      (OpCode::Not, 19,),
+      (OpCode::Jmp, 20,),
+      (OpCode::Jz, 21,),
+      (OpCode::Jnz, 22,),
+      (OpCode::Call, 23,),
+      (OpCode::SysCall, 24,),
+      (OpCode::Ret, 25,),
+      (OpCode::Alloc, 26,),
+      (OpCode::Realloc, 27,),
+      (OpCode::Dealloc, 28,),
+      (OpCode::RMem, 29,),
+      (OpCode::WMem, 30,),
+      (OpCode::WriteStr, 31,),
+      (OpCode::Push, 32,),
+      (OpCode::Pop, 33,),
+      (OpCode::PopR, 34,),
+      (OpCode::Noop, 35,),
+      (OpCode::ModRI, 36,),
+      (OpCode::RvModRI, 37,),
+      (OpCode::ModRR, 38,),
+      (OpCode::Neg, 39,),
+      (OpCode::Abs, 40,),
+      (OpCode::Sqrt, 41,),
+      (OpCode::Floor, 42,),
+      (OpCode::Ceil, 43,),
+      (OpCode::Round, 44,),
+      (OpCode::Trunc, 45,),
+      (OpCode::AndRR, 46,),
+      (OpCode::OrRR, 47,),
+      (OpCode::XorRR, 48,),
+      (OpCode::AndRI, 49,),
+      (OpCode::OrRI, 50,),
+      (OpCode::XorRI, 51,),
+      (OpCode::ShlRI, 52,),
+      (OpCode::ShrRI, 53,),
+      (OpCode::ShlRR, 54,),
+      (OpCode::ShrRR, 55,),
+      (OpCode::MinRR, 56,),
+      (OpCode::MaxRR, 57,),
+      (OpCode::MinRI, 58,),
+      (OpCode::MaxRI, 59,),
+      (OpCode::Sin, 60,),
+      (OpCode::Cos, 61,),
+      (OpCode::Tan, 62,),
+      (OpCode::Atan2, 63,),
+      (OpCode::Ln, 64,),
+      (OpCode::Log2, 65,),
+      (OpCode::Exp, 66,),
+      (OpCode::JmpCmpRR, 67,),
+      (OpCode::JmpCmpRI, 68,),
+      (OpCode::JmpR, 69,),
+      (OpCode::CallR, 70,),
+      (OpCode::JmpRel, 71,),
+      (OpCode::JzRel, 72,),
+      (OpCode::JnzRel, 73,),
+      (OpCode::Inc, 74,),
+      (OpCode::Dec, 75,),
+      (OpCode::Swap, 76,),
+      (OpCode::LoadU32, 77,),
+      (OpCode::PushI, 78,),
+      (OpCode::PopN, 79,),
+      (OpCode::Enter, 80,),
+      (OpCode::Leave, 81,),
+      (OpCode::MemCpyN, 82,),
+      (OpCode::MemSet, 83,),
+      (OpCode::Trap, 84,),
+      (OpCode::ReadStr, 85,),
+      (OpCode::DbgReg, 86,),
+    ];
+    for (op, value,) in opcodes {
+      assert_eq!(u8::from(op,), value, "{:?} was renumbered", op);
+      assert_eq!(OpCode::try_from(value,), Ok(op));
+    }
+    assert_eq!(opcodes.len(), OpCode::COUNT);
+
+    #[rustfmt::skip]
+    let flags = [
+      (CmpFlag::Eq, 0,),
+      (CmpFlag::Gt, 1,),
+      (CmpFlag::Lt, 2,),
+      (CmpFlag::Geq, 3,),
+      (CmpFlag::Leq, 4,),
+      (CmpFlag::Neq, 5,),
+    ];
+    for (flag, value,) in flags {
+      assert_eq!(u8::from(flag,), value, "{:?} was renumbered", flag);
+      assert_eq!(CmpFlag::try_from(value,), Ok(flag));
     }
+    assert_eq!(flags.len(), CmpFlag::ALL.len());
   }
 }
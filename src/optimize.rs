@@ -0,0 +1,640 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::{
+  analysis::{self, ends_block},
+  errors::DecodeError,
+  instruction::Instruction,
+  program::Program,
+  registers::Register,
+};
+
+/// Returns a copy of the [`Program`] without the instructions which can never
+/// run, found by walking its [`Cfg`](crate::analysis::Cfg) from the entry
+/// point. Functions which are never called are removed along with their
+/// symbols. The remaining jumps and symbols are moved for the shrunken
+/// layout.
+///
+/// A reachable `JmpR` or `CallR` could go anywhere so programs with one are
+/// returned unchanged.
+///
+/// Errors if the program cannot be decoded.
+pub fn eliminate_dead_code(program:&Program,) -> Result<Program, DecodeError,> {
+  let cfg = analysis::cfg(program,)?;
+  let Some(entry,) = cfg.block_at(program.entry_point(),)
+  else {
+    return Ok(program.clone(),);
+  };
+
+  let mut reachable = vec![false; cfg.blocks().len()];
+  let mut work = vec![entry];
+  while let Some(block,) = work.pop() {
+    if core::mem::replace(&mut reachable[block], true,) {
+      continue;
+    }
+    for edge in cfg.successors(block,) {
+      match edge.to {
+        Some(to,) => work.push(to,),
+        None => return Ok(program.clone(),),
+      }
+    }
+  }
+  let keep = |at:u32| cfg.block_at(at,).is_some_and(|block| reachable[block],);
+  let mut pruned = program.clone();
+  pruned.symbols_mut().retain(keep,);
+  pruned.retain_instructions(|at, _| keep(at,),)
+}
+
+/// A rewrite applied by [`peephole`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+pub enum PeepholeRule {
+  /// `Load r, x` followed by `Copy d, r` becomes `Load d, x` when `r` is not
+  /// read again before it is overwritten.
+  ForwardLoad,
+  /// `Add_RI d, d, 0` and `Mul_RI d, d, 1` are removed.
+  DropIdentity,
+  /// `Cmp` followed by `Not $EQ, $EQ` becomes a single `Cmp` with the
+  /// [inverse](crate::opcodes::CmpFlag::inverse) flag. Comparisons with NaN
+  /// give a different result afterwards.
+  InvertCmp,
+  /// Runs of two or more `Noop`s are removed. A lone `Noop` is kept since it
+  /// may be reserved for patching.
+  RemoveNoops,
+}
+
+impl PeepholeRule {
+  pub const ALL:[PeepholeRule; 4] = [
+    PeepholeRule::ForwardLoad,
+    PeepholeRule::DropIdentity,
+    PeepholeRule::InvertCmp,
+    PeepholeRule::RemoveNoops,
+  ];
+}
+
+/// Returns a copy of the [`Program`] with every [`PeepholeRule`] applied.
+///
+/// Errors if the program cannot be decoded.
+pub fn peephole(program:&Program,) -> Result<Program, DecodeError,> {
+  peephole_with(program, &PeepholeRule::ALL,)
+}
+
+/// Returns a copy of the [`Program`] with the given [`PeepholeRule`]s
+/// applied. Rules which merge two instructions are not applied when the
+/// second one is a jump target. Jumps, symbols, and the entry point are moved
+/// for the new layout.
+///
+/// Errors if the program cannot be decoded.
+pub fn peephole_with(program:&Program, rules:&[PeepholeRule],) -> Result<Program, DecodeError,> {
+  let insts = program.instructions().collect::<Result<Vec<_,>, _,>>()?;
+  let targets = jump_targets(program, &insts,);
+  let is_target = |at:u32| targets.contains(&at,);
+  let enabled = |rule| rules.contains(&rule,);
+
+  let mut edits = insts.iter().map(|(_, inst,)| Some(*inst,),).collect::<Vec<_,>>();
+  let mut i = 0;
+  while i < insts.len() {
+    let inst = insts[i].1;
+    let next = insts
+      .get(i + 1,)
+      .filter(|(at, _,)| !is_target(*at,),)
+      .map(|(_, inst,)| *inst,);
+    match (inst, next,) {
+      (Instruction::Load { dst: r, imm, }, Some(Instruction::Copy { dst, src, },),)
+        if enabled(PeepholeRule::ForwardLoad,)
+          && src == r
+          && is_dead(insts[i + 2..].iter().map(|(_, inst,)| inst,), r,) =>
+      {
+        edits[i] = None;
+        edits[i + 1] = Some(Instruction::Load { dst, imm, },);
+        i += 1;
+      }
+      (Instruction::AddRI { dst, src, imm: 0.0, } | Instruction::MulRI { dst, src, imm: 1.0, }, _,)
+        if enabled(PeepholeRule::DropIdentity,) && dst == src =>
+      {
+        edits[i] = None;
+      }
+      (Instruction::CmpRI { flag, src, imm, }, Some(Instruction::Not { dst, src: not, },),)
+        if enabled(PeepholeRule::InvertCmp,) && dst == Register::EQ && not == Register::EQ =>
+      {
+        edits[i] = Some(Instruction::CmpRI {
+          flag:flag.inverse(),
+          src,
+          imm,
+        },);
+        edits[i + 1] = None;
+        i += 1;
+      }
+      (Instruction::CmpRR { flag, a, b, }, Some(Instruction::Not { dst, src, },),)
+        if enabled(PeepholeRule::InvertCmp,) && dst == Register::EQ && src == Register::EQ =>
+      {
+        edits[i] = Some(Instruction::CmpRR {
+          flag:flag.inverse(),
+          a,
+          b,
+        },);
+        edits[i + 1] = None;
+        i += 1;
+      }
+      (Instruction::Noop, _,) if enabled(PeepholeRule::RemoveNoops,) => {
+        let run = insts[i..]
+          .iter()
+          .take_while(|(_, inst,)| matches!(inst, Instruction::Noop),)
+          .count();
+        if run > 1 {
+          edits[i..i + run].fill(None,);
+        }
+        i += run - 1;
+      }
+      _ => {}
+    }
+    i += 1;
+  }
+  let mut edits = edits.into_iter();
+  program.rewrite_instructions(|_, _| edits.next().unwrap(),)
+}
+
+/// Returns a copy of the [`Program`] with arithmetic on registers holding
+/// known constants replaced by `Load`s of the result. Registers are known
+/// after a `Load` until the end of its basic block, a `Call` or `SysCall`, or
+/// anything else which writes them. Results are computed with the same `f32`
+/// operations the opcodes define so they are bit-identical. Powers are only
+/// folded with the `std` feature.
+///
+/// `Load`s which fed a folded instruction, or were themselves folded, are
+/// removed if their register is overwritten before it is read again. A
+/// register which is still live when its block ends is kept.
+///
+/// Errors if the program cannot be decoded.
+pub fn fold_constants(program:&Program,) -> Result<Program, DecodeError,> {
+  let insts = program.instructions().collect::<Result<Vec<_,>, _,>>()?;
+  let targets = jump_targets(program, &insts,);
+
+  let mut edits = insts.iter().map(|(_, inst,)| Some(*inst,),).collect::<Vec<_,>>();
+  // Maps a register to its value and the index of the `Load` which set it
+  let mut known = BTreeMap::<Register, (f32, usize,),>::new();
+  let mut folded = Vec::new();
+  for (i, (at, inst,),) in insts.iter().enumerate() {
+    if targets.contains(at,) {
+      known.clear();
+    }
+    match fold(inst, &known,) {
+      Some((dst, imm, from,),) => {
+        edits[i] = Some(Instruction::Load { dst, imm, },);
+        folded.extend([from, i,],);
+        known.insert(dst, (imm, i,),);
+      }
+      None => {
+        for reg in inst.writes().into_iter().chain(inst.implicit_writes(),) {
+          known.remove(&reg,);
+        }
+        match inst {
+          Instruction::Load { dst, imm, } => {
+            known.insert(*dst, (*imm, i,),);
+          }
+          Instruction::SysCall { .. } => known.clear(),
+          _ => {}
+        }
+      }
+    }
+    if ends_block(inst,) {
+      known.clear();
+    }
+  }
+
+  folded.sort_unstable();
+  folded.dedup();
+  for i in folded {
+    if let Some(Instruction::Load { dst, .. },) = edits[i] {
+      if is_dead(edits[i + 1..].iter().flatten(), dst,) {
+        edits[i] = None;
+      }
+    }
+  }
+  let mut edits = edits.into_iter();
+  program.rewrite_instructions(|_, _| edits.next().unwrap(),)
+}
+
+/// Returns the destination and result of `inst` if it is register and
+/// immediate arithmetic on a `known` register, along with the index of the
+/// `Load` the register came from.
+fn fold(inst:&Instruction, known:&BTreeMap<Register, (f32, usize,),>,) -> Option<(Register, f32, usize,),> {
+  let (dst, src, imm, op,):(_, _, _, fn(f32, f32,) -> f32,) = match *inst {
+    Instruction::AddRI { dst, src, imm, } => (dst, src, imm, |a, b| a + b,),
+    Instruction::SubRI { dst, src, imm, } => (dst, src, imm, |a, b| a - b,),
+    Instruction::RvSubRI { dst, src, imm, } => (dst, src, imm, |a, b| b - a,),
+    Instruction::MulRI { dst, src, imm, } => (dst, src, imm, |a, b| a * b,),
+    Instruction::DivRI { dst, src, imm, } => (dst, src, imm, |a, b| a / b,),
+    Instruction::RvDivRI { dst, src, imm, } => (dst, src, imm, |a, b| b / a,),
+    // `powf` needs the platform's math library
+    #[cfg(feature = "std")]
+    Instruction::PowRI { dst, src, imm, } => (dst, src, imm, f32::powf,),
+    #[cfg(feature = "std")]
+    Instruction::RvPowRI { dst, src, imm, } => (dst, src, imm, |a, b| b.powf(a,),),
+    _ => return None,
+  };
+  let &(value, from,) = known.get(&src,)?;
+  Some((dst, op(value, imm,), from,),)
+}
+
+/// Returns every offset control can arrive at other than by falling through:
+/// the entry point, symbols, and the targets of jumps and calls.
+fn jump_targets(program:&Program, insts:&[(u32, Instruction,)],) -> Vec<u32,> {
+  let mut targets = vec![program.entry_point()];
+  targets.extend(insts.iter().filter_map(|(at, inst,)| inst.resolve_target(*at,),),);
+  targets.extend(program.symbols().iter().map(|sym| sym.offset,),);
+  targets
+}
+
+/// Whether `reg` is overwritten before it is read by the instructions in
+/// `rest`. Anything that leaves the block may read it.
+fn is_dead<'a,>(rest:impl IntoIterator<Item = &'a Instruction,>, reg:Register,) -> bool {
+  for inst in rest {
+    if inst.reads().contains(&reg,) || inst.implicit_reads().contains(&reg,) {
+      return false;
+    }
+    if inst.writes().contains(&reg,) || inst.implicit_writes().contains(&reg,) {
+      return true;
+    }
+    if matches!(inst, Instruction::Hlt) {
+      return true;
+    }
+    if ends_block(inst,) || matches!(inst, Instruction::SysCall { .. }) {
+      return false;
+    }
+  }
+  true
+}
+
+#[cfg(test)]
+mod test {
+  use super::{eliminate_dead_code, fold_constants, peephole, peephole_with, PeepholeRule};
+  use crate::{
+    builder::ProgramBuilder,
+    instruction::Instruction,
+    opcodes::CmpFlag,
+    program::Program,
+    registers::{reg, Register},
+  };
+  use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+  };
+
+  /// Returns the instruction the jump at `at` in `program` lands on. Its own
+  /// targets may have moved so tests compare opcodes.
+  fn landing(program:&Program, at:u32,) -> Instruction {
+    let (_, inst,) = program
+      .instructions()
+      .find(|item| item.unwrap().0 == at,)
+      .unwrap()
+      .unwrap();
+    let target = inst.resolve_target(at,).unwrap();
+    program
+      .instructions()
+      .find(|item| item.unwrap().0 == target,)
+      .unwrap()
+      .unwrap()
+      .1
+  }
+
+  #[test]
+  fn remove_unreachable_code() {
+    let mut b = ProgramBuilder::new();
+    let func = b.named_label("func",);
+    let unused = b.named_label("unused",);
+    let end = b.new_label();
+    let skip = b.new_label();
+    b.call(func,);
+    b.jmp(end,);
+    b.emit(Instruction::Noop,);
+    b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:1.0,
+    },);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Hlt,);
+    b.bind(unused,).unwrap();
+    b.call(unused,);
+    b.emit(Instruction::Ret { args:0, },);
+    b.bind(func,).unwrap();
+    b.jz(Register::EQ, skip,);
+    b.emit(Instruction::Inc { dst:reg(16,), },);
+    b.bind(skip,).unwrap();
+    b.emit(Instruction::Ret { args:0, },);
+    let p = b.finish().unwrap();
+    assert!(p.symbols().get("unused",).is_some());
+
+    let pruned = eliminate_dead_code(&p,).unwrap();
+    assert_eq!(
+      pruned.to_string(),
+      "Call 11\nJmp 10\nHlt\nJz $EQ, 19\nInc $16\nRet 0\n"
+    );
+    assert_eq!(pruned.validate_strict(), Ok(()));
+    assert_eq!(pruned.symbols().get("func",), Some(11));
+    assert_eq!(pruned.symbols().get("unused",), None);
+    assert!(pruned
+      .relocations()
+      .iter()
+      .all(|reloc| (reloc.offset as usize) < pruned.len()));
+
+    // The surviving jumps land on the same instructions
+    for (old, new,) in [(0, 0,), (5, 5,), (25, 11,),] {
+      assert_eq!(landing(&p, old,).opcode(), landing(&pruned, new,).opcode());
+    }
+    assert_eq!(eliminate_dead_code(&pruned,).unwrap(), pruned);
+  }
+
+  #[test]
+  fn keep_code_after_indirect_jumps() {
+    let mut p = Program::new();
+    p.emit_jmp_r(reg(16,),);
+    p.emit(Instruction::Hlt,);
+    p.emit(Instruction::Noop,);
+    assert_eq!(eliminate_dead_code(&p,).unwrap(), p);
+  }
+
+  /// Runs the rule on its own, with every other rule, and with every rule but
+  /// it. Returns the listing after it is applied.
+  fn apply(rule:PeepholeRule, p:&Program,) -> String {
+    let without = PeepholeRule::ALL
+      .into_iter()
+      .filter(|&other| other != rule,)
+      .collect::<Vec<_,>>();
+    assert_eq!(peephole_with(p, &without,).unwrap(), *p);
+    let applied = peephole_with(p, &[rule,],).unwrap();
+    assert_eq!(peephole(p,).unwrap(), applied);
+    assert_eq!(applied.validate_strict(), Ok(()));
+    applied.to_string()
+  }
+
+  #[test]
+  fn forward_loads() {
+    let mut p = Program::new();
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    p.emit(Instruction::Copy {
+      dst:reg(17,),
+      src:reg(16,),
+    },);
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:3.0,
+    },);
+    p.emit(Instruction::Copy {
+      dst:reg(18,),
+      src:reg(16,),
+    },);
+    p.emit(Instruction::Inc { dst:reg(16,), },);
+    p.emit(Instruction::Hlt,);
+    assert_eq!(
+      p.to_string(),
+      "Load $16, 2.0\nCopy $17, $16\nLoad $16, 3.0\nCopy $18, $16\nInc $16\nHlt\n"
+    );
+    // The second load is still read by `Inc`
+    assert_eq!(
+      apply(PeepholeRule::ForwardLoad, &p,),
+      "Load $17, 2.0\nLoad $16, 3.0\nCopy $18, $16\nInc $16\nHlt\n"
+    );
+  }
+
+  #[test]
+  fn drop_identities() {
+    let mut p = Program::new();
+    p.emit(Instruction::AddRI {
+      dst:reg(16,),
+      src:reg(16,),
+      imm:0.0,
+    },);
+    p.emit(Instruction::MulRI {
+      dst:reg(16,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    p.emit(Instruction::MulRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    p.emit(Instruction::Hlt,);
+    assert_eq!(
+      p.to_string(),
+      "Add_RI $16, $16, 0.0\nMul_RI $16, $16, 1.0\nMul_RI $17, $16, 1.0\nHlt\n"
+    );
+    assert_eq!(
+      apply(PeepholeRule::DropIdentity, &p,),
+      "Mul_RI $17, $16, 1.0\nHlt\n"
+    );
+  }
+
+  #[test]
+  fn invert_comparisons() {
+    let mut b = ProgramBuilder::new();
+    let end = b.new_label();
+    b.emit(Instruction::CmpRI {
+      flag:CmpFlag::Lt,
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.emit(Instruction::Not {
+      dst:Register::EQ,
+      src:Register::EQ,
+    },);
+    b.jz(Register::EQ, end,);
+    b.emit(Instruction::CmpRR {
+      flag:CmpFlag::Eq,
+      a:reg(16,),
+      b:reg(17,),
+    },);
+    b.emit(Instruction::Not {
+      dst:Register::EQ,
+      src:Register::EQ,
+    },);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(
+      p.to_string(),
+      "Cmp_RI LT, $16, 1.0\nNot $EQ, $EQ\nJz $EQ, 23\nCmp_RR EQ, $16, $17\nNot $EQ, $EQ\nHlt\n"
+    );
+    assert_eq!(
+      apply(PeepholeRule::InvertCmp, &p,),
+      "Cmp_RI GEQ, $16, 1.0\nJz $EQ, 17\nCmp_RR NEQ, $16, $17\nHlt\n"
+    );
+  }
+
+  #[test]
+  fn remove_noops() {
+    let mut b = ProgramBuilder::new();
+    let end = b.new_label();
+    b.emit(Instruction::Noop,);
+    b.jmp(end,);
+    b.emit(Instruction::Noop,);
+    b.emit(Instruction::Noop,);
+    b.bind(end,).unwrap();
+    b.emit(Instruction::Noop,);
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(p.to_string(), "Noop\nJmp 8\nNoop\nNoop\nNoop\nHlt\n");
+    assert_eq!(apply(PeepholeRule::RemoveNoops, &p,), "Noop\nJmp 6\nHlt\n");
+  }
+
+  #[test]
+  fn keep_pairs_split_by_jump_targets() {
+    let mut b = ProgramBuilder::new();
+    let not = b.new_label();
+    b.jmp(not,);
+    b.emit(Instruction::CmpRI {
+      flag:CmpFlag::Lt,
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.bind(not,).unwrap();
+    b.emit(Instruction::Not {
+      dst:Register::EQ,
+      src:Register::EQ,
+    },);
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(peephole(&p,).unwrap(), p);
+  }
+
+  #[test]
+  fn fold_constant_arithmetic() {
+    let mut p = Program::new();
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    p.emit(Instruction::MulRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:3.0,
+    },);
+    p.emit(Instruction::AddRI {
+      dst:reg(17,),
+      src:reg(17,),
+      imm:1.0,
+    },);
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:3.0,
+    },);
+    p.emit(Instruction::RvSubRI {
+      dst:reg(18,),
+      src:reg(16,),
+      imm:10.0,
+    },);
+    p.emit(Instruction::RvDivRI {
+      dst:reg(19,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    // Powers are only folded with `std`
+    #[cfg(feature = "std")]
+    p.emit(Instruction::RvPowRI {
+      dst:reg(20,),
+      src:reg(16,),
+      imm:2.0,
+    },);
+    // Registers may be read after returning so the last ones are kept
+    p.emit(Instruction::Ret { args:0, },);
+
+    let folded = fold_constants(&p,).unwrap();
+    let loads = folded
+      .instructions()
+      .filter_map(|item| match item.unwrap().1 {
+        Instruction::Load { dst, imm, } => Some((dst, imm.to_bits(),),),
+        Instruction::Ret { .. } => None,
+        inst => panic!("{inst:?} was not folded"),
+      },)
+      .collect::<Vec<_,>>();
+    let expected = [
+      (reg(17,), 7.0f32.to_bits(),),
+      (reg(16,), 3.0f32.to_bits(),),
+      (reg(18,), 7.0f32.to_bits(),),
+      (reg(19,), (1.0f32 / 3.0).to_bits(),),
+    ];
+    #[cfg(feature = "std")]
+    let expected = [&expected[..], &[(reg(20,), 8.0f32.to_bits(),),],].concat();
+    assert_eq!(loads, expected);
+    assert_eq!(fold_constants(&folded,).unwrap().code(), folded.code());
+  }
+
+  #[test]
+  fn forget_constants() {
+    let mut b = ProgramBuilder::new();
+    let block = b.new_label();
+    b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    b.emit(Instruction::SysCall { idx:0, },);
+    b.emit(Instruction::AddRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    b.emit(Instruction::RMem {
+      dst:reg(16,),
+      src:reg(18,),
+      imm_offset:0,
+      reg_offset:Register::PC,
+    },);
+    b.emit(Instruction::AddRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    b.bind(block,).unwrap();
+    b.emit(Instruction::AddRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.jz(Register::EQ, block,);
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(fold_constants(&p,).unwrap(), p);
+  }
+
+  #[test]
+  fn passes_keep_metadata() {
+    let mut p = Program::new();
+    p.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:2.0,
+    },);
+    p.emit(Instruction::AddRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    p.emit(Instruction::Noop,);
+    p.emit(Instruction::Noop,);
+    p.emit(Instruction::Hlt,);
+    // Unreachable
+    p.emit(Instruction::Noop,);
+    p.metadata_mut().set_name("enemy_ai",).unwrap();
+    p.metadata_mut()
+      .set_source_path("scripts/enemy_ai.script",)
+      .unwrap();
+
+    for optimized in [
+      peephole(&p,).unwrap(),
+      fold_constants(&p,).unwrap(),
+      eliminate_dead_code(&p,).unwrap(),
+    ] {
+      assert_ne!(optimized.code(), p.code());
+      assert_eq!(optimized.metadata(), p.metadata());
+    }
+  }
+}
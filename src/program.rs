@@ -1,52 +1,207 @@
-use crate::opcodes::{CmpFlag, OpCode};
-use eyre::Result;
-use std::{
+#[cfg(feature = "compress")]
+use crate::compress::{self, Level};
+#[cfg(feature = "std")]
+use crate::errors::SaveStage;
+#[cfg(feature = "mmap")]
+use crate::mmap::MappedProgram;
+use crate::{
+  analysis::{self, ProgramStats},
+  asm,
+  debug::DebugInfo,
+  diff::{self, ProgramDiff},
+  disasm::{self, DisasmOptions},
+  errors::{
+    AsmError, DecodeError, LinkError, PatchError, ProgramError, ReassembleError, SliceError, SymbolError,
+    ValidationError,
+  },
+  format::{self, SectionKind},
+  instruction::Instruction,
+  memory::{Address, MemoryLayout},
+  metadata::ProgramMetadata,
+  opcodes::{CmpFlag, IsaVersion, OpCode, CURRENT_ISA_VERSION},
+  registers::{Register, FIRST_FREE_REGISTER, REG_COUNT},
+  relocs::{self, RelocKind, Relocation},
+  symbols::SymbolTable,
+  syscalls::SyscallTable,
+};
+use alloc::{
+  format,
+  string::{String, ToString},
+  vec::{self, Vec},
+};
+use core::{
+  borrow::Borrow,
   fmt::{Debug, Display},
+  hash::{Hash, Hasher},
+  ops::{Index, IndexMut, Range, RangeFrom, RangeFull, RangeTo},
+  slice,
+};
+#[cfg(feature = "std")]
+use std::{
   fs::File,
   io::{Read, Write},
-  mem::transmute,
-  ops::{Index, IndexMut, Range},
+  path::{Path, PathBuf},
+  sync::OnceLock,
 };
 
-// Refactor:
-// - Add a thing so a target can only be updated once?
-// - Add better errors to the save/load functions
+// Without `std` there is no `OnceLock` so programs are not `Sync`
+#[cfg(not(feature = "std"))]
+type OnceLock<T,> = core::cell::OnceCell<T,>;
 
 #[derive(Clone,)]
 /// A VM program.
 ///
 /// - `Program` is indexed with [`u32`] so every index into it is `[u8;4]`.
+/// - Programs are compared and hashed by their code alone, like the `[u8]` they
+///   borrow as, so they can key maps looked up by bytes. Compare
+///   [`Program::data`], [`Program::symbols`], [`Program::metadata`],
+///   [`Program::debug_info`], and the rest separately where they matter.
+/// - Every multi-byte operand is encoded little-endian, both in memory and in
+///   saved files, so programs disassemble the same on any host.
+/// - A program may carry a data segment of bytes the VM copies into memory
+///   before running it, one byte per memory cell.
 pub struct Program {
   inner:Vec<u8,>,
+  data:Vec<u8,>,
+  /// Memory address the data segment is copied to.
+  data_base:u32,
+  /// Offset execution starts at.
+  entry_point:u32,
+  symbols:SymbolTable,
+  /// Sorted by offset.
+  relocations:Vec<Relocation,>,
+  metadata:ProgramMetadata,
+  debug_info:DebugInfo,
+  boundaries:BoundaryCache,
+}
+
+#[derive(Debug, Clone, Default,)]
+/// Lazily built offsets of every instruction in a [`Program`], up to the first
+/// which fails to decode, and the error it failed with. Cleared whenever the
+/// program's code is mutated.
+struct BoundaryCache(OnceLock<(Vec<u32,>, Option<DecodeError,>,),>,);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+/// A run of bytes in a [`Program`]'s data segment created by
+/// [`Program::add_data`].
+pub struct DataHandle {
+  offset:u32,
+  len:u32,
+}
+
+impl DataHandle {
+  /// Offset of the bytes from the start of the data segment.
+  pub fn offset(&self,) -> u32 {
+    self.offset
+  }
+
+  /// Number of bytes, and so memory cells, the data spans.
+  pub fn len(&self,) -> u32 {
+    self.len
+  }
+
+  pub fn is_empty(&self,) -> bool {
+    self.len == 0
+  }
 }
 
 impl<const N: usize,> From<&[u8; N],> for Program {
   fn from(value:&[u8; N],) -> Self {
-    Program {
-      inner:Vec::from(value,),
-    }
+    Program::from(Vec::from(value,),)
   }
 }
 
 impl<const N: usize,> From<[u8; N],> for Program {
   fn from(value:[u8; N],) -> Self {
-    Program {
-      inner:Vec::from(value,),
-    }
+    Program::from(Vec::from(value,),)
   }
 }
 
 impl From<&[u8],> for Program {
   fn from(value:&[u8],) -> Self {
-    Program {
-      inner:Vec::from(value,),
-    }
+    Program::from(Vec::from(value,),)
   }
 }
 
 impl From<Vec<u8,>,> for Program {
   fn from(value:Vec<u8,>,) -> Self {
-    Program { inner:value, }
+    Program {
+      inner:value,
+      ..Program::new()
+    }
+  }
+}
+
+impl FromIterator<u8,> for Program {
+  fn from_iter<T:IntoIterator<Item = u8,>,>(iter:T,) -> Self {
+    Program::from(Vec::from_iter(iter,),)
+  }
+}
+
+impl Extend<u8,> for Program {
+  fn extend<T:IntoIterator<Item = u8,>,>(&mut self, iter:T,) {
+    self.code_mut().extend(iter,);
+  }
+}
+
+impl<'a,> Extend<&'a u8,> for Program {
+  fn extend<T:IntoIterator<Item = &'a u8,>,>(&mut self, iter:T,) {
+    self.code_mut().extend(iter,);
+  }
+}
+
+impl IntoIterator for Program {
+  type Item = u8;
+  type IntoIter = vec::IntoIter<u8,>;
+
+  /// Iterates over the code, dropping the rest of the program.
+  fn into_iter(self,) -> Self::IntoIter {
+    self.inner.into_iter()
+  }
+}
+
+impl<'a,> IntoIterator for &'a Program {
+  type Item = &'a u8;
+  type IntoIter = slice::Iter<'a, u8,>;
+
+  fn into_iter(self,) -> Self::IntoIter {
+    self.inner.iter()
+  }
+}
+
+impl<'a,> IntoIterator for &'a mut Program {
+  type Item = &'a mut u8;
+  type IntoIter = slice::IterMut<'a, u8,>;
+
+  fn into_iter(self,) -> Self::IntoIter {
+    self.code_mut().iter_mut()
+  }
+}
+
+impl PartialEq for Program {
+  fn eq(&self, other:&Self,) -> bool {
+    self.inner == other.inner
+  }
+}
+
+impl Eq for Program {}
+
+impl Hash for Program {
+  /// Hashes the code the same way as the `[u8]` it borrows as.
+  fn hash<H:Hasher,>(&self, state:&mut H,) {
+    self.inner.as_slice().hash(state,);
+  }
+}
+
+impl AsRef<[u8],> for Program {
+  fn as_ref(&self,) -> &[u8] {
+    &self.inner
+  }
+}
+
+impl Borrow<[u8],> for Program {
+  fn borrow(&self,) -> &[u8] {
+    &self.inner
   }
 }
 
@@ -58,321 +213,3577 @@ impl Index<u32,> for Program {
   }
 }
 
+impl Index<Range<u32,>,> for Program {
+  type Output = [u8];
+
+  fn index(&self, index:Range<u32,>,) -> &[u8] {
+    &self.inner[index.start as usize..index.end as usize]
+  }
+}
+
+impl Index<RangeTo<u32,>,> for Program {
+  type Output = [u8];
+
+  fn index(&self, index:RangeTo<u32,>,) -> &[u8] {
+    &self.inner[..index.end as usize]
+  }
+}
+
+impl Index<RangeFrom<u32,>,> for Program {
+  type Output = [u8];
+
+  fn index(&self, index:RangeFrom<u32,>,) -> &[u8] {
+    &self.inner[index.start as usize..]
+  }
+}
+
+impl Index<RangeFull,> for Program {
+  type Output = [u8];
+
+  fn index(&self, _:RangeFull,) -> &[u8] {
+    &self.inner
+  }
+}
+
 impl IndexMut<u32,> for Program {
   fn index_mut(&mut self, index:u32,) -> &mut Self::Output {
-    &mut self.inner[index as usize]
+    &mut self.code_mut()[index as usize]
   }
 }
 
 impl Display for Program {
-  fn fmt(&self, f:&mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
-    let mut output = String::new();
-    let mut src = self.inner.clone().into_iter();
-
-    while let Some(val,) = src.next() {
-      let op = OpCode::from(val,);
-      match op {
-        OpCode::Load => {
-          let target = src.next().unwrap();
-          let num = unsafe { transmute::<[u8; 4], f32,>(src.next_chunk::<4>().unwrap(),) };
-          output.push_str(&format!("{} ${}, {}", op, target, num),);
-        }
-        OpCode::AddRI
-        | OpCode::SubRI
-        | OpCode::MulRI
-        | OpCode::DivRI
-        | OpCode::PowRI
-        | OpCode::RvSubRI
-        | OpCode::RvDivRI
-        | OpCode::RvPowRI => {
-          let target = src.next().unwrap();
-          let a = src.next().unwrap();
-          let b = unsafe { transmute::<[u8; 4], f32,>(src.next_chunk::<4>().unwrap(),) };
-          output.push_str(&format!("{} ${}, ${}, {}", op, target, a, b),);
-        }
-        OpCode::AddRR | OpCode::SubRR | OpCode::MulRR | OpCode::DivRR | OpCode::PowRR => {
-          let target = src.next().unwrap();
-          let a = src.next().unwrap();
-          let b = src.next().unwrap();
-          output.push_str(&format!("{} ${}, ${}, ${}", op, target, a, b),);
-        }
-        OpCode::Jmp => {
-          let idx = unsafe { transmute::<[u8; 4], u32,>(src.next_chunk::<4>().unwrap(),) };
-          output.push_str(&format!("{} {}", op, idx),);
-        }
-        OpCode::Jnz | OpCode::Jz => {
-          let cond = match src.next() {
-            Some(a,) if a == 2 => "EQ",
-            Some(a,) => &a.to_string(),
-            None => unreachable!(),
-          };
-          let idx = unsafe { transmute::<[u8; 4], u32,>(src.next_chunk::<4>().unwrap(),) };
-          output.push_str(&format!("{} ${}, {}", op, cond, idx),);
-        }
-        OpCode::CmpRI => {
-          let fl = CmpFlag::from(src.next().unwrap(),);
-          let a = src.next().unwrap();
-          let b = unsafe { transmute::<[u8; 4], f32,>(src.next_chunk::<4>().unwrap(),) };
-          output.push_str(&format!("{} {}, ${}, {}", op, fl, a, b),);
-        }
-        OpCode::CmpRR => {
-          let fl = CmpFlag::from(src.next().unwrap(),);
-          let a = src.next().unwrap();
-          let b = src.next().unwrap();
-          output.push_str(&format!("{} {}, ${}, ${}", op, fl, a, b),);
-        }
-        OpCode::Not | OpCode::WriteStr => {
-          let a = match src.next() {
-            Some(a,) if a == 2 => "EQ",
-            Some(a,) => &a.to_string(),
-            None => unreachable!(),
-          };
-          let b = src.next().unwrap();
-          output.push_str(&format!("{} ${}, ${}", op, a, b),);
-        }
-        OpCode::Copy | OpCode::MemCpy => {
-          let rd = src.next().unwrap();
-          let r0 = src.next().unwrap();
-          output.push_str(&format!("{} ${}, ${}", op, rd, r0,),);
-        }
-        OpCode::Call | OpCode::SysCall | OpCode::Ret => {
-          output.push_str(&format!("{} {}", op, src.next().unwrap()),)
-        }
-        OpCode::Alloc | OpCode::Realloc => {
-          let dst = src.next().unwrap();
-          let r0 = src.next().unwrap();
-          output.push_str(&format!("{} ${}, ${}", op, dst, r0),);
+  /// Prints one instruction per line followed by a hex and ASCII dump of the
+  /// data segment, if there is one. The dump is commented out so the listing
+  /// still assembles.
+  ///
+  /// The alternate form (`{:#}`) prefixes each line with the instruction's
+  /// offset and raw bytes, follows relative jumps with their absolute
+  /// target, and comments the source line each instruction was compiled from
+  /// whenever it changes.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    Display::fmt(
+      &Listing::new(self.as_slice(), &self.symbols, self.data_segment(),).with_debug_info(&self.debug_info,),
+      f,
+    )
+  }
+}
+
+/// A [`Program`]'s listing with `SysCall`s named by a [`SyscallTable`].
+/// Created by [`Program::with_syscalls`].
+pub struct Listing<'a,> {
+  code:&'a [u8],
+  symbols:&'a SymbolTable,
+  data:Option<(Address, &'a [u8],),>,
+  syscalls:Option<&'a SyscallTable,>,
+  debug_info:Option<&'a DebugInfo,>,
+}
+
+impl<'a,> Listing<'a,> {
+  /// Lists `code`, naming calls with `symbols` and dumping `data` after it.
+  pub(crate) fn new(code:&'a [u8], symbols:&'a SymbolTable, data:Option<(Address, &'a [u8],),>,) -> Self {
+    Listing {
+      code,
+      symbols,
+      data,
+      syscalls:None,
+      debug_info:None,
+    }
+  }
+
+  /// Comments the source lines of instructions in the alternate form.
+  pub(crate) fn with_debug_info(self, debug_info:&'a DebugInfo,) -> Self {
+    Listing {
+      debug_info:Some(debug_info,),
+      ..self
+    }
+  }
+}
+
+impl Display for Listing<'_,> {
+  /// Formats the listing the same way as [`Program`]'s `Display`
+  /// implementation, including the alternate form.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    let inner = self.code;
+    if !f.alternate() {
+      for (offset, _, inst,) in listing_lines(inner,) {
+        match inst {
+          Some(inst,) => write_instruction(f, &inst, self.syscalls,)?,
+          None => write!(f, "??? (0x{:02X})", inner[offset])?,
         }
-        OpCode::RMem | OpCode::WMem => {
-          let rd = src.next().unwrap();
-          let r0 = src.next().unwrap();
-          let i_o = unsafe { transmute::<[u8; 4], u32,>(src.next_chunk::<4>().unwrap(),) };
-          let r_o = src.next().unwrap();
-          output.push_str(&format!("{} ${}, ${}, {}, ${}", op, rd, r0, i_o, r_o),);
+        writeln!(f)?;
+      }
+      return write_data(f, self.data,);
+    }
+
+    // Pad the raw bytes to the longest instruction so the mnemonics line up
+    let width = listing_lines(inner,)
+      .map(|(_, len, _,)| len * 3 - 1,)
+      .max()
+      .unwrap_or_default();
+    let mut line = None;
+    for (offset, len, inst,) in listing_lines(inner,) {
+      let loc = self
+        .debug_info
+        .and_then(|debug_info| debug_info.lookup(offset as u32,),);
+      if let Some(loc,) = loc {
+        if line != Some((loc.file, loc.line,),) {
+          writeln!(f, "; {}:{}", loc.file, loc.line)?;
+          line = Some((loc.file, loc.line,),);
         }
-        OpCode::Dealloc | OpCode::Push | OpCode::PopR => {
-          output.push_str(&format!("{} ${}", op, src.next().unwrap()),)
+      }
+      write!(f, "0x{:04X}:  ", offset)?;
+      write_hex(f, &inner[offset..offset + len],)?;
+      write!(f, "{:1$}   ", "", width - (len * 3 - 1))?;
+      let Some(inst,) = inst
+      else {
+        writeln!(f, "??? (0x{:02X})", inner[offset])?;
+        continue;
+      };
+
+      // The annotated listing names called functions
+      let symbol = match inst {
+        Instruction::Call { target, } => self
+          .symbols
+          .extern_at(offset as u32,)
+          .or_else(|| self.symbols.at(target,).map(|symbol| symbol.name.as_str(),),),
+        _ => None,
+      };
+      match symbol {
+        Some(name,) => write!(f, "{} <{}>", inst.opcode(), name)?,
+        None => write_instruction(f, &inst, self.syscalls,)?,
+      }
+      let target = inst
+        .relative_offset()
+        .and_then(|_| inst.target_address(offset as u32,),);
+      match target {
+        Some(target,) => writeln!(f, " ; -> {}", target)?,
+        None => writeln!(f)?,
+      }
+    }
+    write_data(f, self.data,)
+  }
+}
+
+/// Iterates over the lines of a listing of `code` as their offset, length,
+/// and instruction.
+///
+/// Each instruction is decoded from exactly the bytes its opcode spans. Bytes
+/// which cannot be decoded are one byte lines without an instruction and
+/// decoding resumes at the next byte.
+pub(crate) fn listing_lines(
+  code:&[u8],
+) -> impl Iterator<Item = (usize, usize, Option<Instruction,>,),> + '_ {
+  let mut offset = 0;
+  core::iter::from_fn(move || {
+    if offset >= code.len() {
+      return None;
+    }
+    let inst = OpCode::try_from(code[offset],)
+      .ok()
+      .and_then(|op| code.get(offset..offset + op.instruction_len(),),)
+      .and_then(|bytes| Instruction::decode(bytes,).ok(),);
+    let line = match inst {
+      Some((inst, len,),) => (offset, len, Some(inst,),),
+      None => (offset, 1, None,),
+    };
+    offset += line.1;
+    Some(line,)
+  },)
+}
+
+/// Writes `bytes` in hex separated by spaces.
+pub(crate) fn write_hex(f:&mut impl core::fmt::Write, bytes:&[u8],) -> core::fmt::Result {
+  for (i, byte,) in bytes.iter().enumerate() {
+    if i > 0 {
+      f.write_str(" ",)?;
+    }
+    write!(f, "{:02X}", byte)?;
+  }
+  Ok((),)
+}
+
+/// Returns `imm` as a `u32` if it is exactly one.
+fn exact_u32(imm:f32,) -> Option<u32,> {
+  // `u32::MAX as f32` rounds up to 2^32, which is not a `u32`
+  ((0.0..u32::MAX as f32).contains(&imm,) && imm as u32 as f32 == imm).then_some(imm as u32,)
+}
+
+/// Writes a hex and ASCII dump of a [`Program`]'s data segment, 16 bytes per
+/// line, with each line commented out.
+fn write_data(f:&mut core::fmt::Formatter<'_,>, data:Option<(Address, &[u8],),>,) -> core::fmt::Result {
+  let Some((base, data,),) = data
+  else {
+    return Ok((),);
+  };
+  writeln!(f, "; data at {} ({} bytes)", base, data.len())?;
+  write_hex_rows(f, ";   ", base.get(), data,)
+}
+
+/// Writes a hex and ASCII dump of `bytes`, which start at `base`, 16 bytes per
+/// line with each line starting with `prefix`.
+fn write_hex_rows(f:&mut impl core::fmt::Write, prefix:&str, base:u32, bytes:&[u8],) -> core::fmt::Result {
+  for (row, chunk,) in bytes.chunks(16,).enumerate() {
+    write!(f, "{}0x{:04X}:  ", prefix, base + row as u32 * 16)?;
+    write_hex(f, chunk,)?;
+    write!(f, "{:1$}  |", "", 47 - (chunk.len() * 3 - 1))?;
+    for &byte in chunk {
+      match byte.is_ascii_graphic() || byte == b' ' {
+        true => write!(f, "{}", byte as char)?,
+        false => f.write_str(".",)?,
+      }
+    }
+    writeln!(f, "|")?;
+  }
+  Ok((),)
+}
+
+/// Writes the `lines` of `code` from `first` on for
+/// [`Program::disassemble_around`], marking the line at index `at`.
+fn write_lines_around(
+  f:&mut impl core::fmt::Write,
+  code:&[u8],
+  lines:&[(usize, usize, Option<Instruction,>,)],
+  first:usize,
+  at:usize,
+) -> core::fmt::Result {
+  let width = lines[first..]
+    .iter()
+    .map(|(_, len, _,)| len * 3 - 1,)
+    .max()
+    .unwrap_or_default();
+  for (i, &(start, len, inst,),) in lines.iter().enumerate().skip(first,) {
+    let marker = if i == at { ">" } else { " " };
+    write!(f, "{} 0x{:04X}:  ", marker, start)?;
+    write_hex(f, &code[start..start + len],)?;
+    write!(f, "{:1$}   ", "", width - (len * 3 - 1))?;
+    match inst {
+      Some(inst,) => {
+        write_instruction(f, &inst, None,)?;
+        // The bytes skipped before it may have been its operands
+        if i > 0 && lines[i - 1].2.is_none() {
+          f.write_str(" ; resynchronized",)?;
         }
-        OpCode::Hlt | OpCode::Pop | OpCode::Noop => output.push_str(&format!("{}", op),),
       }
-      output.push('\n',);
+      None => write!(f, "??? (0x{:02X}) ; unsynchronized", code[start])?,
+    }
+    writeln!(f)?;
+  }
+  Ok((),)
+}
+
+/// Writes `inst` as it appears in a [`Program`]'s listing, which is its
+/// `Display` implementation except that `SysCall`s in `syscalls` are printed
+/// by name along with their argument count.
+fn write_instruction(
+  f:&mut impl core::fmt::Write,
+  inst:&Instruction,
+  syscalls:Option<&SyscallTable,>,
+) -> core::fmt::Result {
+  if let Instruction::SysCall { idx, } = *inst {
+    if let Some(syscall,) = syscalls.and_then(|table| table.get(idx,),) {
+      let plural = if syscall.args == 1 { "" } else { "s" };
+      return write!(
+        f,
+        "{} {} ({} arg{})",
+        inst.opcode(),
+        syscall.name,
+        syscall.args,
+        plural
+      );
     }
-    write!(f, "{output}",)
   }
+  write!(f, "{}", inst)
 }
 
 impl Debug for Program {
-  fn fmt(&self, f:&mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
     Display::fmt(&self, f,)
   }
 }
 
+impl Default for Program {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Program {
   pub fn new() -> Self {
-    Self { inner:Vec::new(), }
+    Self {
+      inner:Vec::new(),
+      data:Vec::new(),
+      data_base:MemoryLayout::default().heap_start(),
+      entry_point:0,
+      symbols:SymbolTable::new(),
+      relocations:Vec::new(),
+      metadata:ProgramMetadata::new(),
+      debug_info:DebugInfo::new(),
+      boundaries:BoundaryCache::default(),
+    }
+  }
+
+  /// Creates an empty [`Program`] with room for `capacity` bytes of code.
+  pub fn with_capacity(capacity:usize,) -> Self {
+    Self {
+      inner:Vec::with_capacity(capacity,),
+      ..Self::new()
+    }
+  }
+
+  /// Returns how many bytes of code the [`Program`] can hold without
+  /// reallocating.
+  pub fn capacity(&self,) -> usize {
+    self.inner.capacity()
+  }
+
+  /// Reserves room for at least `additional` more bytes of code.
+  pub fn reserve(&mut self, additional:usize,) {
+    self.inner.reserve(additional,);
+  }
+
+  /// Shrinks the capacity of the code as much as possible.
+  pub fn shrink_to_fit(&mut self,) {
+    self.inner.shrink_to_fit();
+  }
+
+  /// Returns the code for mutation, clearing the cached instruction
+  /// boundaries. Every method which changes the code must go through this.
+  fn code_mut(&mut self,) -> &mut Vec<u8,> {
+    self.boundaries = BoundaryCache::default();
+    &mut self.inner
   }
 
   pub fn push(&mut self, value:u8,) {
-    self.inner.push(value,);
+    self.code_mut().push(value,);
   }
 
   /// Prepends arguments to the "front" (`Program.inner[0]`) of the
   /// [`Program`]'s inner vector.
-  pub fn push_front(&mut self, args:Vec<u8,>,) {
-    self.inner.splice(Range { start:0, end:0, }, args.into_iter(),);
+  pub fn push_front(&mut self, args:impl IntoIterator<Item = u8,>,) {
+    self.code_mut().splice(Range { start:0, end:0, }, args,);
   }
 
   pub fn extend_from_slice(&mut self, other:&[u8],) {
-    self.inner.extend_from_slice(other,);
+    self.code_mut().extend_from_slice(other,);
+  }
+
+  /// Appends `value` as 4 little-endian bytes, the encoding of every 4 byte
+  /// operand.
+  pub fn push_u32(&mut self, value:u32,) {
+    self.extend_from_slice(&value.to_le_bytes(),);
+  }
+
+  /// Appends an `i32` like [`Program::push_u32`].
+  pub fn push_i32(&mut self, value:i32,) {
+    self.extend_from_slice(&value.to_le_bytes(),);
+  }
+
+  /// Appends an `f32` like [`Program::push_u32`].
+  pub fn push_f32(&mut self, value:f32,) {
+    self.extend_from_slice(&value.to_le_bytes(),);
   }
 
   pub fn len(&self,) -> usize {
     self.inner.len()
   }
 
+  pub fn is_empty(&self,) -> bool {
+    self.inner.is_empty()
+  }
+
   pub fn as_slice(&self,) -> &[u8] {
     self.inner.as_slice()
   }
 
+  /// Returns a 64-bit FNV-1a hash of the code. Unlike [`Hash`], which may vary
+  /// between hashers and releases, the fingerprint of a program never changes
+  /// so it can be persisted.
+  pub fn fingerprint(&self,) -> u64 {
+    const OFFSET_BASIS:u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME:u64 = 0x0000_0100_0000_01b3;
+    self.inner.iter().fold(OFFSET_BASIS, |hash, &byte| {
+      (hash ^ byte as u64).wrapping_mul(PRIME,)
+    },)
+  }
+
   pub fn as_mut_slice(&mut self,) -> &mut [u8] {
-    self.inner.as_mut_slice()
+    self.code_mut().as_mut_slice()
   }
 
-  pub fn save(&self, output:&str,) -> Result<(),> {
-    // TODO: Add better errors?
-    let mut file = File::create(output,)?;
-    file.write_all(self.inner.as_slice(),)?;
-    Ok((),)
+  /// Returns the offset of every instruction in the [`Program`] in order.
+  ///
+  /// The offsets are found by decoding the program once and cached until its
+  /// code is next mutated.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn instruction_boundaries(&self,) -> Result<Vec<u32,>, DecodeError,> {
+    match self.boundaries() {
+      (starts, None,) => Ok(starts.clone(),),
+      (_, Some(err,),) => Err(*err,),
+    }
   }
 
-  pub fn load(source:&str,) -> Result<Self,> {
-    // TODO: Add better errors?
-    let mut file = File::open(source,)?;
-    let mut inner = Vec::new();
-    file.read_to_end(&mut inner,)?;
-    Ok(Program { inner, },)
+  /// Returns whether an instruction starts at `offset`. Instructions after
+  /// one which fails to decode are not found.
+  ///
+  /// Uses the same cache as [`Program::instruction_boundaries`].
+  pub fn is_instruction_start(&self, offset:u32,) -> bool {
+    self.boundaries().0.binary_search(&offset,).is_ok()
   }
-}
 
-#[cfg(test)]
-mod test {
-  use super::Program;
-  use crate::{
-    opcodes::{CmpFlag, OpCode},
-    registers::EQ,
-  };
-  use eyre::{eyre, Result};
-  use std::io::Write;
+  /// Compares the instructions of this program to those of `other`, which is
+  /// treated as the newer version. Instructions are matched up so one
+  /// inserted instruction does not make every later one differ. Instructions
+  /// with the same opcode and different operands are reported as changed
+  /// rather than removed and inserted.
+  ///
+  /// Errors if either program cannot be decoded.
+  pub fn diff(&self, other:&Program,) -> Result<ProgramDiff, DecodeError,> {
+    diff::diff(self, other,)
+  }
 
-  #[test]
-  #[rustfmt::skip]
-  fn opcode_printing_in_program_display() {
-    // Test Hlt
-    op_cmp([OpCode::Hlt.into(),], "Hlt",).unwrap();
-    // Test Load
-    op_cmp([OpCode::Load.into(), 14, 0, 0, 128, 63,], "Load $14, 1",).unwrap();
-    // Test Copy
-    op_cmp([OpCode::Copy.into(), 14, 15,], "Copy $14, $15",).unwrap();
-    // Test MemCpy
-    op_cmp([OpCode::MemCpy.into(), 14, 15,], "MemCpy $14, $15",).unwrap();
-    // Test Add_RI
-    op_cmp([OpCode::AddRI.into(), 14, 15, 0, 0, 128, 63,], "Add_RI $14, $15, 1",).unwrap();
-    // Test Sub_RI
-    op_cmp([OpCode::SubRI.into(), 14, 15, 0, 0, 128, 63,], "Sub_RI $14, $15, 1",).unwrap();
-    // Test RvSubRI
-    op_cmp([OpCode::RvSubRI.into(), 14, 15, 0, 0, 128, 63,], "RvSub_RI $14, $15, 1",).unwrap();
-    // Test Mul_RI
-    op_cmp([OpCode::MulRI.into(), 14, 15, 0, 0, 128, 63,], "Mul_RI $14, $15, 1",).unwrap();
-    // Test Div_RI
-    op_cmp([OpCode::DivRI.into(), 14, 15, 0, 0, 128, 63,], "Div_RI $14, $15, 1",).unwrap();
-    // Test RvDivRI
-    op_cmp([OpCode::RvDivRI.into(), 14, 15, 0, 0, 128, 63,], "RvDiv_RI $14, $15, 1",).unwrap();
-    // Test Pow_RI
-    op_cmp([OpCode::PowRI.into(), 14, 15, 0, 0, 128, 63,], "Pow_RI $14, $15, 1",).unwrap();
-    // Test RvPowRI
-    op_cmp([OpCode::RvPowRI.into(), 14, 15, 0, 0, 128, 63,], "RvPow_RI $14, $15, 1",).unwrap();
-    // Test Add_RR
-    op_cmp([OpCode::AddRR.into(), 14, 15, 16,], "Add_RR $14, $15, $16",).unwrap();
-    // Test Sub_RR
-    op_cmp([OpCode::SubRR.into(), 14, 15, 16,], "Sub_RR $14, $15, $16",).unwrap();
-    // Test Mul_RR
-    op_cmp([OpCode::MulRR.into(), 14, 15, 16,], "Mul_RR $14, $15, $16",).unwrap();
-    // Test Div_RR
-    op_cmp([OpCode::DivRR.into(), 14, 15, 16,], "Div_RR $14, $15, $16",).unwrap();
-    // Test Pow_RR
-    op_cmp([OpCode::PowRR.into(), 14, 15, 16,], "Pow_RR $14, $15, $16",).unwrap();
-    // Test Cmp_RI
-    op_cmp([OpCode::CmpRI.into(), CmpFlag::Eq.into(), 14, 0, 0, 128, 63,], "Cmp_RI EQ, $14, 1",).unwrap();
-    // Test Cmp_RR
-    op_cmp([OpCode::CmpRR.into(), CmpFlag::Gt.into(), 14, 15,], "Cmp_RR GT, $14, $15",).unwrap();
-    // Test Not
-    op_cmp([OpCode::Not.into(), EQ as u8, 14], "Not $EQ, $14").unwrap();
-    // Test Jmp
-    op_cmp([OpCode::Jmp.into(), 50, 0, 0 ,0,], "Jmp 50").unwrap();
-    // Test Jz
-    op_cmp([OpCode::Jz.into(), 2, 50, 0, 0 ,0,], "Jz $EQ, 50").unwrap();
-    // Test Jnz
-    op_cmp([OpCode::Jnz.into(), 2, 50, 0, 0 ,0,], "Jnz $EQ, 50").unwrap();
-    // Test Call
-    op_cmp([OpCode::Call.into(), 14,], "Call 14").unwrap();
-    // Test SysCall
-    op_cmp([OpCode::SysCall.into(), 14,], "SysCall 14").unwrap();
-    // Test Ret
-    op_cmp([OpCode::Ret.into(), 14,], "Ret 14").unwrap();
-    // Test Alloc
-    op_cmp([OpCode::Alloc.into(), 14, 15,], "Alloc $14, $15").unwrap();
-    // Test Realloc
-    op_cmp([OpCode::Realloc.into(), 14, 15,], "Realloc $14, $15").unwrap();
-    // Test Dealloc
-    op_cmp([OpCode::Dealloc.into(), 14,], "Dealloc $14").unwrap();
-    // Test RMem
-    op_cmp([OpCode::RMem.into(), 14, 15, 1, 0, 0, 0, 16,], "RMem $14, $15, 1, $16").unwrap();
-    // Test WMem
-    op_cmp([OpCode::WMem.into(), 14, 15, 1, 0, 0, 0, 16,], "WMem $14, $15, 1, $16").unwrap();
-    // Test Push
-    op_cmp([OpCode::Push.into(), 14,], "Push $14").unwrap();
-    // Test Pop
-    op_cmp([OpCode::Pop.into(),], "Pop").unwrap();
-    // Test PopR
-    op_cmp([OpCode::PopR.into(), 14,], "PopR $14").unwrap();
-    // Test WriteStr
-    op_cmp([OpCode::WriteStr.into(), 15, 16,], "WriteStr $15, $16").unwrap();
-    // Test Noop
-    op_cmp([OpCode::Noop.into(),], "Noop").unwrap();
-    // Test All
-    op_cmp(
-    [
-        OpCode::Load.into(), 14, 0, 0, 128, 63, 
-        OpCode::Copy.into(), 14, 15,
-        OpCode::MemCpy.into(), 14, 15,
-        OpCode::AddRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::SubRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::RvSubRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::MulRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::DivRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::RvDivRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::PowRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::RvPowRI.into(), 14, 15, 0, 0, 128, 63,
-        OpCode::AddRR.into(), 14, 15, 16,
-        OpCode::SubRR.into(), 14, 15, 16,
-        OpCode::MulRR.into(), 14, 15, 16,
-        OpCode::DivRR.into(), 14, 15, 16,
-        OpCode::PowRR.into(), 14, 15, 16,
-        OpCode::CmpRI.into(), CmpFlag::Eq.into(), 14, 0, 0, 128, 63,
-        OpCode::CmpRR.into(), CmpFlag::Gt.into(), 14, 15,
-        OpCode::Not.into(), EQ as u8, 14,
-        OpCode::Jmp.into(), 50, 0, 0 ,0,
-        OpCode::Jz.into(), 2, 50, 0, 0 ,0,
-        OpCode::Jnz.into(), 2, 50, 0, 0 ,0,
-        OpCode::Call.into(), 14,
-        OpCode::SysCall.into(), 14,
-        OpCode::Ret.into(), 14,
-        OpCode::Alloc.into(), 14, 15,
-        OpCode::Realloc.into(), 14, 15,
-        OpCode::Dealloc.into(), 14,
-        OpCode::RMem.into(), 14, 15, 1, 0, 0, 0, 16,
-        OpCode::WMem.into(), 14, 15, 1, 0, 0, 0, 16,
-        OpCode::Push.into(), 14,
-        OpCode::Pop.into(),
-        OpCode::PopR.into(), 14,
-        OpCode::WriteStr.into(), 15, 16,
-        OpCode::Noop.into(),
-      ], "\
-      Load $14, 1\n\
-      Copy $14, $15\n\
-      MemCpy $14, $15\n\
-      Add_RI $14, $15, 1\n\
-      Sub_RI $14, $15, 1\n\
-      RvSub_RI $14, $15, 1\n\
-      Mul_RI $14, $15, 1\n\
-      Div_RI $14, $15, 1\n\
-      RvDiv_RI $14, $15, 1\n\
-      Pow_RI $14, $15, 1\n\
-      RvPow_RI $14, $15, 1\n\
-      Add_RR $14, $15, $16\n\
-      Sub_RR $14, $15, $16\n\
-      Mul_RR $14, $15, $16\n\
-      Div_RR $14, $15, $16\n\
-      Pow_RR $14, $15, $16\n\
-      Cmp_RI EQ, $14, 1\n\
-      Cmp_RR GT, $14, $15\n\
-      Not $EQ, $14\n\
-      Jmp 50\n\
-      Jz $EQ, 50\n\
-      Jnz $EQ, 50\n\
-      Call 14\n\
-      SysCall 14\n\
-      Ret 14\n\
-      Alloc $14, $15\n\
-      Realloc $14, $15\n\
-      Dealloc $14\n\
-      RMem $14, $15, 1, $16\n\
-      WMem $14, $15, 1, $16\n\
-      Push $14\n\
-      Pop\n\
-      PopR $14\n\
-      WriteStr $15, $16\n\
-      Noop").unwrap();
+  /// Returns the index of the highest register the program uses, or `None` if
+  /// it uses none.
+  ///
+  /// Registers used implicitly are included: `Cmp_RI` and `Cmp_RR` write
+  /// [`EQ`](crate::registers::EQ), stack instructions move
+  /// [`SP`](crate::registers::SP), and jumps and calls write
+  /// [`PC`](crate::registers::PC). These are all reserved registers below
+  /// [`FIRST_FREE_REGISTER`]. Use [`analysis::register_usage`] to tell them
+  /// apart from operands.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn max_register_used(&self,) -> Result<Option<u8,>, DecodeError,> {
+    let usage = analysis::register_usage(self,)?;
+    Ok(usage.max_register_used().map(|reg| reg.index(),),)
+  }
+
+  /// Returns how many registers a VM needs to run the program: one more than
+  /// [`Program::max_register_used`], but always at least every reserved
+  /// register and one free register.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn required_registers(&self,) -> Result<usize, DecodeError,> {
+    let max = self.max_register_used()?.map_or(0, |max| max as usize + 1,);
+    Ok(max.max(FIRST_FREE_REGISTER + 1,),)
+  }
+
+  /// Returns the oldest [`IsaVersion`] with every opcode and comparison flag
+  /// the program uses, which is the version [`Program::save`] records so VMs
+  /// supporting only older versions can still load it.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn required_isa_version(&self,) -> Result<IsaVersion, DecodeError,> {
+    let mut version = IsaVersion(1,);
+    for item in self.instructions() {
+      let (_, inst,) = item?;
+      version = version.max(inst.isa_version(),);
+    }
+    Ok(version,)
+  }
+
+  /// Counts the instructions of each [`OpCode`] in the program and the bytes
+  /// they take up.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn stats(&self,) -> Result<ProgramStats, DecodeError,> {
+    let mut stats = ProgramStats::default();
+    for item in self.instructions() {
+      let (_, inst,) = item?;
+      stats.add(inst.opcode(),);
+    }
+    Ok(stats,)
+  }
+
+  fn boundaries(&self,) -> &(Vec<u32,>, Option<DecodeError,>,) {
+    self.boundaries.0.get_or_init(|| {
+      let mut starts = Vec::new();
+      for item in self.instructions() {
+        match item {
+          Ok((at, _,),) => starts.push(at,),
+          Err(err,) => return (starts, Some(err,),),
+        }
+      }
+      (starts, None,)
+    },)
+  }
+
+  /// Reads the little-endian [`u32`] stored at `offset`.
+  pub fn read_u32_at(&self, offset:u32,) -> Result<u32, PatchError,> {
+    Ok(u32::from_le_bytes(self.chunk(offset,)?,),)
+  }
+
+  /// Reads the little-endian [`f32`] stored at `offset`.
+  pub fn read_f32_at(&self, offset:u32,) -> Result<f32, PatchError,> {
+    Ok(f32::from_le_bytes(self.chunk(offset,)?,),)
+  }
+
+  /// Overwrites the 4 bytes at `offset` with `value` in little-endian order.
+  pub fn patch_u32(&mut self, offset:u32, value:u32,) -> Result<(), PatchError,> {
+    self.chunk_mut(offset,)?.copy_from_slice(&value.to_le_bytes(),);
+    Ok((),)
+  }
+
+  /// Overwrites the 4 bytes at `offset` with `value` in little-endian order.
+  pub fn patch_f32(&mut self, offset:u32, value:f32,) -> Result<(), PatchError,> {
+    self.chunk_mut(offset,)?.copy_from_slice(&value.to_le_bytes(),);
+    Ok((),)
+  }
+
+  /// Overwrites the 4 bytes at `offset` with `value` only if they are still a
+  /// zeroed placeholder, so a target can only be updated once.
+  pub fn patch_once_u32(&mut self, offset:u32, value:u32,) -> Result<(), PatchError,> {
+    match self.read_u32_at(offset,)? {
+      0 => self.patch_u32(offset, value,),
+      current => Err(PatchError::AlreadyPatched { offset, current, },),
+    }
+  }
+
+  /// Replaces the first byte of the instruction at `offset` with a `Trap`
+  /// and returns the byte it replaced so it can be restored by
+  /// [`Program::clear_breakpoint`].
+  ///
+  /// Errors if `offset` is not the start of an instruction or already holds
+  /// a `Trap`, so the byte returned is never a breakpoint. Instructions are
+  /// found with [`Program::instruction_boundaries`] as they were before any
+  /// breakpoint was set, so breakpoints may be set in any order. Changing the
+  /// code in other ways while breakpoints are set finds instructions in the
+  /// patched code instead.
+  pub fn set_breakpoint(&mut self, offset:u32,) -> Result<u8, PatchError,> {
+    if !self.is_instruction_start(offset,) {
+      return Err(PatchError::NotAnInstruction { offset, },);
+    }
+    if self.inner[offset as usize] == u8::from(OpCode::Trap,) {
+      return Err(PatchError::BreakpointSet { offset, },);
+    }
+
+    // The cached boundaries are left alone since they are still those of the
+    // program without its breakpoints
+    let original = self.inner[offset as usize];
+    self.inner[offset as usize] = OpCode::Trap.into();
+    Ok(original,)
+  }
+
+  /// Inserts `inst` before the instruction at `offset`, or at the end of the
+  /// program if `offset` is its length. Jump and call targets, symbols, the
+  /// entry point, and relocations at or after `offset` are moved so they
+  /// refer to the same instructions as before. A jump to `offset` still lands
+  /// on the instruction which was there rather than on `inst`.
+  ///
+  /// The target of `inst` is an offset in the program after the insertion
+  /// and is given a relocation if it is absolute.
+  ///
+  /// Errors if `offset` is not the start of an instruction or the end of the
+  /// program, or if the program cannot be decoded.
+  pub fn insert(&mut self, offset:u32, inst:Instruction,) -> Result<(), PatchError,> {
+    if offset as usize != self.len() && !self.is_instruction_start(offset,) {
+      return Err(PatchError::NotAnInstruction { offset, },);
+    }
+    self.splice(offset, 0, Some(inst,),)?;
+    if inst.jump_target().is_some() {
+      let len = inst.opcode().instruction_len() as u32;
+      self.add_relocation(offset + len - 4, RelocKind::Code,);
+    }
+    Ok((),)
+  }
+
+  /// Removes the instruction at `offset` and returns it. Jump and call
+  /// targets, symbols, the entry point, and relocations after it are moved so
+  /// they refer to the same instructions as before. Those pointing at the
+  /// removed instruction move to the one after it.
+  ///
+  /// Errors if `offset` is not the start of an instruction or if the program
+  /// cannot be decoded.
+  pub fn remove_at(&mut self, offset:u32,) -> Result<Instruction, PatchError,> {
+    if !self.is_instruction_start(offset,) {
+      return Err(PatchError::NotAnInstruction { offset, },);
+    }
+    let (inst, _,) = Instruction::decode(&self.code()[offset as usize..],).map_err(PatchError::Decode,)?;
+    self.splice(offset, inst.opcode().instruction_len() as u32, None,)?;
+    Ok(inst,)
+  }
+
+  /// Replaces the `old_len` bytes of instructions at `offset` with `new` and
+  /// moves everything which refers to an offset after them.
+  fn splice(&mut self, offset:u32, old_len:u32, new:Option<Instruction,>,) -> Result<(), PatchError,> {
+    let insts = self
+      .instructions()
+      .collect::<Result<Vec<_,>, _,>>()
+      .map_err(PatchError::Decode,)?;
+    let new_len = new.map_or(0, |inst| inst.opcode().instruction_len() as u32,);
+    let end = offset + old_len;
+    let relocate = |x:u32| match x {
+      x if x >= end => x - old_len + new_len,
+      x if x >= offset => offset + new_len,
+      x => x,
+    };
+
+    let mut code = Program::new();
+    for (at, mut inst,) in insts {
+      if at == offset {
+        if let Some(new,) = new {
+          code.emit(new,);
+        }
+      }
+      if (offset..end).contains(&at,) {
+        continue;
+      }
+      let next = code.len() as u32 + inst.opcode().instruction_len() as u32;
+      let target = inst.resolve_target(at,);
+      if let Some(old,) = inst.jump_target_mut() {
+        *old = relocate(*old,);
+      }
+      match &mut inst {
+        Instruction::JmpRel { offset, }
+        | Instruction::JzRel { offset, .. }
+        | Instruction::JnzRel { offset, .. } => {
+          *offset = relocate(target.unwrap(),).wrapping_sub(next,) as i32;
+        }
+        _ => {}
+      }
+      code.emit(inst,);
+    }
+    if offset as usize == self.len() {
+      if let Some(new,) = new {
+        code.emit(new,);
+      }
+    }
+
+    *self.code_mut() = code.inner;
+    self.entry_point = relocate(self.entry_point,);
+    self.forget(offset..end,);
+    self.symbols.relocate(relocate,);
+    self.debug_info.relocate(relocate,);
+    for reloc in &mut self.relocations {
+      reloc.offset = relocate(reloc.offset,);
+    }
+    Ok((),)
+  }
+
+  /// Overwrites the instruction at `offset` with as many `Noop`s as it is
+  /// long. Nothing else in the program moves.
+  ///
+  /// Errors if `offset` is not the start of an instruction or the instruction
+  /// cannot be decoded.
+  pub fn nop_out(&mut self, offset:u32,) -> Result<(), PatchError,> {
+    self.replace_at(offset, Instruction::Noop,)
+  }
+
+  /// Overwrites the instruction at `offset` with `inst`, filling any bytes
+  /// left over with `Noop`s. Nothing else in the program moves. Relocations
+  /// and externs in the old instruction are dropped and the target of `inst`
+  /// is given a relocation if it is absolute.
+  ///
+  /// Errors if `offset` is not the start of an instruction, the instruction
+  /// cannot be decoded, or `inst` is longer than it.
+  pub fn replace_at(&mut self, offset:u32, inst:Instruction,) -> Result<(), PatchError,> {
+    if !self.is_instruction_start(offset,) {
+      return Err(PatchError::NotAnInstruction { offset, },);
+    }
+    let (old, _,) = Instruction::decode(&self.code()[offset as usize..],).map_err(PatchError::Decode,)?;
+    let (old, new,) = (old.opcode().instruction_len(), inst.opcode().instruction_len(),);
+    if new > old {
+      return Err(PatchError::TooWide { offset, old, new, },);
+    }
+
+    let mut bytes = Program::new();
+    bytes.emit(inst,);
+    while bytes.len() < old {
+      bytes.emit(Instruction::Noop,);
+    }
+    let start = offset as usize;
+    self.as_mut_slice()[start..start + old].copy_from_slice(bytes.code(),);
+    self.forget(offset..offset + old as u32,);
+    if inst.jump_target().is_some() {
+      self.add_relocation(offset + new as u32 - 4, RelocKind::Code,);
+    }
+    Ok((),)
+  }
+
+  /// Drops the relocations and externs inside `range`, which is being
+  /// overwritten.
+  fn forget(&mut self, range:Range<u32,>,) {
+    self.symbols.retain_externs(|at| !range.contains(&at,),);
+    self.relocations.retain(|reloc| !range.contains(&reloc.offset,),);
+  }
+
+  /// Restores the byte a breakpoint set by [`Program::set_breakpoint`]
+  /// replaced.
+  ///
+  /// Errors if the byte at `offset` is not a `Trap`.
+  pub fn clear_breakpoint(&mut self, offset:u32, original:u8,) -> Result<(), PatchError,> {
+    match self.inner.get(offset as usize,) {
+      Some(&byte,) if byte == u8::from(OpCode::Trap,) => {
+        self.inner[offset as usize] = original;
+        Ok((),)
+      }
+      _ => Err(PatchError::NoBreakpoint { offset, },),
+    }
+  }
+
+  fn chunk(&self, offset:u32,) -> Result<[u8; 4], PatchError,> {
+    let start = offset as usize;
+    match self.inner.get(start..start + 4,) {
+      Some(chunk,) => Ok(chunk.try_into().unwrap(),),
+      None => Err(PatchError::OutOfBounds {
+        offset,
+        len:self.len(),
+      },),
+    }
+  }
+
+  fn chunk_mut(&mut self, offset:u32,) -> Result<&mut [u8], PatchError,> {
+    let len = self.len();
+    let start = offset as usize;
+    match self.code_mut().get_mut(start..start + 4,) {
+      Some(chunk,) => Ok(chunk,),
+      None => Err(PatchError::OutOfBounds { offset, len, },),
+    }
+  }
+
+  /// Appends the encoded [`Instruction`] to the end of the [`Program`].
+  ///
+  /// Returns the offset the instruction starts at so it can be patched later.
+  pub fn emit(&mut self, inst:Instruction,) -> u32 {
+    let offset = self.inner.len() as u32;
+    inst.encode(self,);
+    offset
+  }
+
+  /// Emits a `Hlt` instruction. Returns the offset it starts at.
+  pub fn emit_hlt(&mut self,) -> u32 {
+    self.emit(Instruction::Hlt,)
+  }
+
+  /// Emits a `Load` instruction. Returns the offset it starts at.
+  pub fn emit_load(&mut self, dst:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::Load { dst, imm, },)
+  }
+
+  /// Emits a `Copy` instruction. Returns the offset it starts at.
+  pub fn emit_copy(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Copy { dst, src, },)
+  }
+
+  /// Emits a `MemCpy` instruction. Returns the offset it starts at.
+  pub fn emit_mem_cpy(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::MemCpy { dst, src, },)
+  }
+
+  /// Emits a `AddRI` instruction. Returns the offset it starts at.
+  pub fn emit_add_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::AddRI { dst, src, imm, },)
+  }
+
+  /// Emits a `SubRI` instruction. Returns the offset it starts at.
+  pub fn emit_sub_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::SubRI { dst, src, imm, },)
+  }
+
+  /// Emits a `RvSubRI` instruction. Returns the offset it starts at.
+  pub fn emit_rv_sub_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::RvSubRI { dst, src, imm, },)
+  }
+
+  /// Emits a `MulRI` instruction. Returns the offset it starts at.
+  pub fn emit_mul_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::MulRI { dst, src, imm, },)
+  }
+
+  /// Emits a `DivRI` instruction. Returns the offset it starts at.
+  pub fn emit_div_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::DivRI { dst, src, imm, },)
+  }
+
+  /// Emits a `RvDivRI` instruction. Returns the offset it starts at.
+  pub fn emit_rv_div_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::RvDivRI { dst, src, imm, },)
+  }
+
+  /// Emits a `PowRI` instruction. Returns the offset it starts at.
+  pub fn emit_pow_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::PowRI { dst, src, imm, },)
+  }
+
+  /// Emits a `RvPowRI` instruction. Returns the offset it starts at.
+  pub fn emit_rv_pow_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::RvPowRI { dst, src, imm, },)
+  }
+
+  /// Emits a `AddRR` instruction. Returns the offset it starts at.
+  pub fn emit_add_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::AddRR { dst, a, b, },)
+  }
+
+  /// Emits a `SubRR` instruction. Returns the offset it starts at.
+  pub fn emit_sub_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::SubRR { dst, a, b, },)
+  }
+
+  /// Emits a `MulRR` instruction. Returns the offset it starts at.
+  pub fn emit_mul_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::MulRR { dst, a, b, },)
+  }
+
+  /// Emits a `DivRR` instruction. Returns the offset it starts at.
+  pub fn emit_div_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::DivRR { dst, a, b, },)
+  }
+
+  /// Emits a `PowRR` instruction. Returns the offset it starts at.
+  pub fn emit_pow_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::PowRR { dst, a, b, },)
+  }
+
+  /// Emits a `CmpRI` instruction. Returns the offset it starts at.
+  pub fn emit_cmp_ri(&mut self, flag:CmpFlag, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::CmpRI { flag, src, imm, },)
+  }
+
+  /// Emits a `CmpRR` instruction. Returns the offset it starts at.
+  pub fn emit_cmp_rr(&mut self, flag:CmpFlag, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::CmpRR { flag, a, b, },)
+  }
+
+  /// Emits a `Cmp_RR` instruction which stores the negation of the `flag`
+  /// comparison, replacing a `Cmp_RR` followed by a `Not` of
+  /// [`EQ`](crate::registers::EQ). Returns the offset it starts at.
+  pub fn emit_cmp_inverted(&mut self, flag:CmpFlag, a:Register, b:Register,) -> u32 {
+    self.emit_cmp_rr(flag.inverse(), a, b,)
+  }
+
+  /// Emits a `Not` instruction. Returns the offset it starts at.
+  pub fn emit_not(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Not { dst, src, },)
+  }
+
+  /// Emits a `Jmp` instruction. Returns the offset it starts at.
+  pub fn emit_jmp(&mut self, target:u32,) -> u32 {
+    self.emit(Instruction::Jmp { target, },)
+  }
+
+  /// Emits a `Jz` instruction. Returns the offset it starts at.
+  pub fn emit_jz(&mut self, cond:Register, target:u32,) -> u32 {
+    self.emit(Instruction::Jz { cond, target, },)
+  }
+
+  /// Emits a `Jnz` instruction. Returns the offset it starts at.
+  pub fn emit_jnz(&mut self, cond:Register, target:u32,) -> u32 {
+    self.emit(Instruction::Jnz { cond, target, },)
+  }
+
+  /// Emits a `Call` instruction. Returns the offset it starts at.
+  pub fn emit_call(&mut self, target:u32,) -> u32 {
+    self.emit(Instruction::Call { target, },)
+  }
+
+  /// Emits a `SysCall` instruction. Returns the offset it starts at.
+  pub fn emit_sys_call(&mut self, idx:u8,) -> u32 {
+    self.emit(Instruction::SysCall { idx, },)
+  }
+
+  /// Emits a `Ret` instruction. Returns the offset it starts at.
+  pub fn emit_ret(&mut self, args:u8,) -> u32 {
+    self.emit(Instruction::Ret { args, },)
+  }
+
+  /// Emits a `Alloc` instruction. Returns the offset it starts at.
+  pub fn emit_alloc(&mut self, dst:Register, len:Register,) -> u32 {
+    self.emit(Instruction::Alloc { dst, len, },)
+  }
+
+  /// Emits a `Realloc` instruction. Returns the offset it starts at.
+  pub fn emit_realloc(&mut self, dst:Register, len:Register,) -> u32 {
+    self.emit(Instruction::Realloc { dst, len, },)
+  }
+
+  /// Emits a `Dealloc` instruction. Returns the offset it starts at.
+  pub fn emit_dealloc(&mut self, src:Register,) -> u32 {
+    self.emit(Instruction::Dealloc { src, },)
+  }
+
+  /// Emits a `RMem` instruction. Returns the offset it starts at.
+  pub fn emit_rmem(&mut self, dst:Register, src:Register, imm_offset:u32, reg_offset:Register,) -> u32 {
+    self.emit(Instruction::RMem {
+      dst,
+      src,
+      imm_offset,
+      reg_offset,
+    },)
+  }
+
+  /// Emits a `WMem` instruction. Returns the offset it starts at.
+  pub fn emit_wmem(&mut self, dst:Register, src:Register, imm_offset:u32, reg_offset:Register,) -> u32 {
+    self.emit(Instruction::WMem {
+      dst,
+      src,
+      imm_offset,
+      reg_offset,
+    },)
+  }
+
+  /// Emits a `WriteStr` instruction. Returns the offset it starts at.
+  pub fn emit_write_str(&mut self, ptr:Register, len:Register,) -> u32 {
+    self.emit(Instruction::WriteStr { ptr, len, },)
+  }
+
+  /// Emits a `Push` instruction. Returns the offset it starts at.
+  pub fn emit_push(&mut self, src:Register,) -> u32 {
+    self.emit(Instruction::Push { src, },)
+  }
+
+  /// Emits a `Pop` instruction. Returns the offset it starts at.
+  pub fn emit_pop(&mut self,) -> u32 {
+    self.emit(Instruction::Pop,)
+  }
+
+  /// Emits a `PopR` instruction. Returns the offset it starts at.
+  pub fn emit_pop_r(&mut self, dst:Register,) -> u32 {
+    self.emit(Instruction::PopR { dst, },)
+  }
+
+  /// Emits a `Noop` instruction. Returns the offset it starts at.
+  pub fn emit_noop(&mut self,) -> u32 {
+    self.emit(Instruction::Noop,)
+  }
+
+  /// Emits a `ModRI` instruction. Returns the offset it starts at.
+  pub fn emit_mod_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::ModRI { dst, src, imm, },)
+  }
+
+  /// Emits a `RvModRI` instruction. Returns the offset it starts at.
+  pub fn emit_rv_mod_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::RvModRI { dst, src, imm, },)
+  }
+
+  /// Emits a `ModRR` instruction. Returns the offset it starts at.
+  pub fn emit_mod_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::ModRR { dst, a, b, },)
+  }
+
+  /// Emits a `Neg` instruction. Returns the offset it starts at.
+  pub fn emit_neg(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Neg { dst, src, },)
+  }
+
+  /// Emits a `Abs` instruction. Returns the offset it starts at.
+  pub fn emit_abs(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Abs { dst, src, },)
+  }
+
+  /// Emits a `Sqrt` instruction. Returns the offset it starts at.
+  pub fn emit_sqrt(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Sqrt { dst, src, },)
+  }
+
+  /// Emits a `Floor` instruction. Returns the offset it starts at.
+  pub fn emit_floor(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Floor { dst, src, },)
+  }
+
+  /// Emits a `Ceil` instruction. Returns the offset it starts at.
+  pub fn emit_ceil(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Ceil { dst, src, },)
+  }
+
+  /// Emits a `Round` instruction. Returns the offset it starts at.
+  pub fn emit_round(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Round { dst, src, },)
+  }
+
+  /// Emits a `Trunc` instruction. Returns the offset it starts at.
+  pub fn emit_trunc(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Trunc { dst, src, },)
+  }
+
+  /// Emits a `AndRR` instruction. Returns the offset it starts at.
+  pub fn emit_and_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::AndRR { dst, a, b, },)
+  }
+
+  /// Emits a `OrRR` instruction. Returns the offset it starts at.
+  pub fn emit_or_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::OrRR { dst, a, b, },)
+  }
+
+  /// Emits a `XorRR` instruction. Returns the offset it starts at.
+  pub fn emit_xor_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::XorRR { dst, a, b, },)
+  }
+
+  /// Emits a `AndRI` instruction. Returns the offset it starts at.
+  pub fn emit_and_ri(&mut self, dst:Register, src:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::AndRI { dst, src, imm, },)
+  }
+
+  /// Emits a `OrRI` instruction. Returns the offset it starts at.
+  pub fn emit_or_ri(&mut self, dst:Register, src:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::OrRI { dst, src, imm, },)
+  }
+
+  /// Emits a `XorRI` instruction. Returns the offset it starts at.
+  pub fn emit_xor_ri(&mut self, dst:Register, src:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::XorRI { dst, src, imm, },)
+  }
+
+  /// Emits a `ShlRI` instruction. Returns the offset it starts at.
+  pub fn emit_shl_ri(&mut self, dst:Register, src:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::ShlRI { dst, src, imm, },)
+  }
+
+  /// Emits a `ShrRI` instruction. Returns the offset it starts at.
+  pub fn emit_shr_ri(&mut self, dst:Register, src:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::ShrRI { dst, src, imm, },)
+  }
+
+  /// Emits a `ShlRR` instruction. Returns the offset it starts at.
+  pub fn emit_shl_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::ShlRR { dst, a, b, },)
+  }
+
+  /// Emits a `ShrRR` instruction. Returns the offset it starts at.
+  pub fn emit_shr_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::ShrRR { dst, a, b, },)
+  }
+
+  /// Emits a `MinRR` instruction. Returns the offset it starts at.
+  pub fn emit_min_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::MinRR { dst, a, b, },)
+  }
+
+  /// Emits a `MaxRR` instruction. Returns the offset it starts at.
+  pub fn emit_max_rr(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::MaxRR { dst, a, b, },)
+  }
+
+  /// Emits a `MinRI` instruction. Returns the offset it starts at.
+  pub fn emit_min_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::MinRI { dst, src, imm, },)
+  }
+
+  /// Emits a `MaxRI` instruction. Returns the offset it starts at.
+  pub fn emit_max_ri(&mut self, dst:Register, src:Register, imm:f32,) -> u32 {
+    self.emit(Instruction::MaxRI { dst, src, imm, },)
+  }
+
+  /// Emits a `Sin` instruction. Returns the offset it starts at.
+  pub fn emit_sin(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Sin { dst, src, },)
+  }
+
+  /// Emits a `Cos` instruction. Returns the offset it starts at.
+  pub fn emit_cos(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Cos { dst, src, },)
+  }
+
+  /// Emits a `Tan` instruction. Returns the offset it starts at.
+  pub fn emit_tan(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Tan { dst, src, },)
+  }
+
+  /// Emits a `Atan2` instruction. Returns the offset it starts at.
+  pub fn emit_atan2(&mut self, dst:Register, a:Register, b:Register,) -> u32 {
+    self.emit(Instruction::Atan2 { dst, a, b, },)
+  }
+
+  /// Emits a `Ln` instruction. Returns the offset it starts at.
+  pub fn emit_ln(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Ln { dst, src, },)
+  }
+
+  /// Emits a `Log2` instruction. Returns the offset it starts at.
+  pub fn emit_log2(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Log2 { dst, src, },)
+  }
+
+  /// Emits a `Exp` instruction. Returns the offset it starts at.
+  pub fn emit_exp(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Exp { dst, src, },)
+  }
+
+  /// Emits a `JmpCmpRR` instruction. Returns the offset it starts at.
+  pub fn emit_jmp_cmp_rr(&mut self, flag:CmpFlag, a:Register, b:Register, target:u32,) -> u32 {
+    self.emit(Instruction::JmpCmpRR { flag, a, b, target, },)
+  }
+
+  /// Emits a `JmpCmpRI` instruction. Returns the offset it starts at.
+  pub fn emit_jmp_cmp_ri(&mut self, flag:CmpFlag, src:Register, imm:f32, target:u32,) -> u32 {
+    self.emit(Instruction::JmpCmpRI {
+      flag,
+      src,
+      imm,
+      target,
+    },)
+  }
+
+  /// Emits a `JmpR` instruction. Returns the offset it starts at.
+  pub fn emit_jmp_r(&mut self, src:Register,) -> u32 {
+    self.emit(Instruction::JmpR { src, },)
+  }
+
+  /// Emits a `CallR` instruction. Returns the offset it starts at.
+  pub fn emit_call_r(&mut self, src:Register,) -> u32 {
+    self.emit(Instruction::CallR { src, },)
+  }
+
+  /// Emits a `JmpRel` instruction. Returns the offset it starts at.
+  pub fn emit_jmp_rel(&mut self, offset:i32,) -> u32 {
+    self.emit(Instruction::JmpRel { offset, },)
+  }
+
+  /// Emits a `JzRel` instruction. Returns the offset it starts at.
+  pub fn emit_jz_rel(&mut self, cond:Register, offset:i32,) -> u32 {
+    self.emit(Instruction::JzRel { cond, offset, },)
+  }
+
+  /// Emits a `JnzRel` instruction. Returns the offset it starts at.
+  pub fn emit_jnz_rel(&mut self, cond:Register, offset:i32,) -> u32 {
+    self.emit(Instruction::JnzRel { cond, offset, },)
+  }
+
+  /// Emits a `Inc` instruction. Returns the offset it starts at.
+  pub fn emit_inc(&mut self, dst:Register,) -> u32 {
+    self.emit(Instruction::Inc { dst, },)
+  }
+
+  /// Emits a `Dec` instruction. Returns the offset it starts at.
+  pub fn emit_dec(&mut self, dst:Register,) -> u32 {
+    self.emit(Instruction::Dec { dst, },)
+  }
+
+  /// Emits a `Swap` instruction. Returns the offset it starts at.
+  pub fn emit_swap(&mut self, dst:Register, src:Register,) -> u32 {
+    self.emit(Instruction::Swap { dst, src, },)
+  }
+
+  /// Emits a `LoadU32` instruction. Returns the offset it starts at.
+  pub fn emit_load_int(&mut self, dst:Register, imm:u32,) -> u32 {
+    self.emit(Instruction::LoadU32 { dst, imm, },)
+  }
+
+  /// Emits a `PushI` instruction. Returns the offset it starts at.
+  pub fn emit_push_i(&mut self, imm:f32,) -> u32 {
+    self.emit(Instruction::PushI { imm, },)
+  }
+
+  /// Emits a `PopN` instruction. Returns the offset it starts at.
+  pub fn emit_pop_n(&mut self, count:u8,) -> u32 {
+    self.emit(Instruction::PopN { count, },)
+  }
+
+  /// Emits a `Enter` instruction. Returns the offset it starts at.
+  pub fn emit_enter(&mut self, locals:u8,) -> u32 {
+    self.emit(Instruction::Enter { locals, },)
+  }
+
+  /// Emits a `Leave` instruction. Returns the offset it starts at.
+  pub fn emit_leave(&mut self,) -> u32 {
+    self.emit(Instruction::Leave,)
+  }
+
+  /// Emits a `MemCpyN` instruction. Returns the offset it starts at.
+  pub fn emit_mem_cpy_n(
+    &mut self,
+    dst:Register,
+    src:Register,
+    len:Register,
+    dst_offset:u32,
+    src_offset:u32,
+  ) -> u32 {
+    self.emit(Instruction::MemCpyN {
+      dst,
+      src,
+      len,
+      dst_offset,
+      src_offset,
+    },)
+  }
+
+  /// Emits a `MemSet` instruction. Returns the offset it starts at.
+  pub fn emit_mem_set(&mut self, dst:Register, val:Register, len:Register,) -> u32 {
+    self.emit(Instruction::MemSet { dst, val, len, },)
+  }
+
+  /// Emits a `Trap` instruction. Returns the offset it starts at.
+  pub fn emit_trap(&mut self,) -> u32 {
+    self.emit(Instruction::Trap,)
+  }
+
+  /// Emits a `ReadStr` instruction. Returns the offset it starts at.
+  pub fn emit_read_str(&mut self, ptr:Register, len:Register,) -> u32 {
+    self.emit(Instruction::ReadStr { ptr, len, },)
+  }
+
+  /// Emits a `DbgReg` instruction. Returns the offset it starts at.
+  pub fn emit_dbg_reg(&mut self, src:Register,) -> u32 {
+    self.emit(Instruction::DbgReg { src, },)
+  }
+
+  /// Returns the code in `range`, or an error if it is out of bounds. Unlike
+  /// indexing with a range this never panics.
+  pub fn slice(&self, range:Range<u32,>,) -> Result<&[u8], SliceError,> {
+    self
+      .inner
+      .get(range.start as usize..range.end as usize,)
+      .ok_or(SliceError::OutOfBounds {
+        start:range.start,
+        end:range.end,
+        len:self.len(),
+      },)
+  }
+
+  /// Copies the instructions in `range` into a new [`Program`]. Both ends of
+  /// the range must be instruction boundaries, the end of the program
+  /// counting as one, so the new program decodes on its own.
+  ///
+  /// Only the code is copied. Absolute jump targets are not moved, so jumps
+  /// out of the range or to a `range.start` other than 0 need relocating.
+  pub fn subprogram(&self, range:Range<u32,>,) -> Result<Program, SliceError,> {
+    let code = self.slice(range.clone(),)?;
+    // Decoding stops at the first bad instruction so nothing after it is a
+    // boundary
+    if let (_, Some(err,),) = self.boundaries() {
+      if err.offset().unwrap_or_default() < range.end {
+        return Err(SliceError::Decode(*err,),);
+      }
+    }
+    for offset in [range.start, range.end,] {
+      if offset as usize != self.len() && !self.is_instruction_start(offset,) {
+        return Err(SliceError::NotAnInstruction { offset, },);
+      }
+    }
+    Ok(Program::from(code,),)
+  }
+
+  /// Iterates over the bytes of the code. Same as iterating over `&program`.
+  /// See [`Program::instructions`] to iterate over instructions instead.
+  pub fn iter_bytes(&self,) -> slice::Iter<'_, u8,> {
+    self.inner.iter()
+  }
+
+  /// Returns an iterator over the decoded [`Instruction`]s in the
+  /// [`Program`] and the offset each one starts at.
+  ///
+  /// If the program contains bytes which cannot be decoded, the iterator
+  /// yields a final `Err` naming the offset of the bad instruction and then
+  /// stops.
+  pub fn instructions(&self,) -> Instructions<'_,> {
+    Instructions::new(&self.inner,)
+  }
+
+  /// Checks the [`Program`] is structurally sound before it is handed to the
+  /// VM.
+  ///
+  /// Checks that:
+  /// - Every instruction decodes.
+  /// - Every jump with an immediate target lands on the start of an instruction
+  ///   inside the program, found with [`Program::instruction_boundaries`].
+  ///   `JmpR` and `CallR` only have their register checked since their target
+  ///   is not known until runtime.
+  /// - No jump targets the end of the program. Execution would run off the end
+  ///   so a jump to `program.len()` is a [`ValidationError::JumpOutOfBounds`];
+  ///   end programs with a `Hlt` to jump to instead.
+  /// - No `RMem` or `WMem` register offset was most recently set, within the
+  ///   same basic block, by a `Load` whose immediate is not exactly a `u32`.
+  ///   Use `LoadU32` for those.
+  ///
+  /// Returns every problem found, ordered by offset.
+  pub fn validate(&self,) -> Result<(), Vec<ValidationError,>,> {
+    let mut errors = Vec::new();
+    let mut jumps = Vec::new();
+    // Registers whose value came from an inexact `Load` and the Load's offset
+    let mut inexact = [None; REG_COUNT];
+    let insts = self
+      .instructions()
+      .map_while(|item| match item {
+        Ok(item,) => Some(item,),
+        Err(err,) => {
+          errors.push(ValidationError::Decode(err,),);
+          None
+        }
+      },)
+      .collect::<Vec<_,>>();
+    // Blocks may be entered from elsewhere so loads in earlier blocks are
+    // forgotten
+    let leaders = analysis::leaders(&insts, self.entry_point,);
+
+    for &(offset, inst,) in &insts {
+      if leaders.binary_search(&offset,).is_ok() {
+        inexact = [None; REG_COUNT];
+      }
+      if let Some(target,) = inst.resolve_target(offset,) {
+        jumps.push((offset, target,),);
+      }
+
+      match inst {
+        Instruction::Load { dst, imm, } => {
+          inexact[dst.index() as usize] = exact_u32(imm,).is_none().then_some(offset,);
+        }
+        Instruction::RMem { reg_offset, .. } | Instruction::WMem { reg_offset, .. } => {
+          if let Some(load,) = inexact[reg_offset.index() as usize].take() {
+            errors.push(ValidationError::InexactOffset {
+              at:offset,
+              register:reg_offset.index(),
+              load,
+            },);
+          }
+        }
+        _ => {}
+      }
+      // Any other use may have overwritten the register
+      if !matches!(inst, Instruction::Load { .. }) {
+        for register in inst.registers() {
+          inexact[register.index() as usize] = None;
+        }
+      }
+    }
+
+    for (at, target,) in jumps {
+      if target as usize >= self.len() {
+        errors.push(ValidationError::JumpOutOfBounds { at, target, },);
+      }
+      else if !self.is_instruction_start(target,) {
+        errors.push(ValidationError::JumpIntoOperand { at, target, },);
+      }
+    }
+
+    match errors.is_empty() {
+      true => Ok((),),
+      false => {
+        errors.sort_by_key(ValidationError::offset,);
+        Err(errors,)
+      }
+    }
+  }
+
+  /// Performs the same checks as [`Program::validate`] and additionally
+  /// requires the program to end with a `Hlt` or `Ret`.
+  pub fn validate_strict(&self,) -> Result<(), Vec<ValidationError,>,> {
+    let mut errors = self.validate().err().unwrap_or_default();
+
+    match self.instructions().last() {
+      Some(Ok((_, Instruction::Hlt | Instruction::Ret { .. },),),) | Some(Err(_,),) => {}
+      _ => errors.push(ValidationError::MissingTerminator {
+        at:self.len() as u32,
+      },),
+    }
+
+    match errors.is_empty() {
+      true => Ok((),),
+      false => Err(errors,),
+    }
+  }
+
+  /// Performs the same checks as [`Program::validate`] and additionally
+  /// requires every `SysCall` to call a syscall in `syscalls`.
+  pub fn validate_with(&self, syscalls:&SyscallTable,) -> Result<(), Vec<ValidationError,>,> {
+    let mut errors = self.validate().err().unwrap_or_default();
+
+    for (at, inst,) in self.instructions().map_while(Result::ok,) {
+      if let Instruction::SysCall { idx, } = inst {
+        if syscalls.get(idx,).is_none() {
+          errors.push(ValidationError::UnknownSyscall { at, idx, },);
+        }
+      }
+    }
+
+    match errors.is_empty() {
+      true => Ok((),),
+      false => {
+        errors.sort_by_key(ValidationError::offset,);
+        Err(errors,)
+      }
+    }
+  }
+
+  /// Performs the same checks as [`Program::validate`] and additionally
+  /// flags `RMem` and `WMem` instructions which always access memory past the
+  /// end of `layout`.
+  ///
+  /// Addresses are only known to be out of range when the immediate offset
+  /// plus the register offset is. The register offset is only known when it
+  /// was most recently set by a `Load` or `LoadU32` in the same basic block,
+  /// with no `SysCall` in between.
+  pub fn validate_layout(&self, layout:&MemoryLayout,) -> Result<(), Vec<ValidationError,>,> {
+    let mut errors = self.validate().err().unwrap_or_default();
+    // Registers whose value is a known address
+    let mut unknown = [None; REG_COUNT];
+    unknown[Register::PC.index() as usize] = Some(0,);
+    let mut known = unknown;
+    let insts = self.instructions().map_while(Result::ok,).collect::<Vec<_,>>();
+    // Blocks may be entered from elsewhere so loads in earlier blocks are
+    // forgotten
+    let leaders = analysis::leaders(&insts, self.entry_point,);
+
+    for (at, inst,) in insts {
+      if leaders.binary_search(&at,).is_ok() {
+        known = unknown;
+      }
+      match inst {
+        Instruction::Load { dst, imm, } => {
+          known[dst.index() as usize] = exact_u32(imm,);
+          continue;
+        }
+        Instruction::LoadU32 { dst, imm, } => {
+          known[dst.index() as usize] = Some(imm,);
+          continue;
+        }
+        Instruction::RMem { reg_offset, .. } | Instruction::WMem { reg_offset, .. } => {
+          // `PC` means there is no register offset
+          let reg_offset = match reg_offset {
+            Register::PC => Some(0,),
+            reg_offset => known[reg_offset.index() as usize],
+          };
+          let imm = inst.imm_address().unwrap();
+          let address = imm
+            .checked_add(reg_offset.unwrap_or_default(),)
+            .unwrap_or(Address::new_unchecked(u32::MAX,),);
+          if Address::new(address.get(), layout,).is_err() {
+            errors.push(ValidationError::AddressOutOfBounds { at, address, },);
+          }
+        }
+        _ => {}
+      }
+      // Any other use may have overwritten the register
+      for register in inst.registers() {
+        if register != Register::PC {
+          known[register.index() as usize] = None;
+        }
+      }
+      // Calls and syscalls may overwrite any register, and the next block may
+      // be entered from elsewhere
+      if analysis::ends_block(&inst,) || matches!(inst, Instruction::SysCall { .. }) {
+        known = unknown;
+      }
+    }
+
+    match errors.is_empty() {
+      true => Ok((),),
+      false => {
+        errors.sort_by_key(ValidationError::offset,);
+        Err(errors,)
+      }
+    }
+  }
+
+  /// Returns a copy of the [`Program`] with every `DbgReg` removed. Jump
+  /// targets and relative offsets are adjusted for the removed bytes.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub fn strip_debug(&self,) -> Result<Program, DecodeError,> {
+    self.retain_instructions(|_, inst| !matches!(inst, Instruction::DbgReg { .. }),)
+  }
+
+  /// Removes and returns the [`Program`]'s [`DebugInfo`] so it is not saved
+  /// with the program. Unlike [`Program::strip_debug`] the code is left
+  /// alone.
+  pub fn strip_debug_info(&mut self,) -> DebugInfo {
+    core::mem::take(&mut self.debug_info,)
+  }
+
+  /// Returns a copy of the [`Program`] with only the instructions `keep`
+  /// returns true for. See [`Program::rewrite_instructions`].
+  ///
+  /// Errors if the program cannot be decoded.
+  pub(crate) fn retain_instructions(
+    &self,
+    mut keep:impl FnMut(u32, &Instruction,) -> bool,
+  ) -> Result<Program, DecodeError,> {
+    self.rewrite_instructions(|at, inst| keep(at, inst,).then_some(*inst,),)
+  }
+
+  /// Returns a copy of the [`Program`] with each instruction replaced by
+  /// what `edit` returns for it, or removed if it returns `None`.
+  /// Replacements may have a different length. Targets in replacements are
+  /// offsets in the original program.
+  ///
+  /// Jump targets, relative offsets, the entry point, symbols, debug info,
+  /// and relocations are adjusted for the new layout. Offsets of removed
+  /// instructions move to the next instruction which is kept. Relocations
+  /// inside an instruction are only kept if its opcode is unchanged. The data
+  /// segment and metadata are copied as they are.
+  ///
+  /// Errors if the program cannot be decoded.
+  pub(crate) fn rewrite_instructions(
+    &self,
+    mut edit:impl FnMut(u32, &Instruction,) -> Option<Instruction,>,
+  ) -> Result<Program, DecodeError,> {
+    let insts = self.instructions().collect::<Result<Vec<_,>, _,>>()?;
+    // The old offset and the new offset of each instruction along with its
+    // replacement
+    let mut layout = Vec::with_capacity(insts.len(),);
+    let mut len = 0;
+    for (at, inst,) in &insts {
+      let new = edit(*at, inst,);
+      layout.push((*at, len, new,),);
+      len += new.map_or(0, |new| new.opcode().instruction_len() as u32,);
+    }
+    // Maps an offset in the original program to the same position in the
+    // new one
+    let relocate = |offset:u32| match layout.partition_point(|&(at, ..,)| at <= offset,) {
+      0 => offset,
+      i if i == layout.len() && offset >= self.len() as u32 => len + offset - self.len() as u32,
+      i => layout[i - 1].1 + offset - layout[i - 1].0,
+    };
+
+    let mut rewritten = Program::new();
+    for ((at, _,), (_, _, new,),) in insts.iter().zip(&layout,) {
+      let Some(mut inst,) = *new
+      else {
+        continue;
+      };
+      let next = relocate(*at,) + inst.opcode().instruction_len() as u32;
+      let target = inst.resolve_target(*at,);
+      if let Some(old,) = inst.jump_target_mut() {
+        *old = relocate(*old,);
+      }
+      match &mut inst {
+        Instruction::JmpRel { offset, }
+        | Instruction::JzRel { offset, .. }
+        | Instruction::JnzRel { offset, .. } => {
+          *offset = relocate(target.unwrap(),).wrapping_sub(next,) as i32;
+        }
+        _ => {}
+      }
+      rewritten.emit(inst,);
+    }
+    rewritten.data = self.data.clone();
+    rewritten.data_base = self.data_base;
+    rewritten.entry_point = relocate(self.entry_point,);
+    rewritten.symbols = self.symbols.clone();
+    rewritten.symbols.relocate(relocate,);
+    rewritten.debug_info = self.debug_info.clone();
+    rewritten.debug_info.relocate(relocate,);
+    rewritten.metadata = self.metadata.clone();
+    // Relocations are inside an instruction so they only survive if its
+    // operands are laid out the same way
+    let unchanged = |offset:u32| {
+      let i = layout.partition_point(|&(at, ..,)| at <= offset,);
+      i > 0 && {
+        let (at, _, new,) = &layout[i - 1];
+        let (_, old,) = &insts[i - 1];
+        offset < at + old.opcode().instruction_len() as u32
+          && new.is_some_and(|new| new.opcode() == old.opcode(),)
+      }
+    };
+    rewritten.relocations = self
+      .relocations
+      .iter()
+      .filter(|reloc| unchanged(reloc.offset,),)
+      .map(|reloc| Relocation {
+        offset:relocate(reloc.offset,),
+        kind:reloc.kind,
+      },)
+      .collect();
+    Ok(rewritten,)
+  }
+
+  /// Appends the code of `other` and moves the targets of its jumps and calls
+  /// by the offset it is appended at. Returns the offset the appended code
+  /// starts at.
+  ///
+  /// Relative jumps need no changes and `JmpR` and `CallR` targets are not
+  /// known until runtime so they are not moved. `other`'s code relocations are
+  /// kept but its data segment, symbols, and debug info are not copied; use
+  /// [`Program::link`] for programs which have them.
+  ///
+  /// Errors if `other` cannot be decoded. Nothing is appended if there is an
+  /// error.
+  pub fn append(&mut self, other:&Program,) -> Result<u32, DecodeError,> {
+    let insts = other.instructions().collect::<Result<Vec<_,>, _,>>()?;
+    let base = self.len() as u32;
+    for (_, mut inst,) in insts {
+      if let Some(target,) = inst.jump_target_mut() {
+        // Like `Program::rebase`, targets wrap around
+        *target = target.wrapping_add(base,);
+      }
+      self.emit(inst,);
+    }
+    for reloc in &other.relocations {
+      if reloc.kind == RelocKind::Code {
+        self.add_relocation(base + reloc.offset, reloc.kind,);
+      }
+    }
+    Ok(base,)
+  }
+
+  /// Links `parts` into one [`Program`] by concatenating their code and data
+  /// segments.
+  ///
+  /// - Each part's [`Relocation`]s are applied for the offsets its code and
+  ///   data are moved to. Addresses without a relocation, such as those in
+  ///   instructions emitted directly rather than through a
+  ///   [`ProgramBuilder`](crate::builder::ProgramBuilder), are not updated.
+  /// - The parts' [`SymbolTable`]s are merged and every extern call is pointed
+  ///   at the symbol it names. Their [`DebugInfo`] is merged too.
+  /// - The linked program starts at the first part's entry point and its data
+  ///   is copied to the first part's data base.
+  ///
+  /// Errors if a part's relocations run past the end of its code, if a
+  /// symbol is defined by more than one part, if an extern call names a
+  /// symbol no part defines, or if an extern is not at the start of a
+  /// `Call`.
+  pub fn link(parts:&[Program],) -> Result<Program, LinkError,> {
+    let mut linked = Program::new();
+    if let Some(first,) = parts.first() {
+      linked.entry_point = first.entry_point;
+      linked.data_base = first.data_base;
+    }
+    for (part, program,) in parts.iter().enumerate() {
+      let base = linked.len() as u32;
+      let data = (linked.data_base + linked.data.len() as u32).wrapping_sub(program.data_base,);
+      let mut moved = program.clone();
+      moved
+        .relocate(base, data,)
+        .map_err(|err| LinkError::Relocation { part, err, },)?;
+
+      linked.extend_from_slice(&moved.inner,);
+      linked.data.extend_from_slice(&moved.data,);
+      linked
+        .relocations
+        .extend(moved.relocations.iter().map(|reloc| Relocation {
+          offset:base + reloc.offset,
+          kind:reloc.kind,
+        },),);
+      linked.debug_info.merge(&program.debug_info, base,);
+      linked
+        .symbols
+        .merge(&program.symbols, base,)
+        .map_err(|err| match err {
+          SymbolError::DuplicateName(name,)
+          | SymbolError::InvalidName(name,)
+          | SymbolError::NameTooLong(name,) => LinkError::DuplicateSymbol(name,),
+        },)?;
+    }
+
+    // Check every extern before patching any so the instruction boundaries
+    // are only found once
+    let mut calls = Vec::new();
+    for ext in linked.symbols.take_externs() {
+      let target = linked
+        .symbols
+        .get(&ext.name,)
+        .ok_or(LinkError::UnresolvedSymbol(ext.name.clone(),),)?;
+      let decoded = linked.inner.get(ext.at as usize..,).map(Instruction::decode,);
+      let call =
+        linked.is_instruction_start(ext.at,) && matches!(decoded, Some(Ok((Instruction::Call { .. }, _,),)));
+      if !call {
+        return Err(LinkError::NotACall {
+          at:ext.at,
+          name:ext.name,
+        },);
+      }
+      calls.push((ext.at, target,),);
+    }
+    for (at, target,) in calls {
+      let operand = at as usize + 1;
+      linked.code_mut()[operand..operand + 4].copy_from_slice(&target.to_le_bytes(),);
+    }
+    Ok(linked,)
+  }
+
+  /// Returns the [`Program`]'s listing with each `SysCall` in `syscalls`
+  /// printed by name, as in `SysCall print_entity (2 args)`. The listing can
+  /// be assembled again by [`asm::assemble_with`].
+  pub fn with_syscalls<'a,>(&'a self, syscalls:&'a SyscallTable,) -> Listing<'a,> {
+    Listing {
+      syscalls:Some(syscalls,),
+      ..Listing::new(self.as_slice(), &self.symbols, self.data_segment(),).with_debug_info(&self.debug_info,)
+    }
+  }
+
+  /// Lists the [`Program`]'s instructions alongside their offsets and raw
+  /// bytes. Equivalent to formatting it with `{:#}`.
+  pub fn disassemble_annotated(&self,) -> String {
+    format!("{:#}", self)
+  }
+
+  /// Lists the [`Program`]'s instructions as chosen by `options`, optionally
+  /// with their offsets, raw bytes, and colors. Unlike `Display` the data
+  /// segment is not dumped.
+  pub fn disassemble_with(&self, options:DisasmOptions,) -> String {
+    disasm::disassemble(self.as_slice(), options,)
+  }
+
+  /// Lists the [`Program`]'s instructions with their offsets for printing to
+  /// stdout, colored if stdout is a terminal and `NO_COLOR` is not set.
+  #[cfg(feature = "color")]
+  pub fn disassemble_colored(&self,) -> String {
+    use std::io::IsTerminal;
+
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR",).is_none();
+    self.disassemble_with(DisasmOptions {
+      color,
+      offsets:true,
+      bytes:false,
+    },)
+  }
+
+  /// Dumps the code in `range`, or all of it if `range` is `None`, in hex and
+  /// ASCII 16 bytes per line. Each line starts with the offset of its first
+  /// byte. Parts of `range` past the end of the code are left out.
+  pub fn hex_dump(&self, range:Option<Range<u32,>,>,) -> String {
+    let range = range.unwrap_or(0..self.len() as u32,);
+    let end = (range.end as usize).min(self.len(),);
+    let start = (range.start as usize).min(end,);
+    let mut text = String::new();
+    // Writing to a `String` cannot fail
+    let _ = write_hex_rows(&mut text, "", start as u32, &self.inner[start..end],);
+    text
+  }
+
+  /// Lists the instruction at `offset` and up to `context_lines` lines either
+  /// side of it in the form of [`Program::disassemble_annotated`], marking
+  /// the line containing `offset` with a `>`.
+  ///
+  /// Decoding starts from the beginning of the program. Bytes which cannot be
+  /// decoded are skipped one at a time and marked `unsynchronized` until an
+  /// instruction decodes again. As the skipped bytes may have been operands,
+  /// the first instruction after them is a guess and is marked
+  /// `resynchronized`.
+  pub fn disassemble_around(&self, offset:u32, context_lines:usize,) -> String {
+    let code = self.as_slice();
+    let lines = listing_lines(code,).collect::<Vec<_,>>();
+    let mut text = String::new();
+    let Some(at,) = lines
+      .iter()
+      .rposition(|(start, _, _,)| *start <= offset as usize,)
+    else {
+      return text;
+    };
+    let first = at.saturating_sub(context_lines,);
+    let last = lines.len().min(at + context_lines + 1,);
+    // Writing to a `String` cannot fail
+    let _ = write_lines_around(&mut text, code, &lines[..last], first, at,);
+    text
+  }
+
+  /// Assembles a [`Program`] from the listing printed by its `Display`
+  /// implementation.
+  pub fn from_asm(src:&str,) -> Result<Self, AsmError,> {
+    asm::assemble(src,)
+  }
+
+  /// Checks that the [`Program`]'s listing assembles back into exactly the same
+  /// code, which holds for every program of instructions whose immediates are
+  /// not NaNs with payloads.
+  ///
+  /// Errors with the listing's first instruction which assembles differently,
+  /// or with the [`AsmError`] if the listing does not assemble because some
+  /// bytes could not be decoded.
+  pub fn reassemble_check(&self,) -> Result<(), ReassembleError,> {
+    let code = self.as_slice();
+    let listing = Listing::new(code, &self.symbols, None,).to_string();
+    let reassembled = asm::assemble(&listing,).map_err(ReassembleError::Asm,)?;
+    let reassembled = reassembled.as_slice();
+
+    let mut lines = listing.lines();
+    let mut original = listing_lines(code,);
+    let mut new = listing_lines(reassembled,);
+    loop {
+      let (offset, before, after,) = match (original.next(), new.next(),) {
+        (None, None,) => return Ok((),),
+        (Some((offset, len, _,),), None,) => (offset, &code[offset..offset + len], &[][..],),
+        (None, Some((offset, len, _,),),) => (offset, &[][..], &reassembled[offset..offset + len],),
+        (Some((offset, len, _,),), Some((new, new_len, _,),),) => (
+          offset,
+          &code[offset..offset + len],
+          &reassembled[new..new + new_len],
+        ),
+      };
+      let text = lines.next().unwrap_or_default();
+      if before != after {
+        return Err(ReassembleError::Mismatch {
+          offset:offset as u32,
+          text:text.to_string(),
+          original:before.to_vec(),
+          reassembled:after.to_vec(),
+        },);
+      }
+    }
+  }
+
+  /// CRC32 checksum of the file [`Program::save`] writes. Matches the
+  /// checksum at the end of the file.
+  pub fn checksum(&self,) -> u32 {
+    let bytes = self.to_file_bytes();
+    u32::from_le_bytes(bytes[bytes.len() - format::CHECKSUM_LEN..].try_into().unwrap(),)
+  }
+
+  /// Returns the offset execution starts at. Defaults to 0.
+  pub fn entry_point(&self,) -> u32 {
+    self.entry_point
+  }
+
+  pub fn set_entry_point(&mut self, entry_point:u32,) {
+    self.entry_point = entry_point;
+  }
+
+  /// Returns the names of the program's functions.
+  pub fn symbols(&self,) -> &SymbolTable {
+    &self.symbols
+  }
+
+  pub fn symbols_mut(&mut self,) -> &mut SymbolTable {
+    &mut self.symbols
+  }
+
+  pub fn set_symbols(&mut self, symbols:SymbolTable,) {
+    self.symbols = symbols;
+  }
+
+  /// Returns the keys and values describing where the program came from.
+  pub fn metadata(&self,) -> &ProgramMetadata {
+    &self.metadata
+  }
+
+  pub fn metadata_mut(&mut self,) -> &mut ProgramMetadata {
+    &mut self.metadata
+  }
+
+  pub fn set_metadata(&mut self, metadata:ProgramMetadata,) {
+    self.metadata = metadata;
+  }
+
+  /// Returns the source lines the program's code was compiled from.
+  pub fn debug_info(&self,) -> &DebugInfo {
+    &self.debug_info
+  }
+
+  pub fn debug_info_mut(&mut self,) -> &mut DebugInfo {
+    &mut self.debug_info
+  }
+
+  pub fn set_debug_info(&mut self, debug_info:DebugInfo,) {
+    self.debug_info = debug_info;
+  }
+
+  /// Records that the instructions emitted from now on were compiled from
+  /// `line` and `column` of `file`, until the next call.
+  pub fn set_source_loc(&mut self, file:&str, line:u32, column:u32,) {
+    self.debug_info.record(self.len() as u32, file, line, column,);
+  }
+
+  /// Returns the program's [`Relocation`]s in order of their offsets.
+  pub fn relocations(&self,) -> &[Relocation] {
+    &self.relocations
+  }
+
+  /// Records that the 4 bytes at `offset` hold an address of the given kind
+  /// which [`Program::rebase`] and [`Program::link`] must update.
+  pub fn add_relocation(&mut self, offset:u32, kind:RelocKind,) {
+    let pos = self.relocations.partition_point(|reloc| reloc.offset < offset,);
+    if self
+      .relocations
+      .get(pos,)
+      .is_some_and(|reloc| reloc.offset == offset,)
+    {
+      self.relocations[pos].kind = kind;
+    }
+    else {
+      self.relocations.insert(pos, Relocation { offset, kind, },);
+    }
+  }
+
+  /// Moves every code address recorded in the program's [`Relocation`]s
+  /// `new_base` bytes later, for loading the program `new_base` bytes into a
+  /// larger image. Programs are built based at 0 so rebasing an already
+  /// rebased program moves it again.
+  ///
+  /// Errors if a relocation runs past the end of the code. Nothing is patched
+  /// if there is an error.
+  pub fn rebase(&mut self, new_base:u32,) -> Result<(), PatchError,> {
+    self.relocate(new_base, 0,)
+  }
+
+  /// Adds `code` to every code address and `data` to every data address
+  /// recorded in the program's [`Relocation`]s.
+  fn relocate(&mut self, code:u32, data:u32,) -> Result<(), PatchError,> {
+    // Check every relocation first so nothing is patched if one is out of
+    // bounds
+    for reloc in &self.relocations {
+      self.read_u32_at(reloc.offset,)?;
+    }
+    for reloc in self.relocations.clone() {
+      let delta = match reloc.kind {
+        RelocKind::Code => code,
+        RelocKind::Data => data,
+      };
+      let address = self.read_u32_at(reloc.offset,)?;
+      self.patch_u32(reloc.offset, address.wrapping_add(delta,),)?;
+    }
+    Ok((),)
+  }
+
+  /// Returns the program's code.
+  pub fn code(&self,) -> &[u8] {
+    self.as_slice()
+  }
+
+  /// Returns the program's data segment. See [`Program::data_segment`] for
+  /// where it is copied to.
+  pub fn data(&self,) -> &[u8] {
+    &self.data
+  }
+
+  /// Appends `bytes` to the data segment and returns a handle to them.
+  pub fn add_data(&mut self, bytes:&[u8],) -> DataHandle {
+    let handle = DataHandle {
+      offset:self.data.len() as u32,
+      len:bytes.len() as u32,
+    };
+    self.data.extend_from_slice(bytes,);
+    handle
+  }
+
+  /// Returns the address the VM copies the data segment to. Defaults to the
+  /// start of the heap in [`MemoryLayout::default`]. VMs should start their
+  /// heap after the data segment.
+  pub fn data_base(&self,) -> Address {
+    Address::new_unchecked(self.data_base,)
+  }
+
+  /// Sets the address the data segment is copied to. Instructions already
+  /// emitted by [`Program::emit_write_data`] are not updated so this should be
+  /// called before referencing any data.
+  pub fn set_data_base(&mut self, base:u32,) {
+    self.data_base = base;
+  }
+
+  /// Returns the address in VM memory the bytes of `data` are copied to.
+  pub fn data_address(&self, data:DataHandle,) -> Address {
+    Address::new_unchecked(self.data_base + data.offset,)
+  }
+
+  /// Returns the address the VM should copy the data segment to and its
+  /// bytes, or `None` if the program has no data.
+  pub fn data_segment(&self,) -> Option<(Address, &[u8],),> {
+    (!self.data.is_empty()).then_some((self.data_base(), self.data.as_slice(),),)
+  }
+
+  /// Emits the `LoadU32`s which point `ptr` and `len` at `data` followed by a
+  /// `WriteStr`. Returns the offset of the first instruction.
+  pub fn emit_write_data(&mut self, ptr:Register, len:Register, data:DataHandle,) -> u32 {
+    let start = self.emit_load_int(ptr, self.data_address(data,).get(),);
+    self.add_relocation(start + 2, RelocKind::Data,);
+    self.emit_load_int(len, data.len,);
+    self.emit_write_str(ptr, len,);
+    start
+  }
+
+  /// Writes the [`Program`] to `w` preceded by a header identifying it as a
+  /// spdr program.
+  #[cfg(feature = "std")]
+  pub fn write_to<W:Write,>(&self, w:&mut W,) -> Result<(), ProgramError,> {
+    w.write_all(&self.to_file_bytes(),)?;
+    Ok((),)
+  }
+
+  /// Reads a [`Program`] written by [`Program::write_to`], checking its
+  /// header and checksum.
+  #[cfg(feature = "std")]
+  pub fn read_from<R:Read,>(r:&mut R,) -> Result<Self, ProgramError,> {
+    Program::read_from_with_warnings(r,).map(|(program, _,)| program,)
+  }
+
+  /// Same as [`Program::read_from`] but also returns a
+  /// [`ProgramError::UnknownSection`] for every section which was skipped
+  /// because this version of the crate does not understand it.
+  #[cfg(feature = "std")]
+  pub fn read_from_with_warnings<R:Read,>(r:&mut R,) -> Result<(Self, Vec<ProgramError,>,), ProgramError,> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes,)?;
+    let (sections, warnings,) = format::decode(&bytes, true,)?;
+    Ok((Program::from_sections(sections,)?, warnings,),)
+  }
+
+  /// Reads a [`Program`] written by [`Program::write_to`] without verifying
+  /// its checksum.
+  #[cfg(feature = "std")]
+  pub fn read_from_unchecked<R:Read,>(r:&mut R,) -> Result<Self, ProgramError,> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes,)?;
+    Program::from_sections(format::decode(&bytes, false,)?.0,)
+  }
+
+  /// Returns the bytes [`Program::save`] writes: the program preceded by a
+  /// header identifying it as a spdr program.
+  pub fn to_file_bytes(&self,) -> Vec<u8,> {
+    self.encode(&self.inner, &self.data, false,)
+  }
+
+  /// Returns the bytes [`Program::save_with_options`] writes.
+  #[cfg(feature = "compress")]
+  pub fn to_file_bytes_with(&self, options:SaveOptions,) -> Vec<u8,> {
+    match options.compression {
+      Some(level,) => self.encode(
+        &compress::compress(&self.inner, level,),
+        &compress::compress(&self.data, level,),
+        true,
+      ),
+      None => self.to_file_bytes(),
+    }
+  }
+
+  /// Encodes the program as a file holding `code` and `data`, which are
+  /// `compressed` versions of its own.
+  fn encode(&self, code:&[u8], data:&[u8], compressed:bool,) -> Vec<u8,> {
+    format::encode(format::Sections {
+      // Programs which cannot be decoded may use any opcode
+      isa_version:self.required_isa_version().unwrap_or(CURRENT_ISA_VERSION,),
+      compressed,
+      entry_point:self.entry_point,
+      code,
+      // Programs without data which use the default base have no data
+      // section
+      data_base:(!self.data.is_empty() || self.data_base != Program::new().data_base)
+        .then_some(self.data_base,),
+      data,
+      symbols:&self.symbols.to_bytes(),
+      relocations:&relocs::to_bytes(&self.relocations,),
+      metadata:&self.metadata.to_bytes(),
+      debug:&self.debug_info.to_bytes(),
+    },)
+  }
+
+  /// Reads a [`Program`] from the bytes of a file written by
+  /// [`Program::save`], checking its header and checksum.
+  pub fn from_file_bytes(bytes:&[u8],) -> Result<Self, ProgramError,> {
+    Program::from_sections(format::decode(bytes, true,)?.0,)
+  }
+
+  pub(crate) fn from_sections(sections:format::Sections,) -> Result<Self, ProgramError,> {
+    let symbols = SymbolTable::from_bytes(sections.symbols,)
+      .ok_or(ProgramError::MalformedSection(SectionKind::Symbols,),)?;
+    let relocations = relocs::from_bytes(sections.relocations,)
+      .ok_or(ProgramError::MalformedSection(SectionKind::Relocations,),)?;
+    let metadata = ProgramMetadata::from_bytes(sections.metadata,)
+      .ok_or(ProgramError::MalformedSection(SectionKind::Metadata,),)?;
+    let debug_info =
+      DebugInfo::from_bytes(sections.debug,).ok_or(ProgramError::MalformedSection(SectionKind::Debug,),)?;
+    let (code, data,) = format::decompress(&sections,)?;
+    Ok(Program {
+      inner:code,
+      data,
+      data_base:sections.data_base.unwrap_or(Program::new().data_base,),
+      entry_point:sections.entry_point,
+      symbols,
+      relocations,
+      metadata,
+      debug_info,
+      ..Program::new()
+    },)
+  }
+
+  /// Saves the [`Program`] to the file at `output` preceded by a header
+  /// identifying the file as a spdr program.
+  ///
+  /// The program is written and synced to a temporary file in the same
+  /// directory which then replaces `output`, so a failed save, even one
+  /// interrupted by a crash, leaves any existing file at `output` untouched.
+  /// On Windows the file is replaced the same way, but fails if another
+  /// process has `output` open.
+  #[cfg(feature = "std")]
+  pub fn save(&self, output:impl AsRef<Path,>,) -> Result<(), ProgramError,> {
+    Program::save_with(output.as_ref(), &self.to_file_bytes(), |file, bytes| {
+      file.write_all(bytes,)
+    },)
+  }
+
+  /// Saves the [`Program`] like [`Program::save`], compressing its code and
+  /// data if `options` asks for it. Every loader decompresses them.
+  #[cfg(all(feature = "std", feature = "compress"))]
+  pub fn save_with_options(
+    &self,
+    output:impl AsRef<Path,>,
+    options:SaveOptions,
+  ) -> Result<(), ProgramError,> {
+    let bytes = self.to_file_bytes_with(options,);
+    Program::save_with(output.as_ref(), &bytes, |file, bytes| file.write_all(bytes,),)
+  }
+
+  /// Saves `bytes` to `output` as [`Program::save`] does, writing the
+  /// temporary file with `write`.
+  #[cfg(feature = "std")]
+  fn save_with(
+    output:&Path,
+    bytes:&[u8],
+    write:impl FnOnce(&mut File, &[u8],) -> std::io::Result<(),>,
+  ) -> Result<(), ProgramError,> {
+    let mut temp = output.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()),);
+    let temp = PathBuf::from(temp,);
+    let fail = |stage| {
+      move |source| ProgramError::Save {
+        stage,
+        path:output.to_path_buf(),
+        source,
+      }
+    };
+
+    let saved = File::create(&temp,)
+      .map_err(fail(SaveStage::Create,),)
+      .and_then(|mut file| {
+        write(&mut file, bytes,).map_err(fail(SaveStage::Write,),)?;
+        file.sync_all().map_err(fail(SaveStage::Sync,),)
+      },)
+      // `rename` replaces an existing file on every platform
+      .and_then(|_| std::fs::rename(&temp, output,).map_err(fail(SaveStage::Rename,),),);
+    if saved.is_err() {
+      let _ = std::fs::remove_file(&temp,);
+    }
+    saved
+  }
+
+  /// Loads a [`Program`] saved by [`Program::save`] without copying its code,
+  /// checking its header and checksum. See [`MappedProgram`] for the caveats
+  /// of mapping a file.
+  ///
+  /// Platforms without memory-mapped files read the whole file instead.
+  ///
+  /// # Safety
+  ///
+  /// Nothing, in this process or any other, may change or truncate the file
+  /// at `source` until the returned [`MappedProgram`] is dropped.
+  #[cfg(feature = "mmap")]
+  pub unsafe fn load_mmap(source:impl AsRef<Path,>,) -> Result<MappedProgram, ProgramError,> {
+    // SAFETY: Upheld by the caller
+    unsafe { MappedProgram::open(source.as_ref(),) }
+  }
+
+  /// Loads a [`Program`] saved by [`Program::save`], checking its header and
+  /// checksum.
+  #[cfg(feature = "std")]
+  pub fn load(source:impl AsRef<Path,>,) -> Result<Self, ProgramError,> {
+    let source = source.as_ref();
+    File::open(source,)
+      .map_err(ProgramError::from,)
+      .and_then(|mut file| Program::read_from(&mut file,),)
+      .map_err(|err| err.with_path(source,),)
+  }
+
+  /// Same as [`Program::load`] but also returns a
+  /// [`ProgramError::UnknownSection`] for every section which was skipped
+  /// because this version of the crate does not understand it.
+  #[cfg(feature = "std")]
+  pub fn load_with_warnings(source:impl AsRef<Path,>,) -> Result<(Self, Vec<ProgramError,>,), ProgramError,> {
+    let source = source.as_ref();
+    File::open(source,)
+      .map_err(ProgramError::from,)
+      .and_then(|mut file| Program::read_from_with_warnings(&mut file,),)
+      .map_err(|err| err.with_path(source,),)
+  }
+
+  /// Loads a [`Program`] saved by [`Program::save`] without verifying its
+  /// checksum. Useful for inspecting half-written files.
+  #[cfg(feature = "std")]
+  pub fn load_unchecked(source:impl AsRef<Path,>,) -> Result<Self, ProgramError,> {
+    let source = source.as_ref();
+    File::open(source,)
+      .map_err(ProgramError::from,)
+      .and_then(|mut file| Program::read_from_unchecked(&mut file,),)
+      .map_err(|err| err.with_path(source,),)
+  }
+
+  /// Loads a headerless [`Program`] by treating the entire file as code.
+  #[cfg(feature = "std")]
+  pub fn load_raw(source:impl AsRef<Path,>,) -> Result<Self, ProgramError,> {
+    let source = source.as_ref();
+    let mut inner = Vec::new();
+    File::open(source,)
+      .and_then(|mut file| file.read_to_end(&mut inner,),)
+      .map_err(|err| ProgramError::from(err,).with_path(source,),)?;
+    Ok(Program::from(inner,),)
+  }
+
+  /// Loads a [`Program`] like [`Program::load_with_warnings`], also loading
+  /// headerless files as [`Program::load_raw`] does if `options` allows it.
+  ///
+  /// The returned [`LoadInfo`] says which kind of file was loaded. A legacy
+  /// file is any file which does not start with the spdr magic bytes.
+  #[cfg(feature = "std")]
+  pub fn load_with_options(
+    source:impl AsRef<Path,>,
+    options:LoadOptions,
+  ) -> Result<(Self, LoadInfo,), ProgramError,> {
+    let source = source.as_ref();
+    let bytes = std::fs::read(source,).map_err(|err| ProgramError::from(err,).with_path(source,),)?;
+    if options.allow_legacy && !bytes.starts_with(&format::MAGIC,) {
+      let info = LoadInfo {
+        format:LoadFormat::Legacy,
+        warnings:Vec::new(),
+      };
+      return Ok((Program::from(bytes,), info,),);
+    }
+
+    let (sections, warnings,) = format::decode(&bytes, true,)?;
+    let info = LoadInfo {
+      format:LoadFormat::Saved,
+      warnings,
+    };
+    Ok((Program::from_sections(sections,)?, info,),)
+  }
+
+  /// Rewrites the headerless file at `path` in the format [`Program::save`]
+  /// writes, returning whether it was rewritten. Files which already have a
+  /// header are left as they are.
+  ///
+  /// Like every save, a failed upgrade leaves the file untouched.
+  #[cfg(feature = "std")]
+  pub fn upgrade_file(path:impl AsRef<Path,>,) -> Result<bool, ProgramError,> {
+    let path = path.as_ref();
+    let options = LoadOptions { allow_legacy:true, };
+    let (program, info,) = Program::load_with_options(path, options,)?;
+    if info.format == LoadFormat::Saved {
+      return Ok(false,);
+    }
+
+    program.save(path,)?;
+    Ok(true,)
+  }
+}
+
+#[cfg(feature = "compress")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+/// Options for [`Program::save_with_options`] and
+/// [`Program::to_file_bytes_with`].
+pub struct SaveOptions {
+  /// Compresses the code and data at the level, or leaves them as they are if
+  /// `None`.
+  pub compression:Option<Level,>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+/// Options for [`Program::load_with_options`].
+pub struct LoadOptions {
+  /// Loads files without a header as raw code instead of rejecting them with
+  /// [`ProgramError::NotASpdrFile`].
+  pub allow_legacy:bool,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// The kind of file [`Program::load_with_options`] loaded.
+pub enum LoadFormat {
+  /// A file written by [`Program::save`].
+  Saved,
+  /// A headerless file of raw code from before programs were saved with a
+  /// header.
+  Legacy,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug,)]
+/// What [`Program::load_with_options`] found while loading a file.
+pub struct LoadInfo {
+  pub format:LoadFormat,
+  /// Sections which were skipped, as returned by
+  /// [`Program::load_with_warnings`]. Always empty for legacy files.
+  pub warnings:Vec<ProgramError,>,
+}
+
+/// Iterator over the [`Instruction`]s of a [`Program`] created by
+/// [`Program::instructions`].
+pub struct Instructions<'a,> {
+  bytes:&'a [u8],
+  offset:usize,
+  done:bool,
+}
+
+impl<'a,> Instructions<'a,> {
+  /// Iterates over the instructions in `code`.
+  pub(crate) fn new(code:&'a [u8],) -> Self {
+    Instructions {
+      bytes:code,
+      offset:0,
+      done:false,
+    }
+  }
+}
+
+impl Iterator for Instructions<'_,> {
+  type Item = Result<(u32, Instruction,), DecodeError,>;
+
+  fn next(&mut self,) -> Option<Self::Item,> {
+    if self.done || self.offset >= self.bytes.len() {
+      return None;
+    }
+
+    let offset = self.offset as u32;
+    match Instruction::decode(&self.bytes[self.offset..],) {
+      Ok((inst, len,),) => {
+        self.offset += len;
+        Some(Ok((offset, inst,),),)
+      }
+      Err(err,) => {
+        self.done = true;
+        Some(Err(err.with_offset(offset,),),)
+      }
+    }
+  }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Program {
+  /// Serializes the bytes [`Program::to_file_bytes`] returns.
+  fn serialize<S:serde::Serializer,>(&self, serializer:S,) -> Result<S::Ok, S::Error,> {
+    serializer.serialize_bytes(&self.to_file_bytes(),)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de,> serde::Deserialize<'de,> for Program {
+  /// Deserializes the bytes of a saved program, checking its header and
+  /// checksum like [`Program::from_file_bytes`].
+  fn deserialize<D:serde::Deserializer<'de,>,>(deserializer:D,) -> Result<Self, D::Error,> {
+    struct FileBytes;
+
+    impl<'de,> serde::de::Visitor<'de,> for FileBytes {
+      type Value = Program;
+
+      fn expecting(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+        f.write_str("the bytes of a spdr program",)
+      }
+
+      fn visit_bytes<E:serde::de::Error,>(self, bytes:&[u8],) -> Result<Program, E,> {
+        Program::from_file_bytes(bytes,).map_err(E::custom,)
+      }
+
+      fn visit_seq<A:serde::de::SeqAccess<'de,>,>(self, mut seq:A,) -> Result<Program, A::Error,> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0,),);
+        while let Some(byte,) = seq.next_element()? {
+          bytes.push(byte,);
+        }
+        self.visit_bytes(&bytes,)
+      }
+    }
+
+    deserializer.deserialize_byte_buf(FileBytes,)
+  }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a,> arbitrary::Arbitrary<'a,> for Program {
+  /// Generates a program of arbitrary instructions. Every instruction is
+  /// valid on its own but jumps may land anywhere.
+  fn arbitrary(u:&mut arbitrary::Unstructured<'a,>,) -> arbitrary::Result<Self,> {
+    let mut program = Program::new();
+    for inst in u.arbitrary_iter::<Instruction>()? {
+      program.emit(inst?,);
+    }
+    Ok(program,)
+  }
+}
+
+/// Serializes a [`Program`] as a list of [`Instruction`]s when the format is
+/// human-readable and as bytes otherwise. Use it with
+/// `#[serde(with = "spdr_isa::program::as_instructions")]`.
+///
+/// Only the code survives the instruction list: the data segment, entry
+/// point, symbols, and relocations are dropped.
+#[cfg(feature = "serde")]
+pub mod as_instructions {
+  use super::Program;
+  use crate::instruction::Instruction;
+  use alloc::vec::Vec;
+  use serde::{ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S:Serializer,>(program:&Program, serializer:S,) -> Result<S::Ok, S::Error,> {
+    if !serializer.is_human_readable() {
+      return program.serialize(serializer,);
+    }
+    let instructions = program
+      .instructions()
+      .map(|item| item.map(|(_, inst,)| inst,),)
+      .collect::<Result<Vec<_,>, _,>>()
+      .map_err(S::Error::custom,)?;
+    serializer.collect_seq(instructions,)
+  }
+
+  pub fn deserialize<'de, D:Deserializer<'de,>,>(deserializer:D,) -> Result<Program, D::Error,> {
+    if !deserializer.is_human_readable() {
+      return Program::deserialize(deserializer,);
+    }
+    let mut program = Program::new();
+    for inst in Vec::<Instruction,>::deserialize(deserializer,)? {
+      program.emit(inst,);
+    }
+    Ok(program,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Program;
+  #[cfg(feature = "std")]
+  use super::{LoadFormat, LoadOptions};
+  #[cfg(feature = "std")]
+  use crate::errors::{ProgramError, SaveStage};
+  use crate::{
+    errors::{DecodeError, LinkError, PatchError, SliceError, ValidationError},
+    instruction::Instruction,
+    memory::{Address, MemoryLayout},
+    opcodes::{CmpFlag, IsaVersion, OpCode, CURRENT_ISA_VERSION},
+    registers::{reg, Register, EQ, FIRST_FREE_REGISTER, FP, LOOP, PC, SP},
+    relocs::RelocKind,
+    syscalls::SyscallTable,
+  };
+  use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+  };
+  use eyre::{eyre, Result};
+  #[cfg(feature = "std")]
+  use std::io::{Cursor, Write};
+
+  #[test]
+  #[rustfmt::skip]
+  fn opcode_printing_in_program_display() {
+    // Test Hlt
+    op_cmp([OpCode::Hlt.into(),], "Hlt",).unwrap();
+    // Test Load
+    op_cmp([OpCode::Load.into(), 14, 0, 0, 128, 63,], "Load $14, 1.0",).unwrap();
+    // Test Copy
+    op_cmp([OpCode::Copy.into(), 14, 15,], "Copy $14, $15",).unwrap();
+    // Test MemCpy
+    op_cmp([OpCode::MemCpy.into(), 14, 15,], "MemCpy $14, $15",).unwrap();
+    // Test Add_RI
+    op_cmp([OpCode::AddRI.into(), 14, 15, 0, 0, 128, 63,], "Add_RI $14, $15, 1.0",).unwrap();
+    // Test Sub_RI
+    op_cmp([OpCode::SubRI.into(), 14, 15, 0, 0, 128, 63,], "Sub_RI $14, $15, 1.0",).unwrap();
+    // Test RvSubRI
+    op_cmp([OpCode::RvSubRI.into(), 14, 15, 0, 0, 128, 63,], "RvSub_RI $14, $15, 1.0",).unwrap();
+    // Test Mul_RI
+    op_cmp([OpCode::MulRI.into(), 14, 15, 0, 0, 128, 63,], "Mul_RI $14, $15, 1.0",).unwrap();
+    // Test Div_RI
+    op_cmp([OpCode::DivRI.into(), 14, 15, 0, 0, 128, 63,], "Div_RI $14, $15, 1.0",).unwrap();
+    // Test RvDivRI
+    op_cmp([OpCode::RvDivRI.into(), 14, 15, 0, 0, 128, 63,], "RvDiv_RI $14, $15, 1.0",).unwrap();
+    // Test Pow_RI
+    op_cmp([OpCode::PowRI.into(), 14, 15, 0, 0, 128, 63,], "Pow_RI $14, $15, 1.0",).unwrap();
+    // Test RvPowRI
+    op_cmp([OpCode::RvPowRI.into(), 14, 15, 0, 0, 128, 63,], "RvPow_RI $14, $15, 1.0",).unwrap();
+    // Test Add_RR
+    op_cmp([OpCode::AddRR.into(), 14, 15, 16,], "Add_RR $14, $15, $16",).unwrap();
+    // Test Sub_RR
+    op_cmp([OpCode::SubRR.into(), 14, 15, 16,], "Sub_RR $14, $15, $16",).unwrap();
+    // Test Mul_RR
+    op_cmp([OpCode::MulRR.into(), 14, 15, 16,], "Mul_RR $14, $15, $16",).unwrap();
+    // Test Div_RR
+    op_cmp([OpCode::DivRR.into(), 14, 15, 16,], "Div_RR $14, $15, $16",).unwrap();
+    // Test Pow_RR
+    op_cmp([OpCode::PowRR.into(), 14, 15, 16,], "Pow_RR $14, $15, $16",).unwrap();
+    // Test Cmp_RI
+    op_cmp([OpCode::CmpRI.into(), CmpFlag::Eq.into(), 14, 0, 0, 128, 63,], "Cmp_RI EQ, $14, 1.0",).unwrap();
+    // Test Cmp_RR
+    op_cmp([OpCode::CmpRR.into(), CmpFlag::Gt.into(), 14, 15,], "Cmp_RR GT, $14, $15",).unwrap();
+    op_cmp([OpCode::CmpRI.into(), CmpFlag::Neq.into(), 14, 0, 0, 128, 63,], "Cmp_RI NEQ, $14, 1.0",).unwrap();
+    // Test Not
+    op_cmp([OpCode::Not.into(), EQ as u8, 14], "Not $EQ, $14").unwrap();
+    op_cmp([OpCode::Not.into(), 14, EQ as u8], "Not $14, $EQ").unwrap();
+    // Test Jmp
+    op_cmp([OpCode::Jmp.into(), 50, 0, 0 ,0,], "Jmp 50").unwrap();
+    // Test Jz
+    op_cmp([OpCode::Jz.into(), 2, 50, 0, 0 ,0,], "Jz $EQ, 50").unwrap();
+    // Test Jnz
+    op_cmp([OpCode::Jnz.into(), 2, 50, 0, 0 ,0,], "Jnz $EQ, 50").unwrap();
+    op_cmp([OpCode::Jnz.into(), 14, 50, 0, 0 ,0,], "Jnz $14, 50").unwrap();
+    op_cmp([OpCode::Jz.into(), LOOP as u8, 50, 0, 0 ,0,], "Jz $LOOP, 50").unwrap();
+    op_cmp([OpCode::Jz.into(), FP as u8, 50, 0, 0 ,0,], "Jz $FP, 50").unwrap();
+    // Test Call
+    op_cmp([OpCode::Call.into(), 14, 0, 0, 0,], "Call 14").unwrap();
+    op_cmp([OpCode::Call.into(), 0, 1, 0, 0,], "Call 256").unwrap();
+    // Test SysCall
+    op_cmp([OpCode::SysCall.into(), 14,], "SysCall 14").unwrap();
+    // Test Ret
+    op_cmp([OpCode::Ret.into(), 14,], "Ret 14").unwrap();
+    // Test Alloc
+    op_cmp([OpCode::Alloc.into(), 14, 15,], "Alloc $14, $15").unwrap();
+    // Test Realloc
+    op_cmp([OpCode::Realloc.into(), 14, 15,], "Realloc $14, $15").unwrap();
+    // Test Dealloc
+    op_cmp([OpCode::Dealloc.into(), 14,], "Dealloc $14").unwrap();
+    // Test RMem
+    op_cmp([OpCode::RMem.into(), 14, 15, 1, 0, 0, 0, 16,], "RMem $14, $15, 1, $16").unwrap();
+    // Test WMem
+    op_cmp([OpCode::WMem.into(), 14, 15, 1, 0, 0, 0, 16,], "WMem $14, $15, 1, $16").unwrap();
+    // Test Push
+    op_cmp([OpCode::Push.into(), 14,], "Push $14").unwrap();
+    op_cmp([OpCode::Push.into(), SP as u8,], "Push $SP").unwrap();
+    op_cmp([OpCode::Copy.into(), PC as u8, SP as u8,], "Copy $PC, $SP").unwrap();
+    // Test Pop
+    op_cmp([OpCode::Pop.into(),], "Pop").unwrap();
+    // Test PopR
+    op_cmp([OpCode::PopR.into(), 14,], "PopR $14").unwrap();
+    // Test WriteStr
+    op_cmp([OpCode::WriteStr.into(), 15, 16,], "WriteStr $15, $16").unwrap();
+    op_cmp([OpCode::WriteStr.into(), EQ as u8, 16,], "WriteStr $EQ, $16").unwrap();
+    // Test Noop
+    op_cmp([OpCode::Noop.into(),], "Noop").unwrap();
+    // Test ModRI
+    op_cmp([OpCode::ModRI.into(), 14, 15, 0, 0, 128, 63,], "Mod_RI $14, $15, 1.0").unwrap();
+    // Test RvModRI
+    op_cmp([OpCode::RvModRI.into(), 14, 15, 0, 0, 128, 63,], "RvMod_RI $14, $15, 1.0").unwrap();
+    // Test ModRR
+    op_cmp([OpCode::ModRR.into(), 14, 15, 16,], "Mod_RR $14, $15, $16").unwrap();
+    // Test Neg
+    op_cmp([OpCode::Neg.into(), 14, 15,], "Neg $14, $15").unwrap();
+    // Test Abs
+    op_cmp([OpCode::Abs.into(), 14, 15,], "Abs $14, $15").unwrap();
+    // Test Sqrt
+    op_cmp([OpCode::Sqrt.into(), 14, 15,], "Sqrt $14, $15").unwrap();
+    // Test Floor
+    op_cmp([OpCode::Floor.into(), 14, 15,], "Floor $14, $15").unwrap();
+    // Test Ceil
+    op_cmp([OpCode::Ceil.into(), 14, 15,], "Ceil $14, $15").unwrap();
+    // Test Round
+    op_cmp([OpCode::Round.into(), 14, 15,], "Round $14, $15").unwrap();
+    // Test Trunc
+    op_cmp([OpCode::Trunc.into(), 14, 15,], "Trunc $14, $15").unwrap();
+    // Test AndRR
+    op_cmp([OpCode::AndRR.into(), 14, 15, 16,], "And_RR $14, $15, $16").unwrap();
+    // Test OrRR
+    op_cmp([OpCode::OrRR.into(), 14, 15, 16,], "Or_RR $14, $15, $16").unwrap();
+    // Test XorRR
+    op_cmp([OpCode::XorRR.into(), 14, 15, 16,], "Xor_RR $14, $15, $16").unwrap();
+    // Test AndRI
+    op_cmp([OpCode::AndRI.into(), 14, 15, 1, 0, 0, 0,], "And_RI $14, $15, 1").unwrap();
+    // Test OrRI
+    op_cmp([OpCode::OrRI.into(), 14, 15, 1, 0, 0, 0,], "Or_RI $14, $15, 1").unwrap();
+    // Test XorRI
+    op_cmp([OpCode::XorRI.into(), 14, 15, 1, 0, 0, 0,], "Xor_RI $14, $15, 1").unwrap();
+    // Test ShlRI
+    op_cmp([OpCode::ShlRI.into(), 14, 15, 1, 0, 0, 0,], "Shl_RI $14, $15, 1").unwrap();
+    // Test ShrRI
+    op_cmp([OpCode::ShrRI.into(), 14, 15, 1, 0, 0, 0,], "Shr_RI $14, $15, 1").unwrap();
+    // Test ShlRR
+    op_cmp([OpCode::ShlRR.into(), 14, 15, 16,], "Shl_RR $14, $15, $16").unwrap();
+    // Test ShrRR
+    op_cmp([OpCode::ShrRR.into(), 14, 15, 16,], "Shr_RR $14, $15, $16").unwrap();
+    // Test MinRR
+    op_cmp([OpCode::MinRR.into(), 14, 15, 16,], "Min_RR $14, $15, $16").unwrap();
+    // Test MaxRR
+    op_cmp([OpCode::MaxRR.into(), 14, 15, 16,], "Max_RR $14, $15, $16").unwrap();
+    // Test MinRI
+    op_cmp([OpCode::MinRI.into(), 14, 15, 0, 0, 128, 63,], "Min_RI $14, $15, 1.0").unwrap();
+    // Test MaxRI
+    op_cmp([OpCode::MaxRI.into(), 14, 15, 0, 0, 128, 63,], "Max_RI $14, $15, 1.0").unwrap();
+    // Test Sin
+    op_cmp([OpCode::Sin.into(), 14, 15,], "Sin $14, $15").unwrap();
+    // Test Cos
+    op_cmp([OpCode::Cos.into(), 14, 15,], "Cos $14, $15").unwrap();
+    // Test Tan
+    op_cmp([OpCode::Tan.into(), 14, 15,], "Tan $14, $15").unwrap();
+    // Test Atan2
+    op_cmp([OpCode::Atan2.into(), 14, 15, 16,], "Atan2 $14, $15, $16").unwrap();
+    // Test Ln
+    op_cmp([OpCode::Ln.into(), 14, 15,], "Ln $14, $15").unwrap();
+    // Test Log2
+    op_cmp([OpCode::Log2.into(), 14, 15,], "Log2 $14, $15").unwrap();
+    // Test Exp
+    op_cmp([OpCode::Exp.into(), 14, 15,], "Exp $14, $15").unwrap();
+    // Test JmpCmpRR
+    op_cmp([OpCode::JmpCmpRR.into(), CmpFlag::Gt as u8, 14, 15, 120, 0, 0, 0,], "JmpCmp_RR GT, $14, $15, 120").unwrap();
+    // Test JmpCmpRI
+    op_cmp([OpCode::JmpCmpRI.into(), CmpFlag::Gt as u8, 14, 0, 0, 128, 63, 120, 0, 0, 0,], "JmpCmp_RI GT, $14, 1.0, 120").unwrap();
+    // Test JmpR
+    op_cmp([OpCode::JmpR.into(), 14,], "JmpR $14").unwrap();
+    // Test CallR
+    op_cmp([OpCode::CallR.into(), 14,], "CallR $14").unwrap();
+    // Test JmpRel
+    op_cmp([OpCode::JmpRel.into(), 244, 255, 255, 255,], "JmpRel -12").unwrap();
+    // Test JzRel
+    op_cmp([OpCode::JzRel.into(), 14, 8, 0, 0, 0,], "JzRel $14, 8").unwrap();
+    // Test JnzRel
+    op_cmp([OpCode::JnzRel.into(), 14, 8, 0, 0, 0,], "JnzRel $14, 8").unwrap();
+    // Test Inc
+    op_cmp([OpCode::Inc.into(), 14,], "Inc $14").unwrap();
+    // Test Dec
+    op_cmp([OpCode::Dec.into(), 14,], "Dec $14").unwrap();
+    // Test Swap
+    op_cmp([OpCode::Swap.into(), 14, 15,], "Swap $14, $15").unwrap();
+    // Test LoadU32
+    op_cmp([OpCode::LoadU32.into(), 14, 112, 17, 1, 0,], "LoadU32 $14, 70000").unwrap();
+    // Test PushI
+    op_cmp([OpCode::PushI.into(), 0, 0, 128, 63,], "PushI 1.0").unwrap();
+    // Test PopN
+    op_cmp([OpCode::PopN.into(), 3,], "PopN 3").unwrap();
+    // Test Enter
+    op_cmp([OpCode::Enter.into(), 3,], "Enter 3").unwrap();
+    // Test Leave
+    op_cmp([OpCode::Leave.into(),], "Leave").unwrap();
+    // Test MemCpyN
+    op_cmp([OpCode::MemCpyN.into(), 14, 15, 16, 8, 0, 0, 0, 0, 0, 0, 0,], "MemCpyN $14, $15, $16, 8, 0").unwrap();
+    // Test MemSet
+    op_cmp([OpCode::MemSet.into(), 14, 15, 16,], "MemSet $14, $15, $16").unwrap();
+    // Test Trap
+    op_cmp([OpCode::Trap.into(),], "Trap").unwrap();
+    // Test ReadStr
+    op_cmp([OpCode::ReadStr.into(), 14, 15,], "ReadStr $14, $15").unwrap();
+    // Test DbgReg
+    op_cmp([OpCode::DbgReg.into(), 14,], "DbgReg $14").unwrap();
+    // Test All
+    op_cmp(ALL_OPCODES, ALL_OPCODES_LISTING,).unwrap();
+  }
+
+  #[test]
+  fn step_through_program_with_operand_len() {
+    let mut offset = 0;
+    let mut count = 0;
+    while offset < ALL_OPCODES.len() {
+      let op = OpCode::try_from(ALL_OPCODES[offset],).unwrap();
+      assert_eq!(op.instruction_len(), op.operand_len() + 1);
+      offset += op.instruction_len();
+      count += 1;
+    }
+    assert_eq!(offset, ALL_OPCODES.len());
+    assert_eq!(count, ALL_OPCODES_LISTING.lines().count());
+  }
+
+  #[test]
+  fn emit_helpers_in_program_display() {
+    let mut p = Program::new();
+    assert_eq!(p.emit_load(reg(14), 1.0,), 0);
+    assert_eq!(p.emit_copy(reg(14), reg(15),), 6);
+    assert_eq!(p.emit_mem_cpy(reg(14), reg(15),), 9);
+    p.emit_add_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_sub_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_rv_sub_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_mul_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_div_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_rv_div_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_pow_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_rv_pow_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_add_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_sub_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_mul_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_div_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_pow_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_cmp_ri(CmpFlag::Eq, reg(14,), 1.0,);
+    p.emit_cmp_rr(CmpFlag::Gt, reg(14,), reg(15,),);
+    p.emit_not(Register::EQ, reg(14,),);
+    p.emit_jmp(50,);
+    p.emit_jz(Register::EQ, 50,);
+    p.emit_jnz(Register::EQ, 50,);
+    p.emit_call(14,);
+    p.emit_sys_call(14,);
+    p.emit_ret(14,);
+    p.emit_alloc(reg(14,), reg(15,),);
+    p.emit_realloc(reg(14,), reg(15,),);
+    p.emit_dealloc(reg(14,),);
+    p.emit_rmem(reg(14,), reg(15,), 1, reg(16,),);
+    p.emit_wmem(reg(14,), reg(15,), 1, reg(16,),);
+    p.emit_push(reg(14,),);
+    p.emit_pop();
+    p.emit_pop_r(reg(14,),);
+    p.emit_write_str(reg(15,), reg(16,),);
+    p.emit_noop();
+    p.emit_mod_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_rv_mod_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_mod_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_neg(reg(14,), reg(15,),);
+    p.emit_abs(reg(14,), reg(15,),);
+    p.emit_sqrt(reg(14,), reg(15,),);
+    p.emit_floor(reg(14,), reg(15,),);
+    p.emit_ceil(reg(14,), reg(15,),);
+    p.emit_round(reg(14,), reg(15,),);
+    p.emit_trunc(reg(14,), reg(15,),);
+    p.emit_and_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_or_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_xor_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_and_ri(reg(14,), reg(15,), 1,);
+    p.emit_or_ri(reg(14,), reg(15,), 1,);
+    p.emit_xor_ri(reg(14,), reg(15,), 1,);
+    p.emit_shl_ri(reg(14,), reg(15,), 1,);
+    p.emit_shr_ri(reg(14,), reg(15,), 1,);
+    p.emit_shl_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_shr_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_min_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_max_rr(reg(14,), reg(15,), reg(16,),);
+    p.emit_min_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_max_ri(reg(14,), reg(15,), 1.0,);
+    p.emit_sin(reg(14,), reg(15,),);
+    p.emit_cos(reg(14,), reg(15,),);
+    p.emit_tan(reg(14,), reg(15,),);
+    p.emit_atan2(reg(14,), reg(15,), reg(16,),);
+    p.emit_ln(reg(14,), reg(15,),);
+    p.emit_log2(reg(14,), reg(15,),);
+    p.emit_exp(reg(14,), reg(15,),);
+    p.emit_jmp_cmp_rr(CmpFlag::Gt, reg(14,), reg(15,), 120,);
+    p.emit_jmp_cmp_ri(CmpFlag::Gt, reg(14,), 1.0, 120,);
+    p.emit_jmp_r(reg(14,),);
+    p.emit_call_r(reg(14,),);
+    p.emit_jmp_rel(-12,);
+    p.emit_jz_rel(reg(14,), 8,);
+    p.emit_jnz_rel(reg(14,), 8,);
+    p.emit_inc(reg(14,),);
+    p.emit_dec(reg(14,),);
+    p.emit_swap(reg(14,), reg(15,),);
+    p.emit_load_int(reg(14,), 70000,);
+    p.emit_push_i(1.0,);
+    p.emit_pop_n(3,);
+    p.emit_enter(3,);
+    p.emit_leave();
+    p.emit_mem_cpy_n(reg(14,), reg(15,), reg(16,), 8, 0,);
+    p.emit_mem_set(reg(14,), reg(15,), reg(16,),);
+    p.emit_trap();
+    p.emit_read_str(reg(14,), reg(15,),);
+    let last = p.emit_dbg_reg(reg(14,),);
+
+    let (_, len,) = Instruction::decode(&p.as_slice()[last as usize..],).unwrap();
+    assert_eq!(last as usize + len, p.len());
+    assert_eq!(format!("{}", p), format!("{}\n", ALL_OPCODES_LISTING));
+
+    let mut p = Program::new();
+    p.emit_hlt();
+    assert_eq!(p.as_slice(), &[u8::from(OpCode::Hlt,)]);
+
+    let mut p = Program::new();
+    p.emit_cmp_inverted(CmpFlag::Lt, reg(14,), reg(15,),);
+    assert_eq!(format!("{}", p), "Cmp_RR GEQ, $14, $15\n");
+  }
+
+  #[test]
+  fn invalid_bytes_in_program_display() {
+    op_cmp([0x7F,], "??? (0x7F)",).unwrap();
+    op_cmp(
+      [OpCode::Pop.into(), 0x7F, OpCode::Noop.into(),],
+      "Pop\n??? (0x7F)\nNoop",
+    )
+    .unwrap();
+    op_cmp([OpCode::Jmp.into(), 50,], "??? (0x14)\n??? (0x32)",).unwrap();
+  }
+
+  #[test]
+  fn annotated_program_display() {
+    let mut program = Program::new();
+    program.emit_load(reg(14,), 1.0,);
+    program.emit_pop();
+    program.push(0x7F,);
+    program.emit_jmp(0,);
+    program.emit_jz_rel(Register::EQ, -19,);
+
+    let expected = format!(
+      "0x0000:  {:02X} 0E 00 00 80 3F   Load $14, 1.0\n\
+       0x0006:  {:02X}                  Pop\n\
+       0x0007:  7F                  ??? (0x7F)\n\
+       0x0008:  {:02X} 00 00 00 00      Jmp 0\n\
+       0x000D:  {:02X} 02 ED FF FF FF   JzRel $EQ, -19 ; -> 0x0000\n",
+      u8::from(OpCode::Load,),
+      u8::from(OpCode::Pop,),
+      u8::from(OpCode::Jmp,),
+      u8::from(OpCode::JzRel,),
+    );
+    assert_eq!(format!("{:#}", program), expected);
+    assert_eq!(program.disassemble_annotated(), expected);
+  }
+
+  #[test]
+  #[rustfmt::skip]
+  fn validate_program() {
+    let program = Program::from([
+      OpCode::Load.into(), 14, 0, 0, 128, 63,
+      OpCode::Jz.into(), EQ as u8, 12, 0, 0, 0,
+      OpCode::Jmp.into(), 0, 0, 0, 0,
+      OpCode::Hlt.into(),
+    ],);
+    assert_eq!(program.validate(), Ok(()));
+    assert_eq!(program.validate_strict(), Ok(()));
+
+    // Register 255 exists
+    let program = Program::from([
+      OpCode::Load.into(), 255, 0, 0, 128, 63,
+      OpCode::Jmp.into(), 2, 0, 0, 0,
+      OpCode::Jnz.into(), EQ as u8, 100, 0, 0, 0,
+      OpCode::JmpR.into(), 255,
+      OpCode::Call.into(), 0, 0, 0, 0,
+    ],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![
+        ValidationError::JumpIntoOperand { at:6, target:2 },
+        ValidationError::JumpOutOfBounds { at:11, target:100 },
+      ])
+    );
+    assert_eq!(
+      program.validate_strict().unwrap_err().last(),
+      Some(&ValidationError::MissingTerminator { at:24 })
+    );
+
+    // Register offsets must hold exact integers
+    let program = Program::from([
+      OpCode::Load.into(), 14, 0, 0, 192, 63,
+      OpCode::Load.into(), 15, 0, 0, 128, 63,
+      OpCode::RMem.into(), 16, 17, 0, 0, 0, 0, 14,
+      OpCode::WMem.into(), 16, 17, 0, 0, 0, 0, 15,
+      OpCode::Load.into(), 14, 0, 0, 192, 63,
+      OpCode::LoadU32.into(), 14, 8, 0, 0, 0,
+      OpCode::RMem.into(), 16, 17, 0, 0, 0, 0, 14,
+      OpCode::Hlt.into(),
+    ],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![ValidationError::InexactOffset { at:12, register:14, load:0 }])
+    );
+
+    // Large integers are exact and loads do not reach past the end of their
+    // block
+    let mut program = Program::new();
+    program.emit_load(reg(14,), 20_000_000.0,);
+    program.emit_load(reg(15,), 0.5,);
+    program.emit_rmem(reg(16,), reg(17,), 0, reg(14,),);
+    program.emit_hlt();
+    let block = program.emit_rmem(reg(16,), reg(17,), 0, reg(15,),);
+    program.emit_hlt();
+    assert_eq!(program.validate(), Ok(()));
+    program.emit_load(reg(15,), 4_294_967_296.0,);
+    program.emit_rmem(reg(16,), reg(17,), 0, reg(15,),);
+    assert_eq!(
+      program.validate(),
+      Err(vec![ValidationError::InexactOffset {
+        at:block + 15,
+        register:15,
+        load:block + 9
+      }])
+    );
+
+    // Relative jumps are checked against the offset they resolve to
+    let program = Program::from([
+      OpCode::JmpRel.into(), 251, 255, 255, 255,
+      OpCode::JnzRel.into(), EQ as u8, 252, 255, 255, 255,
+      OpCode::Hlt.into(),
+    ],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![ValidationError::JumpIntoOperand { at:5, target:7 }])
+    );
+  }
+
+  #[test]
+  fn insert_and_remove_instructions() {
+    use crate::builder::ProgramBuilder;
+
+    let mut b = ProgramBuilder::new();
+    let top = b.new_label();
+    b.emit(Instruction::Load {
+      dst:reg(16,),
+      imm:0.0,
+    },);
+    b.bind(top,).unwrap();
+    b.emit(Instruction::AddRI {
+      dst:reg(16,),
+      src:reg(16,),
+      imm:1.0,
+    },);
+    b.emit(Instruction::MulRI {
+      dst:reg(17,),
+      src:reg(16,),
+      imm:2.0,
+    },);
+    b.emit(Instruction::CmpRI {
+      flag:CmpFlag::Lt,
+      src:reg(16,),
+      imm:10.0,
+    },);
+    b.jnz(Register::EQ, top,);
+    b.emit(Instruction::JzRel {
+      cond:Register::EQ,
+      offset:7,
+    },);
+    b.emit(Instruction::AddRI {
+      dst:reg(18,),
+      src:reg(18,),
+      imm:1.0,
+    },);
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(p.validate_strict(), Ok(()));
+
+    let mut instrumented = p.clone();
+    let arithmetic = p
+      .instructions()
+      .map(|item| item.unwrap(),)
+      .filter(|(_, inst,)| matches!(inst.opcode(), OpCode::AddRI | OpCode::MulRI),)
+      .collect::<Vec<_,>>();
+    for (at, inst,) in arithmetic.iter().rev() {
+      let dst = inst.writes()[0];
+      instrumented
+        .insert(*at, Instruction::DbgReg { src:dst, },)
+        .unwrap();
+    }
+    assert_eq!(
+      instrumented.to_string(),
+      "\
+Load $16, 0.0
+DbgReg $16
+Add_RI $16, $16, 1.0
+DbgReg $17
+Mul_RI $17, $16, 2.0
+Cmp_RI LT, $16, 10.0
+Jnz $EQ, 8
+JzRel $EQ, 9
+DbgReg $18
+Add_RI $18, $18, 1.0
+Hlt
+"
+    );
+    assert_eq!(instrumented.validate_strict(), Ok(()));
+
+    // Every jump lands on the same instruction as before
+    let landings = |program:&Program| {
+      program
+        .instructions()
+        .filter_map(|item| {
+          let (at, inst,) = item.unwrap();
+          let target = inst.resolve_target(at,)?;
+          let landing = Instruction::decode(&program.code()[target as usize..],)
+            .unwrap()
+            .0;
+          Some(landing,)
+        },)
+        .collect::<Vec<_,>>()
+    };
+    assert_eq!(landings(&instrumented,), landings(&p,));
+
+    let dbg = instrumented
+      .instructions()
+      .map(|item| item.unwrap(),)
+      .filter(|(_, inst,)| inst.opcode() == OpCode::DbgReg,)
+      .map(|(at, _,)| at,)
+      .collect::<Vec<_,>>();
+    for at in dbg.into_iter().rev() {
+      assert_eq!(instrumented.remove_at(at,).unwrap().opcode(), OpCode::DbgReg);
+    }
+    assert_eq!(instrumented, p);
+
+    assert_eq!(
+      instrumented.insert(1, Instruction::Noop,),
+      Err(PatchError::NotAnInstruction { offset:1 })
+    );
+    assert_eq!(
+      instrumented.remove_at(p.len() as u32,),
+      Err(PatchError::NotAnInstruction {
+        offset:p.len() as u32
+      })
+    );
+    instrumented.insert(p.len() as u32, Instruction::Noop,).unwrap();
+    assert_eq!(instrumented.len(), p.len() + 1);
+  }
+
+  #[test]
+  fn patch_instructions_in_place() {
+    let mut p = Program::new();
+    p.emit_load(reg(16,), 1.0,);
+    p.emit_call(0,);
+    p.emit(Instruction::Hlt,);
+    p.add_relocation(7, RelocKind::Code,);
+    let len = p.len();
+
+    p.nop_out(0,).unwrap();
+    assert_eq!(p.to_string(), "Noop\nNoop\nNoop\nNoop\nNoop\nNoop\nCall 0\nHlt\n");
+    assert!(p.is_instruction_start(5,));
+    assert_eq!(p.len(), len);
+
+    p.replace_at(6, Instruction::Inc { dst:reg(16,), },).unwrap();
+    assert_eq!(
+      p.to_string(),
+      "Noop\nNoop\nNoop\nNoop\nNoop\nNoop\nInc $16\nNoop\nNoop\nNoop\nHlt\n"
+    );
+    assert!(p.relocations().is_empty());
+    let err = p.replace_at(1, Instruction::Jmp { target:6, },).unwrap_err();
+    assert_eq!(
+      err,
+      PatchError::TooWide {
+        offset:1,
+        old:1,
+        new:5
+      }
+    );
+    assert_eq!(
+      err.to_string(),
+      "a 5 byte instruction does not fit in place of the 1 byte instruction at offset 1"
+    );
+    assert_eq!(p.nop_out(7,), Err(PatchError::NotAnInstruction { offset:7 }));
+  }
+
+  #[test]
+  #[rustfmt::skip]
+  fn validate_misaligned_jumps() {
+    let program = Program::from([
+      OpCode::Load.into(), 14, 0, 0, 128, 63,
+      // Lands on the Load's immediate, which decodes as a `Hlt`
+      OpCode::Jmp.into(), 3, 0, 0, 0,
+      OpCode::Call.into(), 13, 0, 0, 0,
+      OpCode::JmpCmpRI.into(), CmpFlag::Eq as u8, 14, 0, 0, 128, 63, 0, 0, 0, 0,
+      OpCode::Jz.into(), EQ as u8, 33, 0, 0, 0,
+      OpCode::Hlt.into(),
+    ],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![
+        ValidationError::JumpIntoOperand { at:6, target:3 },
+        ValidationError::JumpIntoOperand { at:11, target:13 },
+      ])
+    );
+
+    // Jumping to the end of the program is out of bounds
+    let program = Program::from([OpCode::Jmp.into(), 5, 0, 0, 0,],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![ValidationError::JumpOutOfBounds { at:0, target:5 }])
+    );
+  }
+
+  #[test]
+  fn validate_truncated_program() {
+    let program = Program::from([OpCode::Hlt.into(), OpCode::Jmp.into(), 0, 0,],);
+    assert_eq!(
+      program.validate(),
+      Err(vec![ValidationError::Decode(DecodeError::Truncated {
+        op:OpCode::Jmp,
+        offset:Some(1),
+      })])
+    );
+  }
+
+  #[test]
+  fn patch_and_read_operands() {
+    let mut p = Program::new();
+    p.emit_load(reg(14,), 0.0,);
+    let jmp = p.emit_jmp(0,);
+
+    p.patch_f32(2, 2.5,).unwrap();
+    assert_eq!(p.read_f32_at(2,).unwrap(), 2.5);
+    p.patch_once_u32(jmp + 1, 40,).unwrap();
+    assert_eq!(p.read_u32_at(jmp + 1,).unwrap(), 40);
+    assert_eq!(format!("{}", p), "Load $14, 2.5\nJmp 40\n");
+
+    assert_eq!(
+      p.patch_once_u32(jmp + 1, 80,),
+      Err(PatchError::AlreadyPatched { offset:7, current:40 })
+    );
+    assert_eq!(
+      p.patch_u32(8, 1,),
+      Err(PatchError::OutOfBounds { offset:8, len:11 })
+    );
+    assert_eq!(
+      p.read_u32_at(u32::MAX,),
+      Err(PatchError::OutOfBounds {
+        offset:u32::MAX,
+        len:11
+      })
+    );
+  }
+
+  #[test]
+  fn strip_debug_registers() {
+    let mut p = Program::new();
+    p.emit_dbg_reg(reg(14,),);
+    let top = p.emit_sub_ri(reg(14,), reg(14,), 1.0,);
+    p.emit_dbg_reg(reg(14,),);
+    p.emit_jnz_rel(reg(14,), -15,);
+    p.emit_dbg_reg(reg(14,),);
+    p.emit_jnz(reg(14,), top,);
+    p.emit_hlt();
+
+    let mut expected = Program::new();
+    expected.emit_sub_ri(reg(14,), reg(14,), 1.0,);
+    expected.emit_jnz_rel(reg(14,), -13,);
+    expected.emit_jnz(reg(14,), 0,);
+    expected.emit_hlt();
+
+    assert_eq!(p.strip_debug(), Ok(expected));
+  }
+
+  #[test]
+  fn symbolic_syscalls() {
+    let syscalls = "3 exit 0\n14 print_entity 2 Prints an entity.\n15 log 1"
+      .parse::<SyscallTable>()
+      .unwrap();
+    let mut p = Program::new();
+    p.emit_sys_call(14,);
+    p.emit_sys_call(15,);
+    p.emit_sys_call(7,);
+    p.emit_sys_call(3,);
+
+    let listing = p.with_syscalls(&syscalls,).to_string();
+    assert_eq!(
+      listing,
+      "SysCall print_entity (2 args)\nSysCall log (1 arg)\nSysCall 7\nSysCall exit (0 args)\n"
+    );
+    assert_eq!(crate::asm::assemble_with(&listing, &syscalls,), Ok(p.clone()));
+    assert_eq!(
+      p.validate_with(&syscalls,),
+      Err(vec![ValidationError::UnknownSyscall { at:4, idx:7 }])
+    );
+  }
+
+  #[test]
+  fn validate_memory_layout() {
+    let layout = MemoryLayout::with_stack(1024, 64,).unwrap();
+    let mut p = Program::new();
+    p.emit_rmem(reg(14,), reg(15,), 1023, Register::PC,);
+    p.emit_wmem(reg(14,), reg(15,), 1024, Register::PC,);
+    p.emit_load_int(reg(16,), 1000,);
+    p.emit_rmem(reg(14,), reg(15,), 24, reg(16,),);
+    // Unknown register offsets may be anything
+    p.emit_add_ri(reg(16,), reg(16,), 1.0,);
+    p.emit_rmem(reg(14,), reg(15,), 24, reg(16,),);
+    p.emit_load(reg(16,), 2048.0,);
+    p.emit_wmem(reg(14,), reg(15,), 0, reg(16,),);
+
+    assert_eq!(p.validate(), Ok(()));
+    assert_eq!(p.validate_layout(&MemoryLayout::default(),), Ok(()));
+    assert_eq!(
+      p.validate_layout(&layout,),
+      Err(vec![
+        ValidationError::AddressOutOfBounds {
+          at:8,
+          address:Address::new_unchecked(1024,),
+        },
+        ValidationError::AddressOutOfBounds {
+          at:22,
+          address:Address::new_unchecked(1024,),
+        },
+        ValidationError::AddressOutOfBounds {
+          at:51,
+          address:Address::new_unchecked(2048,),
+        },
+      ])
+    );
+  }
+
+  #[test]
+  fn validate_layout_forgets_loads_at_joins() {
+    use crate::builder::ProgramBuilder;
+
+    let read = Instruction::RMem {
+      dst:reg(14,),
+      src:reg(15,),
+      imm_offset:0,
+      reg_offset:reg(16,),
+    };
+    let mut b = ProgramBuilder::new();
+    let join = b.new_label();
+    b.emit(Instruction::LoadU32 { dst:reg(16,), imm:0, },);
+    b.jz(Register::EQ, join,);
+    b.emit(Instruction::LoadU32 {
+      dst:reg(16,),
+      imm:70_000,
+    },);
+    // Reached with either value
+    b.bind(join,).unwrap();
+    b.emit(read,);
+    b.emit(Instruction::Hlt,);
+    let p = b.finish().unwrap();
+    assert_eq!(p.validate(), Ok(()));
+    assert_eq!(p.validate_layout(&MemoryLayout::default(),), Ok(()));
+  }
+
+  #[test]
+  fn validate_layout_forgets_loads_after_calls() {
+    use crate::builder::ProgramBuilder;
+
+    let read = Instruction::RMem {
+      dst:reg(14,),
+      src:reg(15,),
+      imm_offset:0,
+      reg_offset:reg(16,),
+    };
+    let mut b = ProgramBuilder::new();
+    let func = b.new_label();
+    b.emit(Instruction::LoadU32 {
+      dst:reg(16,),
+      imm:70_000,
+    },);
+    b.call(func,);
+    // The callee set the register
+    b.emit(read,);
+    b.emit(Instruction::LoadU32 {
+      dst:reg(16,),
+      imm:70_000,
+    },);
+    b.emit(Instruction::SysCall { idx:0, },);
+    b.emit(read,);
+    b.emit(Instruction::Hlt,);
+    b.bind(func,).unwrap();
+    b.emit(Instruction::LoadU32 { dst:reg(16,), imm:0, },);
+    b.emit(Instruction::Ret { args:0, },);
+    let p = b.finish().unwrap();
+    assert_eq!(p.validate(), Ok(()));
+    assert_eq!(p.validate_layout(&MemoryLayout::default(),), Ok(()));
+  }
+
+  #[test]
+  fn instruction_boundaries() {
+    // Every byte of the operands is also a valid opcode
+    let imm = u32::from_le_bytes([
+      OpCode::Jmp.into(),
+      OpCode::Noop.into(),
+      OpCode::Hlt.into(),
+      OpCode::Jmp.into(),
+    ],);
+    let mut p = Program::new();
+    p.emit_load_int(reg(20,), imm,);
+    p.emit_jmp(imm,);
+    p.emit_call(0,);
+    assert_eq!(p.instruction_boundaries(), Ok(vec![0, 6, 11]));
+    assert!(p.is_instruction_start(6,));
+    assert!(!p.is_instruction_start(2,));
+    assert!(!p.is_instruction_start(7,));
+    assert!(!p.is_instruction_start(16,));
+
+    // Mutations clear the cache
+    p.emit(Instruction::Hlt,);
+    assert!(p.is_instruction_start(16,));
+    // Replacing the `Jmp` decodes its operand as instructions
+    p[6] = OpCode::Noop.into();
+    assert_eq!(p.instruction_boundaries(), Ok(vec![0, 6, 7, 12, 13, 14, 15, 16]));
+    p.push_front(vec![OpCode::Noop.into()],);
+    assert!(p.is_instruction_start(1,));
+    assert!(!p.is_instruction_start(3,));
+    p.as_mut_slice()[0] = OpCode::Jmp.into();
+    assert!(!p.is_instruction_start(1,));
+    p.extend_from_slice(&[0xFF,],);
+    assert!(matches!(
+      p.instruction_boundaries(),
+      Err(DecodeError::InvalidOpCode { byte:0xFF, .. })
+    ));
+    assert!(p.is_instruction_start(5,));
+  }
+
+  #[test]
+  fn append_programs() {
+    let mut p = Program::new();
+    p.emit_load(reg(16,), 0.0,);
+    p.emit_load(reg(17,), 10.0,);
+
+    let mut looped = Program::new();
+    looped.emit_add_ri(reg(16,), reg(16,), 1.0,);
+    looped.emit_jmp_cmp_rr(CmpFlag::Lt, reg(16,), reg(17,), 0,);
+    looped.emit_jz(Register::EQ, 0,);
+    looped.emit_jmp_rel(0,);
+    looped.emit_call(31,);
+    looped.emit_ret(0,);
+
+    assert_eq!(p.append(&looped,), Ok(12));
+    assert_eq!(
+      p.to_string(),
+      "Load $16, 0.0\nLoad $17, 10.0\nAdd_RI $16, $16, 1.0\nJmpCmp_RR LT, $16, $17, 12\nJz $EQ, 12\nJmpRel 0\nCall 43\nRet 0\n"
+    );
+    assert_eq!(p.validate_strict(), Ok(()));
+
+    let len = p.len();
+    assert!(matches!(
+      p.append(&Program::from([0xFF,]),),
+      Err(DecodeError::InvalidOpCode { .. })
+    ));
+    assert_eq!(p.len(), len);
+
+    // Targets past the end of the address space wrap
+    let mut far = Program::new();
+    far.emit_jmp(u32::MAX,);
+    assert_eq!(p.append(&far,), Ok(len as u32));
+    assert_eq!(
+      p.instructions().last(),
+      Some(Ok((
+        len as u32,
+        Instruction::Jmp {
+          target:len as u32 - 1
+        }
+      )))
+    );
+  }
+
+  #[test]
+  fn link_programs() {
+    use crate::builder::ProgramBuilder;
+
+    let mut main = ProgramBuilder::new();
+    let top = main.named_label("top",);
+    main.bind(top,).unwrap();
+    main.call_extern("helper",);
+    main.jz(Register::EQ, top,);
+    main.emit(Instruction::Hlt,);
+    let main = main.finish().unwrap();
+
+    let mut helper = ProgramBuilder::new();
+    let done = helper.named_label("done",);
+    helper.emit(Instruction::Noop,);
+    helper.jnz(Register::EQ, done,);
+    helper.call_extern("log",);
+    helper.bind(done,).unwrap();
+    helper.emit(Instruction::Ret { args:0, },);
+    let mut helper = helper.finish().unwrap();
+    helper.symbols_mut().insert("helper", 0,).unwrap();
+
+    let mut log = Program::new();
+    log.emit_ret(0,);
+    log.symbols_mut().insert("log", 0,).unwrap();
+
+    let linked = Program::link(&[main.clone(), helper.clone(), log.clone(),],).unwrap();
+    assert_eq!(
+      linked.to_string(),
+      "Call 12\nJz $EQ, 0\nHlt\nNoop\nJnz $EQ, 24\nCall 26\nRet 0\nRet 0\n"
+    );
+    assert_eq!(linked.symbols().get("helper",), Some(12));
+    assert_eq!(linked.symbols().get("log",), Some(26));
+    assert_eq!(linked.symbols().externs().count(), 0);
+    assert_eq!(linked.validate_strict(), Ok(()));
+    assert!(format!("{:#}", main).contains("Call <helper>"));
+    assert!(format!("{:#}", linked).contains("Call <log>"));
+
+    assert_eq!(
+      Program::link(&[main.clone(), log.clone(),],),
+      Err(LinkError::UnresolvedSymbol("helper".to_string()))
+    );
+    assert_eq!(
+      Program::link(&[main.clone(), helper.clone(), log.clone(), log.clone(),],),
+      Err(LinkError::DuplicateSymbol("log".to_string()))
+    );
+    let mut bad = Program::from([0xFF,],);
+    bad.add_relocation(0, RelocKind::Code,);
+    assert!(matches!(
+      Program::link(&[main.clone(), bad,],),
+      Err(LinkError::Relocation { part:1, .. })
+    ));
+
+    // Externs loaded from a file may point anywhere
+    for at in [1, 5, 100,] {
+      let mut bad = main.clone();
+      bad.symbols_mut().add_extern(at, "helper",).unwrap();
+      assert_eq!(
+        Program::link(&[bad, helper.clone(), log.clone(),],),
+        Err(LinkError::NotACall {
+          at,
+          name:"helper".to_string()
+        })
+      );
+    }
+  }
+
+  #[test]
+  fn rebase_and_link_in_any_order() {
+    use crate::builder::ProgramBuilder;
+
+    // Every string in the parts is 5 bytes long
+    let mut main = ProgramBuilder::new();
+    let top = main.named_label("main",);
+    main.bind(top,).unwrap();
+    let hello = main.intern_str("hello",);
+    main.emit_write_str(reg(16,), reg(17,), hello,);
+    main.call_extern("square",);
+    main.call_extern("greet",);
+    main.jz(Register::EQ, top,);
+    main.emit(Instruction::Hlt,);
+    let mut main = main.finish().unwrap();
+    main.symbols_mut().insert("main", 0,).unwrap();
+
+    let mut square = ProgramBuilder::new();
+    let top = square.new_label();
+    square.bind(top,).unwrap();
+    square.emit(Instruction::MulRR {
+      dst:reg(5,),
+      a:reg(5,),
+      b:reg(5,),
+    },);
+    square.jmp_cmp_ri(CmpFlag::Lt, reg(5,), 100.0, top,);
+    square.emit(Instruction::Ret { args:0, },);
+    let mut square = square.finish().unwrap();
+    square.symbols_mut().insert("square", 0,).unwrap();
+
+    let mut greet = ProgramBuilder::new();
+    let world = greet.intern_str("world",);
+    greet.emit_write_str(reg(16,), reg(17,), world,);
+    greet.call_extern("square",);
+    greet.emit(Instruction::Ret { args:0, },);
+    let mut greet = greet.finish().unwrap();
+    greet.symbols_mut().insert("greet", 0,).unwrap();
+
+    let mut rebased = square.clone();
+    rebased.rebase(100,).unwrap();
+    assert_eq!(
+      rebased.to_string(),
+      "Mul_RR $5, $5, $5\nJmpCmp_RI LT, $5, 100.0, 100\nRet 0\n"
+    );
+    assert_eq!(rebased.relocations(), square.relocations());
+
+    // Lists each function with targets and data addresses replaced by what
+    // they refer to so programs linked in different orders can be compared
+    let normalize = |p:&Program| {
+      let mut functions = Vec::new();
+      let symbols = p.symbols().iter().collect::<Vec<_,>>();
+      for (idx, symbol,) in symbols.iter().enumerate() {
+        let end = symbols.get(idx + 1,).map_or(p.len() as u32, |next| next.offset,);
+        let mut lines = Vec::new();
+        for item in p.instructions() {
+          let (at, inst,) = item.unwrap();
+          if at < symbol.offset || at >= end {
+            continue;
+          }
+          let line = match (inst, inst.jump_target(),) {
+            (Instruction::Call { target, }, _,) => format!("Call {}", p.symbols().at(target,).unwrap().name),
+            (_, Some(target,),) => format!("{} -> {}", inst.opcode(), target - symbol.offset),
+            (Instruction::LoadU32 { dst, imm, }, _,)
+              if p.relocations().iter().any(|r| r.offset == at + 2,) =>
+            {
+              let start = (imm - p.data_base().get()) as usize;
+              format!(
+                "LoadU32 {}, {:?}",
+                dst,
+                core::str::from_utf8(&p.data()[start..start + 5],).unwrap()
+              )
+            }
+            _ => inst.to_string(),
+          };
+          lines.push(line,);
+        }
+        functions.push((symbol.name.clone(), lines,),);
+      }
+      functions.sort();
+      functions
+    };
+
+    let orders = [
+      [main.clone(), square.clone(), greet.clone(),],
+      [greet.clone(), main.clone(), square.clone(),],
+      [square.clone(), greet.clone(), main.clone(),],
+    ];
+    let expected = normalize(&Program::link(&orders[0],).unwrap(),);
+    assert_eq!(expected[1].0, "main");
+    assert_eq!(
+      expected[1].1,
+      [
+        "LoadU32 $16, \"hello\"",
+        "LoadU32 $17, 5",
+        "WriteStr $16, $17",
+        "Call square",
+        "Call greet",
+        "Jz -> 0",
+        "Hlt",
+      ]
+    );
+    for parts in &orders {
+      let linked = Program::link(parts,).unwrap();
+      assert_eq!(linked.validate_strict(), Ok(()));
+      assert_eq!(linked.data().len(), 10);
+      assert_eq!(normalize(&linked), expected);
+      // Programs compare by code alone so compare every section
+      #[cfg(feature = "std")]
+      {
+        let mut bytes = Vec::new();
+        linked.write_to(&mut bytes,).unwrap();
+        let read = Program::read_from(&mut Cursor::new(bytes,),).unwrap();
+        assert_eq!(read.to_file_bytes(), linked.to_file_bytes());
+      }
+      let read = Program::from_file_bytes(&linked.to_file_bytes(),).unwrap();
+      assert_eq!(read.to_file_bytes(), linked.to_file_bytes());
+    }
+  }
+
+  #[test]
+  fn symbolic_calls() {
+    let src = "
+      Call update_position
+      Call <update_position>
+      Hlt
+    update_position:
+      Ret 0
+    ";
+    let p = crate::asm::assemble(src,).unwrap();
+    assert_eq!(p.symbols().get("update_position",), Some(11));
+    assert_eq!(p.to_string(), "Call 11\nCall 11\nHlt\nRet 0\n");
+    let annotated = format!("{:#}", p);
+    assert_eq!(
+      annotated,
+      "0x0000:  17 0B 00 00 00   Call <update_position>
+0x0005:  17 0B 00 00 00   Call <update_position>
+0x000A:  00               Hlt
+0x000B:  19 00            Ret 0
+"
+    );
+
+    // Symbols are saved with the program
+    let read = Program::from_file_bytes(&p.to_file_bytes(),).unwrap();
+    assert_eq!(read, p);
+    assert_eq!(read.symbols(), p.symbols());
+  }
+
+  #[test]
+  fn data_segment() {
+    let mut p = Program::new();
+    assert_eq!(p.data_segment(), None);
+    p.set_data_base(100,);
+    let hello = p.add_data(b"Hello, world!\n",);
+    let table = p.add_data(&(0..20).collect::<Vec<u8,>>(),);
+    assert_eq!(p.data_address(hello,).get(), 100);
+    assert_eq!(p.data_address(table,).get(), 114);
+    assert_eq!(table.len(), 20);
+
+    p.emit_write_data(reg(14,), reg(15,), hello,);
+    p.emit_hlt();
+    let (base, data,) = p.data_segment().unwrap();
+    assert_eq!(base.get(), 100);
+    assert_eq!(&data[..14], b"Hello, world!\n");
+
+    let listing = p.to_string();
+    assert_eq!(
+      listing,
+      "LoadU32 $14, 100
+LoadU32 $15, 14
+WriteStr $14, $15
+Hlt
+; data at 0x0064 (34 bytes)
+;   0x0064:  48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 21 0A 00 01  |Hello, world!...|
+;   0x0074:  02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F 10 11  |................|
+;   0x0084:  12 13                                            |..|
+"
+    );
+    // The dump is ignored by the assembler
+    assert_eq!(crate::asm::assemble(&listing,).unwrap().as_slice(), p.as_slice());
+    assert!(
+      format!("{:#}", p).ends_with(";   0x0084:  12 13                                            |..|\n")
+    );
+  }
+
+  #[test]
+  fn set_and_clear_breakpoints() {
+    let mut p = Program::new();
+    p.emit_load(reg(14,), 1.0,);
+    let jmp = p.emit_jmp(0,);
+    let original = p.clone();
+
+    assert_eq!(
+      p.set_breakpoint(jmp + 1,),
+      Err(PatchError::NotAnInstruction { offset:7 })
+    );
+    assert_eq!(
+      p.clear_breakpoint(jmp, 0,),
+      Err(PatchError::NoBreakpoint { offset:6 })
+    );
+
+    // Breakpoints may be set in any order
+    let load_byte = p.set_breakpoint(0,).unwrap();
+    let jmp_byte = p.set_breakpoint(jmp,).unwrap();
+    // Setting one twice would lose the original byte
+    assert_eq!(
+      p.set_breakpoint(jmp,),
+      Err(PatchError::BreakpointSet { offset:6 })
+    );
+    assert_eq!(jmp_byte, u8::from(OpCode::Jmp,));
+    assert_eq!(load_byte, u8::from(OpCode::Load,));
+    assert!(format!("{}", p).starts_with("Trap\n",));
+
+    p.clear_breakpoint(0, load_byte,).unwrap();
+    assert_eq!(p.set_breakpoint(0,), Ok(load_byte));
+    p.clear_breakpoint(jmp, jmp_byte,).unwrap();
+    p.clear_breakpoint(0, load_byte,).unwrap();
+    assert_eq!(p, original);
+    assert_eq!(p.instruction_boundaries(), original.instruction_boundaries());
   }
 
   #[test]
@@ -386,24 +3797,677 @@ mod test {
       program.as_slice(),
       &[1u8, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
     );
+
+    program.push_front([6, 7,],);
+    program.push_front(b"\x08".iter().copied(),);
+    assert_eq!(&program.as_slice()[..4], &[8, 6, 7, 1,]);
   }
 
   #[test]
-  fn serilize_deserialize_program() {
+  #[cfg(feature = "std")]
+  // The cached boundaries are not hashed so mutating them cannot move a key
+  #[allow(clippy::mutable_key_type)]
+  fn programs_compare_by_code() {
+    use std::{
+      collections::HashMap,
+      hash::{BuildHasher, RandomState},
+    };
+
+    let mut program = Program::new();
+    program.emit(Instruction::Hlt,);
+    let mut with_data = program.clone();
+    with_data.add_data(b"spdr",);
+    assert_eq!(program, with_data);
+    assert_eq!(Program::default(), Program::new());
+
+    let state = RandomState::new();
+    assert_eq!(state.hash_one(&program,), state.hash_one(program.as_slice(),));
+
+    let mut cache = HashMap::new();
+    cache.insert(program.clone(), "halt",);
+    assert_eq!(cache.get(&[u8::from(OpCode::Hlt,)][..],), Some(&"halt"));
+
+    // The fingerprint is FNV-1a, which is fixed
+    assert_eq!(Program::new().fingerprint(), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(Program::from(b"a",).fingerprint(), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(program.fingerprint(), with_data.fingerprint());
+  }
+
+  #[test]
+  fn collect_and_iterate_bytes() {
+    let mut program = [OpCode::Inc, OpCode::Inc,]
+      .into_iter()
+      .flat_map(|op| [u8::from(op,), 16,],)
+      .collect::<Program>();
+    program.extend([u8::from(OpCode::Hlt,),],);
+    assert_eq!(
+      program
+        .instructions()
+        .map(|item| item.unwrap().1,)
+        .collect::<Vec<_,>>(),
+      [
+        Instruction::Inc { dst:reg(16,), },
+        Instruction::Inc { dst:reg(16,), },
+        Instruction::Hlt,
+      ]
+    );
+
+    for byte in &mut program {
+      if *byte == 16 {
+        *byte = 17;
+      }
+    }
+    assert_eq!(program.iter_bytes().filter(|&&byte| byte == 17,).count(), 2);
+    assert_eq!((&program).into_iter().count(), program.len());
+    let len = program.len();
+    assert_eq!(program.into_iter().collect::<Vec<_,>>().len(), len);
+  }
+
+  #[test]
+  fn slice_programs() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.0,);
+    program.emit(Instruction::Inc { dst:reg(16,), },);
+    program.emit(Instruction::Hlt,);
+
+    assert_eq!(&program[6..8], &[u8::from(OpCode::Inc,), 16,]);
+    assert_eq!(&program[..1], &[u8::from(OpCode::Load,)]);
+    assert_eq!(&program[8..], &[u8::from(OpCode::Hlt,)]);
+    assert_eq!(&program[..], program.as_slice());
+    assert_eq!(program.slice(6..8,), Ok(&program[6..8]));
+    assert_eq!(
+      program.slice(8..10,),
+      Err(SliceError::OutOfBounds {
+        start:8,
+        end:10,
+        len:9,
+      },)
+    );
+
+    let body = program.subprogram(6..9,).unwrap();
+    assert_eq!(body.to_string(), "Inc $16\nHlt\n");
+    assert_eq!(program.subprogram(0..0,).unwrap().len(), 0);
+    // Raw bytes can be sliced mid-instruction but subprograms cannot
+    assert_eq!(program[2..7].len(), 5);
+    assert_eq!(
+      program.subprogram(2..6,),
+      Err(SliceError::NotAnInstruction { offset:2, },)
+    );
+    assert_eq!(
+      program.subprogram(0..7,),
+      Err(SliceError::NotAnInstruction { offset:7, },)
+    );
+
+    program.push(0xFF,);
+    assert!(matches!(program.subprogram(6..10,), Err(SliceError::Decode(_,),)));
+    assert!(program.subprogram(0..6,).is_ok());
+  }
+
+  #[test]
+  fn dump_around_bad_bytes() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.push(0xFF,);
+    program.emit(Instruction::Inc { dst:reg(16,), },);
+    program.extend(b"spdr program",);
+    program.emit(Instruction::Hlt,);
+
+    assert_eq!(
+      program.hex_dump(None,).lines().collect::<Vec<_,>>(),
+      [
+        "0x0000:  01 10 00 00 C0 3F FF 4A 10 73 70 64 72 20 70 72  |.....?.J.spdr pr|",
+        "0x0010:  6F 67 72 61 6D 00                                |ogram.|",
+      ]
+    );
+    assert_eq!(
+      program.hex_dump(Some(6..9,),),
+      "0x0006:  FF 4A 10                                         |.J.|\n"
+    );
+    assert_eq!(program.hex_dump(Some(30..40,),), "");
+
+    assert_eq!(
+      program.disassemble_around(6, 1,).lines().collect::<Vec<_,>>(),
+      [
+        "  0x0000:  01 10 00 00 C0 3F   Load $16, 1.5",
+        "> 0x0006:  FF                  ??? (0xFF) ; unsynchronized",
+        "  0x0007:  4A 10               Inc $16 ; resynchronized",
+      ]
+    );
+    // The line containing the offset is marked
+    assert!(program.disassemble_around(3, 0,).starts_with("> 0x0000:",));
+    assert_eq!(Program::new().disassemble_around(0, 3,), "");
+
+    let errors = program.validate().unwrap_err();
+    assert_eq!(
+      errors[0]
+        .display_in(&program,)
+        .to_string()
+        .lines()
+        .collect::<Vec<_,>>(),
+      [
+        "255 is not a valid OpCode (offset 6)",
+        "0x0000:  01 10 00 00 C0 3F FF 4A 10 73 70 64 72 20 70 72  |.....?.J.spdr pr|",
+        "  0x0000:  01 10 00 00 C0 3F   Load $16, 1.5",
+        "> 0x0006:  FF                  ??? (0xFF) ; unsynchronized",
+        "  0x0007:  4A 10               Inc $16 ; resynchronized",
+        "  0x0009:  73                  ??? (0x73) ; unsynchronized",
+      ]
+    );
+    // Other errors display as usual
+    let jump = ValidationError::JumpOutOfBounds { at:0, target:100, };
+    assert_eq!(jump.display_in(&program,).to_string(), jump.to_string());
+  }
+
+  #[test]
+  fn registers_a_program_needs() {
+    let mut program = Program::new();
+    assert_eq!(program.max_register_used(), Ok(None));
+    assert_eq!(program.required_registers(), Ok(FIRST_FREE_REGISTER + 1));
+
+    // Only reserved registers, some of them implicitly
+    program.emit_load_int(Register::LOOP, 3,);
+    program.emit(Instruction::Call { target:0, },);
+    program.emit(Instruction::Hlt,);
+    assert_eq!(program.max_register_used(), Ok(Some(LOOP as u8)));
+    assert_eq!(program.required_registers(), Ok(FIRST_FREE_REGISTER + 1));
+
+    program.emit(Instruction::Inc { dst:reg(200,), },);
+    assert_eq!(program.max_register_used(), Ok(Some(200)));
+    assert_eq!(program.required_registers(), Ok(201));
+
+    program.push(0xFF,);
+    assert!(program.required_registers().is_err());
+  }
+
+  #[test]
+  fn required_isa_version_is_saved() -> Result<(),> {
+    let mut program = Program::new();
+    assert_eq!(program.required_isa_version(), Ok(IsaVersion(1)));
+    program.emit(Instruction::CmpRR {
+      flag:CmpFlag::Neq,
+      a:reg(16,),
+      b:reg(17,),
+    },);
+    assert_eq!(program.required_isa_version(), Ok(IsaVersion(10)));
+    program.emit(Instruction::MinRR {
+      dst:reg(16,),
+      a:reg(16,),
+      b:reg(17,),
+    },);
+    program.emit(Instruction::Hlt,);
+    assert_eq!(program.required_isa_version(), Ok(IsaVersion(10)));
+
+    let bytes = program.to_file_bytes();
+    assert_eq!(bytes[6..8], 10u16.to_le_bytes());
+    assert_eq!(Program::from_file_bytes(&bytes,)?, program);
+
+    // Files from a newer crate are rejected
+    let mut program = Program::new();
+    program.push(0xFF,);
+    assert!(program.required_isa_version().is_err());
+    let mut bytes = program.to_file_bytes();
+    assert_eq!(bytes[6..8], CURRENT_ISA_VERSION.0.to_le_bytes());
+    bytes[6..8].copy_from_slice(&(CURRENT_ISA_VERSION.0 + 1).to_le_bytes(),);
+    let err = Program::from_file_bytes(&bytes,).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!(
+        "program requires ISA version {} but the newest supported version is {}",
+        CURRENT_ISA_VERSION.0 + 1,
+        CURRENT_ISA_VERSION
+      )
+    );
+    Ok((),)
+  }
+
+  #[test]
+  fn program_capacity() {
+    let mut program = Program::with_capacity(64,);
+    assert!(program.is_empty());
+    assert!(program.capacity() >= 64);
+
+    program.emit(Instruction::Hlt,);
+    program.reserve(1000,);
+    assert!(program.capacity() >= 1001);
+    program.shrink_to_fit();
+    assert!(program.capacity() < 1001);
+    assert_eq!(program.as_slice(), &[u8::from(OpCode::Hlt,)]);
+  }
+
+  #[test]
+  #[rustfmt::skip]
+  fn iterate_program_instructions() {
+    let program = Program::from([
+      OpCode::Load.into(), 14, 0, 0, 128, 63,
+      OpCode::Jmp.into(), 50, 0, 0, 0,
+      OpCode::Hlt.into(),
+    ],);
+
+    let insts = program.instructions().collect::<Result<Vec<_,>, _,>>().unwrap();
+    assert_eq!(
+      insts,
+      vec![
+        (0, Instruction::Load { dst:reg(14), imm:1.0, },),
+        (6, Instruction::Jmp { target:50, },),
+        (11, Instruction::Hlt,),
+      ]
+    );
+
+    assert_eq!(Program::new().instructions().count(), 0);
+  }
+
+  #[test]
+  fn iterate_truncated_program() {
+    let program = Program::from([OpCode::Pop.into(), OpCode::Load.into(), 14, 0, 0,],);
+    let mut insts = program.instructions();
+
+    assert_eq!(insts.next().unwrap().unwrap(), (0, Instruction::Pop,));
+    assert_eq!(
+      insts.next().unwrap(),
+      Err(DecodeError::Truncated {
+        op:OpCode::Load,
+        offset:Some(1),
+      })
+    );
+    assert!(insts.next().is_none());
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn write_read_program_in_memory() {
     let p = Program::from(&[0, 15, 20, 90,],);
-    p.save("test_output.spdr",).unwrap();
-    let new_p = Program::load("test_output.spdr",).unwrap();
+    let mut buf = Cursor::new(Vec::new(),);
+    p.write_to(&mut buf,).unwrap();
 
+    buf.set_position(0,);
+    let new_p = Program::read_from(&mut buf,).unwrap();
     assert_eq!(new_p.inner, p.inner);
+
+    let mut bytes = buf.into_inner();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    assert!(matches!(
+      Program::read_from(&mut bytes.as_slice(),),
+      Err(ProgramError::ChecksumMismatch { .. })
+    ));
+    assert_eq!(
+      Program::read_from_unchecked(&mut bytes.as_slice(),)
+        .unwrap()
+        .inner,
+      p.inner
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn failed_saves_leave_the_file_untouched() -> Result<(),> {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Hlt,);
+    let path = std::env::temp_dir().join("spdr_isa_failed_save.spdr",);
+    program.save(&path,)?;
+    let saved = std::fs::read(&path,)?;
+
+    // Saving over an existing file replaces it
+    let mut changed = program.clone();
+    changed.emit(Instruction::Noop,);
+    changed.save(&path,)?;
+    assert_eq!(Program::load(&path,)?, changed);
+    program.save(&path,)?;
+
+    // Fail halfway through writing the new program
+    let err = Program::save_with(&path, &changed.to_file_bytes(), |file, bytes| {
+      file.write_all(&bytes[..bytes.len() / 2],)?;
+      Err(std::io::Error::other("disk full",),)
+    },)
+    .unwrap_err();
+    assert!(matches!(
+      err,
+      ProgramError::Save {
+        stage:SaveStage::Write,
+        ..
+      }
+    ));
+    assert_eq!(
+      err.to_string(),
+      format!(
+        "{}: failed to write the temporary file: disk full",
+        path.display()
+      )
+    );
+    assert_eq!(std::fs::read(&path,)?, saved);
+    // The temporary file is removed
+    let dir = std::fs::read_dir(std::env::temp_dir(),)?;
+    assert!(!dir.filter_map(|entry| entry.ok(),).any(|entry| entry
+      .file_name()
+      .to_string_lossy()
+      .starts_with("spdr_isa_failed_save.spdr.",)));
+
+    assert!(matches!(
+      program.save("does/not/exist.spdr",),
+      Err(ProgramError::Save {
+        stage:SaveStage::Create,
+        ..
+      })
+    ));
+    std::fs::remove_file(&path,)?;
+    Ok((),)
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn load_errors_name_the_path() {
+    let err = Program::load("does/not/exist.spdr",).unwrap_err();
+    match &err {
+      ProgramError::Io {
+        path: Some(path,), ..
+      } => assert_eq!(path.to_str(), Some("does/not/exist.spdr")),
+      _ => panic!("expected an io error but found {:?}", err),
+    }
+    assert!(err.to_string().starts_with("does/not/exist.spdr: "));
+
+    // ProgramError converts into eyre reports
+    let report:eyre::Report = err.into();
+    assert!(report.downcast_ref::<ProgramError>().is_some());
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn serilize_deserialize_program() {
+    let mut p = Program::from(&[0, 15, 20, 90,],);
+    p.add_data(b"data",);
+    p.set_entry_point(1,);
+    p.symbols_mut().insert("main", 0,).unwrap();
+    p.save("test_output.spdr",).unwrap();
+    let (new_p, warnings,) = Program::load_with_warnings("test_output.spdr",).unwrap();
+    assert_eq!(new_p, p);
+    assert!(warnings.is_empty());
+    assert_eq!(new_p.entry_point(), 1);
+    assert_eq!(new_p.code(), &[0, 15, 20, 90]);
+    assert_eq!(new_p.data(), b"data");
+
+    // Identical programs produce identical files
+    let mut bytes = Vec::new();
+    p.clone().write_to(&mut bytes,).unwrap();
+    assert_eq!(bytes, std::fs::read("test_output.spdr").unwrap());
+
+    let raw_p = Program::load_raw("test_output.spdr",).unwrap();
+    assert_eq!(&raw_p.inner[..4], b"SPDR");
+    assert_eq!(raw_p.inner[raw_p.len() - 4..], p.checksum().to_le_bytes());
+  }
+
+  #[test]
+  #[cfg(feature = "std")]
+  fn load_and_upgrade_legacy_files() -> Result<(),> {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Hlt,);
+    let path = std::env::temp_dir().join("spdr_isa_legacy_program.spdr",);
+    std::fs::write(&path, program.as_slice(),)?;
+
+    assert!(matches!(Program::load(&path,), Err(ProgramError::NotASpdrFile)));
+    assert!(matches!(
+      Program::load_with_options(&path, LoadOptions::default(),),
+      Err(ProgramError::NotASpdrFile)
+    ));
+    let legacy = LoadOptions { allow_legacy:true, };
+    let (loaded, info,) = Program::load_with_options(&path, legacy,)?;
+    assert_eq!(loaded, program);
+    assert_eq!(info.format, LoadFormat::Legacy);
+
+    assert!(Program::upgrade_file(&path,)?);
+    assert_eq!(Program::load(&path,)?, program);
+    let (loaded, info,) = Program::load_with_options(&path, legacy,)?;
+    assert_eq!(loaded, program);
+    assert_eq!(info.format, LoadFormat::Saved);
+    assert!(info.warnings.is_empty());
+    // Upgraded files are left alone
+    assert!(!Program::upgrade_file(&path,)?);
+
+    std::fs::remove_file(&path,)?;
+    assert!(matches!(
+      Program::upgrade_file(&path,),
+      Err(ProgramError::Io { path:Some(_,), .. })
+    ));
+    Ok((),)
   }
 
+  /// Expected listing of a program containing every opcode (except `Hlt`) once.
+  /// One of every instruction in the order [`ALL_OPCODES_LISTING`] lists them.
+  #[rustfmt::skip]
+  const ALL_OPCODES:[u8; 384] = [
+    OpCode::Load as u8, 14, 0, 0, 128, 63,
+    OpCode::Copy as u8, 14, 15,
+    OpCode::MemCpy as u8, 14, 15,
+    OpCode::AddRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::SubRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::RvSubRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::MulRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::DivRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::RvDivRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::PowRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::RvPowRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::AddRR as u8, 14, 15, 16,
+    OpCode::SubRR as u8, 14, 15, 16,
+    OpCode::MulRR as u8, 14, 15, 16,
+    OpCode::DivRR as u8, 14, 15, 16,
+    OpCode::PowRR as u8, 14, 15, 16,
+    OpCode::CmpRI as u8, CmpFlag::Eq as u8, 14, 0, 0, 128, 63,
+    OpCode::CmpRR as u8, CmpFlag::Gt as u8, 14, 15,
+    OpCode::Not as u8, EQ as u8, 14,
+    OpCode::Jmp as u8, 50, 0, 0,0,
+    OpCode::Jz as u8, 2, 50, 0, 0,0,
+    OpCode::Jnz as u8, 2, 50, 0, 0,0,
+    OpCode::Call as u8, 14, 0, 0, 0,
+    OpCode::SysCall as u8, 14,
+    OpCode::Ret as u8, 14,
+    OpCode::Alloc as u8, 14, 15,
+    OpCode::Realloc as u8, 14, 15,
+    OpCode::Dealloc as u8, 14,
+    OpCode::RMem as u8, 14, 15, 1, 0, 0, 0, 16,
+    OpCode::WMem as u8, 14, 15, 1, 0, 0, 0, 16,
+    OpCode::Push as u8, 14,
+    OpCode::Pop as u8,
+    OpCode::PopR as u8, 14,
+    OpCode::WriteStr as u8, 15, 16,
+    OpCode::Noop as u8,
+    
+    OpCode::ModRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::RvModRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::ModRR as u8, 14, 15, 16,
+    OpCode::Neg as u8, 14, 15,
+    OpCode::Abs as u8, 14, 15,
+    OpCode::Sqrt as u8, 14, 15,
+    OpCode::Floor as u8, 14, 15,
+    OpCode::Ceil as u8, 14, 15,
+    OpCode::Round as u8, 14, 15,
+    OpCode::Trunc as u8, 14, 15,
+    OpCode::AndRR as u8, 14, 15, 16,
+    OpCode::OrRR as u8, 14, 15, 16,
+    OpCode::XorRR as u8, 14, 15, 16,
+    OpCode::AndRI as u8, 14, 15, 1, 0, 0, 0,
+    OpCode::OrRI as u8, 14, 15, 1, 0, 0, 0,
+    OpCode::XorRI as u8, 14, 15, 1, 0, 0, 0,
+    OpCode::ShlRI as u8, 14, 15, 1, 0, 0, 0,
+    OpCode::ShrRI as u8, 14, 15, 1, 0, 0, 0,
+    OpCode::ShlRR as u8, 14, 15, 16,
+    OpCode::ShrRR as u8, 14, 15, 16,
+    OpCode::MinRR as u8, 14, 15, 16,
+    OpCode::MaxRR as u8, 14, 15, 16,
+    OpCode::MinRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::MaxRI as u8, 14, 15, 0, 0, 128, 63,
+    OpCode::Sin as u8, 14, 15,
+    OpCode::Cos as u8, 14, 15,
+    OpCode::Tan as u8, 14, 15,
+    OpCode::Atan2 as u8, 14, 15, 16,
+    OpCode::Ln as u8, 14, 15,
+    OpCode::Log2 as u8, 14, 15,
+    OpCode::Exp as u8, 14, 15,
+    OpCode::JmpCmpRR as u8, CmpFlag::Gt as u8, 14, 15, 120, 0, 0, 0,
+    OpCode::JmpCmpRI as u8, CmpFlag::Gt as u8, 14, 0, 0, 128, 63, 120, 0, 0, 0,
+    OpCode::JmpR as u8, 14,
+    OpCode::CallR as u8, 14,
+    OpCode::JmpRel as u8, 244, 255, 255, 255,
+    OpCode::JzRel as u8, 14, 8, 0, 0, 0,
+    OpCode::JnzRel as u8, 14, 8, 0, 0, 0,
+    OpCode::Inc as u8, 14,
+    OpCode::Dec as u8, 14,
+    OpCode::Swap as u8, 14, 15,
+    OpCode::LoadU32 as u8, 14, 112, 17, 1, 0,
+    OpCode::PushI as u8, 0, 0, 128, 63,
+    OpCode::PopN as u8, 3,
+    OpCode::Enter as u8, 3,
+    OpCode::Leave as u8,
+    OpCode::MemCpyN as u8, 14, 15, 16, 8, 0, 0, 0, 0, 0, 0, 0,
+    OpCode::MemSet as u8, 14, 15, 16,
+    OpCode::Trap as u8,
+    OpCode::ReadStr as u8, 14, 15,
+    OpCode::DbgReg as u8, 14,
+  ];
+
+  const ALL_OPCODES_LISTING:&str = "\
+      Load $14, 1.0\n\
+      Copy $14, $15\n\
+      MemCpy $14, $15\n\
+      Add_RI $14, $15, 1.0\n\
+      Sub_RI $14, $15, 1.0\n\
+      RvSub_RI $14, $15, 1.0\n\
+      Mul_RI $14, $15, 1.0\n\
+      Div_RI $14, $15, 1.0\n\
+      RvDiv_RI $14, $15, 1.0\n\
+      Pow_RI $14, $15, 1.0\n\
+      RvPow_RI $14, $15, 1.0\n\
+      Add_RR $14, $15, $16\n\
+      Sub_RR $14, $15, $16\n\
+      Mul_RR $14, $15, $16\n\
+      Div_RR $14, $15, $16\n\
+      Pow_RR $14, $15, $16\n\
+      Cmp_RI EQ, $14, 1.0\n\
+      Cmp_RR GT, $14, $15\n\
+      Not $EQ, $14\n\
+      Jmp 50\n\
+      Jz $EQ, 50\n\
+      Jnz $EQ, 50\n\
+      Call 14\n\
+      SysCall 14\n\
+      Ret 14\n\
+      Alloc $14, $15\n\
+      Realloc $14, $15\n\
+      Dealloc $14\n\
+      RMem $14, $15, 1, $16\n\
+      WMem $14, $15, 1, $16\n\
+      Push $14\n\
+      Pop\n\
+      PopR $14\n\
+      WriteStr $15, $16\n\
+      Noop
+\
+      Mod_RI $14, $15, 1.0
+\
+      RvMod_RI $14, $15, 1.0
+\
+      Mod_RR $14, $15, $16
+\
+      Neg $14, $15
+\
+      Abs $14, $15
+\
+      Sqrt $14, $15
+\
+      Floor $14, $15
+\
+      Ceil $14, $15
+\
+      Round $14, $15
+\
+      Trunc $14, $15
+\
+      And_RR $14, $15, $16
+\
+      Or_RR $14, $15, $16
+\
+      Xor_RR $14, $15, $16
+\
+      And_RI $14, $15, 1
+\
+      Or_RI $14, $15, 1
+\
+      Xor_RI $14, $15, 1
+\
+      Shl_RI $14, $15, 1
+\
+      Shr_RI $14, $15, 1
+\
+      Shl_RR $14, $15, $16
+\
+      Shr_RR $14, $15, $16
+\
+      Min_RR $14, $15, $16
+\
+      Max_RR $14, $15, $16
+\
+      Min_RI $14, $15, 1.0
+\
+      Max_RI $14, $15, 1.0
+\
+      Sin $14, $15
+\
+      Cos $14, $15
+\
+      Tan $14, $15
+\
+      Atan2 $14, $15, $16
+\
+      Ln $14, $15
+\
+      Log2 $14, $15
+\
+      Exp $14, $15
+\
+      JmpCmp_RR GT, $14, $15, 120
+\
+      JmpCmp_RI GT, $14, 1.0, 120
+\
+      JmpR $14
+\
+      CallR $14
+\
+      JmpRel -12
+\
+      JzRel $14, 8
+\
+      JnzRel $14, 8
+\
+      Inc $14
+\
+      Dec $14
+\
+      Swap $14, $15
+\
+      LoadU32 $14, 70000
+\
+      PushI 1.0
+\
+      PopN 3
+\
+      Enter 3
+\
+      Leave
+\
+      MemCpyN $14, $15, $16, 8, 0
+\
+      MemSet $14, $15, $16
+\
+      Trap
+\
+      ReadStr $14, $15
+\
+      DbgReg $14";
+
   /// Helper function for comparing the output of printing a program in tests.
   fn op_cmp<const N: usize,>(p:[u8; N], exp:&str,) -> Result<(),> {
-    let mut w = Vec::new();
-    let p = Program::from(p,);
-    write!(&mut w, "{}", p).unwrap();
+    let out = Program::from(p,).to_string();
     let exp = String::from_iter([exp, "\n",],);
-    let out = String::from_utf8(w,).unwrap();
 
     match out == exp {
       true => Ok((),),
@@ -412,4 +4476,46 @@ mod test {
       ),),
     }
   }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde_round_trip() {
+    #[derive(serde::Serialize, serde::Deserialize,)]
+    struct Listing(#[serde(with = "super::as_instructions")] Program,);
+
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::CmpRI {
+      flag:CmpFlag::Geq,
+      src:reg(16,),
+      imm:2.0,
+    },);
+    program.emit(Instruction::Hlt,);
+    program.add_data(b"spdr",);
+
+    let json = serde_json::to_string(&program,).unwrap();
+    let read = serde_json::from_str::<Program,>(&json,).unwrap();
+    assert_eq!(read.to_file_bytes(), program.to_file_bytes());
+    let bytes = postcard::to_allocvec(&program,).unwrap();
+    let read = postcard::from_bytes::<Program,>(&bytes,).unwrap();
+    assert_eq!(read.to_file_bytes(), program.to_file_bytes());
+
+    // Human-readable formats list the instructions, dropping the data
+    let json = serde_json::to_string(&Listing(program.clone(),),).unwrap();
+    assert_eq!(
+      json,
+      r#"[{"Load":{"dst":16,"imm":1.5}},{"CmpRI":{"flag":"Geq","src":16,"imm":2.0}},"Hlt"]"#
+    );
+    assert_eq!(
+      serde_json::from_str::<Listing,>(&json,).unwrap().0.code(),
+      program.code()
+    );
+    let bytes = postcard::to_allocvec(&Listing(program.clone(),),).unwrap();
+    let read = postcard::from_bytes::<Listing,>(&bytes,).unwrap().0;
+    assert_eq!(read.to_file_bytes(), program.to_file_bytes());
+
+    // Unknown opcodes and corrupt programs are errors
+    assert!(serde_json::from_str::<Listing,>(r#"["Halt"]"#).is_err());
+    assert!(serde_json::from_str::<Program,>("[1, 2, 3]").is_err());
+  }
 }
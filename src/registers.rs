@@ -1,8 +1,20 @@
+use crate::{
+  builder::{Label, ProgramBuilder},
+  errors::{AllocError, CallError, RegisterError},
+  instruction::Instruction,
+};
+use alloc::{
+  string::{String, ToString},
+  vec::Vec,
+};
+use core::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
 /// Number of registers in the [`VM`](https://github.com/Barca545/galaxy).
-pub const REG_COUNT:usize = u8::MAX as usize;
+/// Every `u8` register operand, including 255, names a register.
+pub const REG_COUNT:usize = u8::MAX as usize + 1;
 
-/// First non-reserved register in the [`VM`](https://github.com/Barca545/galaxy). Registers R4-R14 are reserved for function arguments.
-pub const FIRST_FREE_REGISTER:usize = 15;
+/// First non-reserved register in the [`VM`](https://github.com/Barca545/galaxy). Registers R5-R15 are reserved for function arguments.
+pub const FIRST_FREE_REGISTER:usize = 16;
 
 /// Program counter. Contains the address of the next
 /// [`OpCode`](crate::opcodes::OpCode) instruction.
@@ -16,3 +28,517 @@ pub const EQ:usize = 2;
 
 /// Register which holds the [loop variable](https://en.wikipedia.org/wiki/For_loop) of the [`VM`](https://github.com/Barca545/galaxy)'s currently executing loop.
 pub const LOOP:usize = 3;
+
+/// Frame pointer. Points to the stack slot below the current function's
+/// locals. Set by `Enter` and restored by `Leave`.
+pub const FP:usize = 4;
+
+/// Names of the reserved registers indexed by register number.
+pub const REGISTER_NAMES:[&str; 5] = ["PC", "SP", "EQ", "LOOP", "FP",];
+
+/// Formats a register operand as it appears in a
+/// [`Program`](crate::program::Program)'s listing. See [`Register`]'s
+/// `Display` implementation.
+pub fn register_name(reg:u8,) -> String {
+  Register::new(reg,).to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(transparent)
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[repr(transparent)]
+/// A register operand. Every `u8` names one of the [`REG_COUNT`] registers.
+pub struct Register(u8,);
+
+impl Register {
+  /// See [`PC`].
+  pub const PC:Register = Register(PC as u8,);
+  /// See [`SP`].
+  pub const SP:Register = Register(SP as u8,);
+  /// See [`EQ`].
+  pub const EQ:Register = Register(EQ as u8,);
+  /// See [`LOOP`].
+  pub const LOOP:Register = Register(LOOP as u8,);
+  /// See [`FP`].
+  pub const FP:Register = Register(FP as u8,);
+
+  /// Number of registers reserved for function arguments.
+  pub const ARG_COUNT:usize = FIRST_FREE_REGISTER - FP - 1;
+
+  pub const fn new(index:u8,) -> Register {
+    Register(index,)
+  }
+
+  /// Returns the register holding the `n`th (0-based) function argument or
+  /// `None` if `n` is not less than [`Register::ARG_COUNT`].
+  pub const fn arg(n:usize,) -> Option<Register,> {
+    match n < Self::ARG_COUNT {
+      true => Some(Register((FP + 1 + n) as u8,),),
+      false => None,
+    }
+  }
+
+  /// Returns the register's number.
+  pub const fn index(self,) -> u8 {
+    self.0
+  }
+}
+
+impl From<u8,> for Register {
+  fn from(value:u8,) -> Self {
+    Register::new(value,)
+  }
+}
+
+impl From<Register,> for u8 {
+  fn from(value:Register,) -> Self {
+    value.0
+  }
+}
+
+impl Display for Register {
+  /// Prints reserved registers by name (`$EQ`) and all others by number
+  /// (`$14`).
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    match REGISTER_NAMES.get(self.0 as usize,) {
+      Some(name,) => write!(f, "${}", name),
+      None => write!(f, "${}", self.0),
+    }
+  }
+}
+
+impl FromStr for Register {
+  type Err = RegisterError;
+
+  /// Parses a register written as a number (`$14`, `$0x0E`) or as the name of
+  /// a reserved register (`$EQ`). The `$` is optional and names are case
+  /// insensitive.
+  fn from_str(s:&str,) -> Result<Self, Self::Err,> {
+    let text = s.strip_prefix('$',).unwrap_or(s,);
+    if let Some(reg,) = REGISTER_NAMES
+      .iter()
+      .position(|name| name.eq_ignore_ascii_case(text,),)
+    {
+      return Ok(Register(reg as u8,),);
+    }
+
+    let (digits, radix,) = match text.strip_prefix("0x",).or_else(|| text.strip_prefix("0X",),) {
+      Some(hex,) => (hex, 16,),
+      None => (text, 10,),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix,),) {
+      return Err(RegisterError::UnknownName(s.to_string(),),);
+    }
+    match u8::from_str_radix(digits, radix,) {
+      Ok(index,) => Ok(Register::new(index,),),
+      Err(_,) => Err(RegisterError::TooLarge(s.to_string(),),),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// Hands out general purpose registers, starting at [`FIRST_FREE_REGISTER`],
+/// to code generators.
+///
+/// The reserved and argument registers are never allocated but may be pinned
+/// with [`RegisterAllocator::reserve`].
+pub struct RegisterAllocator {
+  in_use:[bool; REG_COUNT],
+}
+
+impl Default for RegisterAllocator {
+  fn default() -> Self {
+    Self {
+      in_use:[false; REG_COUNT],
+    }
+  }
+}
+
+impl RegisterAllocator {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Allocates the lowest free general purpose register or returns `None` if
+  /// all of them are in use.
+  pub fn alloc(&mut self,) -> Option<Register,> {
+    let index = (FIRST_FREE_REGISTER..REG_COUNT).find(|&index| !self.in_use[index],)?;
+    self.in_use[index] = true;
+    Some(Register(index as u8,),)
+  }
+
+  /// Allocates `n` contiguous general purpose registers.
+  ///
+  /// Errors if there is no free run of `n` registers.
+  pub fn alloc_range(&mut self, n:usize,) -> Result<Vec<Register,>, AllocError,> {
+    let exhausted = AllocError::Exhausted { requested:n, };
+    if n == 0 {
+      return Ok(Vec::new(),);
+    }
+    let start = (FIRST_FREE_REGISTER..=REG_COUNT.checked_sub(n,).ok_or(exhausted,)?)
+      .find(|&start| self.in_use[start..start + n].iter().all(|used| !used,),)
+      .ok_or(exhausted,)?;
+    self.in_use[start..start + n].fill(true,);
+    Ok((start..start + n).map(|index| Register(index as u8,),).collect(),)
+  }
+
+  /// Marks `reg` as in use until it is freed so [`RegisterAllocator::alloc`]
+  /// will not hand it out. Any register, including the reserved and argument
+  /// registers, may be reserved.
+  ///
+  /// Errors if `reg` is already in use.
+  pub fn reserve(&mut self, reg:Register,) -> Result<(), AllocError,> {
+    match self.in_use[reg.0 as usize] {
+      true => Err(AllocError::InUse(reg,),),
+      false => {
+        self.in_use[reg.0 as usize] = true;
+        Ok((),)
+      }
+    }
+  }
+
+  /// Returns an allocated or reserved register to the allocator.
+  ///
+  /// Errors if `reg` is not in use, for example because it was already freed.
+  pub fn free(&mut self, reg:Register,) -> Result<(), AllocError,> {
+    match self.in_use[reg.0 as usize] {
+      true => {
+        self.in_use[reg.0 as usize] = false;
+        Ok((),)
+      }
+      false => Err(AllocError::NotInUse(reg,),),
+    }
+  }
+
+  /// Returns whether `reg` is allocated or reserved.
+  pub fn is_in_use(&self, reg:Register,) -> bool {
+    self.in_use[reg.0 as usize]
+  }
+
+  /// Returns the number of allocated and reserved registers.
+  pub fn in_use(&self,) -> usize {
+    self.in_use.iter().filter(|used| **used,).count()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq,)]
+/// An argument passed to a function by [`CallingConvention::lower_call`].
+pub enum CallArg {
+  /// The value of a register.
+  Reg(Register,),
+  /// A constant.
+  Imm(f32,),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+/// The convention functions called with `Call` follow.
+///
+/// - The first [`Register::ARG_COUNT`] arguments are passed in
+///   [`CallingConvention::ARG_REGISTERS`] in order.
+/// - Any remaining arguments are pushed onto the stack in order before the
+///   `Call`, so the last argument is directly beneath the return address.
+/// - The callee returns with `Ret` and an operand equal to the number of
+///   arguments passed on the stack, which `Ret` pops along with the return
+///   address.
+pub struct CallingConvention;
+
+impl CallingConvention {
+  /// Registers which hold a function's first arguments.
+  pub const ARG_REGISTERS:RangeInclusive<u8,> = FP as u8 + 1..=FIRST_FREE_REGISTER as u8 - 1;
+
+  /// Returns the number of arguments which are passed in registers.
+  pub const fn max_register_args() -> usize {
+    Register::ARG_COUNT
+  }
+
+  /// Returns the number of arguments a function taking `args` arguments
+  /// receives on the stack. This is the operand of the function's `Ret`.
+  pub const fn stack_args(args:usize,) -> usize {
+    args.saturating_sub(Self::max_register_args(),)
+  }
+
+  /// Emits the instructions which pass `args` to the function at `target`
+  /// followed by the `Call`. Returns the operand the function's `Ret` should
+  /// use to clean up its stack arguments.
+  ///
+  /// Arguments may be read from the argument registers themselves. Registers
+  /// are shuffled so every argument is read before it is overwritten.
+  ///
+  /// Errors if more than `u8::MAX` arguments would be passed on the stack.
+  /// Nothing is emitted if there is an error.
+  pub fn lower_call(builder:&mut ProgramBuilder, target:Label, args:&[CallArg],) -> Result<u8, CallError,> {
+    let (regs, stack,) = args.split_at(args.len().min(Self::max_register_args(),),);
+    let cleanup = u8::try_from(stack.len(),).map_err(|_| CallError::TooManyArgs { stack:stack.len(), },)?;
+
+    // Push the stack arguments before any argument register is overwritten
+    for arg in stack {
+      match *arg {
+        CallArg::Reg(src,) => builder.emit(Instruction::Push { src, },),
+        CallArg::Imm(imm,) => builder.emit(Instruction::PushI { imm, },),
+      };
+    }
+
+    // Move registers into place as a parallel move, breaking cycles with
+    // `Swap`
+    let mut moves = regs
+      .iter()
+      .enumerate()
+      .filter_map(|(n, arg,)| match *arg {
+        CallArg::Reg(src,) => Some((Register::arg(n,).unwrap(), src,),),
+        CallArg::Imm(_,) => None,
+      },)
+      .filter(|(dst, src,)| dst != src,)
+      .collect::<Vec<_,>>();
+    while !moves.is_empty() {
+      let free = moves
+        .iter()
+        .position(|(dst, _,)| moves.iter().all(|(_, src,)| src != dst,),);
+      match free {
+        Some(pos,) => {
+          let (dst, src,) = moves.remove(pos,);
+          builder.emit(Instruction::Copy { dst, src, },);
+        }
+        None => {
+          // Every remaining destination is still needed so swap it with its
+          // source, which now holds the destination's old value
+          let (dst, src,) = moves.remove(0,);
+          builder.emit(Instruction::Swap { dst, src, },);
+          for (_, other,) in moves.iter_mut().filter(|(_, other,)| *other == dst,) {
+            *other = src;
+          }
+          moves.retain(|(dst, src,)| dst != src,);
+        }
+      }
+    }
+
+    // Constants are loaded last since they do not read any register
+    for (n, arg,) in regs.iter().enumerate() {
+      if let CallArg::Imm(imm,) = *arg {
+        builder.emit(Instruction::Load {
+          dst:Register::arg(n,).unwrap(),
+          imm,
+        },);
+      }
+    }
+
+    builder.call(target,);
+    Ok(cleanup,)
+  }
+}
+
+#[cfg(test)]
+/// Shorthand for creating registers in tests.
+pub(crate) fn reg(index:u8,) -> Register {
+  Register::new(index,)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{reg, CallArg, CallingConvention, Register, RegisterAllocator, FIRST_FREE_REGISTER, REG_COUNT};
+  use crate::{
+    builder::ProgramBuilder,
+    errors::{AllocError, CallError, RegisterError},
+    instruction::Instruction,
+    opcodes::OpCode,
+    program::Program,
+  };
+  use alloc::{string::ToString, vec::Vec};
+
+  #[test]
+  fn register_constructors() {
+    assert_eq!(Register::new(14,).index(), 14);
+    assert_eq!(Register::from(255,).index(), 255);
+    assert_eq!(REG_COUNT, 256);
+    assert_eq!(Register::arg(0,), Some(Register::new(5,)));
+    assert_eq!(
+      Register::arg(Register::ARG_COUNT - 1,).map(Register::index,),
+      Some(FIRST_FREE_REGISTER as u8 - 1)
+    );
+    assert_eq!(Register::arg(Register::ARG_COUNT,), None);
+    assert_eq!(Register::EQ.to_string(), "$EQ");
+    assert_eq!(Register::new(14,).to_string(), "$14");
+  }
+
+  #[test]
+  fn parse_registers() {
+    // Round trip every register through its listing form
+    for index in 0..=u8::MAX {
+      let reg = Register::new(index,);
+      assert_eq!(reg.to_string().parse(), Ok(reg));
+    }
+
+    assert_eq!("$eq".parse(), Ok(Register::EQ));
+    assert_eq!("Loop".parse(), Ok(Register::LOOP));
+    assert_eq!("SP".parse(), Ok(Register::SP));
+    assert_eq!("14".parse::<Register>().map(Register::index,), Ok(14));
+    assert_eq!("$0x0E".parse::<Register>().map(Register::index,), Ok(14));
+    assert_eq!("$2".parse(), Ok(Register::EQ));
+
+    assert_eq!(
+      "$256".parse::<Register>(),
+      Err(RegisterError::TooLarge("$256".to_string()))
+    );
+    assert_eq!(
+      "$XY".parse::<Register>(),
+      Err(RegisterError::UnknownName("$XY".to_string()))
+    );
+    assert_eq!(
+      "$".parse::<Register>(),
+      Err(RegisterError::UnknownName("$".to_string()))
+    );
+    assert_eq!(
+      "$-1".parse::<Register>(),
+      Err(RegisterError::UnknownName("$-1".to_string()))
+    );
+  }
+
+  #[test]
+  fn allocate_registers() {
+    let mut regs = RegisterAllocator::new();
+    let first = regs.alloc().unwrap();
+    assert_eq!(first.index() as usize, FIRST_FREE_REGISTER);
+    assert_eq!(regs.in_use(), 1);
+
+    // Argument registers can be pinned but are never allocated
+    let arg = Register::arg(0,).unwrap();
+    regs.reserve(arg,).unwrap();
+    assert_eq!(regs.reserve(arg,), Err(AllocError::InUse(arg)));
+    assert_eq!(regs.reserve(first,), Err(AllocError::InUse(first)));
+    assert!(regs.is_in_use(arg,));
+    regs.free(arg,).unwrap();
+    assert_eq!(regs.free(arg,), Err(AllocError::NotInUse(arg)));
+
+    // Pinned registers are skipped
+    regs.reserve(reg(17,),).unwrap();
+    assert_eq!(regs.alloc(), Some(reg(18)));
+    let range = regs.alloc_range(3,).unwrap();
+    assert_eq!(range, [reg(19), reg(20), reg(21)]);
+
+    // Freed registers are reused
+    regs.free(first,).unwrap();
+    assert_eq!(regs.free(first,), Err(AllocError::NotInUse(first)));
+    assert_eq!(regs.alloc(), Some(first));
+
+    // Exhaustion
+    let free = REG_COUNT - regs.in_use() - FIRST_FREE_REGISTER;
+    assert_eq!(
+      regs.alloc_range(free + 1,),
+      Err(AllocError::Exhausted { requested:free + 1 })
+    );
+    assert_eq!(regs.alloc_range(free,).unwrap().len(), free);
+    assert_eq!(regs.alloc(), None);
+    assert_eq!(regs.alloc_range(1,), Err(AllocError::Exhausted { requested:1 }));
+    assert_eq!(
+      regs.alloc_range(REG_COUNT + 1,),
+      Err(AllocError::Exhausted {
+        requested:REG_COUNT + 1
+      })
+    );
+    assert_eq!(regs.in_use(), REG_COUNT - FIRST_FREE_REGISTER);
+  }
+
+  #[test]
+  fn lower_calls() {
+    assert_eq!(CallingConvention::ARG_REGISTERS, 5..=15);
+    assert_eq!(CallingConvention::max_register_args(), 11);
+
+    let lower = |args:&[CallArg]| {
+      let mut b = ProgramBuilder::new();
+      let func = b.new_label();
+      let cleanup = CallingConvention::lower_call(&mut b, func, args,).unwrap();
+      b.emit(Instruction::Hlt,);
+      b.bind(func,).unwrap();
+      b.emit(Instruction::Ret { args:cleanup, },);
+      // Only the code is compared, not the call's relocation
+      (cleanup, Program::from(b.finish().unwrap().code().to_vec(),),)
+    };
+
+    // 0 arguments
+    let (cleanup, program,) = lower(&[],);
+    assert_eq!(cleanup, 0);
+    assert_eq!(
+      program,
+      Program::from([
+        OpCode::Call.into(),
+        6,
+        0,
+        0,
+        0,
+        OpCode::Hlt.into(),
+        OpCode::Ret.into(),
+        0
+      ])
+    );
+
+    // 3 arguments, one of which is already in place
+    let (cleanup, program,) = lower(&[
+      CallArg::Reg(reg(20,),),
+      CallArg::Imm(1.0,),
+      CallArg::Reg(reg(7,),),
+    ],);
+    assert_eq!(cleanup, 0);
+    let [a, b, c, d] = 1.0f32.to_le_bytes();
+    #[rustfmt::skip]
+    assert_eq!(
+      program,
+      Program::from([
+        OpCode::Copy.into(), 5, 20,
+        OpCode::Load.into(), 6, a, b, c, d,
+        OpCode::Call.into(), 15, 0, 0, 0,
+        OpCode::Hlt.into(),
+        OpCode::Ret.into(), 0,
+      ])
+    );
+
+    // 10 arguments including a cycle between argument registers
+    let mut args = (0..10).map(|n| CallArg::Reg(reg(20 + n,),),).collect::<Vec<_,>>();
+    args[0] = CallArg::Reg(reg(6,),);
+    args[1] = CallArg::Reg(reg(5,),);
+    let (cleanup, program,) = lower(&args,);
+    assert_eq!(cleanup, 0);
+    let mut expected = Program::new();
+    for n in 2..10 {
+      expected.emit_copy(Register::arg(n as usize,).unwrap(), reg(20 + n,),);
+    }
+    expected.emit_swap(reg(5,), reg(6,),);
+    expected.emit_call(expected.len() as u32 + 6,);
+    expected.emit_hlt();
+    expected.emit_ret(0,);
+    assert_eq!(program, expected);
+
+    // 14 arguments spill 3 onto the stack
+    let mut args = (0..11).map(|n| CallArg::Reg(reg(20 + n,),),).collect::<Vec<_,>>();
+    args.extend([
+      CallArg::Reg(reg(5,),),
+      CallArg::Imm(2.0,),
+      CallArg::Reg(reg(40,),),
+    ],);
+    let (cleanup, program,) = lower(&args,);
+    assert_eq!(cleanup, 3);
+    let mut expected = Program::new();
+    expected.emit_push(reg(5,),);
+    expected.emit_push_i(2.0,);
+    expected.emit_push(reg(40,),);
+    for n in 0..11 {
+      expected.emit_copy(Register::arg(n as usize,).unwrap(), reg(20 + n,),);
+    }
+    expected.emit_call(expected.len() as u32 + 6,);
+    expected.emit_hlt();
+    expected.emit_ret(3,);
+    assert_eq!(program, expected);
+
+    // Ret cannot pop more than 255 stack arguments
+    let mut b = ProgramBuilder::new();
+    let func = b.new_label();
+    let args = [CallArg::Imm(0.0,); 11 + 256];
+    assert_eq!(
+      CallingConvention::lower_call(&mut b, func, &args,),
+      Err(CallError::TooManyArgs { stack:256 })
+    );
+    assert_eq!(b.offset(), 0);
+  }
+}
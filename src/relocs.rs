@@ -0,0 +1,93 @@
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+#[repr(u8)]
+/// What the address patched by a [`Relocation`] points into.
+pub enum RelocKind {
+  /// An offset into the program's code, such as a jump or call target.
+  Code = 0,
+  /// An address in the program's data segment.
+  Data = 1,
+}
+
+impl TryFrom<u8,> for RelocKind {
+  type Error = u8;
+
+  fn try_from(value:u8,) -> Result<Self, Self::Error,> {
+    match value {
+      0 => Ok(RelocKind::Code,),
+      1 => Ok(RelocKind::Data,),
+      kind => Err(kind,),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+/// A 4 byte address in a [`Program`](crate::program::Program)'s code which
+/// must be updated when the program's code or data is moved.
+pub struct Relocation {
+  /// Offset of the address in the program's code.
+  pub offset:u32,
+  pub kind:RelocKind,
+}
+
+/// Encodes `relocs` as the relocations section of a saved program. Each
+/// relocation is its offset as a little-endian u32 followed by its kind as a
+/// u8.
+pub(crate) fn to_bytes(relocs:&[Relocation],) -> Vec<u8,> {
+  let mut bytes = Vec::with_capacity(relocs.len() * 5,);
+  for reloc in relocs {
+    bytes.extend_from_slice(&reloc.offset.to_le_bytes(),);
+    bytes.push(reloc.kind as u8,);
+  }
+  bytes
+}
+
+/// Decodes relocations encoded by [`to_bytes`]. Returns `None` if the bytes
+/// are not valid relocations or are not sorted by offset with at most one
+/// relocation per offset, as programs keep them.
+pub(crate) fn from_bytes(bytes:&[u8],) -> Option<Vec<Relocation,>,> {
+  if !bytes.len().is_multiple_of(5,) {
+    return None;
+  }
+  let relocs = bytes
+    .chunks(5,)
+    .map(|entry| {
+      Some(Relocation {
+        offset:u32::from_le_bytes(entry[..4].try_into().unwrap(),),
+        kind:RelocKind::try_from(entry[4],).ok()?,
+      },)
+    },)
+    .collect::<Option<Vec<_,>,>>()?;
+  let sorted = relocs.windows(2,).all(|pair| pair[0].offset < pair[1].offset,);
+  sorted.then_some(relocs,)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{from_bytes, to_bytes, RelocKind, Relocation};
+
+  #[test]
+  fn encode_relocations() {
+    let relocs = [
+      Relocation {
+        offset:1,
+        kind:RelocKind::Code,
+      },
+      Relocation {
+        offset:300,
+        kind:RelocKind::Data,
+      },
+    ];
+    let bytes = to_bytes(&relocs,);
+    assert_eq!(bytes, [1, 0, 0, 0, 0, 44, 1, 0, 0, 1]);
+    assert_eq!(from_bytes(&bytes,).as_deref(), Some(&relocs[..]));
+    assert_eq!(from_bytes(&bytes[..9],), None);
+    assert_eq!(from_bytes(&[0, 0, 0, 0, 2,],), None);
+
+    // Relocations must be sorted and unique
+    let unsorted = [&bytes[5..], &bytes[..5],].concat();
+    assert_eq!(from_bytes(&unsorted,), None);
+    assert_eq!(from_bytes(&[&bytes[..5], &bytes[..5],].concat(),), None);
+  }
+}
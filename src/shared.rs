@@ -0,0 +1,109 @@
+use crate::program::Program;
+use alloc::sync::Arc;
+use core::{fmt::Display, ops::Deref};
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// A [`Program`] which can be cloned without copying its code, for running the
+/// same program in many VMs.
+///
+/// Derefs to the shared [`Program`], so it is indexed, decoded, printed, and
+/// saved the same way. Changing it with [`SharedProgram::make_mut`] copies the
+/// program first if any clone still shares it.
+pub struct SharedProgram(Arc<Program,>,);
+
+impl SharedProgram {
+  /// Returns the program for mutation, first copying it if another
+  /// [`SharedProgram`] shares it.
+  pub fn make_mut(&mut self,) -> &mut Program {
+    Arc::make_mut(&mut self.0,)
+  }
+
+  /// Returns the owned [`Program`], copying it only if another
+  /// [`SharedProgram`] shares it.
+  pub fn into_program(self,) -> Program {
+    Arc::try_unwrap(self.0,).unwrap_or_else(|shared| Program::clone(&shared,),)
+  }
+
+  /// Whether `self` and `other` share the same program.
+  pub fn ptr_eq(&self, other:&SharedProgram,) -> bool {
+    Arc::ptr_eq(&self.0, &other.0,)
+  }
+}
+
+impl Deref for SharedProgram {
+  type Target = Program;
+
+  fn deref(&self,) -> &Program {
+    &self.0
+  }
+}
+
+impl AsRef<Program,> for SharedProgram {
+  fn as_ref(&self,) -> &Program {
+    &self.0
+  }
+}
+
+impl Display for SharedProgram {
+  /// Prints the same listing as [`Program`]'s `Display` implementation,
+  /// including the alternate form.
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    Display::fmt(&*self.0, f,)
+  }
+}
+
+impl From<Program,> for SharedProgram {
+  // Without `std` programs are not `Sync` so they are only shared within a
+  // thread
+  #[cfg_attr(not(feature = "std"), allow(clippy::arc_with_non_send_sync))]
+  fn from(value:Program,) -> Self {
+    SharedProgram(Arc::new(value,),)
+  }
+}
+
+impl From<SharedProgram,> for Program {
+  fn from(value:SharedProgram,) -> Self {
+    value.into_program()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::SharedProgram;
+  use crate::{instruction::Instruction, program::Program, registers::reg};
+  use alloc::{format, string::ToString, vec::Vec};
+
+  #[test]
+  fn clones_share_code_until_changed() {
+    let mut program = Program::new();
+    program.emit_load(reg(16,), 1.5,);
+    program.emit(Instruction::Hlt,);
+
+    let shared = SharedProgram::from(program.clone(),);
+    let mut clone = shared.clone();
+    assert!(clone.ptr_eq(&shared,));
+    assert_eq!(clone.as_slice().as_ptr(), shared.as_slice().as_ptr());
+    assert_eq!(clone[0], program[0]);
+    assert_eq!(clone.to_string(), program.to_string());
+    assert_eq!(format!("{:#}", clone), format!("{:#}", program));
+    assert_eq!(
+      clone.instructions().collect::<Vec<_,>>(),
+      program.instructions().collect::<Vec<_,>>()
+    );
+    assert_eq!(clone.to_file_bytes(), program.to_file_bytes());
+
+    // The first change copies the program
+    clone.make_mut().emit(Instruction::Noop,);
+    assert!(!clone.ptr_eq(&shared,));
+    assert_eq!(shared.len(), program.len());
+    assert_eq!(clone.len(), program.len() + 1);
+    // Later changes do not
+    let code = clone.as_slice().as_ptr();
+    clone.make_mut()[0] = 0;
+    assert_eq!(clone.as_slice().as_ptr(), code);
+
+    // Unshared programs are moved out without copying
+    let code = shared.as_slice().as_ptr();
+    assert_eq!(shared.into_program().as_slice().as_ptr(), code);
+  }
+}
@@ -0,0 +1,310 @@
+use crate::{
+  instruction::{Instruction, OperandReader},
+  opcodes::{CmpFlag, OpCode},
+  program::Program,
+  registers::{Register, FIRST_FREE_REGISTER},
+};
+use alloc::{borrow::Cow, vec::Vec};
+use core::{convert::Infallible, ops::RangeInclusive};
+use proptest::{
+  collection::{vec, SizeRange},
+  prelude::*,
+  sample::{select, Index},
+};
+
+/// Most instructions [`program_with_balanced_stack`] generates before its
+/// closing `Pop`s and `Hlt`.
+const MAX_STACK_PROGRAM_LEN:usize = 64;
+
+/// Opcodes which only compute on registers.
+const ARITHMETIC:&[OpCode] = &[
+  OpCode::Load,
+  OpCode::Copy,
+  OpCode::AddRI,
+  OpCode::SubRI,
+  OpCode::RvSubRI,
+  OpCode::MulRI,
+  OpCode::DivRI,
+  OpCode::RvDivRI,
+  OpCode::PowRI,
+  OpCode::RvPowRI,
+  OpCode::AddRR,
+  OpCode::SubRR,
+  OpCode::MulRR,
+  OpCode::DivRR,
+  OpCode::PowRR,
+  OpCode::CmpRI,
+  OpCode::CmpRR,
+  OpCode::Not,
+  OpCode::Noop,
+  OpCode::ModRI,
+  OpCode::RvModRI,
+  OpCode::ModRR,
+  OpCode::Neg,
+  OpCode::Abs,
+  OpCode::Sqrt,
+  OpCode::Floor,
+  OpCode::Ceil,
+  OpCode::Round,
+  OpCode::Trunc,
+  OpCode::AndRR,
+  OpCode::OrRR,
+  OpCode::XorRR,
+  OpCode::AndRI,
+  OpCode::OrRI,
+  OpCode::XorRI,
+  OpCode::ShlRI,
+  OpCode::ShrRI,
+  OpCode::ShlRR,
+  OpCode::ShrRR,
+  OpCode::MinRR,
+  OpCode::MaxRR,
+  OpCode::MinRI,
+  OpCode::MaxRI,
+  OpCode::Sin,
+  OpCode::Cos,
+  OpCode::Tan,
+  OpCode::Ln,
+  OpCode::Exp,
+  OpCode::Inc,
+  OpCode::Dec,
+  OpCode::Swap,
+];
+
+/// Opcodes which push to or pop from the stack.
+const STACK:&[OpCode] = &[OpCode::Push, OpCode::PushI, OpCode::Pop, OpCode::PopR,];
+
+/// Opcodes which jump to an immediate target.
+const JUMPS:&[OpCode] = &[
+  OpCode::Jmp,
+  OpCode::Jz,
+  OpCode::Jnz,
+  OpCode::JmpCmpRR,
+  OpCode::JmpCmpRI,
+  OpCode::JmpRel,
+  OpCode::JzRel,
+  OpCode::JnzRel,
+];
+
+/// Operands drawn for one instruction. Registers are handed out in order and
+/// every other kind of operand reuses its one value.
+#[derive(Debug, Clone,)]
+struct Operands {
+  regs:[u8; 4],
+  next:usize,
+  byte:u8,
+  int:u32,
+  imm:f32,
+  flag:CmpFlag,
+}
+
+impl OperandReader for Operands {
+  type Error = Infallible;
+
+  fn reg(&mut self,) -> Result<Register, Infallible,> {
+    let reg = self.regs[self.next % self.regs.len()];
+    self.next += 1;
+    Ok(Register::new(reg,),)
+  }
+
+  fn u8(&mut self,) -> Result<u8, Infallible,> {
+    Ok(self.byte,)
+  }
+
+  fn u32(&mut self,) -> Result<u32, Infallible,> {
+    Ok(self.int,)
+  }
+
+  fn i32(&mut self,) -> Result<i32, Infallible,> {
+    Ok(self.int as i32,)
+  }
+
+  fn f32(&mut self,) -> Result<f32, Infallible,> {
+    Ok(self.imm,)
+  }
+
+  fn flag(&mut self,) -> Result<CmpFlag, Infallible,> {
+    Ok(self.flag,)
+  }
+}
+
+/// Operands with registers from `regs` and finite immediates. Shrinks toward
+/// the lowest register and zero.
+fn operands(regs:RangeInclusive<u8,>,) -> impl Strategy<Value = Operands,> {
+  (
+    [regs.clone(), regs.clone(), regs.clone(), regs,],
+    any::<u8,>(),
+    any::<u32,>(),
+    prop::num::f32::NORMAL | prop::num::f32::ZERO,
+    select(CmpFlag::ALL.as_slice(),),
+  )
+    .prop_map(|(regs, byte, int, imm, flag,)| Operands {
+      regs,
+      next:0,
+      byte,
+      int,
+      imm,
+      flag,
+    },)
+}
+
+/// Instructions with an opcode from `ops` and registers from `regs`.
+fn instruction(
+  ops:impl Into<Cow<'static, [OpCode],>,>,
+  regs:RangeInclusive<u8,>,
+) -> impl Strategy<Value = Instruction,> {
+  (select(ops,), operands(regs,),).prop_map(
+    |(op, mut src,)| match Instruction::read_operands(op, &mut src,) {
+      Ok(inst,) => inst,
+      Err(never,) => match never {},
+    },
+  )
+}
+
+/// Registers the program strategies use. They leave the reserved registers
+/// alone so the only control flow is the jumps they generate.
+fn free_registers() -> RangeInclusive<u8,> {
+  FIRST_FREE_REGISTER as u8..=u8::MAX
+}
+
+/// Emits `insts` followed by a `Hlt`.
+fn terminated(insts:impl IntoIterator<Item = Instruction,>,) -> Program {
+  let mut program = Program::new();
+  for inst in insts {
+    program.emit(inst,);
+  }
+  program.emit(Instruction::Hlt,);
+  program
+}
+
+/// Generates any [`Instruction`] with finite immediates. Jump targets and
+/// memory offsets are arbitrary so programs built from these are not
+/// necessarily valid.
+///
+/// Shrinks toward `Hlt`, register 0, and zero immediates.
+pub fn any_instruction() -> impl Strategy<Value = Instruction,> {
+  instruction(OpCode::iter().collect::<Vec<_,>>(), 0..=u8::MAX,)
+}
+
+/// Generates programs of `len` arithmetic and comparison instructions on
+/// general purpose registers, ending with a `Hlt`. They never touch memory,
+/// the stack, or the reserved registers and never jump.
+///
+/// Passes [`Program::validate_strict`]. Shrinks toward shorter programs and
+/// lower registers.
+pub fn arithmetic_only_program(len:impl Into<SizeRange,>,) -> impl Strategy<Value = Program,> {
+  vec(instruction(ARITHMETIC, free_registers(),), len,).prop_map(terminated,)
+}
+
+/// Generates programs of arithmetic instructions mixed with `Push`, `PushI`,
+/// `Pop`, and `PopR`, ending with a `Hlt`. The stack is never popped while
+/// empty and is empty again at the `Hlt`.
+///
+/// Passes [`Program::validate_strict`]. Shrinks toward shorter programs and
+/// lower registers.
+pub fn program_with_balanced_stack() -> impl Strategy<Value = Program,> {
+  let inst = prop_oneof![
+    3 => instruction(ARITHMETIC, free_registers()),
+    2 => instruction(STACK, free_registers()),
+  ];
+  vec(inst, 0..=MAX_STACK_PROGRAM_LEN,).prop_map(|insts| {
+    let mut depth = 0usize;
+    let mut balanced = Vec::with_capacity(insts.len(),);
+    for inst in insts {
+      match inst {
+        Instruction::Push { .. } | Instruction::PushI { .. } => depth += 1,
+        Instruction::Pop | Instruction::PopR { .. } => match depth.checked_sub(1,) {
+          Some(lower,) => depth = lower,
+          // Popping an empty stack
+          None => continue,
+        },
+        _ => {}
+      }
+      balanced.push(inst,);
+    }
+    balanced.extend((0..depth).map(|_| Instruction::Pop,),);
+    terminated(balanced,)
+  },)
+}
+
+/// Generates programs of up to `max_len` arithmetic instructions and jumps,
+/// ending with a `Hlt`. Every jump lands on an instruction of the program,
+/// possibly itself, so the programs may loop forever.
+///
+/// Passes [`Program::validate_strict`]. Shrinks toward shorter programs,
+/// lower registers, and earlier jump targets.
+pub fn program_with_valid_jumps(max_len:usize,) -> impl Strategy<Value = Program,> {
+  let slot = prop_oneof![
+    3 => instruction(ARITHMETIC, free_registers()).prop_map(|inst| (inst, None)),
+    1 => (instruction(JUMPS, free_registers()), any::<Index>()).prop_map(|(inst, to)| (inst, Some(to))),
+  ];
+  vec(slot, 0..=max_len,).prop_map(|slots| {
+    let mut program = terminated(slots.iter().map(|(inst, _,)| *inst,),);
+    // Offsets of every instruction including the `Hlt`
+    let offsets = program
+      .instructions()
+      .map(|item| item.expect("generated instructions decode",).0,)
+      .collect::<Vec<_,>>();
+
+    for ((mut inst, to,), &at,) in slots.into_iter().zip(&offsets,) {
+      let Some(to,) = to
+      else {
+        continue;
+      };
+      let target = offsets[to.index(offsets.len(),)];
+      let next = at + inst.opcode().instruction_len() as u32;
+      match &mut inst {
+        Instruction::JmpRel { offset, }
+        | Instruction::JzRel { offset, .. }
+        | Instruction::JnzRel { offset, .. } => {
+          *offset = target.wrapping_sub(next,) as i32;
+        }
+        inst => *inst.jump_target_mut().expect("jumps have a target",) = target,
+      }
+      program
+        .replace_at(at, inst,)
+        .expect("the jump is the same length",);
+    }
+    program
+  },)
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    any_instruction, arithmetic_only_program, program_with_balanced_stack, program_with_valid_jumps,
+  };
+  use crate::{instruction::Instruction, program::Program};
+  use proptest::prelude::*;
+
+  proptest! {
+    #[test]
+    fn any_instruction_round_trips(inst in any_instruction()) {
+      let mut program = Program::new();
+      program.emit(inst,);
+      prop_assert_eq!(Instruction::decode(program.as_slice(),).unwrap(), (inst, program.len()));
+    }
+
+    #[test]
+    fn generated_programs_validate(
+      arithmetic in arithmetic_only_program(0..32),
+      stack in program_with_balanced_stack(),
+      jumps in program_with_valid_jumps(32),
+    ) {
+      prop_assert_eq!(arithmetic.validate_strict(), Ok(()));
+      prop_assert_eq!(stack.validate_strict(), Ok(()));
+      prop_assert_eq!(jumps.validate_strict(), Ok(()));
+
+      let mut depth = 0i32;
+      for item in stack.instructions() {
+        match item.unwrap().1 {
+          Instruction::Push { .. } | Instruction::PushI { .. } => depth += 1,
+          Instruction::Pop | Instruction::PopR { .. } => depth -= 1,
+          _ => {}
+        }
+        prop_assert!(depth >= 0);
+      }
+      prop_assert_eq!(depth, 0);
+    }
+  }
+}
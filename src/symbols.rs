@@ -0,0 +1,280 @@
+use crate::{asm::is_label_name, errors::SymbolError};
+use alloc::{
+  string::{String, ToString},
+  vec::Vec,
+};
+
+/// Longest name in bytes a [`SymbolTable`] accepts.
+pub const MAX_NAME_LEN:usize = u8::MAX as usize;
+
+/// Errors if `name` is not a valid identifier or is longer than
+/// [`MAX_NAME_LEN`].
+pub(crate) fn check_name(name:&str,) -> Result<(), SymbolError,> {
+  if !is_label_name(name,) {
+    return Err(SymbolError::InvalidName(name.to_string(),),);
+  }
+  if name.len() > MAX_NAME_LEN {
+    return Err(SymbolError::NameTooLong(name.to_string(),),);
+  }
+  Ok((),)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// A named offset in a [`Program`](crate::program::Program), usually the start
+/// of a function.
+pub struct Symbol {
+  pub name:String,
+  pub offset:u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// A `Call` to a function defined in another
+/// [`Program`](crate::program::Program), resolved by
+/// [`Program::link`](crate::program::Program::link).
+pub struct Extern {
+  /// Offset of the `Call`.
+  pub at:u32,
+  /// Name of the called function.
+  pub name:String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+/// Names the functions in a [`Program`](crate::program::Program) so `Call`
+/// targets can be printed and assembled symbolically.
+pub struct SymbolTable {
+  /// Sorted by offset and then name.
+  symbols:Vec<Symbol,>,
+  /// Sorted by offset.
+  externs:Vec<Extern,>,
+}
+
+impl SymbolTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Names the offset `offset`.
+  ///
+  /// Errors if `name` is not a valid identifier, is longer than
+  /// [`MAX_NAME_LEN`], or is already in the table.
+  pub fn insert(&mut self, name:&str, offset:u32,) -> Result<(), SymbolError,> {
+    check_name(name,)?;
+    if self.get(name,).is_some() {
+      return Err(SymbolError::DuplicateName(name.to_string(),),);
+    }
+    let symbol = Symbol {
+      name:name.to_string(),
+      offset,
+    };
+    let pos = self
+      .symbols
+      .partition_point(|s| (s.offset, s.name.as_str(),) < (offset, name,),);
+    self.symbols.insert(pos, symbol,);
+    Ok((),)
+  }
+
+  /// Returns the offset named `name`.
+  pub fn get(&self, name:&str,) -> Option<u32,> {
+    self.symbols.iter().find(|s| s.name == name,).map(|s| s.offset,)
+  }
+
+  /// Returns the first [`Symbol`] naming `offset`.
+  pub fn at(&self, offset:u32,) -> Option<&Symbol,> {
+    let pos = self.symbols.partition_point(|s| s.offset < offset,);
+    self.symbols.get(pos,).filter(|s| s.offset == offset,)
+  }
+
+  /// Records that the `Call` at `at` calls `name`, which is defined in
+  /// another program.
+  ///
+  /// Errors if `name` is not a valid identifier or is longer than
+  /// [`MAX_NAME_LEN`].
+  pub fn add_extern(&mut self, at:u32, name:&str,) -> Result<(), SymbolError,> {
+    check_name(name,)?;
+    let pos = self.externs.partition_point(|ext| ext.at < at,);
+    self.externs.insert(
+      pos,
+      Extern {
+        at,
+        name:name.to_string(),
+      },
+    );
+    Ok((),)
+  }
+
+  /// Returns the name of the function called by the extern `Call` at `at`.
+  pub fn extern_at(&self, at:u32,) -> Option<&str,> {
+    self
+      .externs
+      .binary_search_by_key(&at, |ext| ext.at,)
+      .ok()
+      .map(|pos| self.externs[pos].name.as_str(),)
+  }
+
+  /// Iterates over the table's [`Extern`]s in order of their offsets.
+  pub fn externs(&self,) -> impl Iterator<Item = &Extern,> {
+    self.externs.iter()
+  }
+
+  /// Removes every extern whose offset `keep` returns false for.
+  pub(crate) fn retain_externs(&mut self, keep:impl Fn(u32,) -> bool,) {
+    self.externs.retain(|ext| keep(ext.at,),);
+  }
+
+  /// Removes and returns every [`Extern`].
+  pub(crate) fn take_externs(&mut self,) -> Vec<Extern,> {
+    core::mem::take(&mut self.externs,)
+  }
+
+  /// Adds every symbol and extern in `other` with its offset moved `base`
+  /// bytes later. Used when `other`'s program is appended `base` bytes into
+  /// this table's.
+  ///
+  /// Errors with the first name which is in both tables. Nothing is added if
+  /// there is an error.
+  pub fn merge(&mut self, other:&SymbolTable, base:u32,) -> Result<(), SymbolError,> {
+    if let Some(s,) = other.iter().find(|s| self.get(&s.name,).is_some(),) {
+      return Err(SymbolError::DuplicateName(s.name.clone(),),);
+    }
+    for s in other.iter() {
+      self.insert(&s.name, base + s.offset,)?;
+    }
+    for ext in other.externs() {
+      self.add_extern(base + ext.at, &ext.name,)?;
+    }
+    Ok((),)
+  }
+
+  /// Moves every offset in the table to `relocate(offset)`. `relocate` must
+  /// preserve the order of offsets.
+  pub(crate) fn relocate(&mut self, relocate:impl Fn(u32,) -> u32,) {
+    for s in &mut self.symbols {
+      s.offset = relocate(s.offset,);
+    }
+    for ext in &mut self.externs {
+      ext.at = relocate(ext.at,);
+    }
+  }
+
+  /// Removes every symbol and extern whose offset `keep` returns false for.
+  pub(crate) fn retain(&mut self, keep:impl Fn(u32,) -> bool,) {
+    self.symbols.retain(|s| keep(s.offset,),);
+    self.retain_externs(keep,);
+  }
+
+  /// Iterates over the table's [`Symbol`]s in order of their offsets.
+  pub fn iter(&self,) -> impl Iterator<Item = &Symbol,> {
+    self.symbols.iter()
+  }
+
+  pub fn len(&self,) -> usize {
+    self.symbols.len()
+  }
+
+  pub fn is_empty(&self,) -> bool {
+    self.symbols.is_empty()
+  }
+
+  /// Encodes the table as the symbols section of a saved program, or no
+  /// bytes if the table is empty.
+  ///
+  /// The section holds the number of symbols as a little-endian u32 followed
+  /// by the symbols and then the externs in the same format. Each entry is its
+  /// offset as a little-endian u32 followed by the length of its name as a u8
+  /// and the name.
+  pub(crate) fn to_bytes(&self,) -> Vec<u8,> {
+    let mut bytes = Vec::new();
+    if self.is_empty() && self.externs.is_empty() {
+      return bytes;
+    }
+    bytes.extend_from_slice(&(self.symbols.len() as u32).to_le_bytes(),);
+    let entries = self.symbols.iter().map(|s| (s.offset, &s.name,),);
+    for (offset, name,) in entries.chain(self.externs.iter().map(|ext| (ext.at, &ext.name,),),) {
+      // Names are never longer than `MAX_NAME_LEN`
+      let name = name.as_bytes();
+      bytes.extend_from_slice(&offset.to_le_bytes(),);
+      bytes.push(name.len() as u8,);
+      bytes.extend_from_slice(name,);
+    }
+    bytes
+  }
+
+  /// Decodes a table encoded by [`SymbolTable::to_bytes`]. Returns `None` if
+  /// the bytes are not a valid table.
+  pub(crate) fn from_bytes(bytes:&[u8],) -> Option<Self,> {
+    let mut table = SymbolTable::new();
+    if bytes.is_empty() {
+      return Some(table,);
+    }
+    let count = u32::from_le_bytes(bytes.get(..4,)?.try_into().unwrap(),) as usize;
+    let mut bytes = &bytes[4..];
+    let mut read = 0;
+    while !bytes.is_empty() {
+      let offset = u32::from_le_bytes(bytes.get(..4,)?.try_into().unwrap(),);
+      let len = *bytes.get(4,)? as usize;
+      let name = core::str::from_utf8(bytes.get(5..5 + len,)?,).ok()?;
+      match read < count {
+        true => table.insert(name, offset,).ok()?,
+        false => table.add_extern(offset, name,).ok()?,
+      }
+      read += 1;
+      bytes = &bytes[5 + len..];
+    }
+    (read >= count).then_some(table,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{SymbolTable, MAX_NAME_LEN};
+  use crate::errors::SymbolError;
+  use alloc::string::ToString;
+
+  #[test]
+  fn symbol_tables() {
+    let mut table = SymbolTable::new();
+    table.insert("update_position", 20,).unwrap();
+    table.insert("main", 0,).unwrap();
+    assert_eq!(table.get("main",), Some(0));
+    assert_eq!(table.at(20,).map(|s| s.name.as_str(),), Some("update_position"));
+    assert_eq!(table.at(4,), None);
+    assert_eq!(
+      table.insert("main", 8,),
+      Err(SymbolError::DuplicateName("main".to_string()))
+    );
+    assert_eq!(
+      table.insert("2fast", 8,),
+      Err(SymbolError::InvalidName("2fast".to_string()))
+    );
+    let long = "f".repeat(MAX_NAME_LEN + 1,);
+    assert_eq!(
+      table.insert(&long, 8,),
+      Err(SymbolError::NameTooLong(long.clone()))
+    );
+    assert_eq!(
+      table.add_extern(12, &long,),
+      Err(SymbolError::NameTooLong(long.clone()))
+    );
+    assert_eq!(
+      table.add_extern(12, "my-func",),
+      Err(SymbolError::InvalidName("my-func".to_string()))
+    );
+    table.insert(&long[1..], 30,).unwrap();
+    table.add_extern(12, "draw",).unwrap();
+    assert_eq!(table.extern_at(12,), Some("draw"));
+    assert_eq!(SymbolTable::from_bytes(&table.to_bytes(),), Some(table.clone()));
+    assert_eq!(SymbolTable::from_bytes(&[0, 0, 0,],), None);
+    assert_eq!(SymbolTable::from_bytes(&[1, 0, 0, 0,],), None);
+    table.take_externs();
+
+    let mut other = SymbolTable::new();
+    other.insert("draw", 4,).unwrap();
+    table.merge(&other, 100,).unwrap();
+    assert_eq!(table.get("draw",), Some(104));
+    assert_eq!(
+      table.merge(&other, 200,),
+      Err(SymbolError::DuplicateName("draw".to_string()))
+    );
+    assert_eq!(table.len(), 4);
+  }
+}
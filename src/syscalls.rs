@@ -0,0 +1,214 @@
+use crate::{asm::is_label_name, errors::SyscallError};
+use alloc::{
+  string::{String, ToString},
+  vec::Vec,
+};
+use core::{fmt::Display, str::FromStr};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Eq,)]
+/// A host function called through `SysCall`.
+pub struct Syscall {
+  /// Operand of the `SysCall` which calls the function.
+  pub index:u8,
+  pub name:String,
+  /// Number of arguments the function takes.
+  pub args:u8,
+  pub doc:Option<String,>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+/// Names the host functions behind `SysCall` indices so listings can refer to
+/// them symbolically.
+///
+/// Tables are stored in a sidecar text file next to the program with one
+/// syscall per line: its index, name, argument count, and an optional doc
+/// string, separated by whitespace. `;` comments out the rest of a line.
+///
+/// ```text
+/// ; index name args doc
+/// 14 print_entity 2 Prints an entity's name and position.
+/// ```
+pub struct SyscallTable {
+  /// Sorted by index.
+  syscalls:Vec<Syscall,>,
+}
+
+impl SyscallTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds the [`Syscall`] to the table.
+  ///
+  /// Errors if its name is not a valid identifier or its index or name is
+  /// already in the table.
+  pub fn insert(&mut self, syscall:Syscall,) -> Result<(), SyscallError,> {
+    if !is_label_name(&syscall.name,) {
+      return Err(SyscallError::InvalidName(syscall.name,),);
+    }
+    if self.by_name(&syscall.name,).is_some() {
+      return Err(SyscallError::DuplicateName(syscall.name,),);
+    }
+    match self.syscalls.binary_search_by_key(&syscall.index, |s| s.index,) {
+      Ok(_,) => Err(SyscallError::DuplicateIndex(syscall.index,),),
+      Err(pos,) => {
+        self.syscalls.insert(pos, syscall,);
+        Ok((),)
+      }
+    }
+  }
+
+  /// Returns the [`Syscall`] called by `SysCall index`.
+  pub fn get(&self, index:u8,) -> Option<&Syscall,> {
+    self
+      .syscalls
+      .binary_search_by_key(&index, |s| s.index,)
+      .ok()
+      .map(|pos| &self.syscalls[pos],)
+  }
+
+  /// Returns the [`Syscall`] named `name`.
+  pub fn by_name(&self, name:&str,) -> Option<&Syscall,> {
+    self.syscalls.iter().find(|s| s.name == name,)
+  }
+
+  /// Iterates over the table's [`Syscall`]s in order of their indices.
+  pub fn iter(&self,) -> impl Iterator<Item = &Syscall,> {
+    self.syscalls.iter()
+  }
+
+  pub fn len(&self,) -> usize {
+    self.syscalls.len()
+  }
+
+  pub fn is_empty(&self,) -> bool {
+    self.syscalls.is_empty()
+  }
+
+  /// Saves the table to the sidecar file at `output`.
+  #[cfg(feature = "std")]
+  pub fn save(&self, output:impl AsRef<Path,>,) -> Result<(), SyscallError,> {
+    fs::write(output, self.to_string(),)?;
+    Ok((),)
+  }
+
+  /// Loads a table saved by [`SyscallTable::save`].
+  #[cfg(feature = "std")]
+  pub fn load(source:impl AsRef<Path,>,) -> Result<Self, SyscallError,> {
+    fs::read_to_string(source,)?.parse()
+  }
+}
+
+impl Display for SyscallTable {
+  fn fmt(&self, f:&mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+    for syscall in &self.syscalls {
+      write!(f, "{} {} {}", syscall.index, syscall.name, syscall.args)?;
+      match &syscall.doc {
+        Some(doc,) => writeln!(f, " {}", doc)?,
+        None => writeln!(f)?,
+      }
+    }
+    Ok((),)
+  }
+}
+
+impl FromStr for SyscallTable {
+  type Err = SyscallError;
+
+  fn from_str(s:&str,) -> Result<Self, Self::Err,> {
+    let mut table = SyscallTable::new();
+    for (idx, text,) in s.lines().enumerate() {
+      let code = match text.find(';',) {
+        Some(comment,) => &text[..comment],
+        None => text,
+      };
+      let mut words = code.trim().splitn(4, char::is_whitespace,);
+      let Some(index,) = words.next().filter(|word| !word.is_empty(),)
+      else {
+        continue;
+      };
+
+      let invalid = || SyscallError::InvalidLine {
+        line:idx + 1,
+        text:text.to_string(),
+      };
+      let index = index.parse().map_err(|_| invalid(),)?;
+      let name = words.next().ok_or_else(invalid,)?.to_string();
+      let args = words
+        .next()
+        .and_then(|args| args.parse().ok(),)
+        .ok_or_else(invalid,)?;
+      let doc = words
+        .next()
+        .map(str::trim,)
+        .filter(|doc| !doc.is_empty(),)
+        .map(str::to_string,);
+      table.insert(Syscall {
+        index,
+        name,
+        args,
+        doc,
+      },)?;
+    }
+    Ok(table,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Syscall, SyscallTable};
+  use crate::errors::SyscallError;
+  use alloc::string::ToString;
+
+  #[test]
+  fn parse_and_print_table() {
+    let src = "
+      ; index name args doc
+      14 print_entity 2 Prints an entity's name and position.
+      3 exit 0
+    ";
+    let table = src.parse::<SyscallTable>().unwrap();
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.get(14,), table.by_name("print_entity"));
+    assert_eq!(
+      table.get(14,).unwrap().doc.as_deref(),
+      Some("Prints an entity's name and position.")
+    );
+    assert_eq!(table.get(4,), None);
+    assert_eq!(
+      table.to_string(),
+      "3 exit 0\n14 print_entity 2 Prints an entity's name and position.\n"
+    );
+    assert_eq!(table.to_string().parse::<SyscallTable>().unwrap(), table);
+  }
+
+  #[test]
+  fn table_errors() {
+    let mut table = SyscallTable::new();
+    let syscall = |index, name:&str| Syscall {
+      index,
+      name:name.to_string(),
+      args:0,
+      doc:None,
+    };
+    table.insert(syscall(1, "exit",),).unwrap();
+    assert!(matches!(
+      table.insert(syscall(1, "quit",),),
+      Err(SyscallError::DuplicateIndex(1))
+    ));
+    assert!(matches!(
+      table.insert(syscall(2, "exit",),),
+      Err(SyscallError::DuplicateName(name)) if name == "exit"
+    ));
+    assert!(matches!(
+      table.insert(syscall(2, "2fast",),),
+      Err(SyscallError::InvalidName(_))
+    ));
+    assert!(matches!(
+      "1 exit\n".parse::<SyscallTable>(),
+      Err(SyscallError::InvalidLine { line:1, .. })
+    ));
+  }
+}
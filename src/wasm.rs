@@ -0,0 +1,54 @@
+use crate::{errors::ValidationError, program::Program};
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// Disassembles the bytes of a saved program into the listing [`Program`]'s
+/// `Display` prints. Errors if the bytes are not a spdr program.
+#[wasm_bindgen]
+pub fn disassemble(bytes:&[u8],) -> Result<String, JsError,> {
+  let program = Program::from_file_bytes(bytes,).map_err(|err| JsError::new(&err.to_string(),),)?;
+  Ok(program.to_string(),)
+}
+
+/// Validates the bytes of a saved program with [`Program::validate`].
+///
+/// Returns an array of `{ kind, offset, message }` objects ordered by offset,
+/// which is empty if the program is valid. Bytes which are not a spdr program
+/// give a single problem of kind `"load"` at offset 0.
+#[wasm_bindgen]
+pub fn validate(bytes:&[u8],) -> JsValue {
+  let problems = Array::new();
+  match Program::from_file_bytes(bytes,) {
+    Ok(program,) => {
+      for err in program.validate().err().unwrap_or_default() {
+        problems.push(&problem(kind(&err,), err.offset(), &err.to_string(),),);
+      }
+    }
+    Err(err,) => {
+      problems.push(&problem("load", 0, &err.to_string(),),);
+    }
+  }
+  problems.into()
+}
+
+/// Names the kind of `err` for JavaScript callers.
+fn kind(err:&ValidationError,) -> &'static str {
+  match err {
+    ValidationError::Decode(_,) => "decode",
+    ValidationError::JumpOutOfBounds { .. } => "jumpOutOfBounds",
+    ValidationError::JumpIntoOperand { .. } => "jumpIntoOperand",
+    ValidationError::MissingTerminator { .. } => "missingTerminator",
+    ValidationError::UnknownSyscall { .. } => "unknownSyscall",
+    ValidationError::InexactOffset { .. } => "inexactOffset",
+    ValidationError::AddressOutOfBounds { .. } => "addressOutOfBounds",
+  }
+}
+
+fn problem(kind:&str, offset:u32, message:&str,) -> JsValue {
+  let problem = Object::new();
+  // Setting properties on a new plain object cannot fail
+  let _ = Reflect::set(&problem, &"kind".into(), &kind.into(),);
+  let _ = Reflect::set(&problem, &"offset".into(), &offset.into(),);
+  let _ = Reflect::set(&problem, &"message".into(), &message.into(),);
+  problem.into()
+}
@@ -0,0 +1,17 @@
+//! Keeps the crate building without its default `std` feature.
+
+use std::process::Command;
+
+#[test]
+fn builds_without_std() {
+  let manifest = env!("CARGO_MANIFEST_DIR");
+  let status = Command::new(env!("CARGO"),)
+    .args(["check", "--lib", "--no-default-features", "--offline", "--quiet",],)
+    .current_dir(manifest,)
+    // A separate target directory so the build lock held by `cargo test` is
+    // not waited on
+    .env("CARGO_TARGET_DIR", format!("{}/target/no-std", manifest),)
+    .status()
+    .unwrap();
+  assert!(status.success());
+}
@@ -0,0 +1,44 @@
+//! Run with `wasm-pack test --node -- --features wasm`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use js_sys::{Array, Reflect};
+use spdr_isa::{
+  instruction::Instruction,
+  program::Program,
+  registers::Register,
+  wasm::{disassemble, validate},
+};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn get(problem:&wasm_bindgen::JsValue, key:&str,) -> wasm_bindgen::JsValue {
+  Reflect::get(problem, &key.into(),).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn disassemble_saved_program() {
+  let mut program = Program::new();
+  program.emit_load(Register::new(16,), 1.5,);
+  program.emit(Instruction::Hlt,);
+  let bytes = program.to_file_bytes();
+
+  assert_eq!(disassemble(&bytes,).unwrap(), "Load $16, 1.5\nHlt\n");
+  assert!(disassemble(&[1, 2, 3,],).is_err());
+}
+
+#[wasm_bindgen_test]
+fn validate_saved_program() {
+  let mut program = Program::new();
+  program.emit(Instruction::Hlt,);
+  assert_eq!(Array::from(&validate(&program.to_file_bytes(),),).length(), 0);
+
+  program.emit(Instruction::Jmp { target:100, },);
+  let problems = Array::from(&validate(&program.to_file_bytes(),),);
+  assert_eq!(problems.length(), 1);
+  let problem = problems.get(0,);
+  assert_eq!(get(&problem, "kind",).as_string().unwrap(), "jumpOutOfBounds");
+  assert_eq!(get(&problem, "offset",).as_f64().unwrap(), 1.0);
+  assert!(get(&problem, "message",).as_string().unwrap().contains("100",));
+
+  let problems = Array::from(&validate(&[1, 2, 3,],),);
+  assert_eq!(get(&problems.get(0,), "kind",).as_string().unwrap(), "load");
+}